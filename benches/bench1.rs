@@ -1,4 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pypipegraph2::stress::StressConfig;
 use pypipegraph2::{
     test_big_graph_in_layers, test_big_linear_graph, test_big_linear_graph_half_ephemeral,
 };
@@ -13,6 +14,10 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("test_big_graph_in_layers", |b| {
         b.iter(|| test_big_graph_in_layers(black_box(10), black_box(10), 1))
     });
+    c.bench_function("random_dag", |b| {
+        let config = StressConfig::default();
+        b.iter(|| black_box(&config).run().unwrap())
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,8 @@
+// cargo +nightly fuzz run event_api
+// (from ppg2-engine/) - see ppg2-engine/src/fuzz.rs for what's checked
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    ppg2_engine::check_event_sequence(data);
+});
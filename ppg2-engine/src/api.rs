@@ -0,0 +1,51 @@
+//! The semver-stable surface of the evaluator.
+//!
+//! Everything re-exported here only changes in a breaking way with a
+//! major version bump. Items that are only reachable from the crate root
+//! (test runners, graph internals, logging helpers) serve the python shim
+//! and the benchmarks, and may change in any release.
+//!
+//! A minimal driver loop:
+//! - build a [PPGEvaluator] with your [PPGEvaluatorStrategy],
+//! - add jobs and edges, then [PPGEvaluator::event_startup] with the last history,
+//! - run what [PPGEvaluator::query_ready_to_run] hands out, reporting each
+//!   outcome via `event_now_running` / `event_job_finished_success` /
+//!   `event_job_finished_failure`,
+//! - once [PPGEvaluator::is_finished], persist [PPGEvaluator::new_history].
+//!
+//! [JobKind], [FinishState] and [PPGEvaluatorError] are `#[non_exhaustive]` -
+//! matches on them need a wildcard arm, so new variants aren't breaking.
+//!
+//! The exception is [unstable]: the graph types
+//! [PPGEvaluatorStrategy::get_input_list] is handed. They follow the graph
+//! storage (`GraphType` changes with the `petgraph-backend` feature), so
+//! strategies implementing get_input_list may need changes in any release.
+
+// the evaluator and what a caller has to implement to drive it
+pub use crate::{PPGEvaluator, PPGEvaluatorError, PPGEvaluatorStrategy, StrategyError};
+// time
+pub use crate::{Clock, SimulatedClock, WallClock};
+// job vocabulary
+pub use crate::{AlwaysGate, FinishState, JobKind};
+// strategies shipped with the engine
+pub use crate::{StrategyContentHash, StrategyFileSystem};
+// scheduling
+pub use crate::{
+    FairShare, Fifo, LongestJobFirst, LongestJobFirstWithAging, MostDownstreamsFirst,
+    ReadyCandidate, ResourceState, SchedulingPolicy,
+};
+// reports
+pub use crate::{
+    EdgeDetails, EvaluationStats, FailedJob, FailedJobInfo, FailureReport, LastSuccess, Progress,
+    RunPlan, RunReason,
+};
+// history persistence
+pub use crate::{
+    HistoryBackend, HistoryStorageError, HistoryStore, JsonFileHistory, Session, SessionConfig,
+};
+
+/// What [PPGEvaluatorStrategy::get_input_list] (and `get_input_lists`) is
+/// handed - not covered by the stability promise, see the module docs.
+pub mod unstable {
+    pub use crate::{sorted_upstream_job_ids, GraphType, NodeIndex, NodeInfo};
+}
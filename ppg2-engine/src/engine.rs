@@ -1,37 +1,193 @@
-#[allow(unused_imports)]
-use log::{debug, error, info, warn};
-use petgraph::{graphmap::GraphMap, Directed, Direction};
+use tracing::{debug_span, level_filters::LevelFilter, Level, Span};
+// tracing's 'log' feature forwards these to the log crate
+// when no tracing subscriber is installed, so env_logger keeps working.
+use crate::graph::Direction;
+use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
+    cell::Cell,
     collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
 };
 
+thread_local! {
+    // verbosity for the job we're currently handling - see PPGEvaluator::set_job_log_level
+    static CURRENT_LOG_LEVEL: Cell<LevelFilter> = const { Cell::new(LevelFilter::TRACE) };
+}
+
+#[inline]
+fn log_enabled(level: Level) -> bool {
+    // a constant with the no-debug-logging feature, so the calls get compiled out
+    level <= tracing::level_filters::STATIC_MAX_LEVEL
+        && CURRENT_LOG_LEVEL.with(|current| level <= current.get())
+}
+
+// The engine's logging goes through these, so it can be filtered per job.
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::engine::log_enabled(tracing::Level::DEBUG) {
+            tracing::debug!($($arg)*)
+        }
+    };
+}
+#[allow(unused_macros)]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::engine::log_enabled(tracing::Level::INFO) {
+            tracing::info!($($arg)*)
+        }
+    };
+}
+#[allow(unused_macros)]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::engine::log_enabled(tracing::Level::WARN) {
+            tracing::warn!($($arg)*)
+        }
+    };
+}
+#[allow(unused_macros)]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        if $crate::engine::log_enabled(tracing::Level::ERROR) {
+            tracing::error!($($arg)*)
+        }
+    };
+}
+
+/// Sets the engine verbosity (and enters the job span, if any) until dropped.
+struct LogScope {
+    previous: LevelFilter,
+    _span: Option<tracing::span::EnteredSpan>,
+}
+
+impl LogScope {
+    fn new(level: LevelFilter, span: Option<&Span>) -> LogScope {
+        let previous = CURRENT_LOG_LEVEL.with(|current| current.replace(level));
+        LogScope {
+            previous,
+            _span: span.map(|span| span.clone().entered()),
+        }
+    }
+}
+
+impl Drop for LogScope {
+    fn drop(&mut self) {
+        CURRENT_LOG_LEVEL.with(|current| current.set(self.previous));
+    }
+}
+
+use crate::history_store::{HistoryStore, HistoryStoreStats};
+use crate::migrate::{HistoryMigrator, HISTORY_SCHEMA_VERSION, HISTORY_VERSION_KEY};
 use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+use absence::AbsenceInfo;
+use barrier::BarrierInfo;
+use cleanup::CleanupInfo;
+use conditional::ConditionalInfo;
+use duplicates::DuplicateEvents;
+use event_order::EventOrder;
+use gating::GatingInfo;
+use generators::GeneratorInfo;
+use global_invariants::GlobalInvariantInfo;
+use ignore::ChangeFilter;
+use interactive::{InteractiveInfo, QueuedChange};
+use reasons::RunReasons;
+use scheduling::SchedulingInfo;
+use shared_output::SharedOutputInfo;
+use soft_dependencies::SoftDependencies;
+use speculation::SpeculationInfo;
+use temp_file::TempFileInfo;
+
+mod absence;
+mod barrier;
+mod cleanup;
+mod clock;
+mod conditional;
+mod debug_dump;
+mod duplicates;
+mod event_order;
+mod fair_share;
+mod gating;
+mod generators;
+mod global_invariants;
+mod ignore;
+mod interactive;
+mod introspection;
+mod invariants;
+mod last_success;
+mod layers;
+mod metadata;
+mod orphans;
+mod planning;
+mod policy;
+mod reasons;
+mod reconstruct;
+mod rename;
+mod run_records;
+mod scheduling;
+mod shared_output;
+mod snapshot;
+mod soft_dependencies;
+mod speculation;
+mod stall;
+mod subgraph;
+mod temp_file;
+mod upstream_failure;
+mod utilization;
+mod validate;
+mod watch;
+pub use absence::ABSENCE_JOB_PREFIX;
+pub use barrier::BARRIER_HISTORY;
+pub use cleanup::CleanupStats;
+pub use clock::{Clock, SimulatedClock, WallClock};
+pub use conditional::SKIPPED_HISTORY;
+pub use fair_share::FairShare;
+pub use gating::AlwaysGate;
+pub use introspection::EdgeDetails;
+pub use last_success::LastSuccess;
+pub use planning::RunPlan;
+pub use policy::{
+    Fifo, LongestJobFirst, LongestJobFirstWithAging, MostDownstreamsFirst, ReadyCandidate,
+    ResourceState, SchedulingPolicy,
+};
+pub use reasons::RunReason;
+pub use run_records::{FailedJobInfo, JobRecord, JobRecordColumns};
+pub use snapshot::{EvaluatorSnapshot, JobSnapshot};
+pub use subgraph::{Subgraph, SUBGRAPH_SEPARATOR};
+pub use utilization::{UtilizationPoint, UtilizationReport};
+pub use validate::{Severity, ValidationIssue, ValidationIssueKind, ValidationReport};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum JobKind {
-    Always, // run always
-    Output, //run if invalidated or output-not-present
+    Always,    // run always
+    Output,    //run if invalidated or output-not-present
     Ephemeral, // run if invalidated, or downstream jobs require them.
-            // cleanups ain't jobs. Because they would not trigger ephemerals -
-            // and that way lies complexity madness. Probably much easier to just have a callback
-            // when a jobs' downstreams have all been finished
+    // cleanups ain't jobs. Because they would not trigger ephemerals -
+    // and that way lies complexity madness. Probably much easier to just have a callback
+    // when a jobs' downstreams have all been finished
+    Invariant, // never runs - its output is given to add_invariant, changes invalidate downstreams
+    Conditional, // like Always, but only runs if the strategy's should_run says so (see conditional.rs)
+    Barrier, // never runs - done once all upstreams are done, never invalidates downstreams (see barrier.rs)
+    TempFile, // like Ephemeral, but reuses a still present output (see temp_file.rs)
 }
 
 trait JobQueries {
     fn is_finished(&self) -> bool;
     fn is_failed(&self) -> bool;
+    #[allow(dead_code)]
     fn is_skipped(&self) -> bool;
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ValidationStatus {
     Unknown,
     Validated,
     Invalidated,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Required {
     Unknown,
     Yes,
@@ -39,10 +195,10 @@ pub enum Required {
 }
 #[derive(Debug)]
 pub struct EdgeInfo {
-    required: Required,
-    invalidated: Required,
+    pub(crate) required: Required,
+    pub(crate) invalidated: Required,
 }
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JobStateAlways {
     Undetermined,
     ReadyToRun, // ie. upstreams done.
@@ -53,7 +209,7 @@ pub enum JobStateAlways {
     FinishedAborted,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JobStateOutput {
     NotReady(ValidationStatus),
     ReadyToRun,
@@ -65,7 +221,7 @@ pub enum JobStateOutput {
     FinishedAborted,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JobStateEphemeral {
     NotReady(ValidationStatus),
     ReadyButDelayed, //ie. we have not made a decision on whether this is a go or not.
@@ -81,7 +237,7 @@ pub enum JobStateEphemeral {
     FinishedAborted,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JobState {
     Always(JobStateAlways),
     Output(JobStateOutput),
@@ -89,6 +245,14 @@ pub enum JobState {
 }
 
 impl JobState {
+    pub(crate) fn kind(&self) -> JobKind {
+        match self {
+            JobState::Always(_) => JobKind::Always,
+            JobState::Output(_) => JobKind::Output,
+            JobState::Ephemeral(_) => JobKind::Ephemeral,
+        }
+    }
+
     fn is_finished(&self) -> bool {
         match self {
             JobState::Always(x) => x.is_finished(),
@@ -105,12 +269,12 @@ impl JobState {
     }
 
     fn is_upstream_failure(&self) -> bool {
-        match self {
-            JobState::Always(JobStateAlways::FinishedUpstreamFailure) => true,
-            JobState::Output(JobStateOutput::FinishedUpstreamFailure) => true,
-            JobState::Ephemeral(JobStateEphemeral::FinishedUpstreamFailure) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            JobState::Always(JobStateAlways::FinishedUpstreamFailure)
+                | JobState::Output(JobStateOutput::FinishedUpstreamFailure)
+                | JobState::Ephemeral(JobStateEphemeral::FinishedUpstreamFailure)
+        )
     }
 }
 
@@ -202,18 +366,53 @@ impl JobQueries for JobStateEphemeral {
 
 #[derive(Clone, Debug)]
 pub struct NodeInfo {
-    job_id: String,
-    state: JobState,
-    history_output: Option<String>,
+    pub(crate) job_id: String,
+    pub(crate) state: JobState,
+    pub(crate) history_output: Option<String>,
     last_considered_in_gen: usize,
+    // lifecycle span of this job, entered whenever we handle an event/signal for it
+    span: Span,
+    log_level: LevelFilter,
+    pub(crate) started_at: Option<Instant>,
+    // when it last became ready to run
+    pub(crate) ready_at: Option<Instant>,
+    pub(crate) runtime: Option<Duration>,
+    // what the executor told us when the job failed
+    pub(crate) error: Option<String>,
+    // JobKind::Invariant - the value passed to add_invariant
+    pub(crate) invariant: Option<String>,
+    // JobKind::Conditional
+    pub(crate) conditional: bool,
+    // JobKind::Barrier
+    pub(crate) barrier: bool,
+    // JobKind::TempFile
+    pub(crate) temp_file: bool,
+    // see set_run_even_if_upstreams_failed
+    pub(crate) run_even_if_upstreams_failed: bool,
+    // see set_job_metadata
+    pub(crate) metadata: Option<String>,
 }
 
 impl NodeInfo {
-    pub(crate) fn clone_job_id(&self) -> String {
+    fn enter(&self) -> LogScope {
+        LogScope::new(self.log_level, Some(&self.span))
+    }
+
+    pub(crate) fn kind(&self) -> JobKind {
+        match self.invariant {
+            Some(_) => JobKind::Invariant,
+            None if self.conditional => JobKind::Conditional,
+            None if self.barrier => JobKind::Barrier,
+            None if self.temp_file => JobKind::TempFile,
+            None => self.state.kind(),
+        }
+    }
+
+    pub fn clone_job_id(&self) -> String {
         self.job_id.clone()
     }
 
-    pub(crate) fn get_job_id(&self) -> &str {
+    pub fn get_job_id(&self) -> &str {
         &self.job_id
     }
 }
@@ -225,8 +424,10 @@ macro_rules! set_node_state {
         $gen: expr
         ) => {
         debug!(
-            "\tset_node_state {} from {:?} to {:?}",
-            $node.job_id, $node.state, $new_state
+            job_id = $node.job_id.as_str(),
+            from = ?$node.state,
+            to = ?$new_state,
+            "set_node_state"
         );
         match ($node.state, $new_state) {
             (JobState::Always(_), JobState::Always(_)) |
@@ -239,18 +440,34 @@ macro_rules! set_node_state {
              => panic!("Moving a job between kinds"), // if you encounter this from python, the
                                                        // sky must be falling
         }
+        $gen.progress.transition(&$node.state, &$new_state);
         $node.state = $new_state;
         $gen.advance();
     };
 }
 
 /// whether a Evaluator was run
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 enum StartStatus {
     NotStarted,
     Running,
     Finished,
 }
 
+/// Why a run ended - see finish_state
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FinishState {
+    NotFinished,
+    Success,
+    /// nothing was invalidated, no job ran
+    NothingToDo,
+    FinishedWithFailures,
+    Aborted,
+    /// an ephemeral changed its output (EphemeralChangedOutput was returned)
+    ContractViolation,
+}
+
 #[derive(Debug)]
 struct Signal {
     kind: SignalKind,
@@ -272,7 +489,7 @@ enum SignalKind {
 
 impl Signal {
     fn job_id<'a>(&self, jobs: &'a [NodeInfo]) -> &'a str {
-        &jobs[self.node_idx as usize].job_id
+        &jobs[self.node_idx].job_id
     }
 }
 
@@ -328,12 +545,141 @@ pub enum JobOutputResult {
     NotDone,
 }
 
-pub(crate) type NodeIndex = usize;
+pub type NodeIndex = usize;
+
+#[cfg(not(feature = "petgraph-backend"))]
+pub type GraphType = crate::graph::Dag<EdgeInfo>;
+#[cfg(feature = "petgraph-backend")]
+pub type GraphType = crate::graph::PetgraphDag<EdgeInfo>;
+
+/// Jobs per state, maintained on every transition (see set_node_state)
+/// so polling is_finished / progress is O(1)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub pending: usize,
+    pub running: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub upstream_failed: usize,
+    pub skipped: usize,
+    pub aborted: usize,
+}
+
+impl Progress {
+    pub fn total(&self) -> usize {
+        self.pending + self.running + self.finished()
+    }
+
+    pub fn finished(&self) -> usize {
+        self.succeeded + self.failed + self.upstream_failed + self.skipped + self.aborted
+    }
+
+    fn bucket(&mut self, state: &JobState) -> &mut usize {
+        match state {
+            JobState::Always(JobStateAlways::Running)
+            | JobState::Output(JobStateOutput::Running)
+            | JobState::Ephemeral(JobStateEphemeral::Running(_)) => &mut self.running,
+            x if !x.is_finished() => &mut self.pending,
+            x if x.is_upstream_failure() => &mut self.upstream_failed,
+            JobState::Always(JobStateAlways::FinishedAborted)
+            | JobState::Output(JobStateOutput::FinishedAborted)
+            | JobState::Ephemeral(JobStateEphemeral::FinishedAborted) => &mut self.aborted,
+            x if x.is_failed() => &mut self.failed,
+            JobState::Output(JobStateOutput::FinishedSkipped)
+            | JobState::Ephemeral(JobStateEphemeral::FinishedSkipped) => &mut self.skipped,
+            _ => &mut self.succeeded,
+        }
+    }
+
+    fn added(&mut self, state: &JobState) {
+        *self.bucket(state) += 1;
+    }
+
+    fn removed(&mut self, state: &JobState) {
+        *self.bucket(state) -= 1;
+    }
 
-pub(crate) type GraphType = GraphMap<NodeIndex, EdgeInfo, Directed>;
+    fn transition(&mut self, from: &JobState, to: &JobState) {
+        *self.bucket(from) -= 1;
+        *self.bucket(to) += 1;
+    }
+}
+
+/// One drain of the signal queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassStats {
+    /// recursion depth of process_signals - 0 for passes started by events
+    pub depth: u32,
+    /// signals handled, i.e. node visits
+    pub nodes_visited: usize,
+    /// excluding the follow up passes
+    pub duration: Duration,
+}
+
+/// How many passes EvaluationStats keeps individually - the totals cover all of them
+pub const RECENT_PASSES: usize = 64;
+
+/// Totals over all passes, plus the last RECENT_PASSES ones -
+/// long running interactive sessions would otherwise grow this without bound
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvaluationStats {
+    pass_count: usize,
+    nodes_visited: usize,
+    duration: Duration,
+    max_depth: u32,
+    max_nodes_visited: usize,
+    max_duration: Duration,
+    recent: VecDeque<PassStats>,
+}
+
+impl EvaluationStats {
+    fn record(&mut self, pass: PassStats) {
+        self.pass_count += 1;
+        self.nodes_visited += pass.nodes_visited;
+        self.duration += pass.duration;
+        self.max_depth = self.max_depth.max(pass.depth);
+        self.max_nodes_visited = self.max_nodes_visited.max(pass.nodes_visited);
+        self.max_duration = self.max_duration.max(pass.duration);
+        if self.recent.len() == RECENT_PASSES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(pass);
+    }
+
+    pub fn pass_count(&self) -> usize {
+        self.pass_count
+    }
+
+    pub fn nodes_visited(&self) -> usize {
+        self.nodes_visited
+    }
 
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    pub fn max_nodes_visited(&self) -> usize {
+        self.max_nodes_visited
+    }
+
+    pub fn max_duration(&self) -> Duration {
+        self.max_duration
+    }
+
+    /// the last RECENT_PASSES passes, oldest first
+    pub fn recent_passes(&self) -> impl Iterator<Item = &PassStats> {
+        self.recent.iter()
+    }
+}
+
+// advanced on every state change - which is why it also keeps the Progress counts
 struct Generation {
     gen: usize,
+    progress: Progress,
 }
 
 impl Generation {
@@ -346,18 +692,63 @@ impl Generation {
     }
 }
 
+// Job ids are Strings throughout, not a type parameter: history keys are
+// composed from them ("upstream!!!downstream"), persisted as json, and
+// strategies answer output queries by them - a generic id would have to be
+// formatted into strings at all of these anyway. See todo.md.
 pub struct PPGEvaluator<T: PPGEvaluatorStrategy> {
-    dag: GraphType,
-    jobs: Vec<NodeInfo>,
-    job_id_to_node_idx: HashMap<String, NodeIndex>,
-    history: HashMap<String, String>,
-    strategy: T,
+    pub(crate) dag: GraphType,
+    pub(crate) jobs: Vec<NodeInfo>,
+    pub(crate) job_id_to_node_idx: HashMap<String, NodeIndex>,
+    pub(crate) history: HistoryStore,
+    pub(crate) strategy: T,
     already_started: StartStatus,
     jobs_ready_to_run: HashSet<String>,
     jobs_ready_for_cleanup: HashSet<String>,
     topo: Option<Vec<NodeIndex>>,
     signals: VecDeque<Signal>,
     gen: Generation,
+    log_level: LevelFilter,
+    job_log_levels: Vec<(String, LevelFilter)>,
+    // output_already_present answers for this run, see invalidate_presence
+    presence_cache: HashMap<String, bool>,
+    evaluation_stats: EvaluationStats,
+    // new_history of a finished run that had jobs added - see event_resume
+    resume_history: Option<HistoryStore>,
+    // (alias, target) - see add_alias
+    aliases: Vec<(String, String)>,
+    change_filter: ChangeFilter,
+    // treat every job as invalidated in event_startup
+    force_rerun_all: bool,
+    // see set_assume_unchanged
+    assume_unchanged: bool,
+    // EphemeralChangedOutput was returned this run
+    contract_violated: bool,
+    // see set_record_last_success
+    record_last_success: bool,
+    cleanup: CleanupInfo,
+    scheduling: SchedulingInfo,
+    policy: Option<Box<dyn SchedulingPolicy>>,
+    speculation: SpeculationInfo,
+    duplicates: DuplicateEvents,
+    interactive: InteractiveInfo,
+    conditional: ConditionalInfo,
+    gating: GatingInfo,
+    barriers: BarrierInfo,
+    temp_files: TempFileInfo,
+    // inside finish_engine_jobs - the events it sends don't start another one
+    finishing_engine_jobs: bool,
+    shared_outputs: SharedOutputInfo,
+    global_invariants: GlobalInvariantInfo,
+    absence: AbsenceInfo,
+    soft_dependencies: SoftDependencies,
+    run_reasons: RunReasons,
+    generators: GeneratorInfo,
+    migrator: HistoryMigrator,
+    // the last events and their outcome, for dump_debug
+    recent_events: VecDeque<String>,
+    event_order: EventOrder,
+    clock: Box<dyn Clock>,
 }
 
 impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
@@ -368,69 +759,256 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     }
 
     #[allow(clippy::type_complexity)]
-    pub fn new_with_history(history: HashMap<String, String>, strategy: T) -> Self {
+    pub fn new_with_history(history: impl Into<HistoryStore>, strategy: T) -> Self {
         PPGEvaluator {
-            dag: GraphMap::new(),
+            dag: GraphType::new(),
             jobs: Vec::new(),
             job_id_to_node_idx: HashMap::new(),
-            history,
+            history: history.into(),
             strategy,
             already_started: StartStatus::NotStarted,
             jobs_ready_to_run: HashSet::new(),
             jobs_ready_for_cleanup: HashSet::new(),
             topo: None,
             signals: VecDeque::new(),
-            gen: Generation { gen: 0 },
+            gen: Generation {
+                gen: 0,
+                progress: Progress::default(),
+            },
+            log_level: LevelFilter::TRACE,
+            job_log_levels: Vec::new(),
+            presence_cache: HashMap::new(),
+            evaluation_stats: EvaluationStats::default(),
+            resume_history: None,
+            aliases: Vec::new(),
+            change_filter: ChangeFilter::default(),
+            force_rerun_all: false,
+            assume_unchanged: false,
+            contract_violated: false,
+            record_last_success: false,
+            cleanup: CleanupInfo::default(),
+            scheduling: SchedulingInfo::default(),
+            policy: None,
+            speculation: SpeculationInfo::default(),
+            duplicates: DuplicateEvents::default(),
+            interactive: InteractiveInfo::default(),
+            conditional: ConditionalInfo::default(),
+            gating: GatingInfo::default(),
+            barriers: BarrierInfo::default(),
+            temp_files: TempFileInfo::default(),
+            finishing_engine_jobs: false,
+            shared_outputs: SharedOutputInfo::default(),
+            global_invariants: GlobalInvariantInfo::default(),
+            absence: AbsenceInfo::default(),
+            soft_dependencies: SoftDependencies::default(),
+            run_reasons: RunReasons::default(),
+            generators: GeneratorInfo::default(),
+            migrator: HistoryMigrator::default(),
+            recent_events: VecDeque::new(),
+            event_order: EventOrder::default(),
+            clock: Box::new(WallClock),
+        }
+    }
+
+    pub fn strategy(&self) -> &T {
+        &self.strategy
+    }
+
+    /// For strategies that carry their own configuration (callbacks etc.)
+    pub fn strategy_mut(&mut self) -> &mut T {
+        &mut self.strategy
+    }
+
+    /// Engine diagnostics verbosity for everything not matched by set_job_log_level.
+    /// (The installed logger/subscriber filters on top of this)
+    pub fn set_log_level(&mut self, level: LevelFilter) {
+        self.log_level = level;
+        for job in self.jobs.iter_mut() {
+            job.log_level = Self::log_level_for(self.log_level, &self.job_log_levels, &job.job_id);
+        }
+    }
+
+    /// Rerun every job, as if all inputs had changed - history is recorded as usual.
+    /// Applies to event_startup, not to the jobs added before an event_resume.
+    pub fn set_force_rerun_all(&mut self, force: bool) {
+        self.force_rerun_all = force;
+    }
+
+    /// Migration steps for older histories, applied in event_startup
+    pub fn set_history_migrator(&mut self, migrator: HistoryMigrator) {
+        self.migrator = migrator;
+    }
+
+    /// Engine diagnostics verbosity for jobs whose id matches pattern ('*' / '?' wildcards),
+    /// e.g. ("lane7_*", LevelFilter::TRACE) with the default level at WARN.
+    /// Later patterns take precedence.
+    pub fn set_job_log_level(&mut self, pattern: &str, level: LevelFilter) {
+        self.job_log_levels.push((pattern.to_string(), level));
+        for job in self.jobs.iter_mut() {
+            if crate::wildcard::wildcard_match(pattern, &job.job_id) {
+                job.log_level = level;
+            }
         }
     }
 
-    fn id_to_idx(&self, id: &str) -> NodeIndex {
-        *self
-            .job_id_to_node_idx
-            .get(id)
-            .expect("Unknown job_id passed")
+    fn log_level_for(
+        default: LevelFilter,
+        job_log_levels: &[(String, LevelFilter)],
+        job_id: &str,
+    ) -> LevelFilter {
+        job_log_levels
+            .iter()
+            .rev()
+            .find(|(pattern, _)| crate::wildcard::wildcard_match(pattern, job_id))
+            .map(|(_, level)| *level)
+            .unwrap_or(default)
     }
 
-    pub fn add_node(&mut self, job_id: &str, kind: JobKind) {
-        assert_ne!(job_id, "");
-        assert!(!job_id.contains("!!!"));
-        let state = match kind {
-            JobKind::Always => JobState::Always(JobStateAlways::Undetermined),
+    fn initial_state(kind: JobKind) -> JobState {
+        match kind {
+            // invariants are always jobs finished by the engine itself
+            JobKind::Always | JobKind::Invariant | JobKind::Conditional => {
+                JobState::Always(JobStateAlways::Undetermined)
+            }
+            // barriers are only needed if a downstream is - like ephemerals
+            JobKind::Barrier | JobKind::TempFile => {
+                JobState::Ephemeral(JobStateEphemeral::NotReady(ValidationStatus::Unknown))
+            }
             JobKind::Output => {
                 JobState::Output(JobStateOutput::NotReady(ValidationStatus::Unknown))
             }
             JobKind::Ephemeral => {
                 JobState::Ephemeral(JobStateEphemeral::NotReady(ValidationStatus::Unknown))
             }
-        };
+        }
+    }
+
+    /// the graph may change before event_startup, and after a finished run (see event_resume)
+    fn check_graph_mutable(&mut self) -> Result<(), PPGEvaluatorError> {
+        match self.already_started {
+            StartStatus::Running => Err(PPGEvaluatorError::APIError(
+                "Can't change the graph while the evaluation is running".to_string(),
+            )),
+            StartStatus::NotStarted => Ok(()),
+            StartStatus::Finished => {
+                // the finished run's history, before the graph no longer matches it
+                if self.resume_history.is_none() {
+                    self.resume_history = Some(self.new_history()?.into());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn add_node(&mut self, job_id: &str, kind: JobKind) -> Result<(), PPGEvaluatorError> {
+        if kind == JobKind::Invariant {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Invariant {} needs a value, use add_invariant",
+                job_id
+            )));
+        }
+        self.add_job(job_id, kind, None)
+    }
+
+    /// A job that never runs: value is its output, compared to the last run's
+    /// to decide whether downstreams are invalidated.
+    /// Invariants can't have upstreams.
+    pub fn add_invariant(&mut self, job_id: &str, value: &str) -> Result<(), PPGEvaluatorError> {
+        self.add_job(job_id, JobKind::Invariant, Some(value.to_string()))
+    }
+
+    fn add_job(
+        &mut self,
+        job_id: &str,
+        kind: JobKind,
+        invariant: Option<String>,
+    ) -> Result<(), PPGEvaluatorError> {
+        if self.queue_if_running(QueuedChange::AddJob {
+            job_id: job_id.to_string(),
+            kind,
+            invariant: invariant.clone(),
+        })? {
+            return Ok(());
+        }
+        self.check_graph_mutable()?;
+        self.insert_job(job_id, kind, invariant)
+    }
+
+    /// add_job without the started checks - also used to splice queued jobs into a run
+    fn insert_job(
+        &mut self,
+        job_id: &str,
+        kind: JobKind,
+        invariant: Option<String>,
+    ) -> Result<(), PPGEvaluatorError> {
+        if let Some(res) = self.check_precreated(job_id, kind) {
+            return res;
+        }
+        // '!!!' separates the history keys (see new_history)
+        if job_id.is_empty() || job_id.contains("!!!") {
+            return Err(PPGEvaluatorError::InvalidJobId(job_id.to_string()));
+        }
+        if self.job_id_to_node_idx.contains_key(job_id) || self.is_alias(job_id) {
+            return Err(PPGEvaluatorError::JobRedefinition(job_id.to_string()));
+        }
+        let state = Self::initial_state(kind);
         let job = NodeInfo {
             job_id: job_id.to_string(),
             state,
             history_output: None,
             last_considered_in_gen: 0,
+            span: debug_span!("job", job_id, kind = ?kind),
+            log_level: Self::log_level_for(self.log_level, &self.job_log_levels, job_id),
+            started_at: None,
+            ready_at: None,
+            runtime: None,
+            error: None,
+            invariant,
+            conditional: kind == JobKind::Conditional,
+            barrier: kind == JobKind::Barrier,
+            temp_file: kind == JobKind::TempFile,
+            run_even_if_upstreams_failed: false,
+            metadata: None,
         };
         let idx = self.jobs.len() as NodeIndex;
-        if self
-            .job_id_to_node_idx
-            .insert(job_id.to_string(), idx)
-            .is_some()
-        {
-            //can't get here from python.
-            panic!("Can not add a node twice to the evaluator.");
-        };
+        self.job_id_to_node_idx.insert(job_id.to_string(), idx);
+        self.gen.progress.added(&job.state);
         self.jobs.push(job);
         self.dag.add_node(idx);
+        Ok(())
     }
 
-    #[allow(dead_code)]
     pub fn contains_node(&self, job_id: &str) -> bool {
         self.job_id_to_node_idx.contains_key(job_id)
     }
 
-    pub fn depends_on(&mut self, downstream: &str, upstream: &str) {
-        let downstream_id = self.id_to_idx(downstream);
-        let upstream_id = self.id_to_idx(upstream);
-        assert_ne!(downstream_id, upstream_id, "can't depend on self");
+    pub fn depends_on(
+        &mut self,
+        downstream: &str,
+        upstream: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        if self.queue_if_running(QueuedChange::DependsOn {
+            downstream: downstream.to_string(),
+            upstream: upstream.to_string(),
+        })? {
+            return Ok(());
+        }
+        self.check_graph_mutable()?;
+        self.insert_edge(downstream, upstream)
+    }
+
+    fn insert_edge(&mut self, downstream: &str, upstream: &str) -> Result<(), PPGEvaluatorError> {
+        let downstream_id = self.known_idx(downstream)?;
+        let upstream_id = self.known_idx(upstream)?;
+        if downstream_id == upstream_id {
+            return Err(PPGEvaluatorError::SelfDependency(downstream.to_string()));
+        }
+        if self.jobs[downstream_id].invariant.is_some() {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Invariant {} can't depend on {}",
+                downstream, upstream
+            )));
+        }
         self.dag.add_edge(
             upstream_id,
             downstream_id,
@@ -439,6 +1017,165 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 invalidated: Required::Unknown,
             },
         );
+        Ok(())
+    }
+
+    /// Add the jobs and edges of another, not yet started evaluator.
+    /// Jobs present in both must be of the same kind (and invariant value).
+    /// The other evaluator's history is not taken over.
+    pub fn merge<S: PPGEvaluatorStrategy>(
+        &mut self,
+        other: &PPGEvaluator<S>,
+    ) -> Result<(), PPGEvaluatorError> {
+        if other.already_started != StartStatus::NotStarted {
+            return Err(PPGEvaluatorError::APIError(
+                "Can only merge graphs that have not been started".to_string(),
+            ));
+        }
+        self.check_graph_mutable()?;
+        // check every collision first, so a failed merge leaves self untouched
+        for job in other.jobs.iter() {
+            match self.job_id_to_node_idx.get(&job.job_id) {
+                Some(idx) => {
+                    let ours = &self.jobs[*idx];
+                    if ours.kind() != job.kind() {
+                        return Err(PPGEvaluatorError::ConflictingJobKind {
+                            job_id: job.job_id.clone(),
+                            kind: ours.kind(),
+                            other_kind: job.kind(),
+                        });
+                    }
+                    if ours.invariant != job.invariant {
+                        return Err(PPGEvaluatorError::ConflictingInvariant {
+                            job_id: job.job_id.clone(),
+                            value: ours.invariant.clone().unwrap_or_default(),
+                            other_value: job.invariant.clone().unwrap_or_default(),
+                        });
+                    }
+                }
+                None => {
+                    if self.is_alias(&job.job_id) {
+                        return Err(PPGEvaluatorError::JobRedefinition(job.job_id.clone()));
+                    }
+                    if let Some(Err(e)) = self.check_precreated(&job.job_id, job.kind()) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        for job in other.jobs.iter() {
+            if !self.contains_node(&job.job_id) {
+                self.add_job(&job.job_id, job.kind(), job.invariant.clone())?;
+            }
+        }
+        for (upstream_idx, downstream_idx, _) in other.dag.all_edges() {
+            self.depends_on(
+                &other.jobs[downstream_idx].job_id,
+                &other.jobs[upstream_idx].job_id,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check_not_started(&self, what: &str) -> Result<(), PPGEvaluatorError> {
+        if self.already_started != StartStatus::NotStarted {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} is only possible before event_startup",
+                what
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn known_idx(&self, job_id: &str) -> Result<NodeIndex, PPGEvaluatorError> {
+        self.job_id_to_node_idx
+            .get(job_id)
+            .copied()
+            .ok_or_else(|| PPGEvaluatorError::UnknownJob(job_id.to_string()))
+    }
+
+    /// Replace the dag with one containing all jobs and just these edges.
+    /// Only before startup - edge state is lost.
+    fn rebuild_dag(&mut self, edges: Vec<(NodeIndex, NodeIndex)>) {
+        let mut dag = GraphType::new();
+        for idx in 0..self.jobs.len() {
+            dag.add_node(idx);
+        }
+        for (upstream_idx, downstream_idx) in edges {
+            dag.add_edge(
+                upstream_idx,
+                downstream_idx,
+                EdgeInfo {
+                    required: Required::Unknown,
+                    invalidated: Required::Unknown,
+                },
+            );
+        }
+        self.dag = dag;
+    }
+
+    /// Undo a depends_on (before event_startup)
+    pub fn remove_dependency(
+        &mut self,
+        downstream: &str,
+        upstream: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.check_not_started("Removing edges")?;
+        let downstream_idx = self.known_idx(downstream)?;
+        let upstream_idx = self.known_idx(upstream)?;
+        if !self.dag.contains_edge(upstream_idx, downstream_idx) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} does not depend on {}",
+                downstream, upstream
+            )));
+        }
+        let edges = self
+            .dag
+            .all_edges()
+            .map(|(from, to, _)| (from, to))
+            .filter(|edge| *edge != (upstream_idx, downstream_idx))
+            .collect();
+        self.rebuild_dag(edges);
+        Ok(())
+    }
+
+    /// Remove a job and its edges (before event_startup).
+    /// Aliases pointing to it are dropped as well.
+    pub fn remove_node(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
+        self.check_not_started("Removing jobs")?;
+        let removed_idx = self.known_idx(job_id)?;
+        let shift = |idx: NodeIndex| if idx > removed_idx { idx - 1 } else { idx };
+        let edges = self
+            .dag
+            .all_edges()
+            .filter(|(from, to, _)| *from != removed_idx && *to != removed_idx)
+            .map(|(from, to, _)| (shift(from), shift(to)))
+            .collect();
+        let job = self.jobs.remove(removed_idx);
+        self.gen.progress.removed(&job.state);
+        self.job_id_to_node_idx.remove(job_id);
+        for idx in self.job_id_to_node_idx.values_mut() {
+            *idx = shift(*idx);
+        }
+        self.aliases.retain(|(_, target)| target != job_id);
+        self.cleanup.expected_sizes.remove(job_id);
+        self.cleanup.pinned.remove(job_id);
+        self.cleanup.retention.remove(job_id);
+        self.scheduling.exclusive.remove(job_id);
+        self.scheduling.tags.remove(job_id);
+        self.scheduling.capabilities.remove(job_id);
+        self.scheduling.runtime_estimates.remove(job_id);
+        self.scheduling.cores.remove(job_id);
+        self.scheduling.memory.remove(job_id);
+        self.scheduling.nice.remove(job_id);
+        self.scheduling.ready_order.remove(job_id);
+        self.gating.gates.remove(job_id);
+        self.shared_outputs.remove_job(job_id);
+        self.global_invariants.remove_job(job_id);
+        self.absence.remove_job(job_id);
+        self.soft_dependencies.remove_job(job_id);
+        self.rebuild_dag(edges);
+        Ok(())
     }
 
     pub fn abort_remaining(&mut self) -> Result<(), PPGEvaluatorError> {
@@ -467,18 +1204,72 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             StartStatus::NotStarted => false,
             StartStatus::Finished => true,
             StartStatus::Running => {
-                for job in self.jobs.iter() {
-                    if !job.state.is_finished() {
-                        debug!("\tUnfinished: {} {:?}", job.job_id, job.state);
-                        return false;
-                    }
+                let progress = &self.gen.progress;
+                if progress.pending + progress.running > 0 {
+                    return false;
                 }
-
                 self.already_started = StartStatus::Finished;
                 true
             }
         }
     }
+
+    pub fn finish_state(&self) -> FinishState {
+        let progress = &self.gen.progress;
+        let finished = match self.already_started {
+            StartStatus::NotStarted => false,
+            StartStatus::Finished => true,
+            StartStatus::Running => progress.pending + progress.running == 0,
+        };
+        if !finished {
+            FinishState::NotFinished
+        } else if self.contract_violated {
+            FinishState::ContractViolation
+        } else if progress.aborted > 0 {
+            FinishState::Aborted
+        } else if progress.failed + progress.upstream_failed > 0 {
+            FinishState::FinishedWithFailures
+        } else if self.jobs.iter().all(|job| job.started_at.is_none()) {
+            FinishState::NothingToDo
+        } else {
+            FinishState::Success
+        }
+    }
+
+    /// Groups of jobs depending on each other in a circle (sorted job ids),
+    /// which event_startup would refuse.
+    #[cfg(feature = "petgraph-backend")]
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut res: Vec<Vec<String>> = petgraph::algo::tarjan_scc(self.dag.graphmap())
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut ids: Vec<String> = component
+                    .into_iter()
+                    .map(|idx| self.jobs[idx].job_id.clone())
+                    .collect();
+                ids.sort();
+                ids
+            })
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// propagation passes so far, with node visits and time spent
+    pub fn evaluation_stats(&self) -> &EvaluationStats {
+        &self.evaluation_stats
+    }
+
+    /// entries and interned values of the loaded history
+    pub fn history_stats(&self) -> HistoryStoreStats {
+        self.history.stats()
+    }
+
+    /// how many jobs are in which state
+    pub fn progress(&self) -> Progress {
+        self.gen.progress.clone()
+    }
     pub fn debug_is_finished(&self) -> bool {
         for job in self.jobs.iter() {
             if !job.state.is_finished() {
@@ -512,8 +1303,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         }
         out.push_str("\n\nEdges:\n");
         for (upstream_idx, downstream_idx, weight) in dag.all_edges() {
-            let upstream_id = &jobs[upstream_idx as usize].job_id;
-            let downstream_id = &jobs[downstream_idx as usize].job_id;
+            let upstream_id = &jobs[upstream_idx].job_id;
+            let downstream_id = &jobs[downstream_idx].job_id;
             out.push_str(&format!(
                 "({}({})->{}({}): {:?}\n",
                 upstream_id, upstream_idx, downstream_id, downstream_idx, weight.required
@@ -521,16 +1312,18 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         }
 
         out.push_str("\n\nin code: \n");
-        for (_job_idx, job) in jobs.iter().enumerate() {
-            out.push_str(&format!(
-                "g.add_node(\"{}\", JobKind::{});\n",
-                job.job_id,
-                match job.state {
-                    JobState::Always(_) => "Always",
-                    JobState::Ephemeral(_) => "Ephemeral",
-                    JobState::Output(_) => "Output",
-                }
-            ));
+        for job in jobs.iter() {
+            match &job.invariant {
+                Some(value) => out.push_str(&format!(
+                    "g.add_invariant(\"{}\", {:?}).unwrap();\n",
+                    job.job_id, value
+                )),
+                None => out.push_str(&format!(
+                    "g.add_node(\"{}\", JobKind::{:?}).unwrap();\n",
+                    job.job_id,
+                    job.kind()
+                )),
+            }
         }
         out.push_str("let edges = vec![\n");
         for (upstream_idx, downstream_idx, _weight) in dag.all_edges() {
@@ -543,7 +1336,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         out.push_str(
             "for (a,b) in edges {
             if g.contains_node(a) && g.contains_node(b){
-                g.depends_on(a,b);
+                g.depends_on(a,b).unwrap();
             }
         }
         ",
@@ -552,37 +1345,49 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         out
     }
 
-    #[allow(clippy::ptr_arg)]
-    #[allow(dead_code)]
-    pub fn verify_order_was_topological(&self, order: &Vec<String>) -> bool {
-        /*
-        Iterate through the edges in G. For each edge, retrieve the index of each of its vertices
-            in the ordering. Compared the indices. If the origin vertex isn't earlier than the
-            destination vertex, return false. If you iterate through all of the edges without
-            returning false, return true.
-            */
-        for (upstream_idx, downstream_idx, _) in self.dag.all_edges() {
-            let upstream_id = &self.jobs[upstream_idx as usize].job_id;
-            let downstream_id = &self.jobs[downstream_idx as usize].job_id;
-            let upstream_in_order = order.iter().position(|x| x == upstream_id);
-            let downstream_in_order = order.iter().position(|x| x == downstream_id);
-            match (upstream_in_order, downstream_in_order) {
-                (None, None) => {}
-                (None, Some(_)) => {}
-                (Some(_), None) => {}
-                (Some(u), Some(o)) => {
-                    if u > o {
-                        return false;
-                    }
-                }
-            }
+    /// Is the output of job_id present?
+    /// Asks the strategy once per run - see invalidate_presence.
+    pub fn query_output_already_present(
+        &mut self,
+        job_id: &str,
+    ) -> Result<bool, PPGEvaluatorError> {
+        Ok(Self::cached_output_already_present(
+            &self.strategy,
+            &mut self.presence_cache,
+            job_id,
+        )?)
+    }
+
+    /// Forget the cached presence of job_id (and of multi file jobs containing it).
+    /// For executors creating / removing outputs outside of job completion events.
+    pub fn invalidate_presence(&mut self, job_id: &str) {
+        self.presence_cache
+            .retain(|key, _| key != job_id && !key.split(":::").any(|part| part == job_id));
+    }
+
+    fn cached_output_already_present(
+        strategy: &T,
+        presence_cache: &mut HashMap<String, bool>,
+        job_id: &str,
+    ) -> Result<bool, crate::StrategyError> {
+        if let Some(present) = presence_cache.get(job_id) {
+            return Ok(*present);
         }
-        true
+        let present = strategy.output_already_present(job_id)?;
+        presence_cache.insert(job_id.to_string(), present);
+        Ok(present)
     }
 
     /// what jobs are ready to run *right now*
+    /// Jobs the executor may start now.
+    /// Exclusive jobs are only returned alone, when nothing else is running.
+    /// See query_ready_to_run_ordered for the scheduling policy's order.
     pub fn query_ready_to_run(&self) -> HashSet<String> {
-        self.jobs_ready_to_run.clone()
+        match self.policy {
+            None => self.schedulable_ready_to_run(),
+            // it may hold back some
+            Some(_) => self.query_ready_to_run_ordered().into_iter().collect(),
+        }
     }
 
     pub fn query_jobs_running(&self) -> HashSet<String> {
@@ -597,10 +1402,45 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             .collect()
     }
 
+    /// running jobs and for how long they have been (since event_now_running)
+    pub fn query_running_durations(&self) -> HashMap<String, Duration> {
+        if self.gen.progress.running == 0 {
+            return HashMap::new();
+        }
+        self.jobs
+            .iter()
+            .filter(|job| {
+                matches!(
+                    job.state,
+                    JobState::Always(JobStateAlways::Running)
+                        | JobState::Output(JobStateOutput::Running)
+                        | JobState::Ephemeral(JobStateEphemeral::Running(_))
+                )
+            })
+            .map(|job| {
+                (
+                    job.job_id.clone(),
+                    job.started_at
+                        .map(|s| self.elapsed_since(s))
+                        .unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    /// ephemerals whose downstreams are done - minus the pinned / retained ones
     pub fn query_ready_for_cleanup(&self) -> HashSet<String> {
-        self.jobs_ready_for_cleanup.clone()
+        if self.cleanup.pinned.is_empty() && self.cleanup.retention.is_empty() {
+            return self.jobs_ready_for_cleanup.clone();
+        }
+        self.jobs_ready_for_cleanup
+            .iter()
+            .filter(|job_id| !self.is_held_back_from_cleanup(job_id))
+            .cloned()
+            .collect()
     }
 
+    /// ids only - query_failed_info has attempts, timing and upstream fingerprints
     #[allow(dead_code)] // used in testing
     pub fn query_failed(&self) -> HashSet<String> {
         // not worth keeping a list to prevent the scanning
@@ -638,7 +1478,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         jobs: &[NodeInfo],
         job_idx: NodeIndex,
     ) -> bool {
-        match jobs[job_idx as usize].state {
+        match jobs[job_idx].state {
             JobState::Ephemeral(_) => {}
             JobState::Always(_) | JobState::Output(_) => {
                 //    debug!("was not ephemeral {}", &self.jobs[job_idx as usize].job_id);
@@ -665,6 +1505,9 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 panic!("Graph wasn't finished, not handing out history..."); // todo: actually, why not, we could save history occasionally?
             }
         }
+        if let Some(history) = &self.resume_history {
+            return Ok(history.to_hash_map());
+        }
         //our history 'keys'
         //(we can't do tuple indices because of json history-save-format.)
         //no !!! -> job output.
@@ -694,13 +1537,13 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     None => true, //not present.
                 }
             } else {
-                return true;
+                true
             }
         };
 
-        let mut out = self.history.clone();
-        let mut out: HashMap<_, _> = out
-            .drain()
+        let mut out: HashMap<_, _> = self
+            .history
+            .iter()
             .filter(|(k, _v)| {
                 if k.contains("!!!") {
                     let (job_id_a, job_id_b) = k.split_once("!!!").unwrap();
@@ -728,6 +1571,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     filter_if_renamed(k)
                 }
             })
+            .map(|(k, v)| (k.clone(), v.to_string()))
             .collect();
 
         for (idx, job) in self.jobs.iter().enumerate() {
@@ -747,7 +1591,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 // if the job did not succeed, we want it to rerun!
                 out.insert(
                     input_name_key,
-                    self.strategy.get_input_list(idx, &self.dag, &self.jobs),
+                    self.strategy.get_input_list(idx, &self.dag, &self.jobs)?,
                 );
 
                 let history = match &job.history_output {
@@ -802,19 +1646,18 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
 
         // record the edges
         for (a, b, _weight) in self.dag.all_edges() {
-            let job_id_a = &self.jobs[a as usize].job_id;
-            let job_id_b = &self.jobs[b as usize].job_id;
+            let job_id_a = &self.jobs[a].job_id;
+            let job_id_b = &self.jobs[b].job_id;
             let key = format!("{}!!!{}", job_id_a, job_id_b);
-            let history = self.jobs[a as usize].history_output.as_ref();
-            let second_job_success = self.jobs[b as usize].history_output.is_some()
-                || self.jobs[b as usize].state
-                    == JobState::Ephemeral(JobStateEphemeral::FinishedSkipped);
+            let history = self.jobs[a].history_output.as_ref();
+            let second_job_success = self.jobs[b].history_output.is_some()
+                || self.jobs[b].state == JobState::Ephemeral(JobStateEphemeral::FinishedSkipped);
             if second_job_success {
                 // we do not store the history link if the second job failed.
                 let history = match history {
                     Some(run_history) => run_history, // we have new history.
                     None => {
-                        match self.jobs[a as usize].state {
+                        match self.jobs[a].state {
                             JobState::Output(JobStateOutput::FinishedSkipped)
                             | JobState::Ephemeral(JobStateEphemeral::FinishedSkipped) => {
                                 match self.history.get(&key) {
@@ -825,7 +1668,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                         let job_key = job_id_a;
                                         match self.history.get(job_key) {
                                             Some(old_run_history) => old_run_history,
-                                            None => match (self.jobs[a as usize]).state {
+                                            None => match (self.jobs[a]).state {
                                                 JobState::Ephemeral(_) => {
                                                     continue;
                                                 }
@@ -855,7 +1698,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                             _ => {
                                 return Err(PPGEvaluatorError::InternalError(format!(
                                     "unexpected ran state when no history was present. job_id: '{}', ran state: '{:?}'",
-                                    job_id_a, self.jobs[a as usize].state
+                                    job_id_a, self.jobs[a].state
                                 )));
                             }
                         }
@@ -864,6 +1707,17 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 out.insert(key, history.to_string());
             }
         }
+        self.record_retention(&mut out);
+        self.record_generated_jobs(&mut out);
+        self.record_runtimes(&mut out);
+        self.record_gated_runs(&mut out);
+        self.record_shared_output_producers(&mut out);
+        self.record_last_successes(&mut out);
+        self.apply_aliases_to_history(&mut out);
+        out.insert(
+            HISTORY_VERSION_KEY.to_string(),
+            HISTORY_SCHEMA_VERSION.to_string(),
+        );
 
         Ok(out)
     }
@@ -872,7 +1726,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         let job_idx = self.job_id_to_node_idx.get(job_id);
         match job_idx {
             None => JobOutputResult::NoSuchJob,
-            Some(job_idx) => match &self.jobs[*job_idx as usize].history_output {
+            Some(job_idx) => match &self.jobs[*job_idx].history_output {
                 Some(v) => JobOutputResult::Done(v.to_string()),
                 None => JobOutputResult::NotDone,
             },
@@ -880,6 +1734,13 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     }
 
     pub fn event_startup(&mut self) -> Result<(), PPGEvaluatorError> {
+        let res = self.startup(false);
+        self.after_event("event_startup", None, res)
+    }
+
+    /// resume: keep what the finished run decided, unless it's affected by
+    /// a change since (see jobs_to_reevaluate)
+    fn startup(&mut self, resume: bool) -> Result<(), PPGEvaluatorError> {
         match self.already_started {
             StartStatus::Running | StartStatus::Finished => {
                 return Err(PPGEvaluatorError::APIError("Can't start twice".to_string()));
@@ -887,33 +1748,244 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             _ => {}
         };
         self.already_started = StartStatus::Running;
-
+        self.cleanup.stats.new_run();
+        if HistoryMigrator::parse_version(self.history.get(HISTORY_VERSION_KEY))?
+            != HISTORY_SCHEMA_VERSION
+        {
+            let mut history = self.history.to_hash_map();
+            self.migrator.migrate(&mut history)?;
+            self.history = history.into();
+        }
+        self.resolve_aliases_in_history();
+
+        self.resolve_global_invariant_subscriptions();
+        self.resolve_soft_dependencies();
+        self.check_absences()?;
+        let nodes: HashSet<NodeIndex> = if resume {
+            let nodes = self.jobs_to_reevaluate()?;
+            self.reset_jobs(&nodes);
+            nodes
+        } else {
+            (0..self.jobs.len()).collect()
+        };
+        self.dag.freeze();
         self.prune_leave_ephemerals();
+        self.update_components();
 
-        // this is not particulary fast.
-        self.topo = Some(petgraph::algo::toposort(&self.dag, None).unwrap());
+        self.topo =
+            Some(self.dag.toposort().map_err(|node_idx| {
+                PPGEvaluatorError::Cycle(self.jobs[node_idx].job_id.clone())
+            })?);
         //self.identify_changed_input_counts();
-        self.identify_missing_outputs()?;
+        self.evaluate_nodes(&nodes)
+    }
+
+    /// Start evaluating nodes not considered so far - all of them on startup,
+    /// jobs spliced into a running evaluation later on.
+    fn evaluate_nodes(&mut self, nodes: &HashSet<NodeIndex>) -> Result<(), PPGEvaluatorError> {
+        self.identify_missing_outputs(nodes)?;
         self.process_signals(0)?; //or they're not correctly invalidated...
                                   //self.fill_in_unfinished_downstream_counts();
                                   //self.update();
-        self.start_on_roots();
+        self.start_on_roots(nodes);
         self.process_signals(0)?;
+        self.finish_invariants()?;
+        self.finish_engine_jobs()?;
+        self.check_stalled()
+    }
+
+    /// Finish the jobs the engine decides itself - skipped conditionals, gated
+    /// jobs, losing shared output producers, present TempFiles and barriers.
+    /// After the events.
+    ///
+    /// Finishing one is an event, which may make more of them ready. That
+    /// nested event leaves them to the outermost call's loop instead of
+    /// starting another one, so chains of them don't recurse.
+    fn finish_engine_jobs(&mut self) -> Result<(), PPGEvaluatorError> {
+        if self.finishing_engine_jobs {
+            return Ok(());
+        }
+        self.finishing_engine_jobs = true;
+        let res = self.finish_engine_jobs_until_settled();
+        self.finishing_engine_jobs = false;
+        res
+    }
+
+    fn finish_engine_jobs_until_settled(&mut self) -> Result<(), PPGEvaluatorError> {
+        loop {
+            let gen = self.gen.gen;
+            self.decide_conditionals()?;
+            self.decide_gates()?;
+            self.decide_shared_outputs()?;
+            self.decide_temp_files()?;
+            self.finish_barriers()?;
+            if self.gen.gen == gen {
+                return Ok(());
+            }
+        }
+    }
 
+    /// Invariants have no upstreams, so they are ready right after startup.
+    /// They 'run' right away, with their given value as output.
+    fn finish_invariants(&mut self) -> Result<(), PPGEvaluatorError> {
+        let invariants: Vec<(String, String)> = self
+            .jobs
+            .iter()
+            .filter_map(|job| {
+                job.invariant
+                    .as_ref()
+                    .filter(|_| self.jobs_ready_to_run.contains(&job.job_id))
+                    .map(|value| (job.job_id.clone(), value.clone()))
+            })
+            .collect();
+        for (job_id, value) in invariants {
+            if self.absence.is_present(&job_id) {
+                self.fail_present_absence(&job_id)?;
+                continue;
+            }
+            self.event_now_running(&job_id)?;
+            self.event_job_finished_success(&job_id, value)?;
+        }
         Ok(())
     }
 
+    /// Continue a finished evaluation after jobs / edges were added.
+    /// Only the jobs affected by the changes are evaluated again, against the
+    /// history of the finished run - new, invalidated or failed jobs, what's
+    /// downstream of them, and the ephemerals they need. The other jobs keep
+    /// their finished state, finished Always jobs don't run again.
+    pub fn event_resume(&mut self) -> Result<(), PPGEvaluatorError> {
+        if self.already_started != StartStatus::Finished {
+            return Err(PPGEvaluatorError::APIError(
+                "Can only resume a finished evaluation".to_string(),
+            ));
+        }
+        self.history = match self.resume_history.take() {
+            Some(history) => history,
+            None => self.new_history()?.into(),
+        };
+        self.dag.restore_removed();
+        self.gen.advance();
+        self.jobs_ready_to_run.clear();
+        self.topo = None;
+        // outputs may have been changed by the finished run
+        self.presence_cache.clear();
+        // the world has been rebuilt once already
+        self.force_rerun_all = false;
+        self.contract_violated = false;
+        self.speculation.new_run();
+        self.conditional.new_run();
+        self.gating.new_run();
+        self.barriers.new_run();
+        self.temp_files.new_run();
+        self.shared_outputs.new_run();
+        self.run_reasons.new_run();
+        self.event_order.new_run();
+        self.duplicates.new_run();
+        self.already_started = StartStatus::NotStarted;
+        let res = self.startup(true);
+        self.after_event("event_resume", None, res)
+    }
+
+    /// Jobs a resumed evaluation has to evaluate again: unfinished or failed
+    /// ones, those whose output or input list doesn't match the history
+    /// anymore (new upstreams, external changes, new invariant values), and
+    /// everything downstream of them. Plus the ephemerals these depend on -
+    /// they may have to run again.
+    fn jobs_to_reevaluate(&self) -> Result<HashSet<NodeIndex>, PPGEvaluatorError> {
+        let mut todo = Vec::new();
+        for node_idx in 0..self.jobs.len() {
+            if self.changed_since_finish(node_idx)? {
+                todo.push(node_idx);
+            }
+        }
+        let mut res = HashSet::new();
+        while let Some(node_idx) = todo.pop() {
+            if res.insert(node_idx) {
+                todo.extend(self.dag.neighbors_directed(node_idx, Direction::Outgoing));
+            }
+        }
+        let mut todo: Vec<NodeIndex> = res.iter().copied().collect();
+        while let Some(node_idx) = todo.pop() {
+            for upstream_idx in self.dag.neighbors_directed(node_idx, Direction::Incoming) {
+                if matches!(self.jobs[upstream_idx].state, JobState::Ephemeral(_))
+                    && res.insert(upstream_idx)
+                {
+                    todo.push(upstream_idx);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    fn changed_since_finish(&self, node_idx: NodeIndex) -> Result<bool, PPGEvaluatorError> {
+        let job = &self.jobs[node_idx];
+        if !job.state.is_finished() || job.state.is_failed() {
+            return Ok(true);
+        }
+        if let Some(value) = &job.invariant {
+            return Ok(job.history_output.as_ref() != Some(value));
+        }
+        Ok(match &job.history_output {
+            Some(output) => {
+                self.history.get(&job.job_id) != Some(output.as_str())
+                    || self.history.get(&format!("{}!!!", job.job_id))
+                        != Some(
+                            self.strategy
+                                .get_input_list(node_idx, &self.dag, &self.jobs)?
+                                .as_str(),
+                        )
+            }
+            // skipped ephemeral that never ran
+            None => false,
+        })
+    }
+
+    /// Back to their initial state, for a resumed evaluation
+    fn reset_jobs(&mut self, nodes: &HashSet<NodeIndex>) {
+        let edges: Vec<(NodeIndex, NodeIndex)> = self
+            .dag
+            .all_edges()
+            .map(|(from, to, _)| (from, to))
+            .collect();
+        for (from, to) in edges {
+            let weight = self.dag.edge_weight_mut(from, to).unwrap();
+            if nodes.contains(&to) {
+                weight.required = Required::Unknown;
+                weight.invalidated = Required::Unknown;
+            } else if nodes.contains(&from) {
+                // the downstream is done
+                weight.required = Required::No;
+            }
+        }
+        for &node_idx in nodes {
+            let job = &mut self.jobs[node_idx];
+            let state = Self::initial_state(job.state.kind());
+            self.gen.progress.transition(&job.state, &state);
+            job.state = state;
+            job.history_output = None;
+            job.last_considered_in_gen = 0;
+            job.started_at = None;
+            job.ready_at = None;
+            job.runtime = None;
+            job.error = None;
+        }
+        let job_id_to_node_idx = &self.job_id_to_node_idx;
+        self.jobs_ready_for_cleanup
+            .retain(|job_id| !nodes.contains(&job_id_to_node_idx[job_id]));
+    }
+
     fn prune_leave_ephemerals(&mut self) {
         //option: speed up by looking at the edges once,
         //finding those that have no-no-ephemeral downstreams,
         let mut ephemerals: HashSet<NodeIndex> = self
             .dag
             .nodes()
-            .filter(|idx| match self.jobs[*idx as usize].state {
+            .filter(|idx| match self.jobs[*idx].state {
                 JobState::Ephemeral(_) => {
                     let downstreams = self.dag.neighbors_directed(*idx, Direction::Outgoing);
                     for downstream_idx in downstreams {
-                        match self.jobs[downstream_idx as usize].state {
+                        match self.jobs[downstream_idx].state {
                             JobState::Ephemeral(_) => {}
                             _ => {
                                 return false;
@@ -930,14 +2002,23 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         loop {
             let candidates: Vec<NodeIndex> = ephemerals
                 .iter()
-                .map(|x| *x)
+                .copied()
                 .filter(|idx| !Self::has_downstreams(&self.dag, *idx))
                 .collect();
 
             for idx in candidates.iter() {
                 debug!("removed leaf ephemeral {}", self.jobs[*idx].job_id);
                 self.dag.remove_node(*idx);
-                self.jobs[*idx].state = JobState::Ephemeral(JobStateEphemeral::FinishedSkipped);
+                // kept from the finished run, on resume
+                if self.jobs[*idx].state.is_finished() {
+                    ephemerals.remove(idx);
+                    continue;
+                }
+                let new_state = JobState::Ephemeral(JobStateEphemeral::FinishedSkipped);
+                self.gen
+                    .progress
+                    .transition(&self.jobs[*idx].state, &new_state);
+                self.jobs[*idx].state = new_state;
                 ephemerals.remove(idx);
             }
 
@@ -948,9 +2029,11 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     }
 
     pub fn event_now_running(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
-        let idx = self.job_id_to_node_idx.get(job_id).expect("Unknown job id");
-        let j = &mut self.jobs[*idx as usize];
-        match j.state {
+        let idx = self.known_idx(job_id)?;
+        let _job_scope = self.jobs[idx].enter();
+        debug!("job running");
+        let j = &mut self.jobs[idx];
+        let res = match j.state {
             JobState::Always(JobStateAlways::ReadyToRun) => {
                 self.jobs_ready_to_run.remove(job_id);
                 set_node_state!(j, JobState::Always(JobStateAlways::Running), self.gen);
@@ -974,7 +2057,13 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 "Requested to run a job that was not ready to run! {:?}",
                 j
             ))),
+        };
+        if res.is_ok() {
+            j.started_at = Some(self.clock.now());
+            self.record_rate_limited_start(job_id);
+            self.event_order.started(job_id);
         }
+        self.after_event("event_now_running", Some(job_id), res)
     }
 
     pub fn event_job_finished_success(
@@ -982,18 +2071,35 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         job_id: &str,
         history_to_store: String,
     ) -> Result<(), PPGEvaluatorError> {
-        let node_idx = *self.job_id_to_node_idx.get(job_id).expect("Unknown job id");
-        let j = &self.jobs[node_idx as usize];
+        let seq = self.event_order.next_seq();
+        let res = self.job_finished_success(job_id, history_to_store);
+        if res.is_ok() {
+            self.event_order.finished(job_id, seq);
+        }
+        self.after_event("event_job_finished_success", Some(job_id), res)
+    }
+
+    fn job_finished_success(
+        &mut self,
+        job_id: &str,
+        history_to_store: String,
+    ) -> Result<(), PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        let _job_scope = self.jobs[node_idx].enter();
+        if self.speculative_finish(job_id, true)
+            || self.duplicate_finish(job_id, Some(&history_to_store))?
+        {
+            return Ok(());
+        }
+        debug!("job finished");
+        // it (presumably) created its outputs
+        self.invalidate_presence(job_id);
+        let j = &self.jobs[node_idx];
         match j.state {
             JobState::Always(JobStateAlways::Running)
             | JobState::Output(JobStateOutput::Running)
             | JobState::Ephemeral(JobStateEphemeral::Running(_)) => {}
-            _ => {
-                return Err(PPGEvaluatorError::APIError(format!(
-                    "Reported a job as finished that was not running ! {:?}",
-                    j
-                )))
-            }
+            _ => return Err(PPGEvaluatorError::JobNotRunning(format!("{:?}", j))),
         }
         if j.state == JobState::Ephemeral(JobStateEphemeral::Running(ValidationStatus::Validated)) {
             // changing your output when you were Validated is not allowed.
@@ -1016,10 +2122,12 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 // we have to check for actually altered history.
                 // the timestamp may change, but the hash not...
                 // any would do
-                if self
-                    .strategy
-                    .is_history_altered(job_id, "!!!", job_history, &history_to_store)
-                {
+                if self.strategy.is_history_altered(
+                    job_id,
+                    "!!!",
+                    job_history,
+                    &history_to_store,
+                )? {
                     self.signals.push_back(NewSignal!(
                         SignalKind::JobFinishedFailure,
                         node_idx,
@@ -1030,6 +2138,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         last_history: job_history.to_string(),
                         new_history: history_to_store,
                     };
+                    self.contract_violated = true;
                     self.process_signals(0)?;
                     return Err(my_err);
                 }
@@ -1103,7 +2212,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             */
         }
 
-        let j = &mut self.jobs[node_idx as usize];
+        let j = &mut self.jobs[node_idx];
         j.history_output = Some(history_to_store);
 
         self.signals.push_back(NewSignal!(
@@ -1112,34 +2221,89 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             self.jobs
         ));
         self.process_signals(0)?;
-        Ok(())
+        self.finish_engine_jobs()?;
+        self.check_stalled()?;
+        self.replan_if_queued()
     }
 
     pub fn event_job_finished_failure(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
-        let idx = *self.job_id_to_node_idx.get(job_id).expect("Unknown job id");
-        let j = &mut self.jobs[idx as usize];
+        let seq = self.event_order.next_seq();
+        let res = self.job_finished_failure(job_id, None);
+        if res.is_ok() {
+            self.event_order.finished(job_id, seq);
+        }
+        self.after_event("event_job_finished_failure", Some(job_id), res)
+    }
+
+    /// Like event_job_finished_failure, but keep the executor's error payload
+    /// (exception / traceback) around for reporting.
+    pub fn event_job_finished_failure_with_error(
+        &mut self,
+        job_id: &str,
+        error: String,
+    ) -> Result<(), PPGEvaluatorError> {
+        let seq = self.event_order.next_seq();
+        let res = self.job_finished_failure(job_id, Some(error));
+        if res.is_ok() {
+            self.event_order.finished(job_id, seq);
+        }
+        self.after_event("event_job_finished_failure_with_error", Some(job_id), res)
+    }
+
+    fn job_finished_failure(
+        &mut self,
+        job_id: &str,
+        error: Option<String>,
+    ) -> Result<(), PPGEvaluatorError> {
+        let idx = self.known_idx(job_id)?;
+        let _job_scope = self.jobs[idx].enter();
+        if self.speculative_finish(job_id, false) || self.duplicate_finish(job_id, None)? {
+            return Ok(());
+        }
+        debug!(error = ?error, "job failed");
+        let j = &mut self.jobs[idx];
         match j.state {
             JobState::Always(JobStateAlways::Running)
             | JobState::Output(JobStateOutput::Running)
             | JobState::Ephemeral(JobStateEphemeral::Running(_)) => {}
-            _ => {
-                return Err(PPGEvaluatorError::APIError(format!(
-                    "Reported a job as finished that was not running ! {:?}",
-                    j
-                )))
-            }
+            _ => return Err(PPGEvaluatorError::JobNotRunning(format!("{:?}", j))),
         }
+        if self.hold_shared_output_failure(job_id, &error) {
+            return Ok(());
+        }
+        let j = &mut self.jobs[idx];
+        j.error = error;
         self.signals
             .push_back(NewSignal!(SignalKind::JobFinishedFailure, idx, self.jobs));
         self.process_signals(0)?;
-        Ok(())
+        self.finish_engine_jobs()?;
+        self.check_stalled()?;
+        self.replan_if_queued()
     }
 
     pub fn event_job_cleanup_done(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
-        let idx = *self.job_id_to_node_idx.get(job_id).expect("Unknown job id");
-        let j = &mut self.jobs[idx as usize];
+        let res = self.cleanup_done(job_id, None);
+        self.after_event("event_job_cleanup_done", Some(job_id), res)
+    }
+
+    fn cleanup_done(
+        &mut self,
+        job_id: &str,
+        bytes_freed: Option<u64>,
+    ) -> Result<(), PPGEvaluatorError> {
+        let idx = self.known_idx(job_id)?;
+        let _job_scope = self.jobs[idx].enter();
+        if self.is_held_back_from_cleanup(job_id) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} is pinned / retained, not cleaning it up",
+                job_id
+            )));
+        }
+        let j = &mut self.jobs[idx];
         match j.state {
             JobState::Ephemeral(JobStateEphemeral::FinishedSuccessReadyForCleanup) => {
+                debug!(bytes_freed = ?bytes_freed, "job cleaned up");
+                self.cleanup.stats.cleaned_up(bytes_freed);
                 self.signals
                     .push_back(NewSignal!(SignalKind::JobCleanedUp, idx, self.jobs));
                 self.process_signals(0)?;
@@ -1154,6 +2318,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     }
 
     fn process_signals(&mut self, depth: u32) -> Result<(), PPGEvaluatorError> {
+        let _pass = debug_span!("evaluation_pass", depth).entered();
+        let _scope = LogScope::new(self.log_level, None);
         debug!("");
         debug!("Process signals, depth {}", depth);
         let res = self.inner_process_signals(depth);
@@ -1165,6 +2331,9 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         if depth > 1500 {
             return Err(PPGEvaluatorError::InternalError("Depth ConsiderJob loop. Either pathological input, or bug. Aborting to avoid stack overflow".to_string()));
         }
+        let pass_started = Instant::now();
+        let now = self.clock.now();
+        let nodes_visited = self.signals.len();
         let mut new_signals = Vec::new();
         let mut ignore_consider_signals = HashSet::new();
         for signal in self.signals.drain(..) {
@@ -1173,12 +2342,13 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 "\tHandling {:?} for {}. Current state: {:?}",
                 signal,
                 signal.job_id(&self.jobs),
-                self.jobs[signal.node_idx as usize].state
+                self.jobs[signal.node_idx].state
             );
             let node_idx = signal.node_idx;
+            let _job_scope = self.jobs[node_idx].enter();
             match signal.kind {
                 SignalKind::JobReadyToRun => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     match j.state {
                         JobState::Always(state) => match state {
                             JobStateAlways::Undetermined => {
@@ -1228,10 +2398,25 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                             }
                         },
                     }
+                    j.ready_at = Some(now);
                     self.jobs_ready_to_run.insert(j.job_id.clone());
+                    self.scheduling.became_ready(&j.job_id);
+                    if j.barrier {
+                        self.barriers.became_ready(&j.job_id);
+                    } else if j.conditional {
+                        self.conditional.became_ready(&j.job_id);
+                    } else if j.temp_file
+                        && j.state
+                            == JobState::Ephemeral(JobStateEphemeral::ReadyToRun(
+                                ValidationStatus::Validated,
+                            ))
+                    {
+                        self.temp_files.became_ready(&j.job_id);
+                    }
+                    self.gating.became_ready(&j.job_id);
                 }
                 SignalKind::JobFinishedSkip => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     match j.state {
                         JobState::Always(_) => {
                             return Err(PPGEvaluatorError::InternalError(
@@ -1260,7 +2445,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                     }
                                 }
                                 Self::reconsider_delayed_upstreams(
-                                    &mut self.dag,
+                                    &self.dag,
                                     &mut self.jobs,
                                     node_idx,
                                     &mut new_signals,
@@ -1282,7 +2467,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                     JobState::Ephemeral(JobStateEphemeral::FinishedSkipped),
                                     self.gen
                                 );
-                                j.history_output = self.history.get(&j.job_id).cloned();
+                                j.history_output =
+                                    self.history.get(&j.job_id).map(|x| x.to_string());
                             }
                             JobStateEphemeral::NotReady(ValidationStatus::Invalidated) => {
                                 set_node_state!(
@@ -1290,7 +2476,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                     JobState::Ephemeral(JobStateEphemeral::FinishedSkipped),
                                     self.gen
                                 );
-                                j.history_output = self.history.get(&j.job_id).cloned();
+                                j.history_output =
+                                    self.history.get(&j.job_id).map(|x| x.to_string());
                                 // yes the assert would be better above the state setting
                                 // but the borrow checker disagrees
                                 assert!(
@@ -1312,7 +2499,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 }
                 SignalKind::JobDone => {
                     // todo : call directly from JobFinished*
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     if !j.state.is_finished() {
                         return Err(PPGEvaluatorError::InternalError(
                             "job_done on job not finished".to_string(),
@@ -1338,7 +2525,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     );
                 }
                 SignalKind::JobFinishedSuccess => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     match j.state {
                         JobState::Always(JobStateAlways::Running) => {
                             set_node_state!(
@@ -1370,10 +2557,13 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                             )))
                         }
                     }
+                    j.runtime = j
+                        .started_at
+                        .map(|started| now.saturating_duration_since(started));
                     new_signals.push(NewSignal!(SignalKind::JobDone, node_idx, self.jobs));
                 }
                 SignalKind::JobFinishedFailure => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     match j.state {
                         JobState::Always(JobStateAlways::Running) => {
                             set_node_state!(
@@ -1403,6 +2593,9 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                             )))
                         }
                     }
+                    j.runtime = j
+                        .started_at
+                        .map(|started| now.saturating_duration_since(started));
                     new_signals.push(NewSignal!(SignalKind::JobDone, node_idx, self.jobs));
                     let downstreams = self.dag.neighbors_directed(node_idx, Direction::Outgoing);
                     for downstream_idx in downstreams {
@@ -1414,13 +2607,26 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     }
                 }
                 SignalKind::JobUpstreamFailure => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     match j.state {
                         JobState::Always(JobStateAlways::FinishedUpstreamFailure)
                         | JobState::Output(JobStateOutput::FinishedUpstreamFailure)
                         | JobState::Ephemeral(JobStateEphemeral::FinishedUpstreamFailure) => {
                             //ignore
                         }
+                        JobState::Always(JobStateAlways::Undetermined)
+                            if j.run_even_if_upstreams_failed =>
+                        {
+                            // runs once all upstreams are done, failed or not
+                            debug!("{} runs despite failed upstreams", j.job_id);
+                            ignore_consider_signals.remove(&node_idx);
+                            new_signals.push(NewSignal!(
+                                SignalKind::ConsiderJob,
+                                node_idx,
+                                self.jobs
+                            ));
+                            continue;
+                        }
                         JobState::Always(JobStateAlways::Undetermined) => {
                             set_node_state!(
                                 j,
@@ -1493,6 +2699,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         &mut self.dag,
                         &mut self.jobs,
                         &self.history,
+                        &mut self.change_filter,
                         node_idx,
                         &mut new_signals,
                         &mut self.gen,
@@ -1500,7 +2707,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     )?;
                 }
                 SignalKind::JobCleanedUp => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     match j.state {
                         JobState::Ephemeral(JobStateEphemeral::FinishedSuccessReadyForCleanup) => {
                             set_node_state!(
@@ -1519,7 +2726,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     }
                 }
                 SignalKind::JobAborted => {
-                    let j = &mut self.jobs[node_idx as usize];
+                    let j = &mut self.jobs[node_idx];
                     if !j.state.is_finished() {
                         match j.state {
                             JobState::Ephemeral(_) => {
@@ -1556,6 +2763,11 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             }
             //self.signals.extend(new_signals.drain(..));
         }
+        self.evaluation_stats.record(PassStats {
+            depth,
+            nodes_visited,
+            duration: pass_started.elapsed(),
+        });
         if !self.signals.is_empty() {
             self.process_signals(depth + 1)?;
         }
@@ -1615,7 +2827,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     ) -> Result<bool, PPGEvaluatorError> {
         let downstreams = dag.neighbors_directed(node_idx, Direction::Outgoing);
         for downstream_idx in downstreams {
-            match jobs[downstream_idx as usize].state {
+            match jobs[downstream_idx].state {
                 JobState::Always(_) => {
                     return Err(PPGEvaluatorError::InternalError(
                         "Unexpected. Should have been required in the first place, I believe"
@@ -1635,7 +2847,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 _ => {
                     return Err(PPGEvaluatorError::InternalError(format!(
                         "should not happen 1272 {:?}",
-                        jobs[downstream_idx as usize]
+                        jobs[downstream_idx]
                     )));
                 }
             }
@@ -1647,7 +2859,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         //there's no point caching this - it never get's called again if it ever returned true
         let upstreams = dag.neighbors_directed(node_idx, Direction::Incoming);
         for upstream_idx in upstreams {
-            if !jobs[upstream_idx as usize].state.is_finished() {
+            if !jobs[upstream_idx].state.is_finished() {
                 return false;
             }
         }
@@ -1667,7 +2879,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     fn try_finding_renamed_multi_output_job(
         missing_upstream_id: &str,
         downstream_id: &str,
-        history: &HashMap<String, String>,
+        history: &HistoryStore,
     ) -> Option<String> {
         // since multi file output jobs change their names
         // but we only invalidate based on the actual job inputs
@@ -1713,7 +2925,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         dag: &mut GraphType,
         strategy: &dyn PPGEvaluatorStrategy,
         jobs: &[NodeInfo],
-        history: &HashMap<String, String>,
+        history: &HistoryStore,
+        change_filter: &mut ChangeFilter,
         upstream_idx: NodeIndex,
         downstream_idx: NodeIndex,
     ) -> Result<bool, PPGEvaluatorError> {
@@ -1789,12 +3002,20 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                             .unwrap();
                         }
                         */
-                        if strategy.is_history_altered(
-                            upstream_id,
-                            downstream_id,
+                        let altered = match change_filter.fields_altered(
+                            &key,
                             &last_history_value,
                             current_value,
                         ) {
+                            Some(altered) => altered,
+                            None => strategy.is_history_altered(
+                                upstream_id,
+                                downstream_id,
+                                &last_history_value,
+                                current_value,
+                            )?,
+                        };
+                        if altered && !change_filter.ignores(&key) {
                             dag.edge_weight_mut(upstream_idx, downstream_idx)
                                 .unwrap()
                                 .invalidated = Required::Yes;
@@ -1827,7 +3048,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         strategy: &dyn PPGEvaluatorStrategy,
         dag: &mut GraphType,
         jobs: &[NodeInfo],
-        history: &HashMap<String, String>,
+        history: &HistoryStore,
+        change_filter: &mut ChangeFilter,
         node_idx: NodeIndex,
     ) -> Result<ValidationStatus, PPGEvaluatorError> {
         let upstreams: Vec<_> = dag
@@ -1836,11 +3058,11 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         let mut not_done = 0;
         let mut invalidated = false;
         for upstream_idx in upstreams {
-            if jobs[upstream_idx as usize].state.is_finished()
-                || (jobs[upstream_idx as usize].state
+            if jobs[upstream_idx].state.is_finished()
+                || (jobs[upstream_idx].state
                     == JobState::Output(JobStateOutput::NotReady(ValidationStatus::Validated)))
             {
-                if jobs[upstream_idx as usize].state.is_upstream_failure() {
+                if jobs[upstream_idx].state.is_upstream_failure() {
                     //this happens when the upstream job was UpstreamFailed,
                     //we do at this point have  JobUpstreamFailure(node_idx),
                     //but there's a ConsiderJob still in the self.signals
@@ -1849,7 +3071,15 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     //return Ok(ValidationStatus::Unknown);
                 }
                 // !jobs[upstream_idx as usize].state.is_skipped() &&
-                if Self::edge_invalidated(dag, strategy, jobs, history, upstream_idx, node_idx)? {
+                if Self::edge_invalidated(
+                    dag,
+                    strategy,
+                    jobs,
+                    history,
+                    change_filter,
+                    upstream_idx,
+                    node_idx,
+                )? {
                     debug!(
                         "\t\tEdge invalidated {}({})-> {}({})",
                         jobs[upstream_idx].job_id, upstream_idx, jobs[node_idx].job_id, node_idx
@@ -1861,9 +3091,9 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         jobs[upstream_idx].job_id, upstream_idx, jobs[node_idx].job_id, node_idx
                     );
                 }
-            } else if (jobs[upstream_idx as usize].state
+            } else if (jobs[upstream_idx].state
                 == JobState::Ephemeral(JobStateEphemeral::ReadyButDelayed))
-                || (jobs[upstream_idx as usize].state
+                || (jobs[upstream_idx].state
                     == JobState::Ephemeral(JobStateEphemeral::NotReady(
                         ValidationStatus::Validated,
                     )))
@@ -1911,7 +3141,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         }
                     }
                 }
-            } else if jobs[upstream_idx as usize].state
+            } else if jobs[upstream_idx].state
                 == JobState::Output(JobStateOutput::NotReady(ValidationStatus::Validated))
             {
                 //neither can an output job that is validated
@@ -1953,11 +3183,13 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn signal_consider_job(
         strategy: &dyn PPGEvaluatorStrategy,
         dag: &mut GraphType,
         jobs: &mut [NodeInfo],
-        history: &HashMap<String, String>,
+        history: &HistoryStore,
+        change_filter: &mut ChangeFilter,
         node_idx: NodeIndex,
         new_signals: &mut Vec<Signal>,
         gen: &mut Generation,
@@ -1968,10 +3200,10 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             "\tconsidering job {}: {:?}",
             jobs[node_idx as usize].job_id, jobs[node_idx as usize].state
         ); */
-        if jobs[node_idx as usize].state.is_finished() {
+        if jobs[node_idx].state.is_finished() {
             debug!("Considering an already finished job -> no-op");
         }
-        match jobs[node_idx as usize].state {
+        match jobs[node_idx].state {
             JobState::Always(JobStateAlways::Undetermined) => {
                 if Self::all_upstreams_done(dag, jobs, node_idx) {
                     Self::remove_consider_signals(new_signals, node_idx);
@@ -1984,7 +3216,12 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                 match validation_state {
                     ValidationStatus::Unknown => {
                         match Self::update_validation_status(
-                            strategy, dag, jobs, history, node_idx,
+                            strategy,
+                            dag,
+                            jobs,
+                            history,
+                            change_filter,
+                            node_idx,
                         )? {
                             ValidationStatus::Unknown => {
                                 debug!("\tstill unknown validation status");
@@ -2020,7 +3257,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                     }
                                     ValidationStatus::Invalidated => {
                                         set_node_state!(
-                                            jobs[node_idx as usize],
+                                            jobs[node_idx],
                                             JobState::Output(JobStateOutput::NotReady(solid_vs)),
                                             gen
                                         );
@@ -2080,27 +3317,23 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
             }
             JobState::Output(_) => {}
             JobState::Ephemeral(state) => match state {
-                JobStateEphemeral::NotReady(ValidationStatus::Invalidated) => {
-                    if Self::all_upstreams_done(dag, jobs, node_idx) {
-                        if Self::has_downstreams(dag, node_idx)
-                            && !Self::_job_and_downstreams_are_ephemeral(dag, jobs, node_idx)
-                        {
-                            Self::remove_consider_signals(new_signals, node_idx);
-                            ignore_consider_signals.insert(node_idx);
-                            new_signals.push(NewSignal!(SignalKind::JobReadyToRun, node_idx, jobs));
-                        } else {
-                            new_signals.push(NewSignal!(
-                                SignalKind::JobFinishedSkip,
-                                node_idx,
-                                jobs
-                            ));
-                        }
+                JobStateEphemeral::NotReady(ValidationStatus::Invalidated)
+                    if Self::all_upstreams_done(dag, jobs, node_idx) =>
+                {
+                    if Self::has_downstreams(dag, node_idx)
+                        && !Self::_job_and_downstreams_are_ephemeral(dag, jobs, node_idx)
+                    {
+                        Self::remove_consider_signals(new_signals, node_idx);
+                        ignore_consider_signals.insert(node_idx);
+                        new_signals.push(NewSignal!(SignalKind::JobReadyToRun, node_idx, jobs));
+                    } else {
+                        new_signals.push(NewSignal!(SignalKind::JobFinishedSkip, node_idx, jobs));
                     }
                 }
                 JobStateEphemeral::NotReady(ValidationStatus::Validated) => {
                     if Self::all_upstreams_done(dag, jobs, node_idx) {
                         set_node_state!(
-                            jobs[node_idx as usize],
+                            jobs[node_idx],
                             JobState::Ephemeral(JobStateEphemeral::ReadyButDelayed),
                             gen
                         );
@@ -2121,7 +3354,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                 // I am not required. Tell
                                 debug!(
                                     "\tNo downstream required {}, setting upstream edges to 'not required'",
-                                    jobs[node_idx as usize].job_id
+                                    jobs[node_idx].job_id
                                 );
                                 Self::set_upstream_edges(dag, node_idx, Required::No);
                                 //
@@ -2163,13 +3396,20 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     //we do signals in reverse order...
                 }
                 JobStateEphemeral::NotReady(ValidationStatus::Unknown) => {
-                    match Self::update_validation_status(strategy, dag, jobs, history, node_idx)? {
+                    match Self::update_validation_status(
+                        strategy,
+                        dag,
+                        jobs,
+                        history,
+                        change_filter,
+                        node_idx,
+                    )? {
                         ValidationStatus::Unknown => {
                             debug!("\tstill unknown validation status");
                         }
                         solid_vs => {
                             set_node_state!(
-                                jobs[node_idx as usize],
+                                jobs[node_idx],
                                 JobState::Ephemeral(JobStateEphemeral::NotReady(solid_vs),),
                                 gen
                             );
@@ -2259,6 +3499,8 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         //return Ok(Required::Unknown);
                         had_unknown = true;
                     }
+                    // kept from the finished run, on resume
+                    state if state.is_finished() => {}
                     _ => {
                         return Err(PPGEvaluatorError::InternalError(format!(
                             "bug1943: {:?}",
@@ -2298,7 +3540,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     ) {
         let upstreams = dag.neighbors_directed(node_idx, Direction::Incoming);
         for upstream_idx in upstreams {
-            match jobs[upstream_idx as usize].state {
+            match jobs[upstream_idx].state {
                 JobState::Always(_) => {}
                 JobState::Output(_) => {}
                 JobState::Ephemeral(state) => match state {
@@ -2330,7 +3572,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
     ) {
         let upstreams = dag.neighbors_directed(node_idx, Direction::Incoming);
         for upstream_idx in upstreams {
-            match jobs[upstream_idx as usize].state {
+            match jobs[upstream_idx].state {
                 JobState::Always(_) => {}
                 JobState::Output(_) => {}
                 //actually we only need to do this
@@ -2368,7 +3610,14 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                     }
                 }
                 if all_downstreams_done {
-                    if no_downstream_failed {
+                    if jobs[upstream_idx].barrier {
+                        // nothing to clean up
+                        set_node_state!(
+                            jobs[upstream_idx],
+                            JobState::Ephemeral(JobStateEphemeral::FinishedSuccessCleanedUp,),
+                            gen
+                        );
+                    } else if no_downstream_failed {
                         debug!("Job ready for cleanup {:?}", jobs[upstream_idx]);
                         set_node_state!(
                             jobs[upstream_idx],
@@ -2401,36 +3650,104 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         }
     }
 
-    fn identify_missing_outputs(&mut self) -> Result<(), PPGEvaluatorError> {
+    /// Ask the strategy for all presence checks / input lists up front,
+    /// in batches it may parallelize.
+    /// The propagation in identify_missing_outputs stays sequential.
+    fn prefetch_strategy_queries(
+        &mut self,
+        nodes: &HashSet<NodeIndex>,
+    ) -> Result<HashMap<NodeIndex, String>, PPGEvaluatorError> {
+        let output_ids: Vec<&str> = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter(|(node_idx, job)| {
+                nodes.contains(node_idx) && matches!(job.state, JobState::Output(_))
+            })
+            .map(|(_, job)| job)
+            .map(|job| job.job_id.as_str())
+            .filter(|job_id| !self.presence_cache.contains_key(*job_id))
+            .collect();
+        let presence = self.strategy.outputs_already_present(&output_ids);
+        for (job_id, present) in output_ids.iter().zip(presence) {
+            // errors are surfaced when the propagation asks again
+            if let Ok(present) = present {
+                self.presence_cache.insert(job_id.to_string(), present);
+            }
+        }
+
+        let with_input_history: Vec<NodeIndex> = (0..self.jobs.len())
+            .filter(|node_idx| nodes.contains(node_idx))
+            .filter(|node_idx| {
+                self.history
+                    .contains_key(&format!("{}!!!", self.jobs[*node_idx].job_id))
+            })
+            .collect();
+        let input_lists = self
+            .strategy
+            .get_input_lists(&with_input_history, &self.dag, &self.jobs);
+        let mut res = HashMap::new();
+        for (node_idx, input_list) in with_input_history.into_iter().zip(input_lists) {
+            res.insert(node_idx, input_list?);
+        }
+        Ok(res)
+    }
+
+    /// For the given nodes - all of them on startup.
+    fn identify_missing_outputs(
+        &mut self,
+        nodes: &HashSet<NodeIndex>,
+    ) -> Result<(), PPGEvaluatorError> {
+        let mut input_lists = self.prefetch_strategy_queries(nodes)?;
+        if self.assume_unchanged {
+            self.reconstruct_missing_history(&mut input_lists)?;
+        }
         // this has to be in (inverse) topological order
         // because we need to set the required edges.
         for &node_idx in self.topo.as_ref().unwrap().iter().rev() {
-            let job = &self.jobs[node_idx as usize];
+            if !nodes.contains(&node_idx) {
+                continue;
+            }
+            let _job_scope = self.jobs[node_idx].enter();
+            let job = &self.jobs[node_idx];
 
             let input_name_key = format!("{}!!!", job.job_id);
             let historical_input_names = self.history.get(&input_name_key);
-            let inputs_changed = match historical_input_names {
-                Some(historical_input_names) => {
-                    *historical_input_names
-                        != self
-                            .strategy
-                            .get_input_list(node_idx, &self.dag, &self.jobs)
-                }
-                None => {
-                    // not having an input job history is not itself
-                    // enough reason to invalidate -
-                    // they'll fail anyhow when we're looking at the individual edges
-                    // and this would trigger building non-used Ephemerals
-                    // but if you don't have an upstream,
-                    // ande the strategy says 'already done',
-                    // this is the only time we can get them invalidated
-                    !Self::has_upstreams(&self.dag, node_idx)
-                }
-            };
-            let mut job = &mut self.jobs[node_idx as usize];
+            let inputs_changed = self.force_rerun_all
+                || match historical_input_names {
+                    Some(historical_input_names) => {
+                        let input_list = match input_lists.remove(&node_idx) {
+                            Some(input_list) => input_list,
+                            None => self
+                                .strategy
+                                .get_input_list(node_idx, &self.dag, &self.jobs)?,
+                        };
+                        *historical_input_names != input_list
+                            && !self.change_filter.ignores(&input_name_key)
+                    }
+                    None => {
+                        // not having an input job history is not itself
+                        // enough reason to invalidate -
+                        // they'll fail anyhow when we're looking at the individual edges
+                        // and this would trigger building non-used Ephemerals
+                        // but if you don't have an upstream,
+                        // ande the strategy says 'already done',
+                        // this is the only time we can get them invalidated
+                        !Self::has_upstreams(&self.dag, node_idx)
+                    }
+                };
+            let job = &mut self.jobs[node_idx];
 
             if inputs_changed {
                 debug!("Input to job {} changed.", job.job_id);
+                let reason = if self.force_rerun_all {
+                    RunReason::Forced
+                } else if historical_input_names.is_none() {
+                    RunReason::NewJob
+                } else {
+                    RunReason::InputsChanged
+                };
+                self.run_reasons.record(&job.job_id, reason);
                 Self::set_upstream_edges(&mut self.dag, node_idx, Required::Yes);
                 match job.state {
                     JobState::Always(JobStateAlways::Undetermined) => {
@@ -2467,7 +3784,11 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         Self::set_upstream_edges(&mut self.dag, node_idx, Required::Yes)
                     }
                     JobState::Output(_) => {
-                        if self.strategy.output_already_present(&job.job_id) {
+                        if Self::cached_output_already_present(
+                            &self.strategy,
+                            &mut self.presence_cache,
+                            &job.job_id,
+                        )? {
                             if self.history.contains_key(&job.job_id) {
                                 Self::set_upstream_edges(&mut self.dag, node_idx, Required::No)
                             } else {
@@ -2475,6 +3796,7 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                                     "output present, but we had no history for {}, redoing",
                                     &job.job_id
                                 );
+                                self.run_reasons.record(&job.job_id, RunReason::NewJob);
                                 Self::set_upstream_edges(&mut self.dag, node_idx, Required::Yes);
                                 set_node_state!(
                                     job,
@@ -2487,6 +3809,12 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
                         } else {
                             Self::set_upstream_edges(&mut self.dag, node_idx, Required::Yes);
                             debug!("output was missing {}", &job.job_id);
+                            let reason = if self.history.contains_key(&job.job_id) {
+                                RunReason::OutputMissing
+                            } else {
+                                RunReason::NewJob
+                            };
+                            self.run_reasons.record(&job.job_id, reason);
                             set_node_state!(
                                 job,
                                 JobState::Output(JobStateOutput::NotReady(
@@ -2543,17 +3871,33 @@ impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
         Ok(())
     }
 
-    fn start_on_roots(&mut self) {
+    /// Consider the given nodes that have no upstreams among them -
+    /// the roots on startup. Those with an upstream outside of them that
+    /// already failed never run.
+    fn start_on_roots(&mut self, nodes: &HashSet<NodeIndex>) {
         let mut out_signals = Vec::new();
-        for root_idx in self.dag.nodes().filter(|node_idx| {
-            self.dag
-                .neighbors_directed(*node_idx, Direction::Incoming)
-                .next()
-                .is_none()
-        }) {
-            let job = &self.jobs[root_idx as usize];
-            debug!("root node '{}'", job.job_id);
-            out_signals.push(NewSignal!(SignalKind::ConsiderJob, root_idx, self.jobs));
+        for node_idx in self.dag.nodes().filter(|node_idx| nodes.contains(node_idx)) {
+            let upstreams: Vec<NodeIndex> = self
+                .dag
+                .neighbors_directed(node_idx, Direction::Incoming)
+                .collect();
+            if upstreams
+                .iter()
+                .any(|upstream_idx| self.jobs[*upstream_idx].state.is_failed())
+            {
+                out_signals.push(NewSignal!(
+                    SignalKind::JobUpstreamFailure,
+                    node_idx,
+                    self.jobs
+                ));
+            } else if upstreams
+                .iter()
+                .all(|upstream_idx| !nodes.contains(upstream_idx))
+            {
+                let job = &self.jobs[node_idx];
+                debug!("root node '{}'", job.job_id);
+                out_signals.push(NewSignal!(SignalKind::ConsiderJob, node_idx, self.jobs));
+            }
         }
         for signal in out_signals.into_iter() {
             // debug!("Adding signal {:?}", signal);
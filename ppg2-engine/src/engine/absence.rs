@@ -0,0 +1,89 @@
+// Jobs that may only run while a file does *not* exist - lock files,
+// 'do not touch' sentinels.
+//
+// require_absent adds an invariant 'absent:path' upstream of the job (one per
+// path, shared by all jobs requiring it). At startup the strategy is asked
+// whether path is present: if not, the invariant finishes like any other, so
+// a job that never ran under it runs now. If it is, the invariant fails with
+// '<path> exists', and everything downstream is upstream-failed for this run -
+// it runs in the first run where the file is gone again.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+pub const ABSENCE_JOB_PREFIX: &str = "absent:";
+const ABSENT_VALUE: &str = "absent";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct AbsenceInfo {
+    // absence job_id -> path
+    paths: HashMap<String, String>,
+    // absence job_ids whose path was present at startup
+    #[serde(skip)]
+    present: HashSet<String>,
+}
+
+impl AbsenceInfo {
+    pub(super) fn remove_job(&mut self, job_id: &str) {
+        self.paths.remove(job_id);
+        self.present.remove(job_id);
+    }
+
+    pub(super) fn is_present(&self, job_id: &str) -> bool {
+        self.present.contains(job_id)
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Only run job_id while path does not exist (as far as the strategy's
+    /// output_already_present is concerned)
+    pub fn require_absent(&mut self, job_id: &str, path: &str) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        let absence_id = format!("{}{}", ABSENCE_JOB_PREFIX, path);
+        if !self.job_id_to_node_idx.contains_key(&absence_id) {
+            self.add_invariant(&absence_id, ABSENT_VALUE)?;
+            self.absence
+                .paths
+                .insert(absence_id.clone(), path.to_string());
+        } else if !self.absence.paths.contains_key(&absence_id) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} is not an absence requirement",
+                absence_id
+            )));
+        }
+        self.depends_on(job_id, &absence_id)
+    }
+
+    /// Ask the strategy about every required-absent path. At startup.
+    pub(super) fn check_absences(&mut self) -> Result<(), PPGEvaluatorError> {
+        self.absence.present.clear();
+        for (absence_id, path) in self.absence.paths.iter() {
+            if self.strategy.output_already_present(path)? {
+                self.absence.present.insert(absence_id.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths that exist and hold back the jobs requiring their absence. Sorted.
+    pub fn query_blocking_files(&self) -> Vec<String> {
+        let mut res: Vec<String> = self
+            .absence
+            .present
+            .iter()
+            .map(|absence_id| self.absence.paths[absence_id].clone())
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// finish_invariants' outcome for an absence requirement that is present
+    pub(super) fn fail_present_absence(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
+        let error = format!("{} exists", self.absence.paths[job_id]);
+        self.event_now_running(job_id)?;
+        self.event_job_finished_failure_with_error(job_id, error)
+    }
+}
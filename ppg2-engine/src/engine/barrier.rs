@@ -0,0 +1,47 @@
+// Barrier nodes: a single synchronization point - never executed,
+// done once all their upstreams are done.
+//
+// They are finished by the engine as soon as they're ready to run,
+// always with BARRIER_HISTORY as output - so, unlike the fake Always jobs
+// they replace, they never invalidate their downstreams.
+//
+// They follow the Ephemeral state machine: a barrier (and so, its upstreams)
+// is only required if one of its downstreams is - an ephemeral upstream
+// isn't rebuilt just because it feeds a barrier. There's nothing to clean up,
+// so they're never offered for cleanup.
+use std::collections::VecDeque;
+
+use super::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+/// The output of every barrier
+pub const BARRIER_HISTORY: &str = "!!!barrier!!!";
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BarrierInfo {
+    // became ready to run, not finished yet
+    pending: VecDeque<String>,
+}
+
+impl BarrierInfo {
+    pub(super) fn new_run(&mut self) {
+        self.pending.clear();
+    }
+
+    /// process_signals - job_id is a barrier that just became ready to run
+    pub(super) fn became_ready(&mut self, job_id: &str) {
+        self.pending.push_back(job_id.to_string());
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Finish the barriers that became ready. After the events.
+    pub(super) fn finish_barriers(&mut self) -> Result<(), PPGEvaluatorError> {
+        while let Some(job_id) = self.barriers.pending.pop_front() {
+            debug!("Barrier {} reached", job_id);
+            self.event_now_running(&job_id)?;
+            self.event_job_finished_success(&job_id, BARRIER_HISTORY.to_string())?;
+        }
+        Ok(())
+    }
+}
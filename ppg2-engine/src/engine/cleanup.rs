@@ -0,0 +1,206 @@
+// Bookkeeping around ephemeral cleanups: reclaimed space and expected sizes,
+// so executors under disk pressure can clean up the biggest outputs first,
+// and pinned ephemerals that are not offered for cleanup at all.
+//
+// Retained ephemerals (set_retention) are not cleaned up after they ran.
+// The history counts down the successful runs their output has left
+// ('!!!retained!!!job_id' -> runs); when it reaches zero the output is
+// reported by query_expired_retentions for the executor to remove.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{JobKind, PPGEvaluator, StartStatus};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+/// Cleanups reported via event_job_cleanup_done(_with_size).
+/// 'run' counts since the last event_startup / event_resume,
+/// 'total' over the lifetime of the evaluator.
+/// Bytes only include cleanups that reported a size.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanupStats {
+    pub run_cleanups: usize,
+    pub run_bytes_freed: u64,
+    pub total_cleanups: usize,
+    pub total_bytes_freed: u64,
+}
+
+impl CleanupStats {
+    pub(super) fn new_run(&mut self) {
+        self.run_cleanups = 0;
+        self.run_bytes_freed = 0;
+    }
+
+    pub(super) fn cleaned_up(&mut self, bytes_freed: Option<u64>) {
+        let bytes_freed = bytes_freed.unwrap_or(0);
+        self.run_cleanups += 1;
+        self.total_cleanups += 1;
+        self.run_bytes_freed += bytes_freed;
+        self.total_bytes_freed += bytes_freed;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CleanupInfo {
+    pub(crate) stats: CleanupStats,
+    // ephemeral job_id -> declared output size in bytes
+    pub(crate) expected_sizes: HashMap<String, u64>,
+    // ephemerals query_ready_for_cleanup leaves out
+    pub(crate) pinned: HashSet<String>,
+    // ephemeral job_id -> successful runs to keep its output for
+    pub(crate) retention: HashMap<String, u32>,
+}
+
+pub(crate) const RETENTION_KEY_PREFIX: &str = "!!!retained!!!";
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// event_job_cleanup_done, recording how much space the cleanup reclaimed
+    pub fn event_job_cleanup_done_with_size(
+        &mut self,
+        job_id: &str,
+        bytes_freed: u64,
+    ) -> Result<(), PPGEvaluatorError> {
+        let res = self.cleanup_done(job_id, Some(bytes_freed));
+        self.after_event("event_job_cleanup_done_with_size", Some(job_id), res)
+    }
+
+    pub fn cleanup_stats(&self) -> &CleanupStats {
+        &self.cleanup.stats
+    }
+
+    /// How big an ephemeral's output is expected to be,
+    /// for query_ready_for_cleanup_by_size
+    pub fn set_expected_output_size(
+        &mut self,
+        job_id: &str,
+        bytes: u64,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.check_ephemeral(job_id)?;
+        self.cleanup
+            .expected_sizes
+            .insert(job_id.to_string(), bytes);
+        Ok(())
+    }
+
+    fn check_ephemeral(&self, job_id: &str) -> Result<(), PPGEvaluatorError> {
+        let idx = self.known_idx(job_id)?;
+        if !matches!(
+            self.jobs[idx].kind(),
+            JobKind::Ephemeral | JobKind::TempFile
+        ) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Only ephemerals get cleaned up, {} is {:?}",
+                job_id,
+                self.jobs[idx].kind()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Keep job_id's output around: it's not offered for cleanup until unpin
+    pub fn pin(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
+        self.check_ephemeral(job_id)?;
+        self.cleanup.pinned.insert(job_id.to_string());
+        Ok(())
+    }
+
+    /// Back to normal - if it's ready for cleanup, it's offered again
+    pub fn unpin(&mut self, job_id: &str) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        self.cleanup.pinned.remove(job_id);
+        Ok(())
+    }
+
+    pub fn query_pinned(&self) -> HashSet<String> {
+        self.cleanup.pinned.clone()
+    }
+
+    /// Keep job_id's output for `runs` successful runs after it last ran,
+    /// instead of offering it for cleanup.
+    pub fn set_retention(&mut self, job_id: &str, runs: u32) -> Result<(), PPGEvaluatorError> {
+        self.check_ephemeral(job_id)?;
+        self.cleanup.retention.insert(job_id.to_string(), runs);
+        Ok(())
+    }
+
+    pub(super) fn is_held_back_from_cleanup(&self, job_id: &str) -> bool {
+        self.cleanup.pinned.contains(job_id)
+            || self.cleanup.retention.get(job_id).copied().unwrap_or(0) > 0
+    }
+
+    fn run_was_successful(&self) -> bool {
+        let progress = &self.gen.progress;
+        progress.failed + progress.upstream_failed + progress.aborted == 0
+    }
+
+    /// (job_id, runs left) after this run, None once expired
+    fn retention_after_run(&self) -> Vec<(String, Option<u32>)> {
+        let mut res = Vec::new();
+        for (job_id, runs) in self.cleanup.retention.iter() {
+            let ran = self.job_id_to_node_idx.get(job_id).is_some_and(|idx| {
+                let job = &self.jobs[*idx];
+                job.started_at.is_some() && !job.state.is_failed()
+            });
+            if ran && *runs > 0 {
+                res.push((job_id.clone(), Some(*runs)));
+            }
+        }
+        let successful = self.run_was_successful();
+        for (key, runs_left) in self.history.iter() {
+            if let Some(job_id) = key.strip_prefix(RETENTION_KEY_PREFIX) {
+                if res.iter().any(|(ran, _)| ran == job_id) {
+                    continue;
+                }
+                let runs_left: u32 = runs_left.parse().unwrap_or(0);
+                let runs_left = if successful {
+                    runs_left.saturating_sub(1)
+                } else {
+                    runs_left
+                };
+                res.push((
+                    job_id.to_string(),
+                    if runs_left > 0 { Some(runs_left) } else { None },
+                ));
+            }
+        }
+        res.sort();
+        res
+    }
+
+    /// write the retention counters into a new history
+    pub(super) fn record_retention(&self, history: &mut HashMap<String, String>) {
+        for (job_id, runs_left) in self.retention_after_run() {
+            let key = format!("{}{}", RETENTION_KEY_PREFIX, job_id);
+            match runs_left {
+                Some(runs_left) => history.insert(key, runs_left.to_string()),
+                None => history.remove(&key),
+            };
+        }
+    }
+
+    /// Retained ephemerals whose output has outlived its retention with this
+    /// (finished) run - the executor should remove it. Sorted.
+    pub fn query_expired_retentions(&self) -> Vec<String> {
+        if self.already_started != StartStatus::Finished {
+            return Vec::new();
+        }
+        self.retention_after_run()
+            .into_iter()
+            .filter(|(_, runs_left)| runs_left.is_none())
+            .map(|(job_id, _)| job_id)
+            .collect()
+    }
+
+    /// query_ready_for_cleanup, largest expected output first
+    /// (those without a declared size last, by job id)
+    pub fn query_ready_for_cleanup_by_size(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.query_ready_for_cleanup().into_iter().collect();
+        res.sort_by_key(|job_id| {
+            (
+                std::cmp::Reverse(self.cleanup.expected_sizes.get(job_id).copied()),
+                job_id.clone(),
+            )
+        });
+        res
+    }
+}
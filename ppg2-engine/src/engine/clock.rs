@@ -0,0 +1,96 @@
+// Where the engine gets the time from.
+//
+// Timestamps (started_at, ready_at, last successes, gates, duplicate events),
+// runtimes, rate limit windows, speculation thresholds and the planning APIs
+// all ask the evaluator's Clock. The default is the wall clock; tests and
+// what-if planning install a SimulatedClock and advance it by hand.
+//
+// Clocks are code: snapshots don't carry them, reinstall after from_snapshot.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::PPGEvaluator;
+use crate::PPGEvaluatorStrategy;
+
+pub trait Clock: Send {
+    fn now(&self) -> Instant;
+    fn system_now(&self) -> SystemTime;
+}
+
+/// The real time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WallClock;
+
+impl Clock for WallClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when advanced. Clones share the time,
+/// so keep one to advance the one handed to the evaluator.
+#[derive(Debug, Clone)]
+pub struct SimulatedClock {
+    start: Instant,
+    start_system: SystemTime,
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl SimulatedClock {
+    /// Starting at unix time start_system
+    pub fn new(start_system: SystemTime) -> SimulatedClock {
+        SimulatedClock {
+            start: Instant::now(),
+            start_system,
+            offset: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap() += by;
+    }
+
+    /// Time passed since creation
+    pub fn elapsed(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+}
+
+impl Default for SimulatedClock {
+    fn default() -> Self {
+        SimulatedClock::new(UNIX_EPOCH)
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.start_system + self.elapsed()
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub(crate) fn system_now(&self) -> SystemTime {
+        self.clock.system_now()
+    }
+
+    /// Time passed since at, by the clock
+    pub(crate) fn elapsed_since(&self, at: Instant) -> Duration {
+        self.now().saturating_duration_since(at)
+    }
+}
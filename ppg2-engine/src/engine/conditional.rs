@@ -0,0 +1,61 @@
+// Conditional jobs: whether they run is decided once they're ready to run,
+// by PPGEvaluatorStrategy::should_run - e.g. only run QC if the input
+// has enough reads.
+//
+// Skipped ones are finished by the engine with SKIPPED_HISTORY as output,
+// so their downstreams see the same value on every run they're skipped
+// and aren't invalidated by it.
+use std::collections::{HashSet, VecDeque};
+
+use super::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+/// The output of a conditional job whose predicate said no
+pub const SKIPPED_HISTORY: &str = "!!!skipped!!!";
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConditionalInfo {
+    // became ready to run, should_run not asked yet
+    pending: VecDeque<String>,
+    skipped: HashSet<String>,
+}
+
+impl ConditionalInfo {
+    pub(super) fn new_run(&mut self) {
+        self.pending.clear();
+        self.skipped.clear();
+    }
+
+    /// process_signals - job_id is a conditional job that just became ready to run
+    pub(super) fn became_ready(&mut self, job_id: &str) {
+        self.pending.push_back(job_id.to_string());
+    }
+
+    pub(super) fn was_skipped(&self, job_id: &str) -> bool {
+        self.skipped.contains(job_id)
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Ask should_run for conditional jobs that became ready,
+    /// and finish those that are skipped. After the events.
+    pub(super) fn decide_conditionals(&mut self) -> Result<(), PPGEvaluatorError> {
+        while let Some(job_id) = self.conditional.pending.pop_front() {
+            if self.strategy.should_run(&job_id)? {
+                continue;
+            }
+            info!("Skipping conditional job {}", job_id);
+            self.conditional.skipped.insert(job_id.clone());
+            self.event_now_running(&job_id)?;
+            self.event_job_finished_success(&job_id, SKIPPED_HISTORY.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Conditional jobs skipped this run. Sorted.
+    pub fn query_skipped_conditionals(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.conditional.skipped.iter().cloned().collect();
+        res.sort();
+        res
+    }
+}
@@ -0,0 +1,94 @@
+// Debug dumps for bug reports.
+//
+// One deflate compressed blob holding the snapshot (graph topology, per node
+// states), only the history entries of jobs in the graph, and the last events
+// with their outcome. Users attach it to an issue, load_debug_dump rebuilds
+// the evaluator exactly as it was - with a strategy of the maintainer's choice.
+use serde::{Deserialize, Serialize};
+
+use super::{EvaluatorSnapshot, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy, HISTORY_VERSION_KEY};
+
+const DEBUG_DUMP_VERSION: u32 = 1;
+const RECENT_EVENT_COUNT: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+struct DebugDump {
+    version: u32,
+    /// the engine that wrote it - state machines differ between versions
+    engine_version: String,
+    snapshot: EvaluatorSnapshot,
+    recent_events: Vec<String>,
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    pub(super) fn record_event(
+        &mut self,
+        event: &str,
+        job_id: Option<&str>,
+        res: &Result<(), PPGEvaluatorError>,
+    ) {
+        if self.recent_events.len() == RECENT_EVENT_COUNT {
+            self.recent_events.pop_front();
+        }
+        let outcome = match res {
+            Ok(()) => "ok".to_string(),
+            Err(e) => format!("error: {}", e),
+        };
+        self.recent_events.push_back(match job_id {
+            Some(job_id) => format!("{}({}): {}", event, job_id, outcome),
+            None => format!("{}: {}", event, outcome),
+        });
+    }
+
+    /// The last (up to 256) events handled, oldest first
+    pub fn query_recent_events(&self) -> Vec<String> {
+        self.recent_events.iter().cloned().collect()
+    }
+
+    /// Everything needed to reproduce the current state, compressed.
+    /// Only possible between events, like snapshot.
+    pub fn dump_debug(&self) -> Result<Vec<u8>, PPGEvaluatorError> {
+        let mut snapshot = self.snapshot()?;
+        // history entries are keyed by job ids, joined / tagged with '!!!'
+        snapshot.history.retain(|key, _| {
+            key == HISTORY_VERSION_KEY
+                || key
+                    .split("!!!")
+                    .any(|part| self.job_id_to_node_idx.contains_key(part))
+        });
+        let dump = DebugDump {
+            version: DEBUG_DUMP_VERSION,
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            snapshot,
+            recent_events: self.query_recent_events(),
+        };
+        let json = serde_json::to_vec(&dump).expect("debug dump serialization can't fail");
+        Ok(miniz_oxide::deflate::compress_to_vec(&json, 9))
+    }
+
+    /// Rebuild the evaluator a dump_debug blob was taken from
+    pub fn load_debug_dump(blob: &[u8], strategy: T) -> Result<Self, PPGEvaluatorError> {
+        let json = miniz_oxide::inflate::decompress_to_vec(blob).map_err(|e| {
+            PPGEvaluatorError::APIError(format!("Invalid debug dump: {:?}", e.status))
+        })?;
+        let dump: DebugDump = serde_json::from_slice(&json)
+            .map_err(|e| PPGEvaluatorError::APIError(format!("Invalid debug dump: {}", e)))?;
+        if dump.version != DEBUG_DUMP_VERSION {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Debug dump version {} not supported (expected {})",
+                dump.version, DEBUG_DUMP_VERSION
+            )));
+        }
+        if dump.engine_version != env!("CARGO_PKG_VERSION") {
+            warn!(
+                "Debug dump was written by engine {}, this is {}",
+                dump.engine_version,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+        let mut res = Self::from_snapshot(dump.snapshot, strategy)?;
+        res.recent_events = dump.recent_events.into();
+        Ok(res)
+    }
+}
@@ -0,0 +1,80 @@
+// Tolerating duplicate finish events from distributed executors.
+//
+// With set_tolerate_duplicate_events(true) every finish is remembered.
+// A second report with the same outcome (and, for successes, the same
+// history) is logged and ignored; a conflicting one is an error naming
+// the job's state and when the first report came in.
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DuplicateEvents {
+    tolerate: bool,
+    // job_id -> (when, history - None for failures)
+    finished: HashMap<String, (SystemTime, Option<String>)>,
+}
+
+impl DuplicateEvents {
+    pub(super) fn new_run(&mut self) {
+        self.finished.clear();
+    }
+}
+
+fn describe(outcome: &Option<String>) -> String {
+    match outcome {
+        Some(history) => format!("success with history '{}'", history),
+        None => "failure".to_string(),
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Ignore repeated identical finish events instead of failing with JobNotRunning
+    pub fn set_tolerate_duplicate_events(&mut self, tolerate: bool) {
+        self.duplicates.tolerate = tolerate;
+        if !tolerate {
+            self.duplicates.finished.clear();
+        }
+    }
+
+    /// Called on every finish event (history None for failures) -
+    /// true if it repeated an earlier one and the engine should not act on it.
+    pub(super) fn duplicate_finish(
+        &mut self,
+        job_id: &str,
+        history: Option<&str>,
+    ) -> Result<bool, PPGEvaluatorError> {
+        if !self.duplicates.tolerate {
+            return Ok(false);
+        }
+        match self.duplicates.finished.get(job_id) {
+            Some((_, prior)) if prior.as_deref() == history => {
+                info!("Ignoring duplicate finish event for {}", job_id);
+                Ok(true)
+            }
+            Some((at, prior)) => {
+                let at = at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|x| x.as_secs_f64())
+                    .unwrap_or_default();
+                Err(PPGEvaluatorError::DuplicateEvent {
+                    job_id: job_id.to_string(),
+                    state: format!("{:?}", self.jobs[self.job_id_to_node_idx[job_id]].state),
+                    prior: format!("{} at unix time {:.3}", describe(prior), at),
+                    now: describe(&history.map(|x| x.to_string())),
+                })
+            }
+            None => {
+                if self.is_running(job_id) {
+                    self.duplicates.finished.insert(
+                        job_id.to_string(),
+                        (self.system_now(), history.map(|x| x.to_string())),
+                    );
+                }
+                Ok(false)
+            }
+        }
+    }
+}
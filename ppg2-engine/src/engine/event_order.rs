@@ -0,0 +1,79 @@
+// Run order verification for executors.
+//
+// Starts and finishes get a sequence number. A finish is numbered when its
+// event begins - finishing a job may start barriers / skipped jobs within.
+// After a run, verify_event_order checks that no job was started before each
+// of its upstreams that ran had finished: the executor handed out a job too
+// early, or reported the events out of order. Invariants never start, and are
+// always in order.
+use std::collections::HashMap;
+
+use super::PPGEvaluator;
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventOrder {
+    seq: u64,
+    started: HashMap<String, u64>,
+    // the first finish event - later ones are absorbed speculative attempts
+    finished: HashMap<String, u64>,
+}
+
+impl EventOrder {
+    pub(super) fn new_run(&mut self) {
+        self.seq = 0;
+        self.started.clear();
+        self.finished.clear();
+    }
+
+    pub(super) fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    pub(super) fn started(&mut self, job_id: &str) {
+        let seq = self.next_seq();
+        self.started.entry(job_id.to_string()).or_insert(seq);
+    }
+
+    pub(super) fn finished(&mut self, job_id: &str, seq: u64) {
+        self.finished.entry(job_id.to_string()).or_insert(seq);
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Err(APIError) listing every job started before an upstream that ran finished
+    pub fn verify_event_order(&self) -> Result<(), PPGEvaluatorError> {
+        let mut problems = Vec::new();
+        let mut started: Vec<(&String, &u64)> = self.event_order.started.iter().collect();
+        started.sort();
+        for (job_id, started_at) in started {
+            let node_idx = match self.job_id_to_node_idx.get(job_id) {
+                Some(node_idx) => *node_idx,
+                None => continue,
+            };
+            let mut upstreams: Vec<&String> = self
+                .dag
+                .neighbors_directed(node_idx, Direction::Incoming)
+                .map(|upstream_idx| &self.jobs[upstream_idx].job_id)
+                .filter(|upstream_id| self.event_order.started.contains_key(*upstream_id))
+                .collect();
+            upstreams.sort();
+            for upstream_id in upstreams {
+                match self.event_order.finished.get(upstream_id) {
+                    Some(finished_at) if finished_at < started_at => {}
+                    _ => problems.push(format!(
+                        "{} started before its upstream {} finished",
+                        job_id, upstream_id
+                    )),
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(PPGEvaluatorError::APIError(problems.join("\n")))
+        }
+    }
+}
@@ -0,0 +1,125 @@
+// Fair share between independent pipelines sharing one evaluator.
+//
+// With set_fair_share, query_ready_to_run_ordered interleaves the ready jobs
+// round-robin across groups - the weakly connected components of the graph,
+// or the jobs' tags - keeping the scheduling policy's order within a group.
+// That way one pipeline's thousands of ready jobs don't starve another's few.
+// weakly_connected_components hands the components to the frontend.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::PPGEvaluator;
+use crate::PPGEvaluatorStrategy;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FairShare {
+    #[default]
+    Off,
+    /// round-robin across weakly connected components
+    Components,
+    /// round-robin across tags (see tag_job) - first tag by name,
+    /// untagged jobs form one group
+    Tags,
+}
+
+fn find(parents: &mut [usize], mut idx: usize) -> usize {
+    while parents[idx] != idx {
+        parents[idx] = parents[parents[idx]];
+        idx = parents[idx];
+    }
+    idx
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    pub fn set_fair_share(&mut self, fair_share: FairShare) {
+        self.scheduling.fair_share = fair_share;
+        self.update_components();
+    }
+
+    /// union-find over the edges: node_idx -> smallest node_idx of its component
+    fn component_roots(&self) -> Vec<usize> {
+        let mut parents: Vec<usize> = (0..self.jobs.len()).collect();
+        for (from, to, _) in self.dag.all_edges() {
+            let (a, b) = (find(&mut parents, from), find(&mut parents, to));
+            if a != b {
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+        (0..self.jobs.len())
+            .map(|idx| find(&mut parents, idx))
+            .collect()
+    }
+
+    /// only done when FairShare::Components is on
+    pub(super) fn update_components(&mut self) {
+        if self.scheduling.fair_share != FairShare::Components {
+            self.scheduling.components.clear();
+            return;
+        }
+        self.scheduling.components = self.component_roots();
+    }
+
+    /// job_id -> component id. Components are numbered 0.. in the order
+    /// their first job was added; a job without any edges is one on its own.
+    pub fn weakly_connected_components(&self) -> HashMap<String, usize> {
+        let mut ids: HashMap<usize, usize> = HashMap::new();
+        self.component_roots()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, root)| {
+                let next_id = ids.len();
+                let id = *ids.entry(root).or_insert(next_id);
+                (self.jobs[idx].job_id.clone(), id)
+            })
+            .collect()
+    }
+
+    fn fair_share_group(&self, job_id: &str) -> String {
+        match self.scheduling.fair_share {
+            FairShare::Off => String::new(),
+            FairShare::Components => {
+                let idx = self.job_id_to_node_idx[job_id];
+                // jobs added after startup: their own group until the next one
+                let component = self.scheduling.components.get(idx).copied().unwrap_or(idx);
+                component.to_string()
+            }
+            FairShare::Tags => self
+                .scheduling
+                .tags
+                .get(job_id)
+                .and_then(|tags| tags.iter().min())
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// round-robin across groups, in order of their first job
+    pub(super) fn fair_share_order(&self, ordered: Vec<String>) -> Vec<String> {
+        if self.scheduling.fair_share == FairShare::Off {
+            return ordered;
+        }
+        let mut group_idx: HashMap<String, usize> = HashMap::new();
+        let mut groups: Vec<Vec<String>> = Vec::new();
+        for job_id in ordered {
+            let next = groups.len();
+            let idx = *group_idx
+                .entry(self.fair_share_group(&job_id))
+                .or_insert(next);
+            if idx == groups.len() {
+                groups.push(Vec::new());
+            }
+            groups[idx].push(job_id);
+        }
+        let longest = groups.iter().map(|g| g.len()).max().unwrap_or(0);
+        let mut groups: Vec<std::vec::IntoIter<String>> =
+            groups.into_iter().map(|g| g.into_iter()).collect();
+        let mut res = Vec::new();
+        for _ in 0..longest {
+            for group in groups.iter_mut() {
+                res.extend(group.next());
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,187 @@
+// Gated Always jobs: expensive environment checks and the like that don't
+// need to run on every invocation.
+//
+// A gate is checked once the job is ready to run. If it says no and the job
+// has an output from a previous run, the engine finishes it with that output
+// right away - its downstreams see no change. Without a previous output,
+// it runs regardless.
+//
+// When gated jobs last ran is engine owned history ('!!!last_run!!!job_id',
+// unix seconds), written by new_history.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{JobKind, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+pub(crate) const LAST_RUN_KEY_PREFIX: &str = "!!!last_run!!!";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AlwaysGate {
+    /// at most once per interval
+    Interval(Duration),
+    /// only if this invariant's value changed since the last run
+    InvariantChanged(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GatingInfo {
+    pub(super) gates: HashMap<String, AlwaysGate>,
+    // became ready to run, gate not checked yet
+    pending: VecDeque<String>,
+    // held back by their gate this run
+    gated: HashSet<String>,
+}
+
+impl GatingInfo {
+    pub(super) fn with_gates(gates: HashMap<String, AlwaysGate>) -> Self {
+        GatingInfo {
+            gates,
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn new_run(&mut self) {
+        self.pending.clear();
+        self.gated.clear();
+    }
+
+    /// process_signals - job_id just became ready to run
+    pub(super) fn became_ready(&mut self, job_id: &str) {
+        if self.gates.contains_key(job_id) {
+            self.pending.push_back(job_id.to_string());
+        }
+    }
+
+    pub(super) fn was_gated(&self, job_id: &str) -> bool {
+        self.gated.contains(job_id)
+    }
+}
+
+fn unix_seconds(at: SystemTime) -> f64 {
+    at.duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Gate an Always job - None removes the gate.
+    /// An InvariantChanged gate's invariant has to be added first.
+    pub fn set_always_gate(
+        &mut self,
+        job_id: &str,
+        gate: Option<AlwaysGate>,
+    ) -> Result<(), PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        if self.jobs[node_idx].kind() != JobKind::Always {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Only Always jobs can be gated, {} is {:?}",
+                job_id,
+                self.jobs[node_idx].kind()
+            )));
+        }
+        match gate {
+            None => {
+                self.gating.gates.remove(job_id);
+            }
+            Some(gate) => {
+                if let AlwaysGate::InvariantChanged(invariant_id) = &gate {
+                    let invariant_idx = self.known_idx(invariant_id)?;
+                    if self.jobs[invariant_idx].kind() != JobKind::Invariant {
+                        return Err(PPGEvaluatorError::APIError(format!(
+                            "{} is not an invariant",
+                            invariant_id
+                        )));
+                    }
+                }
+                self.gating.gates.insert(job_id.to_string(), gate);
+            }
+        }
+        Ok(())
+    }
+
+    fn gate_allows(&self, gate: &AlwaysGate, job_id: &str) -> bool {
+        match gate {
+            AlwaysGate::Interval(interval) => {
+                let last_run = self
+                    .history
+                    .get(&format!("{}{}", LAST_RUN_KEY_PREFIX, job_id))
+                    .and_then(|x| x.parse::<f64>().ok());
+                match last_run {
+                    Some(last_run) => {
+                        unix_seconds(self.system_now()) - last_run >= interval.as_secs_f64()
+                    }
+                    None => true,
+                }
+            }
+            AlwaysGate::InvariantChanged(invariant_id) => {
+                match self.job_id_to_node_idx.get(invariant_id) {
+                    Some(idx) => {
+                        self.jobs[*idx].invariant.as_deref() != self.history.get(invariant_id)
+                    }
+                    // removed since - nothing left to hold the job back
+                    None => true,
+                }
+            }
+        }
+    }
+
+    /// Check the gates of gated jobs that became ready,
+    /// and finish those held back. After the events.
+    pub(super) fn decide_gates(&mut self) -> Result<(), PPGEvaluatorError> {
+        while let Some(job_id) = self.gating.pending.pop_front() {
+            let last_output = match self.history.get(&job_id) {
+                Some(last_output) => last_output.to_string(),
+                None => continue,
+            };
+            if self.gate_allows(&self.gating.gates[&job_id], &job_id) {
+                continue;
+            }
+            info!("Gate holds back {}", job_id);
+            self.gating.gated.insert(job_id.clone());
+            self.event_now_running(&job_id)?;
+            self.event_job_finished_success(&job_id, last_output)?;
+        }
+        Ok(())
+    }
+
+    /// Gated jobs held back this run. Sorted.
+    pub fn query_gated_jobs(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.gating.gated.iter().cloned().collect();
+        res.sort();
+        res
+    }
+
+    /// Called by new_history - when did the gated jobs last really run
+    /// (finish, that is)
+    pub(super) fn record_gated_runs(&self, history: &mut HashMap<String, String>) {
+        history.retain(|key, _| match key.strip_prefix(LAST_RUN_KEY_PREFIX) {
+            Some(job_id) => self.gating.gates.contains_key(job_id),
+            None => true,
+        });
+        for job_id in self.gating.gates.keys() {
+            if self.gating.gated.contains(job_id) {
+                continue;
+            }
+            let job = match self.job_id_to_node_idx.get(job_id) {
+                Some(idx) => &self.jobs[*idx],
+                None => continue,
+            };
+            if job.history_output.is_none() {
+                continue;
+            }
+            let finished = match (job.started_at, job.runtime) {
+                (Some(started_at), Some(runtime)) => {
+                    self.system_now() - self.elapsed_since(started_at + runtime)
+                }
+                _ => continue,
+            };
+            history.insert(
+                format!("{}{}", LAST_RUN_KEY_PREFIX, job_id),
+                unix_seconds(finished).to_string(),
+            );
+        }
+    }
+}
@@ -0,0 +1,222 @@
+// Jobs generating a variable number of jobs (ppg1's JobGeneratingJob) -
+// e.g. one job per sample in a sample sheet that's only read at runtime.
+//
+// A generator (set_job_generator) reports the jobs it generated while it's
+// running (event_jobs_generated). They are recorded in the history
+// ('!!!generated!!!generator_id' -> json [[job_id, kind], ...]), and on the
+// next run set_job_generator pre-creates them, depending on the generator -
+// adding them again with the same kind is fine. Generated jobs that aren't in
+// the graph yet are queued like interactive changes - non-ephemeral ones join
+// the running evaluation, the others are added once the run finished. Jobs recorded last time but no longer generated are reported by
+// query_vanished_generated_jobs, so the executor can clean up their outputs.
+use std::collections::{HashMap, HashSet};
+
+use super::interactive::QueuedChange;
+use super::{JobKind, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+pub(crate) const GENERATED_KEY_PREFIX: &str = "!!!generated!!!";
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GeneratorInfo {
+    generators: HashSet<String>,
+    // pre-created from the history - adding them again is a no-op
+    precreated: HashMap<String, JobKind>,
+    // generator -> what it reported (this evaluator)
+    generated: HashMap<String, Vec<(String, JobKind)>>,
+    // generator -> recorded last time, but not reported
+    vanished: HashMap<String, Vec<String>>,
+}
+
+fn parse_kind(kind: &str) -> Option<JobKind> {
+    match kind {
+        "Always" => Some(JobKind::Always),
+        "Output" => Some(JobKind::Output),
+        "Ephemeral" => Some(JobKind::Ephemeral),
+        "Conditional" => Some(JobKind::Conditional),
+        "Barrier" => Some(JobKind::Barrier),
+        "TempFile" => Some(JobKind::TempFile),
+        _ => None,
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Mark job_id as generating jobs, and pre-create the ones it generated last
+    /// time. Returns the pre-created ones.
+    pub fn set_job_generator(&mut self, job_id: &str) -> Result<Vec<String>, PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        self.check_graph_mutable()?;
+        self.generators.generators.insert(job_id.to_string());
+        let mut precreated = Vec::new();
+        for (generated, kind) in self.recorded_generated_jobs(job_id) {
+            if !self.job_id_to_node_idx.contains_key(&generated) {
+                self.add_node(&generated, kind)?;
+                self.generators
+                    .precreated
+                    .insert(generated.to_string(), kind);
+                precreated.push(generated.to_string());
+            }
+            self.depends_on(&generated, job_id)?;
+        }
+        Ok(precreated)
+    }
+
+    /// add_node for a job set_job_generator already created:
+    /// Some(Ok) if that's fine (same kind)
+    pub(super) fn check_precreated(
+        &self,
+        job_id: &str,
+        kind: JobKind,
+    ) -> Option<Result<(), PPGEvaluatorError>> {
+        self.generators
+            .precreated
+            .get(job_id)
+            .map(|precreated| match *precreated == kind {
+                true => Ok(()),
+                false => Err(PPGEvaluatorError::ConflictingJobKind {
+                    job_id: job_id.to_string(),
+                    kind,
+                    other_kind: *precreated,
+                }),
+            })
+    }
+
+    fn recorded_generated_jobs(&self, generator_id: &str) -> Vec<(String, JobKind)> {
+        let key = format!("{}{}", GENERATED_KEY_PREFIX, generator_id);
+        let recorded: Vec<(String, String)> = match self.history.get(&key) {
+            Some(raw) => match serde_json::from_str(raw) {
+                Ok(recorded) => recorded,
+                Err(e) => {
+                    warn!("Ignoring unreadable {}: {}", key, e);
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+        recorded
+            .into_iter()
+            .filter_map(|(job_id, kind)| parse_kind(&kind).map(|kind| (job_id, kind)))
+            .collect()
+    }
+
+    /// The running generator generator_id generated these jobs.
+    /// Replaces what it reported before.
+    pub fn event_jobs_generated(
+        &mut self,
+        generator_id: &str,
+        jobs: &[(&str, JobKind)],
+    ) -> Result<(), PPGEvaluatorError> {
+        if !self.generators.generators.contains(generator_id) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} is not a job generator - see set_job_generator",
+                generator_id
+            )));
+        }
+        if !self.is_running(generator_id) {
+            return Err(PPGEvaluatorError::JobNotRunning(generator_id.to_string()));
+        }
+        let generator_idx = self.job_id_to_node_idx[generator_id];
+        for (job_id, kind) in jobs {
+            if job_id.is_empty() || job_id.contains("!!!") {
+                return Err(PPGEvaluatorError::InvalidJobId(job_id.to_string()));
+            }
+            if *job_id == generator_id {
+                return Err(PPGEvaluatorError::SelfDependency(job_id.to_string()));
+            }
+            if *kind == JobKind::Invariant {
+                return Err(PPGEvaluatorError::APIError(format!(
+                    "Generated job {} can't be an invariant",
+                    job_id
+                )));
+            }
+            let missing_edge = match self.job_id_to_node_idx.get(*job_id) {
+                Some(idx) if self.jobs[*idx].kind() != *kind => {
+                    return Err(PPGEvaluatorError::ConflictingJobKind {
+                        job_id: job_id.to_string(),
+                        kind: *kind,
+                        other_kind: self.jobs[*idx].kind(),
+                    });
+                }
+                Some(idx) => self.dag.edge_weight(generator_idx, *idx).is_none(),
+                None => {
+                    if self.queued_job(job_id).is_none() {
+                        self.queue_change(QueuedChange::AddJob {
+                            job_id: job_id.to_string(),
+                            kind: *kind,
+                            invariant: None,
+                        });
+                    }
+                    true
+                }
+            };
+            if missing_edge {
+                self.queue_change(QueuedChange::DependsOn {
+                    downstream: job_id.to_string(),
+                    upstream: generator_id.to_string(),
+                });
+            }
+        }
+        let vanished = self
+            .recorded_generated_jobs(generator_id)
+            .into_iter()
+            .filter(|(job_id, _)| !jobs.iter().any(|(x, _)| x == job_id))
+            .map(|(job_id, _)| job_id)
+            .collect();
+        self.generators
+            .vanished
+            .insert(generator_id.to_string(), vanished);
+        self.generators.generated.insert(
+            generator_id.to_string(),
+            jobs.iter()
+                .map(|(job_id, kind)| (job_id.to_string(), *kind))
+                .collect(),
+        );
+        Ok(())
+    }
+
+    /// What generator_id generated - this run if it reported, otherwise last time
+    pub fn query_generated_jobs(&self, generator_id: &str) -> Vec<String> {
+        match self.generators.generated.get(generator_id) {
+            Some(jobs) => jobs.iter().map(|(job_id, _)| job_id.clone()).collect(),
+            None => self
+                .recorded_generated_jobs(generator_id)
+                .into_iter()
+                .map(|(job_id, _)| job_id)
+                .collect(),
+        }
+    }
+
+    /// Jobs generated last time, but not by this run's generators. Sorted.
+    pub fn query_vanished_generated_jobs(&self) -> Vec<String> {
+        let mut res: Vec<String> = self
+            .generators
+            .vanished
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// write what the successful generators reported into a new history
+    pub(super) fn record_generated_jobs(&self, history: &mut HashMap<String, String>) {
+        for (generator_id, jobs) in self.generators.generated.iter() {
+            let succeeded = self
+                .job_id_to_node_idx
+                .get(generator_id)
+                .is_some_and(|idx| self.jobs[*idx].history_output.is_some());
+            if !succeeded {
+                continue;
+            }
+            let recorded: Vec<(&str, String)> = jobs
+                .iter()
+                .map(|(job_id, kind)| (job_id.as_str(), format!("{:?}", kind)))
+                .collect();
+            history.insert(
+                format!("{}{}", GENERATED_KEY_PREFIX, generator_id),
+                serde_json::to_string(&recorded).expect("generated jobs are always serializable"),
+            );
+        }
+    }
+}
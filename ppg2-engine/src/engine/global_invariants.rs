@@ -0,0 +1,138 @@
+// Environment fingerprints: python version, tool versions, container hash.
+//
+// add_global_invariant registers one as an invariant job. Jobs subscribe
+// explicitly - one by one, by tag (see tag_job) or by subgraph prefix -
+// and only those are invalidated when its value changes. Tag and subgraph
+// subscriptions are resolved at startup, so they cover jobs tagged / added
+// after the subscription.
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{EdgeInfo, PPGEvaluator, Required, SUBGRAPH_SEPARATOR};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct GlobalInvariantInfo {
+    names: HashSet<String>,
+    // (global invariant, tag)
+    tag_subscriptions: Vec<(String, String)>,
+    // (global invariant, subgraph prefix)
+    subgraph_subscriptions: Vec<(String, String)>,
+}
+
+impl GlobalInvariantInfo {
+    pub(super) fn remove_job(&mut self, job_id: &str) {
+        if self.names.remove(job_id) {
+            self.tag_subscriptions.retain(|(name, _)| name != job_id);
+            self.subgraph_subscriptions
+                .retain(|(name, _)| name != job_id);
+        }
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// An invariant no job depends on unless it subscribes
+    pub fn add_global_invariant(
+        &mut self,
+        name: &str,
+        value: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.add_invariant(name, value)?;
+        self.global_invariants.names.insert(name.to_string());
+        Ok(())
+    }
+
+    fn check_global_invariant(&self, name: &str) -> Result<(), PPGEvaluatorError> {
+        if self.global_invariants.names.contains(name) {
+            Ok(())
+        } else {
+            Err(PPGEvaluatorError::APIError(format!(
+                "{} is not a global invariant",
+                name
+            )))
+        }
+    }
+
+    pub fn subscribe_global_invariant(
+        &mut self,
+        job_id: &str,
+        name: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.check_global_invariant(name)?;
+        self.depends_on(job_id, name)
+    }
+
+    /// Every job tagged with tag (at startup) depends on name
+    pub fn subscribe_tag_to_global_invariant(
+        &mut self,
+        tag: &str,
+        name: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.check_global_invariant(name)?;
+        self.check_graph_mutable()?;
+        self.global_invariants
+            .tag_subscriptions
+            .push((name.to_string(), tag.to_string()));
+        Ok(())
+    }
+
+    /// Every job of subgraph prefix (at startup, nested ones included) depends on name
+    pub fn subscribe_subgraph_to_global_invariant(
+        &mut self,
+        prefix: &str,
+        name: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.check_global_invariant(name)?;
+        self.check_graph_mutable()?;
+        self.global_invariants
+            .subgraph_subscriptions
+            .push((name.to_string(), prefix.to_string()));
+        Ok(())
+    }
+
+    /// Add the edges of tag / subgraph subscriptions. Before the dag is frozen.
+    pub(super) fn resolve_global_invariant_subscriptions(&mut self) {
+        let mut edges = Vec::new();
+        for job in self.jobs.iter() {
+            if job.invariant.is_some() {
+                continue;
+            }
+            let tags = self.scheduling.tags.get(&job.job_id);
+            let by_tag = self
+                .global_invariants
+                .tag_subscriptions
+                .iter()
+                .filter(|(_, tag)| tags.is_some_and(|tags| tags.contains(tag)));
+            let by_subgraph =
+                self.global_invariants
+                    .subgraph_subscriptions
+                    .iter()
+                    .filter(|(_, prefix)| {
+                        job.job_id
+                            .strip_prefix(prefix.as_str())
+                            .is_some_and(|rest| rest.starts_with(SUBGRAPH_SEPARATOR))
+                    });
+            for (name, _) in by_tag.chain(by_subgraph) {
+                edges.push((
+                    self.job_id_to_node_idx[name],
+                    self.job_id_to_node_idx[&job.job_id],
+                ));
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        for (upstream_idx, downstream_idx) in edges {
+            if !self.dag.contains_edge(upstream_idx, downstream_idx) {
+                self.dag.add_edge(
+                    upstream_idx,
+                    downstream_idx,
+                    EdgeInfo {
+                        required: Required::Unknown,
+                        invalidated: Required::Unknown,
+                    },
+                );
+            }
+        }
+    }
+}
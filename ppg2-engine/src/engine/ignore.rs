@@ -0,0 +1,125 @@
+// Ignoring known-cosmetic changes for one run.
+//
+// History keys matching an ignore_changes pattern don't invalidate their
+// downstream when their value changed. The new values are recorded as usual,
+// so the next run compares against them.
+//
+// Field subscriptions (set_edge_fields) are the permanent, per edge variant:
+// with JSON object history values, only the listed fields are compared,
+// so e.g. runtime metadata next to an output hash doesn't trigger the downstream.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{PPGEvaluator, StartStatus};
+use crate::wildcard::wildcard_match;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ChangeFilter {
+    patterns: Vec<String>,
+    // keys whose change was ignored this run
+    ignored: Vec<String>,
+    // 'up!!!down' -> the fields of up's history that down depends on
+    #[serde(default)]
+    edge_fields: HashMap<String, Vec<String>>,
+}
+
+// top level key, or a JSON pointer ('/output/hash')
+fn field<'a>(value: &'a Value, field: &str) -> Option<&'a Value> {
+    if field.starts_with('/') {
+        value.pointer(field)
+    } else {
+        value.get(field)
+    }
+}
+
+impl ChangeFilter {
+    /// Is a change to history_key to be ignored? Remembers the ones that were.
+    pub(crate) fn ignores(&mut self, history_key: &str) -> bool {
+        if self
+            .patterns
+            .iter()
+            .any(|pattern| wildcard_match(pattern, history_key))
+        {
+            self.ignored.push(history_key.to_string());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Did a subscribed field of edge history_key change?
+    /// None if the edge has no subscription or the values aren't JSON objects -
+    /// the strategy decides then.
+    pub(crate) fn fields_altered(
+        &self,
+        history_key: &str,
+        last_value: &str,
+        current_value: &str,
+    ) -> Option<bool> {
+        let fields = self.edge_fields.get(history_key)?;
+        let last: Value = serde_json::from_str(last_value).ok()?;
+        let current: Value = serde_json::from_str(current_value).ok()?;
+        if !(last.is_object() && current.is_object()) {
+            return None;
+        }
+        Some(
+            fields
+                .iter()
+                .any(|name| field(&last, name) != field(&current, name)),
+        )
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Don't let changed history entries matching pattern
+    /// ('up!!!down' for edges, 'job!!!' for input lists) invalidate anything
+    /// in this run (before event_startup).
+    pub fn ignore_changes(&mut self, pattern: &str) -> Result<(), PPGEvaluatorError> {
+        if self.already_started != StartStatus::NotStarted {
+            return Err(PPGEvaluatorError::APIError(
+                "ignore_changes must be set before event_startup".to_string(),
+            ));
+        }
+        self.change_filter.patterns.push(pattern.to_string());
+        Ok(())
+    }
+
+    /// Only changes to these fields of upstream's (JSON object) history
+    /// invalidate downstream. Top level keys, or JSON pointers ('/output/hash').
+    /// An empty list removes the subscription.
+    pub fn set_edge_fields(
+        &mut self,
+        downstream: &str,
+        upstream: &str,
+        fields: &[&str],
+    ) -> Result<(), PPGEvaluatorError> {
+        let downstream_idx = self.known_idx(downstream)?;
+        let upstream_idx = self.known_idx(upstream)?;
+        if !self.dag.contains_edge(upstream_idx, downstream_idx) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} does not depend on {}",
+                downstream, upstream
+            )));
+        }
+        let key = format!("{}!!!{}", upstream, downstream);
+        if fields.is_empty() {
+            self.change_filter.edge_fields.remove(&key);
+        } else {
+            self.change_filter
+                .edge_fields
+                .insert(key, fields.iter().map(|x| x.to_string()).collect());
+        }
+        Ok(())
+    }
+
+    /// History keys whose changes were ignored, sorted
+    pub fn query_ignored_changes(&self) -> Vec<String> {
+        let mut res = self.change_filter.ignored.clone();
+        res.sort();
+        res.dedup();
+        res
+    }
+}
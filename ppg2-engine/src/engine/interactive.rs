@@ -0,0 +1,232 @@
+// Growing the graph while it's being evaluated - notebooks, cell by cell.
+//
+// With set_interactive(true), add_node / add_invariant / depends_on are
+// accepted while the evaluation is running. The changes are queued, and the
+// next event_replan (called after every finish event as well) splices the
+// new non-ephemeral jobs into the running evaluation - they run in this run.
+// The rest (edges onto jobs already in the graph, ephemerals) is applied once
+// the run has finished: the finish event of the last job (or event_replan)
+// adds them and continues via event_resume, so only new and invalidated jobs
+// (and what they need) become ready.
+// Changes made to a finished evaluation are picked up by event_replan as well.
+use std::collections::HashSet;
+
+use super::{JobKind, JobState, PPGEvaluator, StartStatus};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone)]
+pub(crate) enum QueuedChange {
+    AddJob {
+        job_id: String,
+        kind: JobKind,
+        invariant: Option<String>,
+    },
+    DependsOn {
+        downstream: String,
+        upstream: String,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InteractiveInfo {
+    enabled: bool,
+    queued: Vec<QueuedChange>,
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Accept graph changes while the evaluation is running (queued, see event_replan)
+    pub fn set_interactive(&mut self, enabled: bool) {
+        self.interactive.enabled = enabled;
+    }
+
+    /// Graph changes waiting to be spliced in / for the running evaluation to finish
+    pub fn query_queued_changes(&self) -> usize {
+        self.interactive.queued.len()
+    }
+
+    pub(super) fn queued_job(&self, job_id: &str) -> Option<&QueuedChange> {
+        self.interactive.queued.iter().find(|change| match change {
+            QueuedChange::AddJob { job_id: queued, .. } => queued == job_id,
+            _ => false,
+        })
+    }
+
+    /// Ok(true) if the change was queued instead of applied
+    pub(super) fn queue_if_running(
+        &mut self,
+        change: QueuedChange,
+    ) -> Result<bool, PPGEvaluatorError> {
+        if !self.interactive.enabled || self.already_started != StartStatus::Running {
+            return Ok(false);
+        }
+        match &change {
+            QueuedChange::AddJob { job_id, .. } => {
+                if job_id.is_empty() || job_id.contains("!!!") {
+                    return Err(PPGEvaluatorError::InvalidJobId(job_id.to_string()));
+                }
+                if self.job_id_to_node_idx.contains_key(job_id)
+                    || self.is_alias(job_id)
+                    || self.queued_job(job_id).is_some()
+                {
+                    return Err(PPGEvaluatorError::JobRedefinition(job_id.to_string()));
+                }
+            }
+            QueuedChange::DependsOn {
+                downstream,
+                upstream,
+            } => {
+                for job_id in [downstream, upstream].iter() {
+                    if !self.job_id_to_node_idx.contains_key(*job_id)
+                        && self.queued_job(job_id).is_none()
+                    {
+                        return Err(PPGEvaluatorError::UnknownJob(job_id.to_string()));
+                    }
+                }
+                if downstream == upstream {
+                    return Err(PPGEvaluatorError::SelfDependency(downstream.to_string()));
+                }
+            }
+        }
+        self.interactive.queued.push(change);
+        Ok(true)
+    }
+
+    /// Queue a change regardless of set_interactive - e.g. jobs a generator reported
+    pub(super) fn queue_change(&mut self, change: QueuedChange) {
+        self.interactive.queued.push(change);
+    }
+
+    /// Apply queued (and since the finished run made) graph changes and
+    /// continue the evaluation. While it's running, the queued jobs that can
+    /// join it are spliced in (see splice_queued). Ok(false) if there was
+    /// nothing to do.
+    pub fn event_replan(&mut self) -> Result<bool, PPGEvaluatorError> {
+        if !self.is_finished() {
+            return match self.already_started {
+                StartStatus::Running => self.splice_queued(),
+                _ => Ok(false),
+            };
+        }
+        let queued = std::mem::take(&mut self.interactive.queued);
+        // resume_history is kept from the first change after the finish on
+        if queued.is_empty() && self.resume_history.is_none() {
+            return Ok(false);
+        }
+        for change in queued {
+            match change {
+                QueuedChange::AddJob {
+                    job_id,
+                    kind,
+                    invariant,
+                } => self.add_job(&job_id, kind, invariant)?,
+                QueuedChange::DependsOn {
+                    downstream,
+                    upstream,
+                } => self.depends_on(&downstream, &upstream)?,
+            }
+        }
+        self.event_resume()?;
+        Ok(true)
+    }
+
+    /// Queued jobs that can join the running evaluation: non-ephemeral ones,
+    /// depending only on non-ephemeral jobs of the graph or on other such
+    /// queued jobs. An ephemeral would have to be required by its downstreams
+    /// before they were evaluated, and may already have been cleaned up.
+    fn spliceable_jobs(&self) -> HashSet<String> {
+        let mut res: HashSet<String> =
+            self.interactive
+                .queued
+                .iter()
+                .filter_map(|change| match change {
+                    QueuedChange::AddJob {
+                        job_id,
+                        kind:
+                            JobKind::Output
+                            | JobKind::Always
+                            | JobKind::Invariant
+                            | JobKind::Conditional,
+                        ..
+                    } => Some(job_id.clone()),
+                    _ => None,
+                })
+                .collect();
+        loop {
+            let blocked: Vec<String> = self
+                .interactive
+                .queued
+                .iter()
+                .filter_map(|change| match change {
+                    QueuedChange::DependsOn {
+                        downstream,
+                        upstream,
+                    } if res.contains(downstream) => {
+                        let upstream_ok = match self.job_id_to_node_idx.get(upstream) {
+                            Some(idx) => !matches!(self.jobs[*idx].state, JobState::Ephemeral(_)),
+                            None => res.contains(upstream),
+                        };
+                        match upstream_ok {
+                            true => None,
+                            false => Some(downstream.clone()),
+                        }
+                    }
+                    _ => None,
+                })
+                .collect();
+            if blocked.is_empty() {
+                return res;
+            }
+            for job_id in blocked {
+                res.remove(&job_id);
+            }
+        }
+    }
+
+    /// Add the spliceable queued jobs (and their upstream edges) to the
+    /// running evaluation, and evaluate just them. Edges onto jobs that were
+    /// already in the graph stay queued until the run finished.
+    fn splice_queued(&mut self) -> Result<bool, PPGEvaluatorError> {
+        let spliceable = self.spliceable_jobs();
+        if spliceable.is_empty() {
+            return Ok(false);
+        }
+        let queued = std::mem::take(&mut self.interactive.queued);
+        let mut nodes = HashSet::new();
+        for change in queued {
+            match change {
+                QueuedChange::AddJob {
+                    job_id,
+                    kind,
+                    invariant,
+                } if spliceable.contains(&job_id) => {
+                    self.insert_job(&job_id, kind, invariant)?;
+                    nodes.insert(self.job_id_to_node_idx[&job_id]);
+                }
+                QueuedChange::DependsOn {
+                    downstream,
+                    upstream,
+                } if spliceable.contains(&downstream) => {
+                    self.insert_edge(&downstream, &upstream)?
+                }
+                change => self.interactive.queued.push(change),
+            }
+        }
+        debug!("spliced {} queued jobs into the run", nodes.len());
+        self.dag.freeze();
+        self.update_components();
+        self.topo =
+            Some(self.dag.toposort().map_err(|node_idx| {
+                PPGEvaluatorError::Cycle(self.jobs[node_idx].job_id.clone())
+            })?);
+        self.evaluate_nodes(&nodes)?;
+        Ok(true)
+    }
+
+    /// After the finish events - picks the queued changes up once the run is done
+    pub(super) fn replan_if_queued(&mut self) -> Result<(), PPGEvaluatorError> {
+        if !self.interactive.queued.is_empty() {
+            self.event_replan()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+// Read-only views on the graph's structure, for sanity checks and
+// inspection tools in user code.
+use super::{JobKind, PPGEvaluator, Required};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+/// One direct neighbour of a job, see upstreams_of / downstreams_of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdgeDetails {
+    /// the job on the other end of the edge
+    pub job_id: String,
+    pub kind: JobKind,
+    /// the upstream's output as the downstream last saw it, from the history
+    pub last_history: Option<String>,
+    /// whether the edge invalidated the downstream this run -
+    /// None until the engine looked at it
+    pub invalidated: Option<bool>,
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    // no neighbours in direction, optionally of one kind. Sorted.
+    fn jobs_without(&self, direction: Direction, kind: Option<JobKind>) -> Vec<String> {
+        let mut res: Vec<String> = self
+            .dag
+            .nodes()
+            .filter(|idx| kind.is_none_or(|kind| self.jobs[*idx].kind() == kind))
+            .filter(|idx| {
+                self.dag
+                    .neighbors_directed(*idx, direction)
+                    .next()
+                    .is_none()
+            })
+            .map(|idx| self.jobs[idx].job_id.clone())
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// Jobs without upstreams (of kind, if given). Sorted.
+    pub fn query_roots(&self, kind: Option<JobKind>) -> Vec<String> {
+        self.jobs_without(Direction::Incoming, kind)
+    }
+
+    /// Jobs without downstreams (of kind, if given). Sorted.
+    pub fn query_leaves(&self, kind: Option<JobKind>) -> Vec<String> {
+        self.jobs_without(Direction::Outgoing, kind)
+    }
+
+    fn edges_of(
+        &self,
+        job_id: &str,
+        direction: Direction,
+    ) -> Result<Vec<EdgeDetails>, PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        let mut res: Vec<EdgeDetails> = self
+            .dag
+            .edges_directed(node_idx, direction)
+            .map(|(upstream_idx, downstream_idx, weight)| {
+                let other_idx = match direction {
+                    Direction::Incoming => upstream_idx,
+                    Direction::Outgoing => downstream_idx,
+                };
+                let key = format!(
+                    "{}!!!{}",
+                    self.jobs[upstream_idx].job_id, self.jobs[downstream_idx].job_id
+                );
+                EdgeDetails {
+                    job_id: self.jobs[other_idx].job_id.clone(),
+                    kind: self.jobs[other_idx].kind(),
+                    last_history: self.history.get(&key).map(|x| x.to_string()),
+                    invalidated: match weight.invalidated {
+                        Required::Unknown => None,
+                        Required::Yes => Some(true),
+                        Required::No => Some(false),
+                    },
+                }
+            })
+            .collect();
+        res.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        Ok(res)
+    }
+
+    /// Direct upstreams of job_id, sorted by job id
+    pub fn upstreams_of(&self, job_id: &str) -> Result<Vec<EdgeDetails>, PPGEvaluatorError> {
+        self.edges_of(job_id, Direction::Incoming)
+    }
+
+    /// Direct downstreams of job_id, sorted by job id
+    pub fn downstreams_of(&self, job_id: &str) -> Result<Vec<EdgeDetails>, PPGEvaluatorError> {
+        self.edges_of(job_id, Direction::Outgoing)
+    }
+}
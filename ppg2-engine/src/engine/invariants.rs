@@ -0,0 +1,132 @@
+// Internal consistency checks.
+//
+// verify_invariants recomputes what the engine otherwise maintains
+// incrementally (ready set, per state counts, the graph's edge lists) and
+// compares. With the debug-invariants feature it runs after every state
+// changing event, and a violation panics with a snapshot of the evaluator -
+// a subtle scheduling bug becomes an immediate, reportable failure
+// instead of a stalled or wrongly skipped job much later.
+use super::{JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, PPGEvaluator, Progress};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Err(InternalError) describing every inconsistency found
+    pub fn verify_invariants(&self) -> Result<(), PPGEvaluatorError> {
+        let mut problems = Vec::new();
+
+        for job_id in self.jobs_ready_to_run.iter() {
+            let node_idx = match self.job_id_to_node_idx.get(job_id) {
+                Some(node_idx) => *node_idx,
+                None => {
+                    problems.push(format!("{} is ready to run, but no such job", job_id));
+                    continue;
+                }
+            };
+            let state = &self.jobs[node_idx].state;
+            if !matches!(
+                state,
+                JobState::Always(JobStateAlways::ReadyToRun)
+                    | JobState::Output(JobStateOutput::ReadyToRun)
+                    | JobState::Ephemeral(JobStateEphemeral::ReadyToRun(_))
+            ) {
+                problems.push(format!(
+                    "{} is ready to run, but in state {:?}",
+                    job_id, state
+                ));
+            }
+            let failure_ok = self.jobs[node_idx].run_even_if_upstreams_failed;
+            for upstream_idx in self.dag.neighbors_directed(node_idx, Direction::Incoming) {
+                let upstream = &self.jobs[upstream_idx];
+                if !upstream.state.is_finished() || (upstream.state.is_failed() && !failure_ok) {
+                    problems.push(format!(
+                        "{} is ready to run, but its upstream {} is {:?}",
+                        job_id, upstream.job_id, upstream.state
+                    ));
+                }
+            }
+        }
+        for job in self.jobs.iter() {
+            if matches!(
+                job.state,
+                JobState::Always(JobStateAlways::ReadyToRun)
+                    | JobState::Output(JobStateOutput::ReadyToRun)
+                    | JobState::Ephemeral(JobStateEphemeral::ReadyToRun(_))
+            ) && !self.jobs_ready_to_run.contains(&job.job_id)
+            {
+                problems.push(format!(
+                    "{} is {:?}, but not in the ready set",
+                    job.job_id, job.state
+                ));
+            }
+        }
+
+        let mut progress = Progress::default();
+        for job in self.jobs.iter() {
+            progress.added(&job.state);
+        }
+        if progress != self.gen.progress {
+            problems.push(format!(
+                "per state counts are {:?}, but the jobs say {:?}",
+                self.gen.progress, progress
+            ));
+        }
+
+        let outgoing: usize = self
+            .dag
+            .nodes()
+            .map(|idx| {
+                self.dag
+                    .neighbors_directed(idx, Direction::Outgoing)
+                    .count()
+            })
+            .sum();
+        let incoming: usize = self
+            .dag
+            .nodes()
+            .map(|idx| {
+                self.dag
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .count()
+            })
+            .sum();
+        let edges = self.dag.all_edges().count();
+        if outgoing != edges || incoming != edges {
+            problems.push(format!(
+                "edge counts disagree: {} edges, {} outgoing, {} incoming",
+                edges, outgoing, incoming
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(PPGEvaluatorError::InternalError(problems.join("\n")))
+        }
+    }
+
+    /// Wraps the result of every state changing event - it's kept for
+    /// dump_debug, and with the debug-invariants feature, verified
+    /// before handing it back.
+    #[inline]
+    pub(super) fn after_event(
+        &mut self,
+        event: &str,
+        job_id: Option<&str>,
+        res: Result<(), PPGEvaluatorError>,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.record_event(event, job_id, &res);
+        #[cfg(feature = "debug-invariants")]
+        if let Err(e) = self.verify_invariants() {
+            let state = match self.snapshot() {
+                Ok(snapshot) => snapshot.to_json(),
+                Err(_) => self.debug_(),
+            };
+            panic!(
+                "Invariant violated after {}: {}\nstate: {}",
+                event, e, state
+            );
+        }
+        res
+    }
+}
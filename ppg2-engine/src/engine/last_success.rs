@@ -0,0 +1,133 @@
+// When did a job last succeed - across runs, without looking at the
+// filesystem. Find the outputs that haven't been refreshed in months.
+//
+// With set_record_last_success, new_history keeps for every job that ran
+// successfully when it finished and in which run
+// ('!!!last_success!!!job_id' -> 'unix seconds run_id'). Runs are numbered
+// by the engine ('!!!run_id!!!' holds the last one).
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{NodeInfo, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+pub(crate) const LAST_SUCCESS_KEY_PREFIX: &str = "!!!last_success!!!";
+const RUN_ID_KEY: &str = "!!!run_id!!!";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LastSuccess {
+    pub finished: SystemTime,
+    pub run_id: u64,
+}
+
+impl LastSuccess {
+    fn parse(value: &str) -> Option<LastSuccess> {
+        let (seconds, run_id) = value.split_once(' ')?;
+        let seconds = seconds.parse::<f64>().ok()?;
+        if !(seconds >= 0.0 && seconds.is_finite()) {
+            return None;
+        }
+        Some(LastSuccess {
+            finished: UNIX_EPOCH + Duration::from_secs_f64(seconds),
+            run_id: run_id.parse().ok()?,
+        })
+    }
+
+    fn to_history(self) -> String {
+        let seconds = self
+            .finished
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs_f64())
+            .unwrap_or(0.0);
+        format!("{} {}", seconds, self.run_id)
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Keep last success times in the history - off by default,
+    /// for it changes the history on every run.
+    pub fn set_record_last_success(&mut self, record: bool) {
+        self.record_last_success = record;
+    }
+
+    /// This run's number - one more than the last recorded one
+    pub fn query_run_id(&self) -> u64 {
+        self.history
+            .get(RUN_ID_KEY)
+            .and_then(|x| x.parse::<u64>().ok())
+            .unwrap_or(0)
+            + 1
+    }
+
+    // when it finished, if it ran successfully this run
+    fn succeeded_this_run(&self, job: &NodeInfo) -> Option<SystemTime> {
+        if !job.state.is_finished()
+            || job.state.is_failed()
+            || job.invariant.is_some()
+            || job.barrier
+            || self.conditional.was_skipped(&job.job_id)
+            || self.gating.was_gated(&job.job_id)
+        {
+            return None;
+        }
+        let finished: Instant = job.started_at? + job.runtime?;
+        Some(self.system_now() - self.elapsed_since(finished))
+    }
+
+    /// None if the job never succeeded (while recording was on)
+    pub fn last_success(&self, job_id: &str) -> Result<Option<LastSuccess>, PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        if let Some(finished) = self.succeeded_this_run(&self.jobs[node_idx]) {
+            return Ok(Some(LastSuccess {
+                finished,
+                run_id: self.query_run_id(),
+            }));
+        }
+        Ok(self
+            .history
+            .get(&format!("{}{}", LAST_SUCCESS_KEY_PREFIX, job_id))
+            .and_then(LastSuccess::parse))
+    }
+
+    /// Jobs that haven't succeeded since `since` - or never did. Sorted.
+    /// Invariants and barriers never run, they're not listed.
+    pub fn jobs_not_built_since(&self, since: SystemTime) -> Vec<String> {
+        let mut res: Vec<String> = self
+            .jobs
+            .iter()
+            .filter(|job| job.invariant.is_none() && !job.barrier)
+            .filter(|job| {
+                self.last_success(&job.job_id)
+                    .ok()
+                    .flatten()
+                    .is_none_or(|last| last.finished < since)
+            })
+            .map(|job| job.job_id.clone())
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// Called by new_history
+    pub(super) fn record_last_successes(&self, history: &mut HashMap<String, String>) {
+        if !self.record_last_success {
+            return;
+        }
+        history.retain(|key, _| match key.strip_prefix(LAST_SUCCESS_KEY_PREFIX) {
+            Some(job_id) => self.job_id_to_node_idx.contains_key(job_id),
+            None => true,
+        });
+        let run_id = self.query_run_id();
+        for job in self.jobs.iter() {
+            if let Some(finished) = self.succeeded_this_run(job) {
+                history.insert(
+                    format!("{}{}", LAST_SUCCESS_KEY_PREFIX, job.job_id),
+                    LastSuccess { finished, run_id }.to_history(),
+                );
+            }
+        }
+        history.insert(RUN_ID_KEY.to_string(), run_id.to_string());
+    }
+}
@@ -0,0 +1,37 @@
+// Dependency waves: every job sits one layer below its deepest upstream,
+// so the jobs of a layer never depend on each other. For batch submission
+// systems (submit a layer, wait, submit the next) and for seeing how wide
+// the pipeline can actually go.
+use super::PPGEvaluator;
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Layer 0 holds the jobs without upstreams. Each layer sorted.
+    /// Pruned ephemerals are left out.
+    pub fn topological_layers(&self) -> Result<Vec<Vec<String>>, PPGEvaluatorError> {
+        let order = self
+            .dag
+            .toposort()
+            .map_err(|node_idx| PPGEvaluatorError::Cycle(self.jobs[node_idx].job_id.clone()))?;
+        let mut depth = vec![0usize; self.jobs.len()];
+        let mut layers: Vec<Vec<String>> = Vec::new();
+        for node_idx in order {
+            let layer = self
+                .dag
+                .neighbors_directed(node_idx, Direction::Incoming)
+                .map(|upstream_idx| depth[upstream_idx] + 1)
+                .max()
+                .unwrap_or(0);
+            depth[node_idx] = layer;
+            if layers.len() <= layer {
+                layers.resize(layer + 1, Vec::new());
+            }
+            layers[layer].push(self.jobs[node_idx].job_id.clone());
+        }
+        for layer in layers.iter_mut() {
+            layer.sort();
+        }
+        Ok(layers)
+    }
+}
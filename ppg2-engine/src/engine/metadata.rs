@@ -0,0 +1,24 @@
+// Free form per node documentation: who created it, what it does, where
+// the python function lives. Part of the graph, not the history - changing
+// it never invalidates anything. Shown in snapshots, the DOT export and
+// the failure reports, so errors can point at the user's code.
+use super::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Any string - json for structured data. None removes it.
+    pub fn set_job_metadata(
+        &mut self,
+        job_id: &str,
+        metadata: Option<String>,
+    ) -> Result<(), PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        self.jobs[node_idx].metadata = metadata;
+        Ok(())
+    }
+
+    pub fn query_job_metadata(&self, job_id: &str) -> Result<Option<&str>, PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        Ok(self.jobs[node_idx].metadata.as_deref())
+    }
+}
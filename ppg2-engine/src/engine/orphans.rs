@@ -0,0 +1,71 @@
+// Outputs nobody should trust: those of jobs that left the graph,
+// and those failed jobs may have written partially.
+//
+// The history remembers every job that ever ran (new_history keeps entries
+// of jobs not in the current graph). Those still present on disk are
+// candidates for a 'clean obsolete files' command.
+use std::collections::HashSet;
+
+use super::{JobState, JobStateEphemeral, JobStateOutput, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Outputs (':::' parts of job ids) recorded in the history, produced by
+    /// no job in the graph, that the strategy reports as present. Sorted.
+    pub fn query_orphaned_outputs(&self) -> Result<Vec<String>, PPGEvaluatorError> {
+        let current: HashSet<&str> = self
+            .jobs
+            .iter()
+            .flat_map(|job| job.job_id.split(":::"))
+            .chain(self.aliases.iter().map(|(alias, _)| alias.as_str()))
+            .collect();
+        let mut candidates: Vec<&str> = self
+            .history
+            .keys()
+            .filter(|key| !key.contains("!!!"))
+            .flat_map(|job_id| job_id.split(":::"))
+            .filter(|output| !current.contains(output))
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        let presence = self.strategy.outputs_already_present(&candidates);
+        let mut res = Vec::new();
+        for (output, present) in candidates.into_iter().zip(presence) {
+            if present? {
+                res.push(output.to_string());
+            }
+        }
+        Ok(res)
+    }
+
+    /// Outputs (':::' parts) of Output / Ephemeral jobs that failed
+    /// (or were aborted while running) and that the strategy reports as present -
+    /// likely half written. Sorted.
+    pub fn query_failed_outputs_for_cleanup(&self) -> Result<Vec<String>, PPGEvaluatorError> {
+        let mut candidates: Vec<&str> = self
+            .jobs
+            .iter()
+            .filter(|job| match job.state {
+                JobState::Output(JobStateOutput::FinishedFailure)
+                | JobState::Ephemeral(JobStateEphemeral::FinishedFailure) => true,
+                JobState::Output(JobStateOutput::FinishedAborted)
+                | JobState::Ephemeral(JobStateEphemeral::FinishedAborted) => {
+                    job.started_at.is_some()
+                }
+                _ => false,
+            })
+            .flat_map(|job| job.job_id.split(":::"))
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        // not the presence cache - the failed job may have written since
+        let presence = self.strategy.outputs_already_present(&candidates);
+        let mut res = Vec::new();
+        for (output, present) in candidates.into_iter().zip(presence) {
+            if present? {
+                res.push(output.to_string());
+            }
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,123 @@
+// Deadline aware run planning - which of the pending jobs fit into a
+// wall clock budget, e.g. a nightly window on a shared cluster.
+//
+// Costs are the runtime estimates (set_runtime_estimate), running jobs count
+// with what's left of theirs. The plan is conservative: jobs are assumed to
+// run one after another, and jobs without an estimate are deferred.
+// Whole chains are preferred: it goes by the pending jobs without pending
+// downstreams, cheapest chain (job plus its pending upstreams) first,
+// and only takes a chain if all of it fits.
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::{JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, NodeIndex, PPGEvaluator};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunPlan {
+    /// jobs to run within the budget, in topological order
+    pub scheduled: Vec<String>,
+    /// pending jobs left for later, in topological order
+    pub deferred: Vec<String>,
+    /// summed estimates of the scheduled jobs
+    pub expected: Duration,
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// None: can't be planned
+    fn remaining_cost(&self, node_idx: NodeIndex) -> Option<Duration> {
+        let job = &self.jobs[node_idx];
+        let estimate = self.scheduling.runtime_estimates.get(&job.job_id).copied();
+        match job.state {
+            JobState::Always(JobStateAlways::Running)
+            | JobState::Output(JobStateOutput::Running)
+            | JobState::Ephemeral(JobStateEphemeral::Running(_)) => {
+                let elapsed = job
+                    .started_at
+                    .map(|s| self.elapsed_since(s))
+                    .unwrap_or_default();
+                Some(estimate.unwrap_or_default().saturating_sub(elapsed))
+            }
+            _ => estimate,
+        }
+    }
+
+    /// node_idx and its pending upstreams not in planned yet - None if one can't be planned
+    fn unplanned_chain(
+        &self,
+        node_idx: NodeIndex,
+        planned: &HashSet<NodeIndex>,
+    ) -> Option<(HashSet<NodeIndex>, Duration)> {
+        let mut chain = HashSet::new();
+        let mut cost = Duration::ZERO;
+        let mut stack = vec![node_idx];
+        while let Some(idx) = stack.pop() {
+            if planned.contains(&idx) || !chain.insert(idx) {
+                continue;
+            }
+            cost += self.remaining_cost(idx)?;
+            stack.extend(
+                self.dag
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .filter(|upstream_idx| !self.jobs[*upstream_idx].state.is_finished()),
+            );
+        }
+        Some((chain, cost))
+    }
+
+    /// Split the pending (not yet finished) jobs into those that can complete
+    /// within budget and those that are deferred - see the module comment.
+    pub fn plan_within(&self, budget: Duration) -> Result<RunPlan, PPGEvaluatorError> {
+        let topo = match &self.topo {
+            Some(topo) => topo.clone(),
+            None => self
+                .dag
+                .toposort()
+                .map_err(|node_idx| PPGEvaluatorError::Cycle(self.jobs[node_idx].job_id.clone()))?,
+        };
+        let pending: Vec<NodeIndex> = topo
+            .into_iter()
+            .filter(|idx| !self.jobs[*idx].state.is_finished())
+            .collect();
+        let mut chains: Vec<(Duration, &str, HashSet<NodeIndex>)> = pending
+            .iter()
+            .filter(|idx| {
+                self.dag
+                    .neighbors_directed(**idx, Direction::Outgoing)
+                    .all(|downstream_idx| self.jobs[downstream_idx].state.is_finished())
+            })
+            .filter_map(|idx| {
+                self.unplanned_chain(*idx, &HashSet::new())
+                    .map(|(chain, cost)| (cost, self.jobs[*idx].job_id.as_str(), chain))
+            })
+            .collect();
+        chains.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        let mut planned: HashSet<NodeIndex> = HashSet::new();
+        let mut expected = Duration::ZERO;
+        for (_, job_id, _) in chains {
+            // shared upstreams may have been planned by a cheaper chain
+            if let Some((chain, cost)) =
+                self.unplanned_chain(self.job_id_to_node_idx[job_id], &planned)
+            {
+                if expected + cost <= budget {
+                    expected += cost;
+                    planned.extend(chain);
+                }
+            }
+        }
+        let (scheduled, deferred): (Vec<NodeIndex>, Vec<NodeIndex>) =
+            pending.into_iter().partition(|idx| planned.contains(idx));
+        let job_ids = |idxs: Vec<NodeIndex>| -> Vec<String> {
+            idxs.into_iter()
+                .map(|idx| self.jobs[idx].job_id.clone())
+                .collect()
+        };
+        Ok(RunPlan {
+            scheduled: job_ids(scheduled),
+            deferred: job_ids(deferred),
+            expected,
+        })
+    }
+}
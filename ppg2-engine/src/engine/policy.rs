@@ -0,0 +1,307 @@
+// Pluggable ordering of the jobs that may start.
+//
+// The engine decides which jobs may run now (dependencies, exclusive jobs,
+// rate limits). An installed SchedulingPolicy then decides in which order
+// the executor should start them - and may hold some back, e.g. to stay
+// within a memory budget. Without one, ready jobs come sorted by job id.
+//
+// Policies are code: snapshots don't carry them, reinstall after from_snapshot.
+//
+// Backfill: while a wide job waits for enough cores to free up,
+// query_backfill_candidates suggests ready jobs that fit into the free cores
+// and - by their runtime estimate - finish before the wide job could start.
+// Jobs without an estimate are never suggested.
+//
+// With set_record_runtimes, successful jobs' runtimes are recorded in the
+// history, and stand in for a missing set_runtime_estimate in the next run's
+// ReadyCandidates.
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use super::PPGEvaluator;
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+pub(crate) const RUNTIME_KEY_PREFIX: &str = "!!!runtime!!!";
+
+/// A job that may be started now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadyCandidate {
+    pub job_id: String,
+    /// see set_runtime_estimate, or the runtime recorded last run (see set_record_runtimes)
+    pub runtime_estimate: Option<Duration>,
+    /// direct downstreams in the graph
+    pub downstream_count: usize,
+    /// see set_required_cores, default 1
+    pub required_cores: usize,
+    /// when the job became ready, relative to the others - lower is earlier
+    pub ready_order: u64,
+    /// how long the job has been ready
+    pub waiting: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceState {
+    /// running jobs, sorted
+    pub running: Vec<String>,
+    pub cores: usize,
+}
+
+pub trait SchedulingPolicy: Send {
+    /// The job ids to start, first one first - any subset of candidates
+    /// (which come sorted by job id). Other job ids are ignored.
+    fn order(&self, candidates: Vec<ReadyCandidate>, resources: &ResourceState) -> Vec<String>;
+}
+
+/// In the order they became ready
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fifo;
+
+impl SchedulingPolicy for Fifo {
+    fn order(&self, mut candidates: Vec<ReadyCandidate>, _: &ResourceState) -> Vec<String> {
+        candidates.sort_by_key(|c| c.ready_order);
+        candidates.into_iter().map(|c| c.job_id).collect()
+    }
+}
+
+/// Largest runtime estimate first, jobs without one last (in ready order)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LongestJobFirst;
+
+impl SchedulingPolicy for LongestJobFirst {
+    fn order(&self, mut candidates: Vec<ReadyCandidate>, _: &ResourceState) -> Vec<String> {
+        candidates.sort_by_key(|c| (std::cmp::Reverse(c.runtime_estimate), c.ready_order));
+        candidates.into_iter().map(|c| c.job_id).collect()
+    }
+}
+
+/// Largest runtime estimate first - but waiting jobs age: every second waited
+/// counts as aging seconds of runtime, so short jobs are not starved forever
+/// by a stream of long ones. Jobs without an estimate count as 0 seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct LongestJobFirstWithAging {
+    pub aging: f64,
+}
+
+impl Default for LongestJobFirstWithAging {
+    fn default() -> Self {
+        LongestJobFirstWithAging { aging: 1.0 }
+    }
+}
+
+impl SchedulingPolicy for LongestJobFirstWithAging {
+    fn order(&self, candidates: Vec<ReadyCandidate>, _: &ResourceState) -> Vec<String> {
+        let mut scored: Vec<(f64, ReadyCandidate)> = candidates
+            .into_iter()
+            .map(|c| {
+                let estimate = c.runtime_estimate.unwrap_or_default().as_secs_f64();
+                (estimate + self.aging * c.waiting.as_secs_f64(), c)
+            })
+            .collect();
+        scored.sort_by(|(score_a, a), (score_b, b)| {
+            score_b
+                .total_cmp(score_a)
+                .then(a.ready_order.cmp(&b.ready_order))
+        });
+        scored.into_iter().map(|(_, c)| c.job_id).collect()
+    }
+}
+
+/// Most direct downstreams first - unlocks the most work soonest
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MostDownstreamsFirst;
+
+impl SchedulingPolicy for MostDownstreamsFirst {
+    fn order(&self, mut candidates: Vec<ReadyCandidate>, _: &ResourceState) -> Vec<String> {
+        candidates.sort_by_key(|c| (std::cmp::Reverse(c.downstream_count), c.ready_order));
+        candidates.into_iter().map(|c| c.job_id).collect()
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// None for the default - sorted by job id
+    pub fn set_scheduling_policy(&mut self, policy: Option<Box<dyn SchedulingPolicy>>) {
+        self.policy = policy;
+    }
+
+    /// For LongestJobFirst & co
+    pub fn set_runtime_estimate(
+        &mut self,
+        job_id: &str,
+        estimate: Duration,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        self.scheduling
+            .runtime_estimates
+            .insert(job_id.to_string(), estimate);
+        Ok(())
+    }
+
+    /// Cores the job occupies while running, default 1
+    pub fn set_required_cores(
+        &mut self,
+        job_id: &str,
+        cores: usize,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        if cores == 0 {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} needs at least one core",
+                job_id
+            )));
+        }
+        self.scheduling.cores.insert(job_id.to_string(), cores);
+        Ok(())
+    }
+
+    pub(crate) fn required_cores(&self, job_id: &str) -> usize {
+        self.scheduling.cores.get(job_id).copied().unwrap_or(1)
+    }
+
+    /// Bytes of memory the job occupies while running, default 0 - see query_utilization
+    pub fn set_required_memory(
+        &mut self,
+        job_id: &str,
+        bytes: u64,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        self.scheduling.memory.insert(job_id.to_string(), bytes);
+        Ok(())
+    }
+
+    pub(crate) fn required_memory(&self, job_id: &str) -> u64 {
+        self.scheduling.memory.get(job_id).copied().unwrap_or(0)
+    }
+
+    /// set_runtime_estimate's, or the runtime recorded in the last run
+    fn candidate_runtime_estimate(&self, job_id: &str) -> Option<Duration> {
+        if let Some(estimate) = self.scheduling.runtime_estimates.get(job_id) {
+            return Some(*estimate);
+        }
+        let recorded = self
+            .history
+            .get(&format!("{}{}", RUNTIME_KEY_PREFIX, job_id))?;
+        match recorded.parse::<f64>() {
+            Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+                Some(Duration::from_secs_f64(seconds))
+            }
+            _ => {
+                warn!("Ignoring unreadable runtime for {}: {}", job_id, recorded);
+                None
+            }
+        }
+    }
+
+    /// Record the runtimes of successful jobs in the history - off by default,
+    /// for it changes the history on every run.
+    pub fn set_record_runtimes(&mut self, record: bool) {
+        self.scheduling.record_runtimes = record;
+    }
+
+    /// write the runtimes of the jobs that succeeded this run into a new history,
+    /// dropping those of jobs no longer in the graph
+    pub(super) fn record_runtimes(&self, history: &mut HashMap<String, String>) {
+        if !self.scheduling.record_runtimes {
+            return;
+        }
+        history.retain(|key, _| match key.strip_prefix(RUNTIME_KEY_PREFIX) {
+            Some(job_id) => self.job_id_to_node_idx.contains_key(job_id),
+            None => true,
+        });
+        for job in self.jobs.iter() {
+            // invariants and barriers are finished by the engine, nothing to learn
+            if job.history_output.is_none() || job.invariant.is_some() || job.barrier {
+                continue;
+            }
+            if let Some(runtime) = job.runtime {
+                history.insert(
+                    format!("{}{}", RUNTIME_KEY_PREFIX, job.job_id),
+                    runtime.as_secs_f64().to_string(),
+                );
+            }
+        }
+    }
+
+    /// Ready jobs to start in free_cores that are expected to be done within
+    /// time_window - shortest first, together fitting into free_cores.
+    pub fn query_backfill_candidates(
+        &self,
+        free_cores: usize,
+        time_window: Duration,
+    ) -> Vec<String> {
+        let mut fitting: Vec<(Duration, String)> = self
+            .schedulable_ready_to_run()
+            .into_iter()
+            .filter_map(|job_id| {
+                let estimate = *self.scheduling.runtime_estimates.get(&job_id)?;
+                (estimate <= time_window && self.required_cores(&job_id) <= free_cores)
+                    .then_some((estimate, job_id))
+            })
+            .collect();
+        fitting.sort();
+        let mut cores_left = free_cores;
+        let mut res = Vec::new();
+        for (_, job_id) in fitting {
+            let cores = self.required_cores(&job_id);
+            if cores <= cores_left {
+                cores_left -= cores;
+                res.push(job_id);
+            }
+        }
+        res
+    }
+
+    /// query_ready_to_run in the order the scheduling policy wants them started
+    /// (then interleaved across groups, see set_fair_share)
+    pub fn query_ready_to_run_ordered(&self) -> Vec<String> {
+        let allowed = self.schedulable_ready_to_run();
+        self.fair_share_order(self.policy_order(allowed))
+    }
+
+    fn policy_order(&self, allowed: HashSet<String>) -> Vec<String> {
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => {
+                let mut res: Vec<String> = allowed.into_iter().collect();
+                res.sort();
+                return res;
+            }
+        };
+        let now = self.now();
+        let mut candidates: Vec<ReadyCandidate> = allowed
+            .iter()
+            .map(|job_id| ReadyCandidate {
+                job_id: job_id.clone(),
+                runtime_estimate: self.candidate_runtime_estimate(job_id),
+                downstream_count: self
+                    .dag
+                    .neighbors_directed(self.job_id_to_node_idx[job_id], Direction::Outgoing)
+                    .count(),
+                required_cores: self.required_cores(job_id),
+                ready_order: self
+                    .scheduling
+                    .ready_order
+                    .get(job_id)
+                    .copied()
+                    .unwrap_or(u64::MAX),
+                waiting: self.jobs[self.job_id_to_node_idx[job_id]]
+                    .ready_at
+                    .map(|ready_at| now.duration_since(ready_at))
+                    .unwrap_or_default(),
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        let mut running: Vec<String> = self.query_jobs_running().into_iter().collect();
+        running.sort();
+        let resources = ResourceState {
+            running,
+            cores: num_cpus::get(),
+        };
+        let mut seen = HashSet::new();
+        policy
+            .order(candidates, &resources)
+            .into_iter()
+            .filter(|job_id| allowed.contains(job_id) && seen.insert(job_id.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,96 @@
+// Why does a ready job run? For 'running X because Y changed' lines while
+// the graph executes.
+//
+// What startup finds out about a job itself (new, output missing, inputs
+// changed) is recorded while identifying missing outputs; invalidated upstreams
+// are read off the edges. Recorded reasons are per run - a job restored
+// from a snapshot only reports what its edges tell.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{JobKind, PPGEvaluator};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunReason {
+    /// Always / Conditional jobs
+    Always,
+    /// set_force_rerun_all
+    Forced,
+    /// never ran successfully before
+    NewJob,
+    OutputMissing,
+    /// upstreams were added or removed
+    InputsChanged,
+    /// these upstreams' output changed, sorted
+    UpstreamChanged(Vec<String>),
+    /// Ephemeral - a downstream has to run
+    NeededByDownstream,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RunReasons {
+    identified: HashMap<String, RunReason>,
+}
+
+impl RunReasons {
+    pub(super) fn new_run(&mut self) {
+        self.identified.clear();
+    }
+
+    pub(super) fn record(&mut self, job_id: &str, reason: RunReason) {
+        self.identified.insert(job_id.to_string(), reason);
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Why job_id runs (or ran) this run. None if it doesn't.
+    pub fn query_run_reason(&self, job_id: &str) -> Result<Option<RunReason>, PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        let job = &self.jobs[node_idx];
+        let runs = job.started_at.is_some() || self.jobs_ready_to_run.contains(job_id);
+        if !runs {
+            return Ok(None);
+        }
+        if matches!(job.kind(), JobKind::Always | JobKind::Conditional) {
+            return Ok(Some(RunReason::Always));
+        }
+        if let Some(reason) = self.run_reasons.identified.get(job_id) {
+            return Ok(Some(reason.clone()));
+        }
+        let mut changed: Vec<String> = self
+            .invalidating_upstreams(node_idx)
+            .into_iter()
+            .map(|upstream_idx| self.jobs[upstream_idx].job_id.clone())
+            .collect();
+        if !changed.is_empty() {
+            changed.sort();
+            return Ok(Some(RunReason::UpstreamChanged(changed)));
+        }
+        if matches!(job.kind(), JobKind::Ephemeral | JobKind::TempFile)
+            && self
+                .dag
+                .neighbors_directed(node_idx, Direction::Outgoing)
+                .next()
+                .is_some()
+        {
+            return Ok(Some(RunReason::NeededByDownstream));
+        }
+        Ok(None)
+    }
+
+    /// query_ready_to_run, each with its reason. Sorted by job id.
+    pub fn query_ready_to_run_with_reasons(&self) -> Vec<(String, Option<RunReason>)> {
+        let mut ready: Vec<String> = self.query_ready_to_run().into_iter().collect();
+        ready.sort();
+        ready
+            .into_iter()
+            .map(|job_id| {
+                let reason = self.query_run_reason(&job_id).ok().flatten();
+                (job_id, reason)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,68 @@
+// Rebuilding lost history from outputs that are present (set_assume_unchanged).
+//
+// Output jobs whose output exists but that have no history take the value
+// the strategy reports for them now (current_history), as do their edges and
+// input lists. They then validate like any unchanged job.
+// Upstreams without history (ephemerals, always jobs that did not record)
+// still invalidate them.
+use std::collections::HashMap;
+
+use super::{JobState, NodeIndex, PPGEvaluator};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Accept present outputs without history as up to date and record their
+    /// current history, instead of rebuilding them.
+    pub fn set_assume_unchanged(&mut self, assume_unchanged: bool) {
+        self.assume_unchanged = assume_unchanged;
+    }
+
+    /// Called with the prefetched input lists, which it extends
+    pub(super) fn reconstruct_missing_history(
+        &mut self,
+        input_lists: &mut HashMap<NodeIndex, String>,
+    ) -> Result<(), PPGEvaluatorError> {
+        let mut reconstructed = Vec::new();
+        for node_idx in 0..self.jobs.len() {
+            let job = &self.jobs[node_idx];
+            if !matches!(job.state, JobState::Output(_))
+                || !self.dag.contains_node(node_idx)
+                || self.history.contains_key(&job.job_id)
+                || !Self::cached_output_already_present(
+                    &self.strategy,
+                    &mut self.presence_cache,
+                    &job.job_id,
+                )?
+            {
+                continue;
+            }
+            if let Some(value) = self.strategy.current_history(&job.job_id)? {
+                debug!("No history for {}, assuming it's unchanged", job.job_id);
+                self.history.insert(job.job_id.clone(), value);
+                reconstructed.push(node_idx);
+            }
+        }
+        for node_idx in reconstructed {
+            let job_id = &self.jobs[node_idx].job_id;
+            for upstream_idx in self.dag.neighbors_directed(node_idx, Direction::Incoming) {
+                let upstream_id = &self.jobs[upstream_idx].job_id;
+                let key = format!("{}!!!{}", upstream_id, job_id);
+                if !self.history.contains_key(&key) {
+                    if let Some(value) = self.history.get(upstream_id).map(|x| x.to_string()) {
+                        self.history.insert(key, value);
+                    }
+                }
+            }
+            let input_key = format!("{}!!!", job_id);
+            if !self.history.contains_key(&input_key) {
+                let input_list = self
+                    .strategy
+                    .get_input_list(node_idx, &self.dag, &self.jobs)?;
+                self.history.insert(input_key, input_list.clone());
+                input_lists.insert(node_idx, input_list);
+            }
+        }
+        Ok(())
+    }
+}
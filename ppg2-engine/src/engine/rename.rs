@@ -0,0 +1,225 @@
+// Carrying history over to renamed jobs.
+//
+// A renamed job has no history under its new id, so it and - through their
+// changed input lists - all its downstreams would be rebuilt.
+// rename_history moves the old entries over before event_startup,
+// detect_renames points out likely candidates after a run.
+// Aliases do the same on every run: history is stored under the alias
+// and moved to the current target id at event_startup.
+use std::collections::HashMap;
+
+use super::cleanup::RETENTION_KEY_PREFIX;
+use super::generators::GENERATED_KEY_PREFIX;
+use super::last_success::LAST_SUCCESS_KEY_PREFIX;
+use super::policy::RUNTIME_KEY_PREFIX;
+use super::{PPGEvaluator, StartStatus};
+use crate::history_store::HistoryStore;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+/// old_id -> new_id in a newline separated, sorted input list.
+/// None if old_id isn't in there.
+fn rename_in_input_list(input_list: &str, old_id: &str, new_id: &str) -> Option<String> {
+    if !input_list.split('\n').any(|input| input == old_id) {
+        return None;
+    }
+    let mut inputs: Vec<&str> = input_list
+        .split('\n')
+        .map(|input| if input == old_id { new_id } else { input })
+        .collect();
+    inputs.sort_unstable();
+    Some(inputs.join("\n"))
+}
+
+/// (key, new key, new value) for all history entries of old_id
+fn planned_moves<'a>(
+    history: impl Iterator<Item = (&'a String, &'a str)>,
+    old_id: &str,
+    new_id: &str,
+) -> Vec<(String, String, String)> {
+    let rename = |job_id: &str| {
+        if job_id == old_id {
+            new_id.to_string()
+        } else {
+            job_id.to_string()
+        }
+    };
+    let mut res = Vec::new();
+    for (key, value) in history {
+        let new_key = match key.split_once("!!!") {
+            None => rename(key),
+            Some(_) if key.starts_with(RETENTION_KEY_PREFIX) => format!(
+                "{}{}",
+                RETENTION_KEY_PREFIX,
+                rename(&key[RETENTION_KEY_PREFIX.len()..])
+            ),
+            Some(_) if key.starts_with(GENERATED_KEY_PREFIX) => format!(
+                "{}{}",
+                GENERATED_KEY_PREFIX,
+                rename(&key[GENERATED_KEY_PREFIX.len()..])
+            ),
+            Some(_) if key.starts_with(RUNTIME_KEY_PREFIX) => format!(
+                "{}{}",
+                RUNTIME_KEY_PREFIX,
+                rename(&key[RUNTIME_KEY_PREFIX.len()..])
+            ),
+            Some(_) if key.starts_with(LAST_SUCCESS_KEY_PREFIX) => format!(
+                "{}{}",
+                LAST_SUCCESS_KEY_PREFIX,
+                rename(&key[LAST_SUCCESS_KEY_PREFIX.len()..])
+            ),
+            Some((upstream, downstream)) => {
+                format!("{}!!!{}", rename(upstream), rename(downstream))
+            }
+        };
+        let new_value = if new_key.ends_with("!!!") {
+            rename_in_input_list(value, old_id, new_id)
+        } else {
+            None
+        };
+        if new_key != *key || new_value.is_some() {
+            res.push((
+                key.clone(),
+                new_key,
+                new_value.unwrap_or_else(|| value.to_string()),
+            ));
+        }
+    }
+    res
+}
+
+/// Rewrite all history entries of old_id to new_id.
+/// Rewritten entries replace existing ones of new_id.
+/// Returns the number of rewritten entries.
+fn move_history(history: &mut HistoryStore, old_id: &str, new_id: &str) -> usize {
+    let moves = planned_moves(history.iter(), old_id, new_id);
+    for (key, _, _) in moves.iter() {
+        history.remove(key);
+    }
+    let count = moves.len();
+    for (_, new_key, value) in moves {
+        history.insert(new_key, value);
+    }
+    count
+}
+
+/// move_history for the plain map new_history hands out
+fn move_history_map(history: &mut HashMap<String, String>, old_id: &str, new_id: &str) {
+    let moves = planned_moves(history.iter().map(|(k, v)| (k, v.as_str())), old_id, new_id);
+    for (key, _, _) in moves.iter() {
+        history.remove(key);
+    }
+    for (_, new_key, value) in moves {
+        history.insert(new_key, value);
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Rewrite the history of old_id to new_id: its output, its input list,
+    /// the edges from and to it, and its appearance in downstream input lists
+    /// (assuming the default newline separated format).
+    /// Returns the number of rewritten entries.
+    pub fn rename_history(
+        &mut self,
+        old_id: &str,
+        new_id: &str,
+    ) -> Result<usize, PPGEvaluatorError> {
+        if self.already_started != StartStatus::NotStarted {
+            return Err(PPGEvaluatorError::APIError(
+                "History can only be renamed before event_startup".to_string(),
+            ));
+        }
+        if new_id.is_empty() || new_id.contains("!!!") {
+            return Err(PPGEvaluatorError::InvalidJobId(new_id.to_string()));
+        }
+        if !self.history.contains_key(old_id) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "No history for {}",
+                old_id
+            )));
+        }
+        if self.history.contains_key(new_id) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} already has a history, not overwriting it",
+                new_id
+            )));
+        }
+        Ok(move_history(&mut self.history, old_id, new_id))
+    }
+
+    /// Store target's history under alias - a stable name for a job whose
+    /// id changes between versions (before event_startup).
+    pub fn add_alias(&mut self, alias: &str, target: &str) -> Result<(), PPGEvaluatorError> {
+        if self.already_started != StartStatus::NotStarted {
+            return Err(PPGEvaluatorError::APIError(
+                "Aliases can only be added before event_startup".to_string(),
+            ));
+        }
+        if alias.is_empty() || alias.contains("!!!") {
+            return Err(PPGEvaluatorError::InvalidJobId(alias.to_string()));
+        }
+        if !self.contains_node(target) {
+            return Err(PPGEvaluatorError::UnknownJob(target.to_string()));
+        }
+        if self.contains_node(alias) || self.aliases.iter().any(|(a, _)| a == alias) {
+            return Err(PPGEvaluatorError::JobRedefinition(alias.to_string()));
+        }
+        if self.aliases.iter().any(|(_, t)| t == target) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "{} already has an alias",
+                target
+            )));
+        }
+        self.aliases.push((alias.to_string(), target.to_string()));
+        Ok(())
+    }
+
+    pub(super) fn is_alias(&self, job_id: &str) -> bool {
+        !self.aliases.is_empty() && self.aliases.iter().any(|(alias, _)| alias == job_id)
+    }
+
+    /// alias -> target, at event_startup
+    pub(super) fn resolve_aliases_in_history(&mut self) {
+        for (alias, target) in self.aliases.iter() {
+            if self.history.contains_key(alias)
+                || self.history.contains_key(&format!("{}!!!", alias))
+            {
+                move_history(&mut self.history, alias, target);
+            }
+        }
+    }
+
+    /// target -> alias, for new_history
+    pub(super) fn apply_aliases_to_history(&self, history: &mut HashMap<String, String>) {
+        for (alias, target) in self.aliases.iter() {
+            move_history_map(history, target, alias);
+        }
+    }
+
+    /// (old_id, new_id) pairs: jobs without history whose output
+    /// exactly matches the recorded output of a job id no longer in the graph.
+    pub fn detect_renames(&self) -> Vec<(String, String)> {
+        let mut vanished: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (key, value) in self.history.iter() {
+            if !key.contains("!!!") && !self.job_id_to_node_idx.contains_key(key) {
+                vanished.entry(value).or_default().push(key.as_str());
+            }
+        }
+        let mut res = Vec::new();
+        for job in self.jobs.iter() {
+            if self.history.contains_key(&job.job_id) {
+                continue;
+            }
+            if let Some(old_ids) = job
+                .history_output
+                .as_ref()
+                .and_then(|output| vanished.get(output.as_str()))
+            {
+                for old_id in old_ids {
+                    res.push((old_id.to_string(), job.job_id.clone()));
+                }
+            }
+        }
+        res.sort();
+        res
+    }
+}
@@ -0,0 +1,203 @@
+// One flat record per job of a run, for analytics outside of the logs.
+//
+// run_records() is row oriented (and serde serializable), run_record_columns()
+// the same as one list per field - what pyarrow.table / pandas.DataFrame /
+// polars.DataFrame take directly. The python side turns that into an
+// Arrow table or Parquet file (pypipegraph2.run_records, needs pyarrow).
+//
+// query_failed_info is the same for just the failed jobs, plus what they
+// saw of their upstreams - so a report doesn't need the executor's logs.
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_64;
+
+use super::{JobKind, JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, PPGEvaluator};
+use crate::graph::Direction;
+use crate::report::outcome;
+use crate::PPGEvaluatorStrategy;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub kind: String,
+    /// success, failed, upstream failed, skipped, aborted, unfinished
+    pub state: String,
+    /// seconds since the unix epoch
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    /// seconds
+    pub runtime: Option<f64>,
+    /// 1 + speculative attempts started
+    pub attempt: Option<u32>,
+    /// why the job ran - None if it didn't
+    pub invalidation_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobRecordColumns {
+    pub job_id: Vec<String>,
+    pub kind: Vec<String>,
+    pub state: Vec<String>,
+    pub start: Vec<Option<f64>>,
+    pub end: Vec<Option<f64>>,
+    pub runtime: Vec<Option<f64>>,
+    pub attempt: Vec<Option<u32>>,
+    pub invalidation_reason: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedJobInfo {
+    pub job_id: String,
+    /// 1 + speculative attempts started
+    pub attempts: u32,
+    /// seconds since the unix epoch
+    pub started: Option<f64>,
+    pub failed: Option<f64>,
+    /// hash over the upstreams' outputs the job ran with - equal
+    /// fingerprints mean a retry sees exactly the same inputs
+    pub upstream_fingerprint: String,
+    pub error: Option<String>,
+}
+
+fn unix_seconds(now: SystemTime, now_instant: Instant, at: Instant) -> Option<f64> {
+    now.checked_sub(now_instant.duration_since(at))
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .map(|x| x.as_secs_f64())
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    fn invalidation_reason(&self, node_idx: usize) -> String {
+        let job = &self.jobs[node_idx];
+        let upstreams = self.invalidating_upstreams(node_idx);
+        if job.kind() == JobKind::Always {
+            "always runs".to_string()
+        } else if self.force_rerun_all {
+            "forced rerun".to_string()
+        } else if !upstreams.is_empty() {
+            let ids: Vec<&str> = upstreams
+                .iter()
+                .map(|idx| self.jobs[*idx].job_id.as_str())
+                .collect();
+            format!("upstream changed: {}", ids.join(", "))
+        } else if !self.history.contains_key(&format!("{}!!!", job.job_id)) {
+            "no history".to_string()
+        } else if self.presence_cache.get(&job.job_id) == Some(&false) {
+            "output missing".to_string()
+        } else {
+            "inputs changed".to_string()
+        }
+    }
+
+    /// One record per job, sorted by job id
+    pub fn run_records(&self) -> Vec<JobRecord> {
+        let now = self.system_now();
+        let now_instant = self.now();
+        let mut res: Vec<JobRecord> = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.dag.contains_node(*idx))
+            .map(|(idx, job)| {
+                let start = job
+                    .started_at
+                    .and_then(|at| unix_seconds(now, now_instant, at));
+                let runtime = job.runtime.map(|x| x.as_secs_f64());
+                JobRecord {
+                    job_id: job.job_id.clone(),
+                    kind: format!("{:?}", job.kind()),
+                    state: outcome(&job.state).to_string(),
+                    start,
+                    end: start.zip(runtime).map(|(start, runtime)| start + runtime),
+                    runtime,
+                    attempt: job
+                        .started_at
+                        .map(|_| self.speculation.attempts_started(&job.job_id)),
+                    invalidation_reason: job.started_at.map(|_| self.invalidation_reason(idx)),
+                }
+            })
+            .collect();
+        res.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        res
+    }
+
+    /// run_records, one vector per field
+    pub fn run_record_columns(&self) -> JobRecordColumns {
+        let mut res = JobRecordColumns::default();
+        for record in self.run_records() {
+            res.job_id.push(record.job_id);
+            res.kind.push(record.kind);
+            res.state.push(record.state);
+            res.start.push(record.start);
+            res.end.push(record.end);
+            res.runtime.push(record.runtime);
+            res.attempt.push(record.attempt);
+            res.invalidation_reason.push(record.invalidation_reason);
+        }
+        res
+    }
+
+    /// What query_failed returns, with attempts, timing and upstream
+    /// fingerprint per job. Sorted by job id.
+    pub fn query_failed_info(&self) -> Vec<FailedJobInfo> {
+        let now = self.system_now();
+        let now_instant = self.now();
+        let mut res: Vec<FailedJobInfo> = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter(|(_, job)| {
+                matches!(
+                    job.state,
+                    JobState::Always(JobStateAlways::FinishedFailure)
+                        | JobState::Output(JobStateOutput::FinishedFailure)
+                        | JobState::Ephemeral(JobStateEphemeral::FinishedFailure)
+                )
+            })
+            .map(|(idx, job)| {
+                let started = job
+                    .started_at
+                    .and_then(|at| unix_seconds(now, now_instant, at));
+                FailedJobInfo {
+                    job_id: job.job_id.clone(),
+                    attempts: self.speculation.attempts_started(&job.job_id),
+                    started,
+                    failed: started
+                        .zip(job.runtime)
+                        .map(|(started, runtime)| started + runtime.as_secs_f64()),
+                    upstream_fingerprint: self.upstream_fingerprint(idx),
+                    error: job.error.clone(),
+                }
+            })
+            .collect();
+        res.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        res
+    }
+
+    // upstreams are all finished before a job starts,
+    // so this is still what the job saw when it failed
+    fn upstream_fingerprint(&self, node_idx: usize) -> String {
+        let mut upstreams: Vec<(&str, &str)> = self
+            .dag
+            .neighbors_directed(node_idx, Direction::Incoming)
+            .map(|upstream_idx| {
+                let upstream = &self.jobs[upstream_idx];
+                let output = upstream
+                    .history_output
+                    .as_deref()
+                    .or_else(|| self.history.get(&upstream.job_id))
+                    .unwrap_or("");
+                (upstream.job_id.as_str(), output)
+            })
+            .collect();
+        upstreams.sort();
+        let mut input = String::new();
+        for (job_id, output) in upstreams {
+            input.push_str(job_id);
+            input.push('\0');
+            input.push_str(output);
+            input.push('\0');
+        }
+        format!("{:016x}", xxh3_64(input.as_bytes()))
+    }
+}
@@ -0,0 +1,328 @@
+// Constraints on which ready jobs query_ready_to_run hands out.
+//
+// Exclusive jobs (set_exclusive) run alone on the machine - think 'needs all
+// the RAM' or 're-indexes the shared database'. They are only handed out
+// when nothing else is running, one at a time, and while one runs nothing
+// else is handed out. Other ready jobs go first, so an exclusive job waits
+// for the machine to drain instead of stalling everything behind it.
+//
+// Rate limits (set_rate_limit) cap how many jobs carrying a tag (tag_job)
+// are handed out per time window, e.g. for jobs talking to a throttled API.
+// Starts are counted in event_now_running; held back jobs become
+// ready again once the oldest start leaves the window - query_rate_limit_wait
+// tells the executor when to look again.
+//
+// Capabilities (require_capability) are for heterogeneous worker pools:
+// query_ready_to_run_for only hands a worker the jobs whose required
+// capabilities ('gpu', 'internet', ...) it has.
+//
+// Niceness (set_nice) demotes jobs - optional plots, say - without removing
+// them from the graph: of the ready jobs, only those with the lowest
+// niceness are handed out, so nice jobs wait until no normal work is ready.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::fair_share::FairShare;
+use super::{JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SchedulingInfo {
+    pub(crate) exclusive: HashSet<String>,
+    // job_id -> capabilities a worker needs to run it
+    pub(crate) capabilities: HashMap<String, HashSet<String>>,
+    // job_id -> tags
+    pub(crate) tags: HashMap<String, HashSet<String>>,
+    // tag -> (max jobs, per window)
+    rate_limits: HashMap<String, (usize, Duration)>,
+    // tag -> recent starts, oldest first. Only meaningful within a process
+    #[serde(skip)]
+    starts: HashMap<String, VecDeque<Instant>>,
+    // job_id -> expected runtime, for the scheduling policy
+    pub(crate) runtime_estimates: HashMap<String, Duration>,
+    // job_id -> cores it occupies, if not 1
+    pub(crate) cores: HashMap<String, usize>,
+    // job_id -> bytes of memory it occupies
+    #[serde(default)]
+    pub(crate) memory: HashMap<String, u64>,
+    // job_id -> niceness, if not 0
+    #[serde(default)]
+    pub(crate) nice: HashMap<String, u32>,
+    // job_id -> ready_seq when it last became ready
+    pub(crate) ready_order: HashMap<String, u64>,
+    ready_seq: u64,
+    // write runtimes into the history, see set_record_runtimes
+    #[serde(default)]
+    pub(crate) record_runtimes: bool,
+    pub(crate) fair_share: FairShare,
+    // node_idx -> weakly connected component, for FairShare::Components
+    #[serde(skip)]
+    pub(crate) components: Vec<usize>,
+}
+
+impl SchedulingInfo {
+    pub(super) fn became_ready(&mut self, job_id: &str) {
+        self.ready_seq += 1;
+        self.ready_order.insert(job_id.to_string(), self.ready_seq);
+    }
+
+    fn starts_within_window(&self, tag: &str, window: Duration, now: Instant) -> usize {
+        self.starts.get(tag).map_or(0, |starts| {
+            starts
+                .iter()
+                .filter(|started| now.duration_since(**started) < window)
+                .count()
+        })
+    }
+
+    fn limited_tags<'a>(&'a self, job_id: &str) -> impl Iterator<Item = &'a String> + 'a {
+        self.tags
+            .get(job_id)
+            .into_iter()
+            .flatten()
+            .filter(move |tag| self.rate_limits.contains_key(*tag))
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Run job_id alone on the machine - see query_ready_to_run
+    pub fn set_exclusive(
+        &mut self,
+        job_id: &str,
+        exclusive: bool,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        if exclusive {
+            self.scheduling.exclusive.insert(job_id.to_string());
+        } else {
+            self.scheduling.exclusive.remove(job_id);
+        }
+        Ok(())
+    }
+
+    pub fn query_exclusive(&self) -> HashSet<String> {
+        self.scheduling.exclusive.clone()
+    }
+
+    /// Only hand out job_id when no ready job is less nice - see query_ready_to_run
+    pub fn set_nice(&mut self, job_id: &str, nice: u32) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        if nice == 0 {
+            self.scheduling.nice.remove(job_id);
+        } else {
+            self.scheduling.nice.insert(job_id.to_string(), nice);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn nice(&self, job_id: &str) -> u32 {
+        self.scheduling.nice.get(job_id).copied().unwrap_or(0)
+    }
+
+    /// candidates with the lowest niceness
+    fn least_nice(&self, candidates: HashSet<String>) -> HashSet<String> {
+        if self.scheduling.nice.is_empty() {
+            return candidates;
+        }
+        let lowest = candidates.iter().map(|job_id| self.nice(job_id)).min();
+        candidates
+            .into_iter()
+            .filter(|job_id| Some(self.nice(job_id)) == lowest)
+            .collect()
+    }
+
+    /// Only hand job_id to workers that have capability - see query_ready_to_run_for
+    pub fn require_capability(
+        &mut self,
+        job_id: &str,
+        capability: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        self.scheduling
+            .capabilities
+            .entry(job_id.to_string())
+            .or_default()
+            .insert(capability.to_string());
+        Ok(())
+    }
+
+    /// sorted
+    pub(crate) fn required_capabilities(&self, job_id: &str) -> Vec<String> {
+        let mut res: Vec<String> = self
+            .scheduling
+            .capabilities
+            .get(job_id)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// query_ready_to_run_ordered, limited to the jobs a worker with
+    /// these capabilities can run
+    pub fn query_ready_to_run_for(&self, capabilities: &[&str]) -> Vec<String> {
+        self.query_ready_to_run_ordered()
+            .into_iter()
+            .filter(|job_id| {
+                self.scheduling
+                    .capabilities
+                    .get(job_id)
+                    .is_none_or(|required| {
+                        required.iter().all(|c| capabilities.contains(&c.as_str()))
+                    })
+            })
+            .collect()
+    }
+
+    /// Tag job_id, for set_rate_limit. Jobs may carry several tags.
+    pub fn tag_job(&mut self, job_id: &str, tag: &str) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        self.scheduling
+            .tags
+            .entry(job_id.to_string())
+            .or_default()
+            .insert(tag.to_string());
+        Ok(())
+    }
+
+    /// Hand out at most max_jobs jobs tagged with tag per window.
+    pub fn set_rate_limit(
+        &mut self,
+        tag: &str,
+        max_jobs: usize,
+        window: Duration,
+    ) -> Result<(), PPGEvaluatorError> {
+        if max_jobs == 0 || window.is_zero() {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Rate limit for {} needs max_jobs > 0 and a window > 0",
+                tag
+            )));
+        }
+        self.scheduling
+            .rate_limits
+            .insert(tag.to_string(), (max_jobs, window));
+        Ok(())
+    }
+
+    pub fn remove_rate_limit(&mut self, tag: &str) {
+        self.scheduling.rate_limits.remove(tag);
+        self.scheduling.starts.remove(tag);
+    }
+
+    pub(super) fn record_rate_limited_start(&mut self, job_id: &str) {
+        if self.scheduling.rate_limits.is_empty() {
+            return;
+        }
+        let now = self.now();
+        let tags: Vec<String> = self.scheduling.limited_tags(job_id).cloned().collect();
+        for tag in tags {
+            let window = self.scheduling.rate_limits[&tag].1;
+            let starts = self.scheduling.starts.entry(tag).or_default();
+            while starts
+                .front()
+                .is_some_and(|started| now.duration_since(*started) >= window)
+            {
+                starts.pop_front();
+            }
+            starts.push_back(now);
+        }
+    }
+
+    /// How long until a rate limit admits another of the ready jobs it holds back.
+    /// None if no ready job is held back by a rate limit.
+    pub fn query_rate_limit_wait(&self) -> Option<Duration> {
+        let now = self.now();
+        let held_back: HashSet<String> = self
+            .jobs_ready_to_run
+            .difference(&self.rate_limited(self.jobs_ready_to_run.clone(), now))
+            .flat_map(|job_id| self.scheduling.limited_tags(job_id))
+            .cloned()
+            .collect();
+        held_back
+            .iter()
+            .filter_map(|tag| {
+                let (max_jobs, window) = self.scheduling.rate_limits[tag];
+                let in_window: Vec<&Instant> = self
+                    .scheduling
+                    .starts
+                    .get(tag)?
+                    .iter()
+                    .filter(|started| now.duration_since(**started) < window)
+                    .collect();
+                // the start that has to leave the window to make room for one more
+                let blocking = in_window.len().checked_sub(max_jobs)?;
+                Some(window - now.duration_since(*in_window[blocking]))
+            })
+            .min()
+    }
+
+    /// candidates within the rate limits - by job id, as long as the budgets last
+    fn rate_limited(&self, candidates: HashSet<String>, now: Instant) -> HashSet<String> {
+        if self.scheduling.rate_limits.is_empty() {
+            return candidates;
+        }
+        let mut budgets: HashMap<&str, usize> = self
+            .scheduling
+            .rate_limits
+            .iter()
+            .map(|(tag, (max_jobs, window))| {
+                let used = self.scheduling.starts_within_window(tag, *window, now);
+                (tag.as_str(), max_jobs.saturating_sub(used))
+            })
+            .collect();
+        let mut candidates: Vec<String> = candidates.into_iter().collect();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .filter(|job_id| {
+                let tags: Vec<&String> = self.scheduling.limited_tags(job_id).collect();
+                if tags.iter().any(|tag| budgets[tag.as_str()] == 0) {
+                    return false;
+                }
+                for tag in tags {
+                    *budgets.get_mut(tag.as_str()).unwrap() -= 1;
+                }
+                true
+            })
+            .collect()
+    }
+
+    fn exclusive_job_running(&self) -> bool {
+        self.scheduling.exclusive.iter().any(|job_id| {
+            self.job_id_to_node_idx.get(job_id).is_some_and(|idx| {
+                matches!(
+                    self.jobs[*idx].state,
+                    JobState::Always(JobStateAlways::Running)
+                        | JobState::Output(JobStateOutput::Running)
+                        | JobState::Ephemeral(JobStateEphemeral::Running(_))
+                )
+            })
+        })
+    }
+
+    /// jobs_ready_to_run, minus standby producers of shared outputs and what the
+    /// rate limits, niceness and exclusive jobs forbid right now
+    pub(super) fn schedulable_ready_to_run(&self) -> HashSet<String> {
+        let candidates = self.least_nice(self.rate_limited(
+            self.without_standby_producers(self.jobs_ready_to_run.clone()),
+            self.now(),
+        ));
+        if self.scheduling.exclusive.is_empty() {
+            return candidates;
+        }
+        if self.exclusive_job_running() {
+            return HashSet::new();
+        }
+        let (exclusive, others): (HashSet<String>, HashSet<String>) = candidates
+            .into_iter()
+            .partition(|job_id| self.scheduling.exclusive.contains(job_id));
+        if !others.is_empty() || self.gen.progress.running > 0 {
+            return others;
+        }
+        exclusive.into_iter().min().into_iter().collect()
+    }
+}
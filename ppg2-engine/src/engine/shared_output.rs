@@ -0,0 +1,258 @@
+// Output sharing jobs: several Output jobs producing the same artifact,
+// any one of which satisfies the downstreams (download from mirror A or
+// mirror B).
+//
+// Only the preferred producer that's still in the race is handed out.
+// If it fails while alternatives remain, the failure is held - the job stays
+// running as far as the engine is concerned - and the next one is handed
+// out. The first producer to succeed (or be skipped as up to date) wins:
+// the others, held failures included, are finished with the winner's output.
+// Only once every producer failed do the held failures fail for real.
+//
+// Which producer actually ran is kept in the history
+// ('!!!producer!!!group' -> job_id).
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{JobKind, JobState, JobStateOutput, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+pub(crate) const PRODUCER_KEY_PREFIX: &str = "!!!producer!!!";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SharedOutputInfo {
+    // group -> producers, most preferred first
+    groups: HashMap<String, Vec<String>>,
+    // failed producer -> its error, while alternatives remain
+    held_failures: HashMap<String, Option<String>>,
+    // group -> producer whose output won this run
+    winners: HashMap<String, String>,
+}
+
+impl SharedOutputInfo {
+    pub(super) fn new_run(&mut self) {
+        self.held_failures.clear();
+        self.winners.clear();
+    }
+
+    pub(super) fn remove_job(&mut self, job_id: &str) {
+        for producers in self.groups.values_mut() {
+            producers.retain(|producer| producer != job_id);
+        }
+    }
+
+    fn group_of(&self, job_id: &str) -> Option<&String> {
+        self.groups
+            .iter()
+            .find(|(_, producers)| producers.iter().any(|producer| producer == job_id))
+            .map(|(group, _)| group)
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Declare producers (Output jobs, most preferred first) of one artifact.
+    /// Before startup.
+    pub fn add_shared_output(
+        &mut self,
+        group: &str,
+        producers: &[&str],
+    ) -> Result<(), PPGEvaluatorError> {
+        self.check_graph_mutable()?;
+        if group.is_empty() || group.contains("!!!") {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Invalid shared output group name: {:?}",
+                group
+            )));
+        }
+        if self.shared_outputs.groups.contains_key(group) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Shared output group {} already defined",
+                group
+            )));
+        }
+        if producers.len() < 2 {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Shared output group {} needs at least two producers",
+                group
+            )));
+        }
+        let mut seen = HashSet::new();
+        for producer in producers {
+            let node_idx = self.known_idx(producer)?;
+            if self.jobs[node_idx].kind() != JobKind::Output {
+                return Err(PPGEvaluatorError::APIError(format!(
+                    "Only Output jobs can share an output, {} is {:?}",
+                    producer,
+                    self.jobs[node_idx].kind()
+                )));
+            }
+            if !seen.insert(*producer) {
+                return Err(PPGEvaluatorError::APIError(format!(
+                    "{} listed twice in shared output group {}",
+                    producer, group
+                )));
+            }
+            if let Some(other) = self.shared_outputs.group_of(producer) {
+                return Err(PPGEvaluatorError::APIError(format!(
+                    "{} already produces shared output group {}",
+                    producer, other
+                )));
+            }
+        }
+        self.shared_outputs.groups.insert(
+            group.to_string(),
+            producers.iter().map(|x| x.to_string()).collect(),
+        );
+        Ok(())
+    }
+
+    /// The producer whose output the group's downstreams got this run
+    pub fn query_shared_output_producer(&self, group: &str) -> Option<String> {
+        self.shared_outputs.winners.get(group).cloned()
+    }
+
+    fn producer_state(&self, job_id: &str) -> Option<&JobState> {
+        self.job_id_to_node_idx
+            .get(job_id)
+            .map(|idx| &self.jobs[*idx].state)
+    }
+
+    // neither failed nor held
+    fn producer_in_race(&self, job_id: &str) -> bool {
+        !self.shared_outputs.held_failures.contains_key(job_id)
+            && self
+                .producer_state(job_id)
+                .is_some_and(|state| !state.is_failed())
+    }
+
+    /// Ready producers that are not (yet) their group's turn
+    pub(super) fn without_standby_producers(
+        &self,
+        mut candidates: HashSet<String>,
+    ) -> HashSet<String> {
+        for (group, producers) in self.shared_outputs.groups.iter() {
+            if self.shared_outputs.winners.contains_key(group) {
+                continue;
+            }
+            let active = producers.iter().find(|x| self.producer_in_race(x));
+            for producer in producers {
+                if Some(producer) != active {
+                    candidates.remove(producer);
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Hold a producer's failure if another one may still succeed
+    pub(super) fn hold_shared_output_failure(
+        &mut self,
+        job_id: &str,
+        error: &Option<String>,
+    ) -> bool {
+        let group = match self.shared_outputs.group_of(job_id) {
+            Some(group) => group.clone(),
+            None => return false,
+        };
+        if self.shared_outputs.winners.contains_key(&group) {
+            return false;
+        }
+        let alternatives = self.shared_outputs.groups[&group]
+            .iter()
+            .any(|producer| producer != job_id && self.producer_in_race(producer));
+        if alternatives {
+            info!(
+                "{} failed, falling back to another producer of {}",
+                job_id, group
+            );
+            self.shared_outputs
+                .held_failures
+                .insert(job_id.to_string(), error.clone());
+        }
+        alternatives
+    }
+
+    /// Pick winners, finish the losing producers and release held
+    /// failures once a group ran out of producers. After the events.
+    pub(super) fn decide_shared_outputs(&mut self) -> Result<(), PPGEvaluatorError> {
+        if self.shared_outputs.groups.is_empty() {
+            return Ok(());
+        }
+        let mut groups: Vec<String> = self.shared_outputs.groups.keys().cloned().collect();
+        groups.sort();
+        for group in groups {
+            let producers = self.shared_outputs.groups[&group].clone();
+            if !self.shared_outputs.winners.contains_key(&group) {
+                let winner = producers.iter().find(|producer| {
+                    self.job_id_to_node_idx.get(*producer).is_some_and(|idx| {
+                        let job = &self.jobs[*idx];
+                        job.state.is_finished()
+                            && !job.state.is_failed()
+                            && job.history_output.is_some()
+                    })
+                });
+                match winner {
+                    Some(winner) => {
+                        info!("{} produced shared output {}", winner, group);
+                        self.shared_outputs
+                            .winners
+                            .insert(group.clone(), winner.clone());
+                    }
+                    None => {
+                        if !producers.iter().any(|x| self.producer_in_race(x)) {
+                            self.release_held_failures(&producers)?;
+                        }
+                        continue;
+                    }
+                }
+            }
+            let winner = self.shared_outputs.winners[&group].clone();
+            let output = self.jobs[self.job_id_to_node_idx[&winner]]
+                .history_output
+                .clone()
+                .expect("winner has an output");
+            for producer in producers.iter() {
+                if self.shared_outputs.held_failures.remove(producer).is_some() {
+                    self.event_job_finished_success(producer, output.clone())?;
+                } else if matches!(
+                    self.producer_state(producer),
+                    Some(JobState::Output(JobStateOutput::ReadyToRun))
+                ) {
+                    self.event_now_running(producer)?;
+                    self.event_job_finished_success(producer, output.clone())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn release_held_failures(&mut self, producers: &[String]) -> Result<(), PPGEvaluatorError> {
+        for producer in producers {
+            if let Some(error) = self.shared_outputs.held_failures.remove(producer) {
+                match error {
+                    Some(error) => self.event_job_finished_failure_with_error(producer, error)?,
+                    None => self.event_job_finished_failure(producer)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Called by new_history - which producer ran for each group
+    pub(super) fn record_shared_output_producers(&self, history: &mut HashMap<String, String>) {
+        history.retain(|key, _| match key.strip_prefix(PRODUCER_KEY_PREFIX) {
+            Some(group) => self.shared_outputs.groups.contains_key(group),
+            None => true,
+        });
+        for (group, winner) in self.shared_outputs.winners.iter() {
+            // an up to date producer didn't run - keep the last one that did
+            if matches!(
+                self.producer_state(winner),
+                Some(JobState::Output(JobStateOutput::FinishedSuccess))
+            ) {
+                history.insert(format!("{}{}", PRODUCER_KEY_PREFIX, group), winner.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+// Serializable snapshot of an evaluator - for checkpointing (python pickling),
+// shipping to another process or post mortem inspection.
+// The strategy is not part of it, from_snapshot takes a new one.
+// Per run caches (presence, evaluation stats) are not kept.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug_span, level_filters::LevelFilter};
+
+use super::absence::AbsenceInfo;
+use super::cleanup::CleanupInfo;
+use super::gating::{AlwaysGate, GatingInfo};
+use super::global_invariants::GlobalInvariantInfo;
+use super::ignore::ChangeFilter;
+use super::scheduling::SchedulingInfo;
+use super::shared_output::SharedOutputInfo;
+use super::soft_dependencies::SoftDependencies;
+use super::{
+    EdgeInfo, EvaluationStats, Generation, GraphType, JobKind, JobState, NodeIndex, NodeInfo,
+    PPGEvaluator, Progress, Required, StartStatus, WallClock,
+};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobSnapshot {
+    pub job_id: String,
+    pub state: JobState,
+    pub history_output: Option<String>,
+    last_considered_in_gen: usize,
+    /// in the graph - pruned ephemerals are not
+    in_dag: bool,
+    /// seconds since it started, if it is running / ran in this process
+    running_for: Option<f64>,
+    runtime: Option<f64>,
+    pub error: Option<String>,
+    #[serde(default)]
+    invariant: Option<String>,
+    #[serde(default)]
+    conditional: bool,
+    #[serde(default)]
+    barrier: bool,
+    #[serde(default)]
+    temp_file: bool,
+    #[serde(default)]
+    run_even_if_upstreams_failed: bool,
+    #[serde(default)]
+    pub metadata: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EvaluatorSnapshot {
+    version: u32,
+    pub jobs: Vec<JobSnapshot>,
+    /// (upstream, downstream, required, invalidated)
+    edges: Vec<(NodeIndex, NodeIndex, Required, Required)>,
+    pub history: HashMap<String, String>,
+    started: StartStatus,
+    jobs_ready_to_run: Vec<String>,
+    jobs_ready_for_cleanup: Vec<String>,
+    topo: Option<Vec<NodeIndex>>,
+    gen: usize,
+    log_level: String,
+    job_log_levels: Vec<(String, String)>,
+    #[serde(default)]
+    resume_history: Option<HashMap<String, String>>,
+    #[serde(default)]
+    aliases: Vec<(String, String)>,
+    #[serde(default)]
+    change_filter: ChangeFilter,
+    #[serde(default)]
+    cleanup: CleanupInfo,
+    #[serde(default)]
+    scheduling: SchedulingInfo,
+    #[serde(default)]
+    shared_outputs: SharedOutputInfo,
+    #[serde(default)]
+    always_gates: HashMap<String, AlwaysGate>,
+    #[serde(default)]
+    record_last_success: bool,
+    #[serde(default)]
+    global_invariants: GlobalInvariantInfo,
+    #[serde(default)]
+    absence: AbsenceInfo,
+    #[serde(default)]
+    soft_dependencies: SoftDependencies,
+}
+
+impl EvaluatorSnapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("snapshot serialization can't fail")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PPGEvaluatorError> {
+        let res: EvaluatorSnapshot = serde_json::from_str(json)
+            .map_err(|e| PPGEvaluatorError::APIError(format!("Invalid snapshot: {}", e)))?;
+        if res.version != SNAPSHOT_VERSION {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Snapshot version {} not supported (expected {})",
+                res.version, SNAPSHOT_VERSION
+            )));
+        }
+        Ok(res)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, PPGEvaluatorError> {
+    level.parse().map_err(|_| {
+        PPGEvaluatorError::APIError(format!("Invalid log level in snapshot {}", level))
+    })
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Only possible between events - not while signals are being processed.
+    pub fn snapshot(&self) -> Result<EvaluatorSnapshot, PPGEvaluatorError> {
+        if !self.signals.is_empty() {
+            return Err(PPGEvaluatorError::InternalError(
+                "snapshot with unprocessed signals".to_string(),
+            ));
+        }
+        let mut jobs_ready_to_run: Vec<String> = self.jobs_ready_to_run.iter().cloned().collect();
+        jobs_ready_to_run.sort();
+        let mut jobs_ready_for_cleanup: Vec<String> =
+            self.jobs_ready_for_cleanup.iter().cloned().collect();
+        jobs_ready_for_cleanup.sort();
+        Ok(EvaluatorSnapshot {
+            version: SNAPSHOT_VERSION,
+            jobs: self
+                .jobs
+                .iter()
+                .enumerate()
+                .map(|(idx, job)| JobSnapshot {
+                    job_id: job.job_id.clone(),
+                    state: job.state,
+                    history_output: job.history_output.clone(),
+                    last_considered_in_gen: job.last_considered_in_gen,
+                    in_dag: self.dag.contains_node(idx),
+                    running_for: job.started_at.map(|s| self.elapsed_since(s).as_secs_f64()),
+                    runtime: job.runtime.map(|r| r.as_secs_f64()),
+                    error: job.error.clone(),
+                    invariant: job.invariant.clone(),
+                    conditional: job.conditional,
+                    barrier: job.barrier,
+                    temp_file: job.temp_file,
+                    run_even_if_upstreams_failed: job.run_even_if_upstreams_failed,
+                    metadata: job.metadata.clone(),
+                })
+                .collect(),
+            edges: self
+                .dag
+                .all_edges()
+                .map(|(a, b, weight)| (a, b, weight.required, weight.invalidated))
+                .collect(),
+            history: self.history.to_hash_map(),
+            started: self.already_started,
+            jobs_ready_to_run,
+            jobs_ready_for_cleanup,
+            topo: self.topo.clone(),
+            gen: self.gen.gen,
+            log_level: self.log_level.to_string(),
+            job_log_levels: self
+                .job_log_levels
+                .iter()
+                .map(|(pattern, level)| (pattern.clone(), level.to_string()))
+                .collect(),
+            resume_history: self.resume_history.as_ref().map(|x| x.to_hash_map()),
+            aliases: self.aliases.clone(),
+            change_filter: self.change_filter.clone(),
+            cleanup: self.cleanup.clone(),
+            scheduling: self.scheduling.clone(),
+            shared_outputs: self.shared_outputs.clone(),
+            always_gates: self.gating.gates.clone(),
+            record_last_success: self.record_last_success,
+            global_invariants: self.global_invariants.clone(),
+            absence: self.absence.clone(),
+            soft_dependencies: self.soft_dependencies.clone(),
+        })
+    }
+
+    pub fn from_snapshot(
+        snapshot: EvaluatorSnapshot,
+        strategy: T,
+    ) -> Result<Self, PPGEvaluatorError> {
+        let log_level = parse_level(&snapshot.log_level)?;
+        let job_log_levels = snapshot
+            .job_log_levels
+            .into_iter()
+            .map(|(pattern, level)| Ok((pattern, parse_level(&level)?)))
+            .collect::<Result<Vec<_>, PPGEvaluatorError>>()?;
+        let mut dag = GraphType::new();
+        let mut jobs = Vec::with_capacity(snapshot.jobs.len());
+        let mut job_id_to_node_idx = HashMap::new();
+        let mut progress = Progress::default();
+        for (idx, job) in snapshot.jobs.into_iter().enumerate() {
+            if job_id_to_node_idx.insert(job.job_id.clone(), idx).is_some() {
+                return Err(PPGEvaluatorError::APIError(format!(
+                    "Job {} twice in snapshot",
+                    job.job_id
+                )));
+            }
+            if job.in_dag {
+                dag.add_node(idx);
+            }
+            progress.added(&job.state);
+            let kind = match job.invariant {
+                Some(_) => JobKind::Invariant,
+                None if job.conditional => JobKind::Conditional,
+                None if job.barrier => JobKind::Barrier,
+                None if job.temp_file => JobKind::TempFile,
+                None => job.state.kind(),
+            };
+            let job_id = job.job_id;
+            jobs.push(NodeInfo {
+                span: debug_span!("job", job_id = job_id.as_str(), kind = ?kind),
+                log_level: Self::log_level_for(log_level, &job_log_levels, &job_id),
+                job_id,
+                state: job.state,
+                history_output: job.history_output,
+                last_considered_in_gen: job.last_considered_in_gen,
+                started_at: job
+                    .running_for
+                    .and_then(|secs| Instant::now().checked_sub(Duration::from_secs_f64(secs))),
+                ready_at: None,
+                runtime: job.runtime.map(Duration::from_secs_f64),
+                error: job.error,
+                invariant: job.invariant,
+                conditional: job.conditional,
+                barrier: job.barrier,
+                temp_file: job.temp_file,
+                run_even_if_upstreams_failed: job.run_even_if_upstreams_failed,
+                metadata: job.metadata,
+            });
+        }
+        for (a, b, required, invalidated) in snapshot.edges {
+            if a >= jobs.len() || b >= jobs.len() {
+                return Err(PPGEvaluatorError::APIError(
+                    "Edge to unknown job in snapshot".to_string(),
+                ));
+            }
+            dag.add_edge(
+                a,
+                b,
+                EdgeInfo {
+                    required,
+                    invalidated,
+                },
+            );
+        }
+        if !matches!(snapshot.started, StartStatus::NotStarted) {
+            dag.freeze();
+        }
+        let mut res = PPGEvaluator {
+            dag,
+            jobs,
+            job_id_to_node_idx,
+            history: snapshot.history.into(),
+            strategy,
+            already_started: snapshot.started,
+            jobs_ready_to_run: snapshot
+                .jobs_ready_to_run
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            jobs_ready_for_cleanup: snapshot
+                .jobs_ready_for_cleanup
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            topo: snapshot.topo,
+            signals: VecDeque::new(),
+            gen: Generation {
+                gen: snapshot.gen,
+                progress,
+            },
+            log_level,
+            job_log_levels,
+            presence_cache: HashMap::new(),
+            evaluation_stats: EvaluationStats::default(),
+            resume_history: snapshot.resume_history.map(|x| x.into()),
+            aliases: snapshot.aliases,
+            change_filter: snapshot.change_filter,
+            // only matters for event_startup
+            force_rerun_all: false,
+            assume_unchanged: false,
+            contract_violated: false,
+            record_last_success: snapshot.record_last_success,
+            cleanup: snapshot.cleanup,
+            scheduling: snapshot.scheduling,
+            policy: None,
+            speculation: Default::default(),
+            duplicates: Default::default(),
+            generators: Default::default(),
+            interactive: Default::default(),
+            conditional: Default::default(),
+            gating: GatingInfo::with_gates(snapshot.always_gates),
+            barriers: Default::default(),
+            temp_files: Default::default(),
+            finishing_engine_jobs: false,
+            shared_outputs: snapshot.shared_outputs,
+            global_invariants: snapshot.global_invariants,
+            absence: snapshot.absence,
+            soft_dependencies: snapshot.soft_dependencies,
+            run_reasons: Default::default(),
+            migrator: Default::default(),
+            recent_events: VecDeque::new(),
+            event_order: Default::default(),
+            clock: Box::new(WallClock),
+        };
+        // not part of the snapshot, derived from the graph
+        res.update_components();
+        Ok(res)
+    }
+}
@@ -0,0 +1,70 @@
+// Soft dependencies - on jobs that may or may not be part of the graph,
+// e.g. optional QC steps a reusable pipeline module wants to wait for.
+//
+// depends_on_if_present adds a normal edge if the upstream exists. If not,
+// it's remembered and resolved at startup: by then the upstream has either
+// been added (normal edge) or the dependency is dropped for this run.
+use serde::{Deserialize, Serialize};
+
+use super::{EdgeInfo, PPGEvaluator, Required};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SoftDependencies {
+    // (downstream, upstream) with an upstream unknown when they were added
+    pending: Vec<(String, String)>,
+}
+
+impl SoftDependencies {
+    pub(super) fn remove_job(&mut self, job_id: &str) {
+        self.pending.retain(|(downstream, _)| downstream != job_id);
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// depends_on, if upstream is part of the graph at startup - ignored otherwise
+    pub fn depends_on_if_present(
+        &mut self,
+        downstream: &str,
+        upstream: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        if self.job_id_to_node_idx.contains_key(upstream) {
+            return self.depends_on(downstream, upstream);
+        }
+        self.check_graph_mutable()?;
+        let downstream_idx = self.known_idx(downstream)?;
+        if self.jobs[downstream_idx].invariant.is_some() {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Invariant {} can't depend on {}",
+                downstream, upstream
+            )));
+        }
+        self.soft_dependencies
+            .pending
+            .push((downstream.to_string(), upstream.to_string()));
+        Ok(())
+    }
+
+    /// Add the edges of soft dependencies whose upstream showed up. Before the dag is frozen.
+    pub(super) fn resolve_soft_dependencies(&mut self) {
+        for (downstream, upstream) in self.soft_dependencies.pending.iter() {
+            let (downstream_idx, upstream_idx) = match (
+                self.job_id_to_node_idx.get(downstream),
+                self.job_id_to_node_idx.get(upstream),
+            ) {
+                (Some(downstream_idx), Some(upstream_idx)) => (*downstream_idx, *upstream_idx),
+                _ => continue,
+            };
+            if !self.dag.contains_edge(upstream_idx, downstream_idx) {
+                self.dag.add_edge(
+                    upstream_idx,
+                    downstream_idx,
+                    EdgeInfo {
+                        required: Required::Unknown,
+                        invalidated: Required::Unknown,
+                    },
+                );
+            }
+        }
+    }
+}
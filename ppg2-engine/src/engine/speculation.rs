@@ -0,0 +1,176 @@
+// Speculative re-execution of jobs that take much longer than usual.
+//
+// With set_speculation(factor), query_speculation_candidates lists running
+// jobs that have been running for factor times their runtime estimate.
+// The executor may start a second attempt elsewhere and report it with
+// event_speculative_attempt_started. The first successful attempt wins:
+// the job finishes as usual, the other attempts show up in
+// query_attempts_to_cancel, and their later finish events (or
+// event_speculative_attempt_cancelled) are absorbed. A failing attempt only
+// fails the job if it was the last one in flight.
+use std::collections::HashMap;
+
+use super::{JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SpeculationInfo {
+    factor: Option<f64>,
+    // running job_id -> attempts in flight, if more than one
+    attempts: HashMap<String, u32>,
+    // finished job_id -> losing attempts still in flight
+    to_cancel: HashMap<String, u32>,
+    // job_id -> speculative attempts started this run
+    extra_attempts: HashMap<String, u32>,
+}
+
+impl SpeculationInfo {
+    pub(super) fn new_run(&mut self) {
+        self.attempts.clear();
+        self.to_cancel.clear();
+        self.extra_attempts.clear();
+    }
+
+    pub(super) fn attempts_started(&self, job_id: &str) -> u32 {
+        1 + self.extra_attempts.get(job_id).copied().unwrap_or(0)
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Suggest a second attempt once a job ran factor x its runtime estimate.
+    /// None to turn it off.
+    pub fn set_speculation(&mut self, factor: Option<f64>) -> Result<(), PPGEvaluatorError> {
+        if let Some(factor) = factor {
+            if !(factor >= 1.0 && factor.is_finite()) {
+                return Err(PPGEvaluatorError::APIError(format!(
+                    "Speculation factor must be >= 1, was {}",
+                    factor
+                )));
+            }
+        }
+        self.speculation.factor = factor;
+        Ok(())
+    }
+
+    pub(super) fn is_running(&self, job_id: &str) -> bool {
+        self.job_id_to_node_idx.get(job_id).is_some_and(|idx| {
+            matches!(
+                self.jobs[*idx].state,
+                JobState::Always(JobStateAlways::Running)
+                    | JobState::Output(JobStateOutput::Running)
+                    | JobState::Ephemeral(JobStateEphemeral::Running(_))
+            )
+        })
+    }
+
+    /// Running jobs overdue for a second attempt - single attempt ones with an estimate. Sorted.
+    pub fn query_speculation_candidates(&self) -> Vec<String> {
+        let factor = match self.speculation.factor {
+            Some(factor) => factor,
+            None => return Vec::new(),
+        };
+        let mut res: Vec<String> = self
+            .scheduling
+            .runtime_estimates
+            .iter()
+            .filter(|(job_id, _)| {
+                !self.speculation.attempts.contains_key(*job_id) && self.is_running(job_id)
+            })
+            .filter(|(job_id, estimate)| {
+                let started = self.jobs[self.job_id_to_node_idx[*job_id]].started_at;
+                started.is_some_and(|started| {
+                    self.elapsed_since(started).as_secs_f64() >= estimate.as_secs_f64() * factor
+                })
+            })
+            .map(|(job_id, _)| job_id.clone())
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// The executor started another attempt of a running job
+    pub fn event_speculative_attempt_started(
+        &mut self,
+        job_id: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        self.known_idx(job_id)?;
+        if !self.is_running(job_id) {
+            return Err(PPGEvaluatorError::JobNotRunning(job_id.to_string()));
+        }
+        *self
+            .speculation
+            .attempts
+            .entry(job_id.to_string())
+            .or_insert(1) += 1;
+        *self
+            .speculation
+            .extra_attempts
+            .entry(job_id.to_string())
+            .or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Jobs that finished while other attempts of them are still running, sorted
+    pub fn query_attempts_to_cancel(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.speculation.to_cancel.keys().cloned().collect();
+        res.sort();
+        res
+    }
+
+    /// The executor cancelled one losing attempt
+    pub fn event_speculative_attempt_cancelled(
+        &mut self,
+        job_id: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        if self.absorb_losing_attempt(job_id) {
+            Ok(())
+        } else {
+            Err(PPGEvaluatorError::APIError(format!(
+                "No attempt of {} left to cancel",
+                job_id
+            )))
+        }
+    }
+
+    fn absorb_losing_attempt(&mut self, job_id: &str) -> bool {
+        match self.speculation.to_cancel.get_mut(job_id) {
+            Some(left) => {
+                *left -= 1;
+                if *left == 0 {
+                    self.speculation.to_cancel.remove(job_id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Called on every finish event - true if it was just one of several
+    /// attempts and the engine should not act on it.
+    pub(super) fn speculative_finish(&mut self, job_id: &str, success: bool) -> bool {
+        if self.speculation.attempts.is_empty() && self.speculation.to_cancel.is_empty() {
+            return false;
+        }
+        if self.absorb_losing_attempt(job_id) {
+            return true;
+        }
+        match self.speculation.attempts.remove(job_id) {
+            None => false,
+            Some(attempts) if success => {
+                self.speculation
+                    .to_cancel
+                    .insert(job_id.to_string(), attempts - 1);
+                false
+            }
+            Some(attempts) => {
+                // another attempt may still succeed
+                if attempts > 2 {
+                    self.speculation
+                        .attempts
+                        .insert(job_id.to_string(), attempts - 1);
+                }
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// Stall detection: unfinished jobs, but nothing running and nothing ready.
+//
+// The engine should never get there - but if it does, the executor would
+// wait forever. Every event checks for it and returns
+// PPGEvaluatorError::Stalled with the jobs that should have become ready.
+use super::{NodeIndex, PPGEvaluator, StartStatus};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+// unfinished jobs listed in the snapshot - the rest are counted
+const STALL_SNAPSHOT_JOBS: usize = 50;
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    pub(super) fn check_stalled(&self) -> Result<(), PPGEvaluatorError> {
+        let progress = &self.gen.progress;
+        if self.already_started != StartStatus::Running
+            || progress.pending == 0
+            || progress.running > 0
+            || !self.jobs_ready_to_run.is_empty()
+        {
+            return Ok(());
+        }
+        Err(PPGEvaluatorError::Stalled {
+            snapshot: self.stall_snapshot(),
+        })
+    }
+
+    /// unfinished jobs whose upstreams are all finished - they are what's stuck
+    fn stall_snapshot(&self) -> String {
+        let mut blocking: Vec<NodeIndex> = (0..self.jobs.len())
+            .filter(|idx| self.dag.contains_node(*idx) && !self.jobs[*idx].state.is_finished())
+            .filter(|idx| {
+                self.dag
+                    .neighbors_directed(*idx, Direction::Incoming)
+                    .all(|upstream_idx| self.jobs[upstream_idx].state.is_finished())
+            })
+            .collect();
+        blocking.sort_by(|a, b| self.jobs[*a].job_id.cmp(&self.jobs[*b].job_id));
+        let mut lines: Vec<String> = blocking
+            .iter()
+            .take(STALL_SNAPSHOT_JOBS)
+            .map(|idx| {
+                let mut upstreams: Vec<String> = self
+                    .dag
+                    .neighbors_directed(*idx, Direction::Incoming)
+                    .map(|upstream_idx| {
+                        format!(
+                            "{} ({:?})",
+                            self.jobs[upstream_idx].job_id, self.jobs[upstream_idx].state
+                        )
+                    })
+                    .collect();
+                upstreams.sort();
+                format!(
+                    "{}: {:?}, upstreams: [{}]",
+                    self.jobs[*idx].job_id,
+                    self.jobs[*idx].state,
+                    upstreams.join(", ")
+                )
+            })
+            .collect();
+        if blocking.len() > STALL_SNAPSHOT_JOBS {
+            lines.push(format!(
+                "... and {} more",
+                blocking.len() - STALL_SNAPSHOT_JOBS
+            ));
+        }
+        let progress = &self.gen.progress;
+        lines.push(format!(
+            "{} of {} jobs unfinished",
+            progress.pending,
+            progress.total()
+        ));
+        lines.join("\n")
+    }
+}
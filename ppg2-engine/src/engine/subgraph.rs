@@ -0,0 +1,83 @@
+// Namespaced construction of reusable pipeline parts.
+//
+// add_subgraph hands the closure a Subgraph whose add_node / depends_on
+// prefix the job ids, so the same component can be instantiated several
+// times. depends_on resolves ids inside the namespace first, and falls back
+// to the full id for jobs defined outside of it.
+use super::{JobKind, PPGEvaluator};
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+/// Between namespace prefix and job id
+pub const SUBGRAPH_SEPARATOR: &str = "/";
+
+pub struct Subgraph<'a, T: PPGEvaluatorStrategy> {
+    evaluator: &'a mut PPGEvaluator<T>,
+    prefix: String,
+}
+
+impl<'a, T: PPGEvaluatorStrategy> Subgraph<'a, T> {
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The full job id of job_id within this namespace
+    pub fn full_id(&self, job_id: &str) -> String {
+        format!("{}{}{}", self.prefix, SUBGRAPH_SEPARATOR, job_id)
+    }
+
+    fn resolve(&self, job_id: &str) -> String {
+        let full_id = self.full_id(job_id);
+        if self.evaluator.contains_node(&full_id) || !self.evaluator.contains_node(job_id) {
+            full_id
+        } else {
+            job_id.to_string()
+        }
+    }
+
+    pub fn add_node(&mut self, job_id: &str, kind: JobKind) -> Result<(), PPGEvaluatorError> {
+        let full_id = self.full_id(job_id);
+        self.evaluator.add_node(&full_id, kind)
+    }
+
+    pub fn contains_node(&self, job_id: &str) -> bool {
+        self.evaluator.contains_node(&self.full_id(job_id))
+    }
+
+    pub fn depends_on(
+        &mut self,
+        downstream: &str,
+        upstream: &str,
+    ) -> Result<(), PPGEvaluatorError> {
+        let downstream = self.resolve(downstream);
+        let upstream = self.resolve(upstream);
+        self.evaluator.depends_on(&downstream, &upstream)
+    }
+
+    /// Nested namespace - ids become prefix/inner_prefix/job_id
+    pub fn add_subgraph<R>(
+        &mut self,
+        prefix: &str,
+        f: impl FnOnce(&mut Subgraph<T>) -> Result<R, PPGEvaluatorError>,
+    ) -> Result<R, PPGEvaluatorError> {
+        let full_prefix = self.full_id(prefix);
+        self.evaluator.add_subgraph(&full_prefix, f)
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Build part of the graph with all job ids prefixed by 'prefix/'.
+    pub fn add_subgraph<R>(
+        &mut self,
+        prefix: &str,
+        f: impl FnOnce(&mut Subgraph<T>) -> Result<R, PPGEvaluatorError>,
+    ) -> Result<R, PPGEvaluatorError> {
+        if prefix.is_empty() || prefix.contains("!!!") {
+            return Err(PPGEvaluatorError::InvalidJobId(prefix.to_string()));
+        }
+        let mut subgraph = Subgraph {
+            evaluator: self,
+            prefix: prefix.to_string(),
+        };
+        f(&mut subgraph)
+    }
+}
@@ -0,0 +1,55 @@
+// TempFile jobs: ppg1's TempFileGeneratingJob.
+//
+// They follow the Ephemeral state machine - only required if a downstream
+// is, offered for cleanup once their last downstream finished, and their
+// output being gone afterwards doesn't make them 'missing' on later runs.
+//
+// Unlike an Ephemeral, a TempFile job that's only needed by a downstream
+// (not invalidated itself) behaves like an Output job: if its output is still
+// there - say, cleanup was skipped because a downstream failed - the engine
+// finishes it with its last output instead of handing it out to rerun.
+use std::collections::VecDeque;
+
+use super::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TempFileInfo {
+    // became ready to run without being invalidated, presence not checked yet
+    pending: VecDeque<String>,
+}
+
+impl TempFileInfo {
+    pub(super) fn new_run(&mut self) {
+        self.pending.clear();
+    }
+
+    /// process_signals - job_id is a TempFile job that just became ready
+    /// to run, needed by a downstream only
+    pub(super) fn became_ready(&mut self, job_id: &str) {
+        self.pending.push_back(job_id.to_string());
+    }
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Finish TempFile jobs whose output is still present. After the events.
+    pub(super) fn decide_temp_files(&mut self) -> Result<(), PPGEvaluatorError> {
+        while let Some(job_id) = self.temp_files.pending.pop_front() {
+            let last_output = match self.history.get(&job_id) {
+                Some(last_output) => last_output.to_string(),
+                None => continue,
+            };
+            if !Self::cached_output_already_present(
+                &self.strategy,
+                &mut self.presence_cache,
+                &job_id,
+            )? {
+                continue;
+            }
+            debug!("TempFile {} still present, not rebuilding it", job_id);
+            self.event_now_running(&job_id)?;
+            self.event_job_finished_success(&job_id, last_output)?;
+        }
+        Ok(())
+    }
+}
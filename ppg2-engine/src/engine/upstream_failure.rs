@@ -0,0 +1,46 @@
+// Per node upstream failure policy.
+//
+// Normally a failure dooms every downstream (FinishedUpstreamFailure).
+// Jobs flagged with set_run_even_if_upstreams_failed (think 'summary report')
+// instead become ready once all their upstreams reached a terminal state,
+// failed or not - and query_failed_upstreams tells them which ones failed.
+//
+// Only Always (and Conditional) jobs - an Output job's history would
+// otherwise record inputs it never saw.
+use super::{JobKind, PPGEvaluator};
+use crate::graph::Direction;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// Before startup.
+    pub fn set_run_even_if_upstreams_failed(
+        &mut self,
+        job_id: &str,
+        run: bool,
+    ) -> Result<(), PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        self.check_graph_mutable()?;
+        let kind = self.jobs[node_idx].kind();
+        if !matches!(kind, JobKind::Always | JobKind::Conditional) {
+            return Err(PPGEvaluatorError::APIError(format!(
+                "Only Always / Conditional jobs can run despite failed upstreams, {} is {:?}",
+                job_id, kind
+            )));
+        }
+        self.jobs[node_idx].run_even_if_upstreams_failed = run;
+        Ok(())
+    }
+
+    /// Direct upstreams of job_id that failed (or were doomed by a failure). Sorted.
+    pub fn query_failed_upstreams(&self, job_id: &str) -> Result<Vec<String>, PPGEvaluatorError> {
+        let node_idx = self.known_idx(job_id)?;
+        let mut res: Vec<String> = self
+            .dag
+            .neighbors_directed(node_idx, Direction::Incoming)
+            .filter(|upstream_idx| self.jobs[*upstream_idx].state.is_failed())
+            .map(|upstream_idx| self.jobs[upstream_idx].job_id.clone())
+            .collect();
+        res.sort();
+        Ok(res)
+    }
+}
@@ -0,0 +1,148 @@
+// How well a run used the machine.
+//
+// The ledger is derived from the jobs of the current run: while running, a
+// job occupies its declared cores (set_required_cores) and memory
+// (set_required_memory); from becoming ready until it starts, it's waiting
+// for a slot. The timeline has one point per change.
+//
+// Idle gaps are the stretches where cores were free but nothing was ready -
+// the executor had to wait for upstreams to finish. Long ones point at graph
+// structure worth changing, e.g. a single slow job everything depends on.
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use super::{JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, PPGEvaluator};
+use crate::PPGEvaluatorStrategy;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtilizationPoint {
+    /// seconds since the first job became ready or started
+    pub at: f64,
+    /// cores / memory occupied by running jobs from here on
+    pub cores: usize,
+    pub memory: u64,
+    pub running: usize,
+    /// jobs ready, but not started yet
+    pub ready: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UtilizationReport {
+    pub timeline: Vec<UtilizationPoint>,
+    pub peak_cores: usize,
+    pub peak_memory: u64,
+    /// cores occupied on average, over the whole timeline
+    pub average_cores: f64,
+    /// average_cores / available cores
+    pub average_occupancy: f64,
+    /// (start, end) in seconds - cores free, but nothing ready
+    pub idle_gaps: Vec<(f64, f64)>,
+}
+
+struct LedgerEntry {
+    at: Instant,
+    cores: isize,
+    memory: i64,
+    running: isize,
+    ready: isize,
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    fn ledger(&self, now: Instant) -> Vec<LedgerEntry> {
+        let mut ledger = Vec::new();
+        let mut entry = |at: Instant, sign: isize, occupies: Option<(usize, u64)>| {
+            let (cores, memory) = occupies.unwrap_or_default();
+            ledger.push(LedgerEntry {
+                at,
+                cores: sign * cores as isize,
+                memory: sign as i64 * memory as i64,
+                running: if occupies.is_some() { sign } else { 0 },
+                ready: if occupies.is_none() { sign } else { 0 },
+            });
+        };
+        for job in self.jobs.iter() {
+            // finished by the engine itself, they never occupy anything
+            if job.invariant.is_some() || job.barrier {
+                continue;
+            }
+            if let Some(ready_at) = job.ready_at {
+                entry(ready_at, 1, None);
+                entry(job.started_at.unwrap_or(now).max(ready_at), -1, None);
+            }
+            let started_at = match job.started_at {
+                Some(started_at) => started_at,
+                None => continue,
+            };
+            let running = matches!(
+                job.state,
+                JobState::Always(JobStateAlways::Running)
+                    | JobState::Output(JobStateOutput::Running)
+                    | JobState::Ephemeral(JobStateEphemeral::Running(_))
+            );
+            let end = match job.runtime {
+                Some(runtime) => started_at + runtime,
+                None if running => now,
+                // aborted - we don't know for how long it ran
+                None => continue,
+            };
+            let occupies = Some((
+                self.required_cores(&job.job_id),
+                self.required_memory(&job.job_id),
+            ));
+            entry(started_at, 1, occupies);
+            entry(end, -1, occupies);
+        }
+        ledger.sort_by_key(|entry| entry.at);
+        ledger
+    }
+
+    /// The current run's resource use over time, for a machine with cores cores
+    pub fn query_utilization(&self, cores: usize) -> UtilizationReport {
+        let ledger = self.ledger(self.now());
+        let first = match ledger.first() {
+            Some(entry) => entry.at,
+            None => return UtilizationReport::default(),
+        };
+        let mut res = UtilizationReport::default();
+        let (mut used_cores, mut memory, mut running, mut ready) = (0isize, 0i64, 0isize, 0isize);
+        for (idx, entry) in ledger.iter().enumerate() {
+            used_cores += entry.cores;
+            memory += entry.memory;
+            running += entry.running;
+            ready += entry.ready;
+            // one point per instant
+            if ledger.get(idx + 1).is_some_and(|next| next.at == entry.at) {
+                continue;
+            }
+            res.timeline.push(UtilizationPoint {
+                at: entry.at.duration_since(first).as_secs_f64(),
+                cores: used_cores as usize,
+                memory: memory as u64,
+                running: running as usize,
+                ready: ready as usize,
+            });
+        }
+        res.peak_cores = res.timeline.iter().map(|p| p.cores).max().unwrap_or(0);
+        res.peak_memory = res.timeline.iter().map(|p| p.memory).max().unwrap_or(0);
+        let mut core_seconds = 0.0;
+        for pair in res.timeline.windows(2) {
+            let (point, next) = (&pair[0], &pair[1]);
+            core_seconds += point.cores as f64 * (next.at - point.at);
+            if point.cores < cores && point.ready == 0 && next.at > point.at {
+                match res.idle_gaps.last_mut() {
+                    Some(gap) if gap.1 == point.at => gap.1 = next.at,
+                    _ => res.idle_gaps.push((point.at, next.at)),
+                }
+            }
+        }
+        let total = res.timeline.last().map_or(0.0, |p| p.at);
+        if total > 0.0 {
+            res.average_cores = core_seconds / total;
+            if cores > 0 {
+                res.average_occupancy = res.average_cores / cores as f64;
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,166 @@
+// Pre-startup sanity checks of the graph as built.
+// Nothing here changes the evaluation - it's for surfacing likely mistakes
+// to the user before a (long) run.
+//
+// There is no dangling edge check: depends_on only connects known jobs.
+use std::collections::HashMap;
+
+use super::{JobKind, PPGEvaluator};
+use crate::graph::Direction;
+use crate::PPGEvaluatorStrategy;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// an ephemeral nobody depends on - it will never run
+    TerminalEphemeral,
+    /// runs every time, but nothing consumes it
+    AlwaysWithoutDownstreams,
+    /// ids differing only in case / whitespace
+    SimilarJobIds,
+    /// jobs sharing an id part after splitting multi output ids on ':::'
+    SeparatorCollision,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub kind: ValidationIssueKind,
+    pub job_ids: Vec<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|x| x.severity == Severity::Error)
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        kind: ValidationIssueKind,
+        job_ids: Vec<String>,
+        message: String,
+    ) {
+        self.issues.push(ValidationIssue {
+            severity,
+            kind,
+            job_ids,
+            message,
+        });
+    }
+}
+
+fn normalize_job_id(job_id: &str) -> String {
+    job_id
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let mut job_order: Vec<usize> = (0..self.jobs.len()).collect();
+        job_order.sort_by(|a, b| self.jobs[*a].job_id.cmp(&self.jobs[*b].job_id));
+
+        for idx in job_order.iter() {
+            if !self.dag.contains_node(*idx) {
+                continue;
+            }
+            let job = &self.jobs[*idx];
+            let terminal = self
+                .dag
+                .neighbors_directed(*idx, Direction::Outgoing)
+                .next()
+                .is_none();
+            if !terminal {
+                continue;
+            }
+            match job.kind() {
+                JobKind::Ephemeral | JobKind::TempFile => report.push(
+                    Severity::Warning,
+                    ValidationIssueKind::TerminalEphemeral,
+                    vec![job.job_id.clone()],
+                    format!(
+                        "{:?} job {} has no downstreams and will never run",
+                        job.kind(),
+                        job.job_id
+                    ),
+                ),
+                JobKind::Always => report.push(
+                    Severity::Warning,
+                    ValidationIssueKind::AlwaysWithoutDownstreams,
+                    vec![job.job_id.clone()],
+                    format!("Always job {} has no downstreams", job.job_id),
+                ),
+                JobKind::Output | JobKind::Invariant | JobKind::Conditional | JobKind::Barrier => {}
+            }
+        }
+
+        let mut similar: HashMap<String, Vec<String>> = HashMap::new();
+        for idx in job_order.iter() {
+            let job_id = &self.jobs[*idx].job_id;
+            similar
+                .entry(normalize_job_id(job_id))
+                .or_default()
+                .push(job_id.clone());
+        }
+        let mut similar: Vec<Vec<String>> = similar.into_values().filter(|x| x.len() > 1).collect();
+        similar.sort();
+        for job_ids in similar {
+            let message = format!(
+                "Job ids differ only in case or whitespace: {}",
+                job_ids.join(", ")
+            );
+            report.push(
+                Severity::Warning,
+                ValidationIssueKind::SimilarJobIds,
+                job_ids,
+                message,
+            );
+        }
+
+        // multi output jobs are 'a:::b' - that must not claim an 'a' another job has
+        let mut part_owners: HashMap<&str, Vec<String>> = HashMap::new();
+        for idx in job_order.iter() {
+            let job_id = &self.jobs[*idx].job_id;
+            let mut parts: Vec<&str> = job_id.split(":::").collect();
+            parts.sort_unstable();
+            parts.dedup();
+            for part in parts {
+                part_owners.entry(part).or_default().push(job_id.clone());
+            }
+        }
+        let mut collisions: Vec<(&str, Vec<String>)> = part_owners
+            .into_iter()
+            .filter(|(_, owners)| owners.len() > 1)
+            .collect();
+        collisions.sort();
+        for (part, job_ids) in collisions {
+            let message = format!(
+                "Jobs {} collide on {} after splitting on ':::'",
+                job_ids.join(", "),
+                part
+            );
+            report.push(
+                Severity::Error,
+                ValidationIssueKind::SeparatorCollision,
+                job_ids,
+                message,
+            );
+        }
+        report
+    }
+}
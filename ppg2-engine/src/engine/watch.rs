@@ -0,0 +1,71 @@
+// Re-running what an external change (a watched source file) affects.
+//
+// notify_external_change is called on a finished evaluation. The strategy's
+// current_history is compared to what the job recorded; if it differs
+// (or the strategy can't tell) the job loses its history entries, so it
+// reruns, and the evaluation continues via event_resume - the downstreams
+// are invalidated as usual once its new output is reported.
+// Invariants take the new value directly.
+use super::{PPGEvaluator, StartStatus};
+use crate::history_store::HistoryStore;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// true if job_id's output changed and the evaluation was resumed
+    pub fn notify_external_change(&mut self, job_id: &str) -> Result<bool, PPGEvaluatorError> {
+        Ok(!self.notify_external_changes(&[job_id])?.is_empty())
+    }
+
+    /// All at once, for watchers batching events. Returns the changed job ids.
+    pub fn notify_external_changes(
+        &mut self,
+        job_ids: &[&str],
+    ) -> Result<Vec<String>, PPGEvaluatorError> {
+        if self.already_started != StartStatus::Finished {
+            return Err(PPGEvaluatorError::APIError(
+                "External changes can only be applied to a finished evaluation".to_string(),
+            ));
+        }
+        for job_id in job_ids {
+            self.known_idx(job_id)?;
+        }
+        let had_resume_history = self.resume_history.is_some();
+        let mut history: HistoryStore = match self.resume_history.take() {
+            Some(history) => history,
+            None => self.new_history()?.into(),
+        };
+        let mut changed = Vec::new();
+        for job_id in job_ids {
+            let node_idx = self.job_id_to_node_idx[*job_id];
+            let current = self.strategy.current_history(job_id)?;
+            let altered = match (history.get(job_id), &current) {
+                (Some(last), Some(current)) => self
+                    .strategy
+                    .is_history_altered(job_id, "!!!", last, current)?,
+                _ => true,
+            };
+            if !altered {
+                continue;
+            }
+            if self.jobs[node_idx].invariant.is_some() {
+                match current {
+                    Some(current) => self.jobs[node_idx].invariant = Some(current),
+                    // the value comes from add_invariant, nothing to rerun
+                    None => continue,
+                }
+            } else {
+                history.remove(job_id);
+                history.remove(&format!("{}!!!", job_id));
+            }
+            changed.push(job_id.to_string());
+        }
+        if changed.is_empty() && !had_resume_history {
+            return Ok(changed);
+        }
+        self.resume_history = Some(history);
+        if !changed.is_empty() {
+            self.event_resume()?;
+        }
+        Ok(changed)
+    }
+}
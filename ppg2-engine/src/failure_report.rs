@@ -0,0 +1,114 @@
+// Machine readable summary of what went wrong in a run -
+// for CI annotations and notification mails.
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{NodeIndex, PPGEvaluator};
+use crate::graph::Direction;
+use crate::report::outcome;
+use crate::PPGEvaluatorStrategy;
+
+// error payloads hinting at the machine rather than the code
+const TRANSIENT_ERROR_MARKERS: &[&str] = &[
+    "MemoryError",
+    "TimeoutError",
+    "ConnectionError",
+    "BrokenPipeError",
+    "Killed",
+    "No space left on device",
+    "Resource temporarily unavailable",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedJob {
+    pub job_id: String,
+    pub error: Option<String>,
+    /// downstream jobs that won't run because of this failure
+    pub doomed_downstreams: usize,
+    /// see set_job_metadata
+    #[serde(default)]
+    pub metadata: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureReport {
+    /// sorted by job id
+    pub failed: Vec<FailedJob>,
+    /// failed jobs that doomed downstreams, most doomed first
+    pub root_causes: Vec<String>,
+    pub upstream_failed: usize,
+    /// failed jobs whose error looks transient (out of memory, timeouts...), sorted
+    pub retry_candidates: Vec<String>,
+}
+
+impl FailureReport {
+    pub fn is_empty(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failure report is always serializable")
+    }
+}
+
+fn looks_transient(error: &str) -> bool {
+    TRANSIENT_ERROR_MARKERS
+        .iter()
+        .any(|marker| error.contains(marker))
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    fn doomed_by(&self, node_idx: NodeIndex) -> usize {
+        let mut seen: HashSet<NodeIndex> = HashSet::new();
+        let mut stack = vec![node_idx];
+        while let Some(idx) = stack.pop() {
+            for downstream_idx in self.dag.neighbors_directed(idx, Direction::Outgoing) {
+                if outcome(&self.jobs[downstream_idx].state) == "upstream failed"
+                    && seen.insert(downstream_idx)
+                {
+                    stack.push(downstream_idx);
+                }
+            }
+        }
+        seen.len()
+    }
+
+    pub fn failure_report(&self) -> FailureReport {
+        let mut failed: Vec<FailedJob> = (0..self.jobs.len())
+            .filter(|idx| outcome(&self.jobs[*idx].state) == "failed")
+            .map(|idx| FailedJob {
+                job_id: self.jobs[idx].job_id.clone(),
+                error: self.jobs[idx].error.clone(),
+                doomed_downstreams: self.doomed_by(idx),
+                metadata: self.jobs[idx].metadata.clone(),
+            })
+            .collect();
+        failed.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+
+        let mut root_causes: Vec<&FailedJob> =
+            failed.iter().filter(|x| x.doomed_downstreams > 0).collect();
+        root_causes.sort_by(|a, b| {
+            b.doomed_downstreams
+                .cmp(&a.doomed_downstreams)
+                .then_with(|| a.job_id.cmp(&b.job_id))
+        });
+        let root_causes = root_causes.iter().map(|x| x.job_id.clone()).collect();
+
+        let retry_candidates = failed
+            .iter()
+            .filter(|x| x.error.as_deref().is_some_and(looks_transient))
+            .map(|x| x.job_id.clone())
+            .collect();
+        FailureReport {
+            root_causes,
+            upstream_failed: self
+                .jobs
+                .iter()
+                .filter(|job| outcome(&job.state) == "upstream failed")
+                .count(),
+            retry_candidates,
+            failed,
+        }
+    }
+}
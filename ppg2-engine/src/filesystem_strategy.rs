@@ -0,0 +1,461 @@
+// A strategy that fingerprints job outputs (files) entirely in rust.
+//
+// job ids are file paths, multi file jobs join their outputs with ':::'
+// (same convention as the python side).
+// The history value of a job is its fingerprint - one line per output
+// 'path\tmtime_ns\tsize[\txxh3]' - obtained from StrategyFileSystem::fingerprint
+// and handed to event_job_finished_success by the executor.
+//
+// An output ending in '/' is a directory (present if non-empty), one containing
+// '*' or '?' a glob (present if it matches anything, no '**').
+// Their fingerprint covers every file in the directory / matched set.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::engine::{GraphType, NodeIndex, NodeInfo};
+use crate::wildcard::wildcard_match;
+use crate::{PPGEvaluatorStrategy, StrategyError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFingerprint {
+    pub mtime_ns: u128,
+    pub size: u64,
+    pub hash: Option<String>,
+}
+
+impl FileFingerprint {
+    fn stat(path: &Path) -> std::io::Result<(u128, u64)> {
+        let meta = std::fs::metadata(path)?;
+        let mtime_ns = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        Ok((mtime_ns, meta.len()))
+    }
+
+    pub fn of(path: impl AsRef<Path>, hash_contents: bool) -> std::io::Result<FileFingerprint> {
+        let path = path.as_ref();
+        let (mtime_ns, size) = Self::stat(path)?;
+        let hash = if hash_contents {
+            Some(format!(
+                "{:032x}",
+                xxhash_rust::xxh3::xxh3_128(&std::fs::read(path)?)
+            ))
+        } else {
+            None
+        };
+        Ok(FileFingerprint {
+            mtime_ns,
+            size,
+            hash,
+        })
+    }
+
+    /// Unchanged if the content hashes match (when both sides have one),
+    /// otherwise if mtime and size match.
+    pub fn same_as(&self, other: &FileFingerprint) -> bool {
+        match (&self.hash, &other.hash) {
+            (Some(a), Some(b)) => a == b && self.size == other.size,
+            _ => self.mtime_ns == other.mtime_ns && self.size == other.size,
+        }
+    }
+}
+
+fn is_glob(output: &str) -> bool {
+    output.contains('*') || output.contains('?')
+}
+
+fn is_directory(output: &str) -> bool {
+    output.ends_with('/')
+}
+
+/// All paths matching a glob, sorted. Wildcards may appear in any component.
+fn expand_glob(pattern: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut candidates = vec![if pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        PathBuf::new()
+    }];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for candidate in candidates {
+            if !is_glob(component) {
+                let p = candidate.join(component);
+                if p.exists() {
+                    next.push(p);
+                }
+                continue;
+            }
+            let dir = if candidate.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                candidate.as_path()
+            };
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::NotADirectory => continue,
+                Err(e) => return Err(e),
+            };
+            for entry in entries {
+                let name = entry?.file_name();
+                if wildcard_match(component, &name.to_string_lossy()) {
+                    next.push(candidate.join(name));
+                }
+            }
+        }
+        candidates = next;
+    }
+    candidates.sort();
+    Ok(candidates)
+}
+
+/// All files below dir, sorted.
+fn walk_files(dir: &Path, res: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            walk_files(&entry, res)?;
+        } else {
+            res.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// The files a job id stands for - directories and globs expanded.
+pub(crate) fn output_files(job_id: &str) -> std::io::Result<Vec<String>> {
+    let mut res = Vec::new();
+    for output in job_id.split(":::") {
+        if is_glob(output) || is_directory(output) {
+            let mut files = Vec::new();
+            let roots = if is_glob(output) {
+                expand_glob(output)?
+            } else {
+                vec![PathBuf::from(output)]
+            };
+            for root in roots {
+                if root.is_dir() {
+                    walk_files(&root, &mut files)?;
+                } else {
+                    files.push(root);
+                }
+            }
+            res.extend(files.iter().map(|p| p.to_string_lossy().to_string()));
+        } else {
+            res.push(output.to_string());
+        }
+    }
+    Ok(res)
+}
+
+fn output_present(output: &str) -> std::io::Result<bool> {
+    if is_glob(output) {
+        Ok(!expand_glob(output)?.is_empty())
+    } else if is_directory(output) {
+        Ok(std::fs::read_dir(output)?.next().is_some())
+    } else {
+        std::fs::metadata(output).map(|_| true)
+    }
+}
+
+/// Do all ':::' separated outputs exist?
+/// Errors other than 'not found' (permissions, stale nfs handles...) are reported.
+pub fn all_paths_present(query: &str) -> Result<bool, StrategyError> {
+    for output in query.split(":::") {
+        match output_present(output) {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => {
+                return Err(StrategyError::Io {
+                    path: output.to_string(),
+                    source: e,
+                })
+            }
+        }
+    }
+    Ok(true)
+}
+
+// below this, spinning up the pool costs more than the stats
+const PARALLEL_PRESENCE_THRESHOLD: usize = 64;
+// network filesystems don't get faster with more concurrent stats
+const MAX_PRESENCE_THREADS: usize = 16;
+
+fn presence_pool() -> &'static rayon::ThreadPool {
+    static POOL: std::sync::OnceLock<rayon::ThreadPool> = std::sync::OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(num_cpus::get().clamp(1, MAX_PRESENCE_THREADS))
+            .thread_name(|ii| format!("ppg2_presence_{}", ii))
+            .build()
+            .expect("Could not build presence check thread pool")
+    })
+}
+
+/// all_paths_present for many queries, on a bounded thread pool
+pub fn all_paths_present_parallel(queries: &[&str]) -> Vec<Result<bool, StrategyError>> {
+    if queries.len() < PARALLEL_PRESENCE_THRESHOLD {
+        return queries.iter().map(|q| all_paths_present(q)).collect();
+    }
+    use rayon::prelude::*;
+    presence_pool().install(|| queries.par_iter().map(|q| all_paths_present(q)).collect())
+}
+
+/// Format a per-path fingerprint map into a history value.
+pub fn format_fingerprints(fingerprints: &[(String, FileFingerprint)]) -> String {
+    let mut lines: Vec<String> = fingerprints
+        .iter()
+        .map(|(path, fp)| match &fp.hash {
+            Some(hash) => format!("{}\t{}\t{}\t{}", path, fp.mtime_ns, fp.size, hash),
+            None => format!("{}\t{}\t{}", path, fp.mtime_ns, fp.size),
+        })
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+/// Parse a history value produced by format_fingerprints.
+/// None if it isn't one (e.g. history written by another strategy)
+pub fn parse_fingerprints(history: &str) -> Option<HashMap<String, FileFingerprint>> {
+    let mut res = HashMap::new();
+    for line in history.lines() {
+        let mut parts = line.split('\t');
+        let path = parts.next()?;
+        let mtime_ns = parts.next()?.parse().ok()?;
+        let size = parts.next()?.parse().ok()?;
+        let hash = parts.next().map(|x| x.to_string());
+        if parts.next().is_some() {
+            return None;
+        }
+        res.insert(
+            path.to_string(),
+            FileFingerprint {
+                mtime_ns,
+                size,
+                hash,
+            },
+        );
+    }
+    Some(res)
+}
+
+/// Fingerprint comparison of two history values.
+/// Falls back to string comparison if either isn't a fingerprint.
+pub fn fingerprints_altered(last_recorded_value: &str, current_value: &str) -> bool {
+    if last_recorded_value == current_value {
+        return false;
+    }
+    match (
+        parse_fingerprints(last_recorded_value),
+        parse_fingerprints(current_value),
+    ) {
+        (Some(last), Some(current)) => {
+            last.len() != current.len()
+                || last.iter().any(|(path, fp)| match current.get(path) {
+                    Some(other) => !fp.same_as(other),
+                    None => true,
+                })
+        }
+        _ => true,
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StrategyFileSystem {
+    /// also hash the file contents (xxh3), so a touched-but-unchanged
+    /// file does not invalidate downstreams
+    pub hash_contents: bool,
+}
+
+impl StrategyFileSystem {
+    pub fn new(hash_contents: bool) -> Self {
+        StrategyFileSystem { hash_contents }
+    }
+
+    /// The history value to report for job_id once it has finished.
+    pub fn fingerprint(&self, job_id: &str) -> std::io::Result<String> {
+        let mut fingerprints = Vec::new();
+        for path in output_files(job_id)? {
+            let fp = FileFingerprint::of(&path, self.hash_contents)?;
+            fingerprints.push((path, fp));
+        }
+        Ok(format_fingerprints(&fingerprints))
+    }
+}
+
+impl PPGEvaluatorStrategy for StrategyFileSystem {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError> {
+        all_paths_present(query)
+    }
+
+    fn outputs_already_present(&self, queries: &[&str]) -> Vec<Result<bool, StrategyError>> {
+        all_paths_present_parallel(queries)
+    }
+
+    fn is_history_altered(
+        &self,
+        _job_id_upstream: &str,
+        _job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError> {
+        Ok(fingerprints_altered(last_recorded_value, current_value))
+    }
+
+    fn get_input_list(
+        &self,
+        node_idx: NodeIndex,
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Result<String, StrategyError> {
+        Ok(crate::sorted_upstream_job_ids(node_idx, dag, jobs))
+    }
+
+    fn current_history(&self, job_id: &str) -> Result<Option<String>, StrategyError> {
+        self.fingerprint(job_id)
+            .map(Some)
+            .map_err(|source| StrategyError::Io {
+                path: job_id.to_string(),
+                source,
+            })
+    }
+}
+
+/// Content (blake3) hashing strategy for filesystems where mtimes can't be trusted
+/// (copying between filesystems...).
+/// Hashes are cached keyed by (path, mtime, size) in a sidecar file,
+/// so we only rehash when the stat changes. Call save() to persist the cache.
+/// History comparison is on hash and size only.
+#[derive(Debug, Default)]
+pub struct StrategyContentHash {
+    cache_path: Option<PathBuf>,
+    cache: RefCell<HashMap<String, FileFingerprint>>,
+    hashed: Cell<usize>,
+}
+
+impl StrategyContentHash {
+    /// Use (and load, if present) the hash cache at cache_path
+    pub fn new(cache_path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let cache_path = cache_path.as_ref().to_path_buf();
+        let cache = match std::fs::read_to_string(&cache_path) {
+            Ok(raw) => parse_fingerprints(&raw).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Could not parse hash cache {:?}", cache_path),
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(StrategyContentHash {
+            cache_path: Some(cache_path),
+            cache: RefCell::new(cache),
+            hashed: Cell::new(0),
+        })
+    }
+
+    /// A cache that is never persisted
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// How many files had to be (re)hashed because the cache had no matching stat
+    pub fn hashed_count(&self) -> usize {
+        self.hashed.get()
+    }
+
+    pub fn file_fingerprint(&self, path: impl AsRef<Path>) -> std::io::Result<FileFingerprint> {
+        let path = path.as_ref();
+        let key = path.to_string_lossy().to_string();
+        let (mtime_ns, size) = FileFingerprint::stat(path)?;
+        if let Some(cached) = self.cache.borrow().get(&key) {
+            if cached.mtime_ns == mtime_ns && cached.size == size && cached.hash.is_some() {
+                return Ok(cached.clone());
+            }
+        }
+        let mut hasher = blake3::Hasher::new();
+        let mut fh = std::fs::File::open(path)?;
+        std::io::copy(&mut fh, &mut hasher)?;
+        self.hashed.set(self.hashed.get() + 1);
+        let fp = FileFingerprint {
+            mtime_ns,
+            size,
+            hash: Some(hasher.finalize().to_hex().to_string()),
+        };
+        self.cache.borrow_mut().insert(key, fp.clone());
+        Ok(fp)
+    }
+
+    /// The history value to report for job_id once it has finished.
+    pub fn fingerprint(&self, job_id: &str) -> std::io::Result<String> {
+        let mut fingerprints = Vec::new();
+        for path in output_files(job_id)? {
+            let fp = self.file_fingerprint(&path)?;
+            fingerprints.push((path, fp));
+        }
+        Ok(format_fingerprints(&fingerprints))
+    }
+
+    /// Persist the hash cache (no-op for in_memory caches)
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(cache_path) = &self.cache_path {
+            let entries: Vec<(String, FileFingerprint)> = self
+                .cache
+                .borrow()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            // write & rename, so an interrupted save does not leave a truncated cache
+            let temp_path = cache_path.with_extension("tmp");
+            std::fs::write(&temp_path, format_fingerprints(&entries))?;
+            std::fs::rename(&temp_path, cache_path)?;
+        }
+        Ok(())
+    }
+}
+
+impl PPGEvaluatorStrategy for StrategyContentHash {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError> {
+        all_paths_present(query)
+    }
+
+    fn outputs_already_present(&self, queries: &[&str]) -> Vec<Result<bool, StrategyError>> {
+        all_paths_present_parallel(queries)
+    }
+
+    fn is_history_altered(
+        &self,
+        _job_id_upstream: &str,
+        _job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError> {
+        Ok(fingerprints_altered(last_recorded_value, current_value))
+    }
+
+    fn get_input_list(
+        &self,
+        node_idx: NodeIndex,
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Result<String, StrategyError> {
+        Ok(crate::sorted_upstream_job_ids(node_idx, dag, jobs))
+    }
+
+    fn current_history(&self, job_id: &str) -> Result<Option<String>, StrategyError> {
+        self.fingerprint(job_id)
+            .map(Some)
+            .map_err(|source| StrategyError::Io {
+                path: job_id.to_string(),
+                source,
+            })
+    }
+}
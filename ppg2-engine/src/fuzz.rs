@@ -0,0 +1,285 @@
+// Fuzzing the event API.
+//
+// Arbitrary bytes decode into a small graph (kinds, edges, last history,
+// which outputs are already present) plus a random-but-legal event order:
+// any ready job may be started, any running job may finish (or fail), in
+// whatever interleaving the bytes dictate. After every event we check what
+// must hold regardless of order - the state machine corner cases
+// (issue_20210726a, issue_20211001) were all ordering dependent.
+//
+// The cargo-fuzz target (ppg2-engine/fuzz) just feeds check_event_sequence,
+// the tests replay a few fixed seeds.
+use std::collections::{HashMap, HashSet};
+
+use crate::{JobKind, PPGEvaluator, PPGEvaluatorError, StrategyForTesting};
+
+const MAX_JOBS: usize = 12;
+
+/// Reads past the end as zeros - every input decodes to *some* case.
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn next(&mut self) -> u8 {
+        let res = self.data.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        res
+    }
+
+    fn exhausted(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// A graph decoded from fuzz input - see FuzzCase::decode.
+#[derive(Debug, Clone)]
+pub struct FuzzCase {
+    pub jobs: Vec<(String, JobKind)>,
+    /// (downstream, upstream), upstream always has the lower index - no cycles
+    pub edges: Vec<(String, String)>,
+    /// history of the 'previous' run
+    pub history: HashMap<String, String>,
+    pub already_done: HashSet<String>,
+    /// what each job outputs when run - fixed per case, so a rerun
+    /// with unchanged inputs is a legal expectation
+    pub outputs: HashMap<String, String>,
+    pub failing: HashSet<String>,
+}
+
+impl FuzzCase {
+    pub fn decode(data: &[u8]) -> (FuzzCase, Vec<u8>) {
+        let mut reader = ByteReader { data, pos: 0 };
+        let job_count = 1 + reader.next() as usize % MAX_JOBS;
+        let mut case = FuzzCase {
+            jobs: Vec::new(),
+            edges: Vec::new(),
+            history: HashMap::new(),
+            already_done: HashSet::new(),
+            outputs: HashMap::new(),
+            failing: HashSet::new(),
+        };
+        for ii in 0..job_count {
+            let job_id = format!("J{}", ii);
+            let flags = reader.next();
+            let kind = match flags % 6 {
+                0 => JobKind::Output,
+                1 => JobKind::Ephemeral,
+                2 => JobKind::Always,
+                3 => JobKind::Invariant,
+                4 => JobKind::Conditional,
+                _ => JobKind::Barrier,
+            };
+            if kind != JobKind::Invariant {
+                // invariants can't have upstreams
+                let upstream_bits = reader.next();
+                for upstream in 0..ii.min(8) {
+                    if upstream_bits & (1 << upstream) != 0 {
+                        case.edges
+                            .push((job_id.clone(), format!("J{}", ii - 1 - upstream)));
+                    }
+                }
+            }
+            if flags & 0x08 != 0 {
+                case.history
+                    .insert(job_id.clone(), format!("out_{}_{}", job_id, flags >> 6));
+            }
+            if flags & 0x10 != 0 {
+                case.already_done.insert(job_id.clone());
+            }
+            if flags & 0x20 != 0 && matches!(kind, JobKind::Output | JobKind::Always) {
+                case.failing.insert(job_id.clone());
+            }
+            case.outputs
+                .insert(job_id.clone(), format!("out_{}_{}", job_id, flags >> 7));
+            case.jobs.push((job_id, kind));
+        }
+        let order = if reader.exhausted() {
+            Vec::new()
+        } else {
+            data[reader.pos..].to_vec()
+        };
+        (case, order)
+    }
+
+    fn build(&self, history: HashMap<String, String>) -> PPGEvaluator<StrategyForTesting> {
+        let strategy = StrategyForTesting::new();
+        strategy
+            .already_done
+            .borrow_mut()
+            .extend(self.already_done.iter().cloned());
+        let mut g = PPGEvaluator::new_with_history(history, strategy);
+        for (job_id, kind) in self.jobs.iter() {
+            match kind {
+                JobKind::Invariant => g.add_invariant(job_id, &self.outputs[job_id]),
+                kind => g.add_node(job_id, *kind),
+            }
+            .expect("fuzz case job rejected");
+        }
+        for (downstream, upstream) in self.edges.iter() {
+            g.depends_on(downstream, upstream)
+                .expect("fuzz case edge rejected");
+        }
+        g
+    }
+
+    fn upstreams(&self, job_id: &str) -> impl Iterator<Item = &str> {
+        let job_id = job_id.to_string();
+        self.edges
+            .iter()
+            .filter(move |(downstream, _)| *downstream == job_id)
+            .map(|(_, upstream)| upstream.as_str())
+    }
+}
+
+/// What one evaluation did - used to compare runs.
+struct Outcome {
+    ran: HashSet<String>,
+    failed: HashSet<String>,
+    history: HashMap<String, String>,
+}
+
+fn check_state(
+    case: &FuzzCase,
+    g: &PPGEvaluator<StrategyForTesting>,
+    ran: &HashSet<String>,
+    running: &HashSet<String>,
+) {
+    if let Err(e) = g.verify_invariants() {
+        panic!("{}\n{}", e, g.debug_());
+    }
+    let ready = g.query_ready_to_run();
+    let failed = g.query_failed();
+    let upstream_failed = g.query_upstream_failed();
+    for job_id in ready.iter() {
+        assert!(!running.contains(job_id), "{} ready and running", job_id);
+        assert!(!ran.contains(job_id), "{} ready after it finished", job_id);
+        assert!(!failed.contains(job_id), "{} ready and failed", job_id);
+        assert!(
+            !upstream_failed.contains(job_id),
+            "{} ready and upstream failed",
+            job_id
+        );
+        for upstream in case.upstreams(job_id) {
+            assert!(
+                !ready.contains(upstream) && !running.contains(upstream),
+                "{} ready before its upstream {} finished",
+                job_id,
+                upstream
+            );
+            assert!(
+                !failed.contains(upstream) && !upstream_failed.contains(upstream),
+                "{} ready though its upstream {} failed",
+                job_id,
+                upstream
+            );
+        }
+    }
+    for job_id in running.iter() {
+        assert!(
+            g.query_jobs_running().contains(job_id),
+            "{} running but unknown to the engine",
+            job_id
+        );
+    }
+}
+
+fn evaluate(
+    case: &FuzzCase,
+    history: HashMap<String, String>,
+    order: &[u8],
+    fail: bool,
+) -> Outcome {
+    let mut g = case.build(history);
+    g.event_startup().expect("event_startup failed");
+    let mut order = ByteReader {
+        data: order,
+        pos: 0,
+    };
+    let mut ran = HashSet::new();
+    let mut running: HashSet<String> = HashSet::new();
+    // every job runs at most once - anything beyond is a livelock
+    let mut steps_left = case.jobs.len() * 2 + 1;
+    check_state(case, &g, &ran, &running);
+    while !g.is_finished() {
+        assert!(steps_left > 0, "evaluation did not terminate");
+        steps_left -= 1;
+        let mut ready: Vec<String> = g.query_ready_to_run().into_iter().collect();
+        ready.sort();
+        let mut in_flight: Vec<String> = running.iter().cloned().collect();
+        in_flight.sort();
+        assert!(
+            !(ready.is_empty() && in_flight.is_empty()),
+            "not finished, but nothing ready or running:\n{}",
+            g.debug_()
+        );
+        let choice = order.next() as usize;
+        // even choices start, odd ones finish - if there's anything to do
+        if !ready.is_empty() && (choice & 1 == 0 || in_flight.is_empty()) {
+            let job_id = &ready[(choice / 2) % ready.len()];
+            g.event_now_running(job_id)
+                .expect("event_now_running failed");
+            running.insert(job_id.clone());
+        } else {
+            let job_id = in_flight[(choice / 2) % in_flight.len()].clone();
+            running.remove(&job_id);
+            if fail && case.failing.contains(&job_id) {
+                g.event_job_finished_failure(&job_id)
+                    .expect("event_job_finished_failure failed");
+            } else {
+                match g.event_job_finished_success(&job_id, case.outputs[&job_id].clone()) {
+                    Ok(()) | Err(PPGEvaluatorError::EphemeralChangedOutput { .. }) => {}
+                    Err(e) => panic!("event_job_finished_success({}) failed: {}", job_id, e),
+                }
+            }
+            ran.insert(job_id);
+        }
+        check_state(case, &g, &ran, &running);
+        for job_id in g.query_ready_for_cleanup() {
+            g.event_job_cleanup_done(&job_id)
+                .expect("event_job_cleanup_done failed");
+        }
+    }
+    assert!(running.is_empty(), "finished with jobs still running");
+    let failed = g.query_failed();
+    let history = g.new_history().expect("new_history failed");
+    for job_id in ran.iter() {
+        if !failed.contains(job_id) {
+            assert!(
+                history.contains_key(job_id),
+                "{} succeeded but has no history",
+                job_id
+            );
+        }
+    }
+    Outcome {
+        ran,
+        failed,
+        history,
+    }
+}
+
+/// The fuzz entry point: decode, evaluate in the decoded order, then
+/// evaluate again on the resulting history - with everything present
+/// and outputs unchanged, no Output job may rerun.
+/// Panics if any engine invariant is violated.
+pub fn check_event_sequence(data: &[u8]) {
+    let (case, order) = FuzzCase::decode(data);
+    let first = evaluate(&case, case.history.clone(), &order, true);
+    if !first.failed.is_empty() {
+        return;
+    }
+    let mut case = case;
+    case.already_done.extend(first.ran.iter().cloned());
+    let second = evaluate(&case, first.history, &order, false);
+    for (job_id, kind) in case.jobs.iter() {
+        if *kind == JobKind::Output {
+            assert!(
+                !second.ran.contains(job_id),
+                "{} reran although nothing changed",
+                job_id
+            );
+        }
+    }
+}
@@ -0,0 +1,351 @@
+// Compact index based storage for the job dag.
+//
+// While the graph is being built (add_node / depends_on) edges are collected
+// in a flat list. event_startup freezes them into CSR (compressed sparse row)
+// arrays: per node a sorted run of downstream indices plus the edge weights,
+// and an inverse index for the upstreams. That's ~12 bytes per edge,
+// so 5M edges fit in well under 100MB and traversals stay cache friendly.
+//
+// Node indices are positions in PPGEvaluator.jobs. Removed nodes
+// (pruned ephemerals) are tombstoned, their edges skipped.
+//
+// feature 'petgraph-backend' swaps this for petgraph (see PetgraphDag).
+
+// the CSR Dag is kept (and tested) when petgraph replaces it
+#![cfg_attr(feature = "petgraph-backend", allow(dead_code))]
+
+#[cfg(feature = "petgraph-backend")]
+mod petgraph_backend;
+#[cfg(feature = "petgraph-backend")]
+pub use petgraph_backend::PetgraphDag;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Debug)]
+struct Csr<E> {
+    // out edges of node n: out_targets[out_offsets[n]..out_offsets[n + 1]], sorted
+    out_offsets: Vec<u32>,
+    out_targets: Vec<u32>,
+    weights: Vec<E>,
+    // in edges of node n: in_edges[in_offsets[n]..in_offsets[n + 1]],
+    // as (source, position in out_targets / weights)
+    in_offsets: Vec<u32>,
+    in_edges: Vec<(u32, u32)>,
+}
+
+#[derive(Debug)]
+pub struct Dag<E> {
+    present: Vec<bool>,
+    // removed (tombstoned) nodes, for restore_removed
+    removed: Vec<usize>,
+    // edges added since the last freeze, in insertion order (later ones win)
+    pending: Vec<(u32, u32, E)>,
+    csr: Csr<E>,
+}
+
+impl<E> Default for Dag<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Dag<E> {
+    pub fn new() -> Self {
+        Dag {
+            present: Vec::new(),
+            removed: Vec::new(),
+            pending: Vec::new(),
+            csr: Csr {
+                out_offsets: vec![0],
+                out_targets: Vec::new(),
+                weights: Vec::new(),
+                in_offsets: vec![0],
+                in_edges: Vec::new(),
+            },
+        }
+    }
+
+    pub fn add_node(&mut self, node_idx: usize) {
+        if node_idx >= self.present.len() {
+            self.present.resize(node_idx + 1, false);
+        }
+        self.present[node_idx] = true;
+    }
+
+    /// Add an edge - replacing the weight if it already exists
+    pub fn add_edge(&mut self, from: usize, to: usize, weight: E) {
+        self.add_node(from);
+        self.add_node(to);
+        self.pending.push((from as u32, to as u32, weight));
+    }
+
+    pub fn remove_node(&mut self, node_idx: usize) {
+        if let Some(present) = self.present.get_mut(node_idx) {
+            if *present {
+                *present = false;
+                self.removed.push(node_idx);
+            }
+        }
+    }
+
+    /// Bring back all removed nodes, with their edges
+    pub fn restore_removed(&mut self) {
+        for node_idx in self.removed.drain(..) {
+            self.present[node_idx] = true;
+        }
+    }
+
+    pub fn contains_node(&self, node_idx: usize) -> bool {
+        self.present.get(node_idx).copied().unwrap_or(false)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Move all edges into the CSR arrays. Queries are only cheap on a frozen dag.
+    pub fn freeze(&mut self) {
+        if self.pending.is_empty() && self.csr.out_offsets.len() == self.present.len() + 1 {
+            return;
+        }
+        let node_count = self.present.len();
+        let old = std::mem::replace(
+            &mut self.csr,
+            Csr {
+                out_offsets: Vec::new(),
+                out_targets: Vec::new(),
+                weights: Vec::new(),
+                in_offsets: Vec::new(),
+                in_edges: Vec::new(),
+            },
+        );
+        let mut edges: Vec<(u32, u32, E)> =
+            Vec::with_capacity(old.out_targets.len() + self.pending.len());
+        let mut source = 0;
+        for (pos, (target, weight)) in old.out_targets.into_iter().zip(old.weights).enumerate() {
+            while old.out_offsets[source + 1] as usize <= pos {
+                source += 1;
+            }
+            edges.push((source as u32, target, weight));
+        }
+        edges.append(&mut self.pending);
+        // stable - so for duplicates the last added one ends up last
+        edges.sort_by_key(|(from, to, _)| (*from, *to));
+
+        let mut out_offsets = vec![0u32; node_count + 1];
+        let mut out_targets = Vec::with_capacity(edges.len());
+        let mut weights: Vec<E> = Vec::with_capacity(edges.len());
+        let mut last: Option<(u32, u32)> = None;
+        for (from, to, weight) in edges {
+            if last == Some((from, to)) {
+                *weights.last_mut().unwrap() = weight;
+                continue;
+            }
+            last = Some((from, to));
+            out_offsets[from as usize + 1] += 1;
+            out_targets.push(to);
+            weights.push(weight);
+        }
+        for ii in 0..node_count {
+            out_offsets[ii + 1] += out_offsets[ii];
+        }
+
+        let mut in_offsets = vec![0u32; node_count + 1];
+        for target in out_targets.iter() {
+            in_offsets[*target as usize + 1] += 1;
+        }
+        for ii in 0..node_count {
+            in_offsets[ii + 1] += in_offsets[ii];
+        }
+        let mut fill: Vec<u32> = in_offsets[..node_count].to_vec();
+        let mut in_edges = vec![(0u32, 0u32); out_targets.len()];
+        for from in 0..node_count {
+            for pos in out_offsets[from]..out_offsets[from + 1] {
+                let to = out_targets[pos as usize] as usize;
+                in_edges[fill[to] as usize] = (from as u32, pos);
+                fill[to] += 1;
+            }
+        }
+        self.csr = Csr {
+            out_offsets,
+            out_targets,
+            weights,
+            in_offsets,
+            in_edges,
+        };
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.present.iter().filter(|x| **x).count()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.all_edges().count()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.present
+            .iter()
+            .enumerate()
+            .filter(|(_, present)| **present)
+            .map(|(idx, _)| idx)
+    }
+
+    fn frozen_out(&self, node_idx: usize) -> std::ops::Range<usize> {
+        match self.csr.out_offsets.get(node_idx + 1) {
+            Some(end) => self.csr.out_offsets[node_idx] as usize..*end as usize,
+            None => 0..0,
+        }
+    }
+
+    fn frozen_in(&self, node_idx: usize) -> std::ops::Range<usize> {
+        match self.csr.in_offsets.get(node_idx + 1) {
+            Some(end) => self.csr.in_offsets[node_idx] as usize..*end as usize,
+            None => 0..0,
+        }
+    }
+
+    fn frozen_position(&self, from: usize, to: usize) -> Option<usize> {
+        let range = self.frozen_out(from);
+        let start = range.start;
+        self.csr.out_targets[range]
+            .binary_search(&(to as u32))
+            .ok()
+            .map(|offset| start + offset)
+    }
+
+    /// (from, to, weight) of the edges touching node_idx in direction
+    pub fn edges_directed(
+        &self,
+        node_idx: usize,
+        direction: Direction,
+    ) -> impl Iterator<Item = (usize, usize, &E)> + '_ {
+        let frozen: Box<dyn Iterator<Item = (usize, usize, &E)>> = match direction {
+            Direction::Outgoing => Box::new(self.frozen_out(node_idx).map(move |pos| {
+                (
+                    node_idx,
+                    self.csr.out_targets[pos] as usize,
+                    &self.csr.weights[pos],
+                )
+            })),
+            Direction::Incoming => {
+                Box::new(self.csr.in_edges[self.frozen_in(node_idx)].iter().map(
+                    move |(from, pos)| (*from as usize, node_idx, &self.csr.weights[*pos as usize]),
+                ))
+            }
+        };
+        // not yet frozen edges - linear, but only seen while building the graph
+        let pending = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter(move |(_, (from, to, _))| match direction {
+                Direction::Outgoing => *from as usize == node_idx,
+                Direction::Incoming => *to as usize == node_idx,
+            })
+            .filter(move |(ii, (from, to, _))| self.is_last_pending(*ii, *from, *to))
+            .map(|(_, (from, to, weight))| (*from as usize, *to as usize, weight));
+        frozen
+            .filter(move |(from, to, _)| {
+                self.pending.is_empty()
+                    || !self
+                        .pending
+                        .iter()
+                        .any(|(f, t, _)| *f as usize == *from && *t as usize == *to)
+            })
+            .chain(pending)
+            .filter(move |(from, to, _)| self.contains_node(*from) && self.contains_node(*to))
+    }
+
+    fn is_last_pending(&self, ii: usize, from: u32, to: u32) -> bool {
+        !self.pending[ii + 1..]
+            .iter()
+            .any(|(f, t, _)| *f == from && *t == to)
+    }
+
+    pub fn neighbors_directed(
+        &self,
+        node_idx: usize,
+        direction: Direction,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.edges_directed(node_idx, direction)
+            .map(move |(from, to, _)| match direction {
+                Direction::Outgoing => to,
+                Direction::Incoming => from,
+            })
+    }
+
+    pub fn all_edges(&self) -> impl Iterator<Item = (usize, usize, &E)> + '_ {
+        self.nodes()
+            .flat_map(move |node_idx| self.edges_directed(node_idx, Direction::Outgoing))
+    }
+
+    pub fn contains_edge(&self, from: usize, to: usize) -> bool {
+        self.edge_weight(from, to).is_some()
+    }
+
+    pub fn edge_weight(&self, from: usize, to: usize) -> Option<&E> {
+        if !(self.contains_node(from) && self.contains_node(to)) {
+            return None;
+        }
+        if let Some((_, _, weight)) = self
+            .pending
+            .iter()
+            .rev()
+            .find(|(f, t, _)| *f as usize == from && *t as usize == to)
+        {
+            return Some(weight);
+        }
+        self.frozen_position(from, to)
+            .map(|pos| &self.csr.weights[pos])
+    }
+
+    pub fn edge_weight_mut(&mut self, from: usize, to: usize) -> Option<&mut E> {
+        if !(self.contains_node(from) && self.contains_node(to)) {
+            return None;
+        }
+        if let Some(ii) = self
+            .pending
+            .iter()
+            .rposition(|(f, t, _)| *f as usize == from && *t as usize == to)
+        {
+            return Some(&mut self.pending[ii].2);
+        }
+        match self.frozen_position(from, to) {
+            Some(pos) => Some(&mut self.csr.weights[pos]),
+            None => None,
+        }
+    }
+
+    /// Kahn's algorithm. Err(a node on a cycle) if there is one.
+    pub fn toposort(&self) -> Result<Vec<usize>, usize> {
+        let mut in_degree = vec![0usize; self.present.len()];
+        for (_, to, _) in self.all_edges() {
+            in_degree[to] += 1;
+        }
+        let mut queue: std::collections::VecDeque<usize> =
+            self.nodes().filter(|idx| in_degree[*idx] == 0).collect();
+        let mut res = Vec::with_capacity(self.present.len());
+        while let Some(node_idx) = queue.pop_front() {
+            res.push(node_idx);
+            for downstream_idx in self.neighbors_directed(node_idx, Direction::Outgoing) {
+                in_degree[downstream_idx] -= 1;
+                if in_degree[downstream_idx] == 0 {
+                    queue.push_back(downstream_idx);
+                }
+            }
+        }
+        if res.len() < self.node_count() {
+            Err(self
+                .nodes()
+                .find(|idx| in_degree[*idx] > 0)
+                .expect("cycle without remaining in-degree?"))
+        } else {
+            Ok(res)
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// The job dag on petgraph's GraphMap (feature 'petgraph-backend'),
+// same interface as the CSR Dag - in exchange for more memory per edge,
+// analysis code gets petgraph's algorithms (scc, dominators...) via graphmap().
+use petgraph::graphmap::GraphMap;
+use petgraph::Directed;
+
+use super::Direction;
+
+fn to_petgraph(direction: Direction) -> petgraph::Direction {
+    match direction {
+        Direction::Outgoing => petgraph::Direction::Outgoing,
+        Direction::Incoming => petgraph::Direction::Incoming,
+    }
+}
+
+#[derive(Debug)]
+pub struct PetgraphDag<E> {
+    graph: GraphMap<usize, E, Directed>,
+    // nodes taken out by remove_node, with their edges - for restore_removed
+    removed_nodes: Vec<usize>,
+    removed_edges: Vec<(usize, usize, E)>,
+}
+
+impl<E> Default for PetgraphDag<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> PetgraphDag<E> {
+    pub fn new() -> Self {
+        PetgraphDag {
+            graph: GraphMap::new(),
+            removed_nodes: Vec::new(),
+            removed_edges: Vec::new(),
+        }
+    }
+
+    pub fn graphmap(&self) -> &GraphMap<usize, E, Directed> {
+        &self.graph
+    }
+
+    pub fn add_node(&mut self, node_idx: usize) {
+        self.graph.add_node(node_idx);
+    }
+
+    /// Add an edge - replacing the weight if it already exists
+    pub fn add_edge(&mut self, from: usize, to: usize, weight: E) {
+        self.graph.add_edge(from, to, weight);
+    }
+
+    pub fn remove_node(&mut self, node_idx: usize) {
+        if !self.graph.contains_node(node_idx) {
+            return;
+        }
+        let edges: Vec<(usize, usize)> = self
+            .graph
+            .edges_directed(node_idx, petgraph::Direction::Outgoing)
+            .chain(
+                self.graph
+                    .edges_directed(node_idx, petgraph::Direction::Incoming),
+            )
+            .map(|(from, to, _)| (from, to))
+            .collect();
+        for (from, to) in edges {
+            if let Some(weight) = self.graph.remove_edge(from, to) {
+                self.removed_edges.push((from, to, weight));
+            }
+        }
+        self.graph.remove_node(node_idx);
+        self.removed_nodes.push(node_idx);
+    }
+
+    /// Bring back all removed nodes, with their edges
+    pub fn restore_removed(&mut self) {
+        for node_idx in self.removed_nodes.drain(..) {
+            self.graph.add_node(node_idx);
+        }
+        for (from, to, weight) in self.removed_edges.drain(..) {
+            self.graph.add_edge(from, to, weight);
+        }
+    }
+
+    pub fn contains_node(&self, node_idx: usize) -> bool {
+        self.graph.contains_node(node_idx)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        true
+    }
+
+    /// nothing to do - GraphMap is always queryable
+    pub fn freeze(&mut self) {}
+
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.graph.nodes()
+    }
+
+    pub fn edges_directed(
+        &self,
+        node_idx: usize,
+        direction: Direction,
+    ) -> impl Iterator<Item = (usize, usize, &E)> + '_ {
+        self.graph.edges_directed(node_idx, to_petgraph(direction))
+    }
+
+    pub fn neighbors_directed(
+        &self,
+        node_idx: usize,
+        direction: Direction,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.graph
+            .neighbors_directed(node_idx, to_petgraph(direction))
+    }
+
+    pub fn all_edges(&self) -> impl Iterator<Item = (usize, usize, &E)> + '_ {
+        self.graph.all_edges()
+    }
+
+    pub fn contains_edge(&self, from: usize, to: usize) -> bool {
+        self.graph.contains_edge(from, to)
+    }
+
+    pub fn edge_weight(&self, from: usize, to: usize) -> Option<&E> {
+        self.graph.edge_weight(from, to)
+    }
+
+    pub fn edge_weight_mut(&mut self, from: usize, to: usize) -> Option<&mut E> {
+        self.graph.edge_weight_mut(from, to)
+    }
+
+    /// Err(a node on a cycle) if there is one.
+    pub fn toposort(&self) -> Result<Vec<usize>, usize> {
+        petgraph::algo::toposort(&self.graph, None).map_err(|cycle| cycle.node_id())
+    }
+}
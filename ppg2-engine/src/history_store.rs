@@ -0,0 +1,131 @@
+// Interned storage for the job history.
+//
+// Histories of big projects are dominated by a few long values repeated
+// over and over - the same file hash is recorded as the output of a job
+// and again on every 'upstream!!!downstream' edge leading out of it.
+// Each distinct value is kept once, as an Arc<str> shared by all keys
+// recording it. The Arc strong count is the reference count - a value
+// leaves the pool once the last key using it is removed or overwritten.
+use std::collections::{HashMap, HashSet};
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HistoryStoreStats {
+    pub entries: usize,
+    pub unique_values: usize,
+    /// bytes of the interned values
+    pub value_bytes: usize,
+    /// bytes the values would take if every key held its own copy
+    pub logical_value_bytes: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct HistoryStore {
+    entries: HashMap<String, Arc<str>>,
+    pool: HashSet<Arc<str>>,
+}
+
+impl HistoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(existing) = self.pool.get(value) {
+            return existing.clone();
+        }
+        let value: Arc<str> = Arc::from(value);
+        self.pool.insert(value.clone());
+        value
+    }
+
+    fn release(&mut self, value: Arc<str>) {
+        // the pool's and ours
+        if Arc::strong_count(&value) == 2 {
+            self.pool.remove(&value);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(|x| x.as_ref())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: String, value: impl AsRef<str>) {
+        let value = self.intern(value.as_ref());
+        if let Some(previous) = self.entries.insert(key, value) {
+            self.release(previous);
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let value = self.entries.remove(key)?;
+        let res = value.to_string();
+        self.release(value);
+        Some(res)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &str)> {
+        self.entries.iter().map(|(k, v)| (k, v.as_ref()))
+    }
+
+    pub fn to_hash_map(&self) -> HashMap<String, String> {
+        self.iter()
+            .map(|(k, v)| (k.clone(), v.to_string()))
+            .collect()
+    }
+
+    pub fn stats(&self) -> HistoryStoreStats {
+        HistoryStoreStats {
+            entries: self.entries.len(),
+            unique_values: self.pool.len(),
+            value_bytes: self.pool.iter().map(|x| x.len()).sum(),
+            logical_value_bytes: self.entries.values().map(|x| x.len()).sum(),
+        }
+    }
+}
+
+// no shared Arcs between stores - the strong counts are per store
+impl Clone for HistoryStore {
+    fn clone(&self) -> Self {
+        self.iter().collect()
+    }
+}
+
+impl PartialEq for HistoryStore {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl<K: Into<String>, V: AsRef<str>> FromIterator<(K, V)> for HistoryStore {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut res = HistoryStore::new();
+        for (key, value) in iter {
+            res.insert(key.into(), value);
+        }
+        res
+    }
+}
+
+impl From<HashMap<String, String>> for HistoryStore {
+    fn from(history: HashMap<String, String>) -> Self {
+        history.into_iter().collect()
+    }
+}
@@ -0,0 +1,113 @@
+// Integrity checks for a history, e.g. after an interrupted write.
+// Corruptions would otherwise show up as jobs rebuilding for no visible reason.
+//
+// Keys as in PPGEvaluator::new_history: 'job' output, 'job!!!' input list,
+// 'upstream!!!job' what job last saw of upstream. Engine bookkeeping keys
+// start with '!!!' and are left alone.
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HistoryIssueKind {
+    /// a json value that doesn't parse - cut off mid write
+    TruncatedValue,
+    /// keys naming the same job(s) differently - ':::' parts reordered, stray whitespace
+    DuplicateLogicalKey,
+    /// an 'upstream!!!job' entry, but job's input list doesn't name upstream
+    ContradictingEdge,
+    /// NUL bytes or U+FFFD replacement characters - lossy decoding of binary junk
+    EncodingArtifact,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryIssue {
+    pub kind: HistoryIssueKind,
+    pub keys: Vec<String>,
+    pub message: String,
+}
+
+fn looks_like_json(value: &str) -> bool {
+    matches!(value.trim_start().chars().next(), Some('{') | Some('['))
+}
+
+fn logical_job_id(job_id: &str) -> String {
+    let mut parts: Vec<&str> = job_id.split(":::").map(|x| x.trim()).collect();
+    parts.sort_unstable();
+    parts.join(":::")
+}
+
+fn logical_key(key: &str) -> String {
+    match key.split_once("!!!") {
+        None => logical_job_id(key),
+        Some((a, b)) => format!("{}!!!{}", logical_job_id(a), logical_job_id(b)),
+    }
+}
+
+/// All issues found, by kind, then key
+pub fn verify_history(history: &HashMap<String, String>) -> Vec<HistoryIssue> {
+    let mut res = Vec::new();
+    let mut keys: Vec<&String> = history
+        .keys()
+        .filter(|key| !key.starts_with("!!!"))
+        .collect();
+    keys.sort();
+
+    for key in keys.iter() {
+        let value = &history[*key];
+        if looks_like_json(value) && serde_json::from_str::<serde_json::Value>(value).is_err() {
+            res.push(HistoryIssue {
+                kind: HistoryIssueKind::TruncatedValue,
+                keys: vec![key.to_string()],
+                message: format!("{}: value is not valid json ({} bytes)", key, value.len()),
+            });
+        }
+    }
+
+    let mut by_logical: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for key in keys.iter() {
+        by_logical
+            .entry(logical_key(key))
+            .or_default()
+            .push(key.to_string());
+    }
+    for (_, variants) in by_logical.into_iter().filter(|(_, v)| v.len() > 1) {
+        res.push(HistoryIssue {
+            kind: HistoryIssueKind::DuplicateLogicalKey,
+            message: format!("{} name the same entry", variants.join(", ")),
+            keys: variants,
+        });
+    }
+
+    for key in keys.iter() {
+        if let Some((upstream, downstream)) = key.split_once("!!!") {
+            if downstream.is_empty() {
+                continue;
+            }
+            let input_list_key = format!("{}!!!", downstream);
+            if let Some(input_list) = history.get(&input_list_key) {
+                if !input_list.split('\n').any(|x| x == upstream) {
+                    res.push(HistoryIssue {
+                        kind: HistoryIssueKind::ContradictingEdge,
+                        keys: vec![key.to_string(), input_list_key],
+                        message: format!(
+                            "{} has an entry for upstream {}, but its input list doesn't name it",
+                            downstream, upstream
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    for key in keys.iter() {
+        let value = &history[*key];
+        let broken = |x: &str| x.contains('\0') || x.contains('\u{FFFD}');
+        if broken(key) || broken(value) {
+            res.push(HistoryIssue {
+                kind: HistoryIssueKind::EncodingArtifact,
+                keys: vec![key.to_string()],
+                message: format!("{:?}: NUL / replacement characters in key or value", key),
+            });
+        }
+    }
+    res
+}
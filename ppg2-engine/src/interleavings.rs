@@ -0,0 +1,142 @@
+// Exhaustive exploration of completion orders.
+//
+// The fuzzer samples event orders; for small graphs we can afford all of
+// them. Every ready job is started right away (like TestGraphRunner::run),
+// and each time one of the running jobs finishes, every choice of which one
+// is explored - by replaying the graph from scratch with a different choice
+// at the last branching point that has untried options left.
+//
+// Whatever the order, the final job states, how often each job ran and the
+// new history have to be the same - the ephemeral propagation bugs
+// (issue_20210726a, issue_20211001) only showed up in some orders.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{finish_test_job, PPGEvaluator, StrategyForTesting, TestGraphRunner};
+
+/// What one interleaving ended with - compared across all of them
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    states: HashMap<String, String>,
+    run_counters: HashMap<String, usize>,
+    history: HashMap<String, String>,
+}
+
+// (options, taken) per branching point
+type Path = Vec<(usize, usize)>;
+
+impl TestGraphRunner {
+    /// Evaluate the graph in every order its running jobs can complete in
+    /// and check they all agree. Returns the number of interleavings,
+    /// or an error describing the first disagreement (or that there were
+    /// more than max_interleavings). Doesn't change the runner's history.
+    pub fn explore_interleavings(
+        &mut self,
+        jobs_to_fail: &[&str],
+        max_interleavings: usize,
+    ) -> Result<usize, String> {
+        let mut prefix: Vec<usize> = Vec::new();
+        let mut first: Option<(Vec<usize>, Outcome)> = None;
+        let mut count = 0;
+        loop {
+            if count == max_interleavings {
+                return Err(format!(
+                    "more than {} interleavings, graph too large to explore",
+                    max_interleavings
+                ));
+            }
+            let (path, outcome) = self.evaluate_interleaving(jobs_to_fail, &prefix)?;
+            count += 1;
+            let choices: Vec<usize> = path.iter().map(|(_, taken)| *taken).collect();
+            match &first {
+                None => first = Some((choices.clone(), outcome)),
+                Some((first_choices, first_outcome)) => {
+                    if *first_outcome != outcome {
+                        return Err(format!(
+                            "interleavings {:?} and {:?} disagree:\n{:?}\nvs\n{:?}",
+                            first_choices, choices, first_outcome, outcome
+                        ));
+                    }
+                }
+            }
+            // backtrack to the last branching point with untried options
+            prefix = choices;
+            loop {
+                match prefix.pop() {
+                    None => return Ok(count),
+                    Some(taken) => {
+                        if taken + 1 < path[prefix.len()].0 {
+                            prefix.push(taken + 1);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // one evaluation - finishing jobs as prefix says, then always the first option
+    fn evaluate_interleaving(
+        &mut self,
+        jobs_to_fail: &[&str],
+        prefix: &[usize],
+    ) -> Result<(Path, Outcome), String> {
+        let strategy = StrategyForTesting::new();
+        strategy
+            .already_done
+            .borrow_mut()
+            .extend(self.already_done.iter().cloned());
+        let already_done = Rc::clone(&strategy.already_done);
+        let mut g = PPGEvaluator::new_with_history(self.history.clone(), strategy);
+        (self.setup_graph)(&mut g, self.runs);
+        g.event_startup().map_err(|e| e.to_string())?;
+        let mut path = Path::new();
+        let mut running: Vec<String> = Vec::new();
+        let mut run_counters = HashMap::new();
+        while !g.is_finished() {
+            let mut ready: Vec<String> = g.query_ready_to_run().into_iter().collect();
+            ready.sort();
+            for job_id in ready {
+                g.event_now_running(&job_id).map_err(|e| e.to_string())?;
+                *run_counters.entry(job_id.clone()).or_insert(0) += 1;
+                running.push(job_id);
+            }
+            if running.is_empty() {
+                return Err(format!(
+                    "not finished, but nothing ready or running:\n{}",
+                    g.debug_()
+                ));
+            }
+            running.sort();
+            let taken = prefix.get(path.len()).copied().unwrap_or(0);
+            path.push((running.len(), taken));
+            let job_id = running.remove(taken);
+            let output = self
+                .outputs
+                .get(&job_id)
+                .cloned()
+                .unwrap_or_else(|| format!("history_{}", job_id));
+            let fail = jobs_to_fail.contains(&job_id.as_str());
+            finish_test_job(&mut g, &job_id, fail, output, &already_done)
+                .map_err(|e| e.to_string())?;
+            for c in g.query_ready_for_cleanup() {
+                g.event_job_cleanup_done(&c).map_err(|e| e.to_string())?;
+            }
+        }
+        g.verify_event_order().map_err(|e| e.to_string())?;
+        let states = g
+            .jobs
+            .iter()
+            .map(|job| (job.job_id.clone(), format!("{:?}", job.state)))
+            .collect();
+        let history = g.new_history().map_err(|e| e.to_string())?;
+        Ok((
+            path,
+            Outcome {
+                states,
+                run_counters,
+                history,
+            },
+        ))
+    }
+}
@@ -0,0 +1,114 @@
+// JSON lines logging - one object per event with ts, level, job_id, event
+// so run logs can be shipped to ELK/Loki.
+// job_id is taken from the innermost enclosing 'job' span the engine opens.
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+struct JobId(String);
+
+#[derive(Default)]
+struct FieldCollector {
+    job_id: Option<String>,
+    message: Option<String>,
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "job_id" => self.job_id = Some(value.to_string()),
+            "message" => self.message = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "job_id" => self.job_id = Some(format!("{:?}", value)),
+            "message" => self.message = Some(format!("{:?}", value)),
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn format_json_line(ts: f64, level: &str, job_id: Option<&str>, event: &str) -> String {
+    serde_json::json!({
+        "ts": ts,
+        "level": level,
+        "job_id": job_id,
+        "event": event,
+    })
+    .to_string()
+}
+
+struct JsonLines {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl<S> Layer<S> for JsonLines
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = FieldCollector::default();
+        attrs.record(&mut fields);
+        if let (Some(job_id), Some(span)) = (fields.job_id, ctx.span(id)) {
+            span.extensions_mut().insert(JobId(job_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldCollector::default();
+        event.record(&mut fields);
+        let job_id = fields.job_id.or_else(|| {
+            ctx.event_scope(event).and_then(|scope| {
+                scope
+                    .into_iter()
+                    .find_map(|span| span.extensions().get::<JobId>().map(|x| x.0.clone()))
+            })
+        });
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+        let line = format_json_line(
+            ts,
+            event.metadata().level().as_str(),
+            job_id.as_deref(),
+            fields.message.as_deref().unwrap_or(""),
+        );
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", line);
+    }
+}
+
+/// Log JSON lines to filename (or stderr).
+/// Shares the once-only initialization with start_logging / start_logging_to_file.
+pub fn start_logging_json(filename: Option<impl AsRef<Path>>) {
+    if !crate::LOGGER_INIT.is_completed() {
+        crate::LOGGER_INIT.call_once(move || {
+            let out: Box<dyn Write + Send> = match filename {
+                Some(filename) => {
+                    Box::new(std::fs::File::create(filename).expect("Could not open log file"))
+                }
+                None => Box::new(std::io::stderr()),
+            };
+            let subscriber = tracing_subscriber::registry()
+                .with(tracing_subscriber::filter::LevelFilter::DEBUG)
+                .with(JsonLines {
+                    out: Mutex::new(out),
+                });
+            tracing::subscriber::set_global_default(subscriber)
+                .expect("Could not install json logging subscriber");
+            // and route the log crate (TestGraphRunner, python side helpers) into it as well
+            tracing_log::LogTracer::init().expect("Could not install log->tracing bridge");
+        });
+    }
+}
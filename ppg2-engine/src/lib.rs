@@ -0,0 +1,841 @@
+//! The pypipegraph2 evaluator - pure rust, no python.
+//!
+//! Other workflow tools should depend on the types in [api],
+//! the rest is exported for the pyo3 shim (pypipegraph2) and may change.
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Once;
+
+use thiserror::Error;
+
+pub mod api;
+mod engine;
+mod failure_report;
+mod filesystem_strategy;
+mod fuzz;
+mod graph;
+mod history_store;
+mod history_verify;
+mod interleavings;
+mod json_log;
+mod migrate;
+#[cfg(feature = "otel")]
+mod otel;
+mod record_replay;
+mod report;
+mod session;
+mod shared_history;
+mod slurm;
+pub mod stress;
+#[cfg(test)]
+mod tests;
+mod wildcard;
+
+pub use engine::{
+    AlwaysGate, CleanupStats, Clock, EdgeDetails, EvaluationStats, EvaluatorSnapshot,
+    FailedJobInfo, FairShare, Fifo, FinishState, GraphType, JobKind, JobOutputResult, JobRecord,
+    JobRecordColumns, JobSnapshot, LastSuccess, LongestJobFirst, LongestJobFirstWithAging,
+    MostDownstreamsFirst, NodeIndex, NodeInfo, PPGEvaluator, PassStats, Progress, ReadyCandidate,
+    ResourceState, RunPlan, RunReason, SchedulingPolicy, Severity, SimulatedClock, Subgraph,
+    UtilizationPoint, UtilizationReport, ValidationIssue, ValidationIssueKind, ValidationReport,
+    WallClock, ABSENCE_JOB_PREFIX, BARRIER_HISTORY, RECENT_PASSES, SKIPPED_HISTORY,
+    SUBGRAPH_SEPARATOR,
+};
+pub use failure_report::{FailedJob, FailureReport};
+pub use filesystem_strategy::{
+    all_paths_present, all_paths_present_parallel, fingerprints_altered, FileFingerprint,
+    StrategyContentHash, StrategyFileSystem,
+};
+pub use fuzz::{check_event_sequence, FuzzCase};
+pub use history_store::{HistoryStore, HistoryStoreStats};
+pub use history_verify::{verify_history, HistoryIssue, HistoryIssueKind};
+pub use json_log::start_logging_json;
+pub use migrate::{
+    import_ppg1_history, HistoryMigration, HistoryMigrator, Ppg1Import, HISTORY_SCHEMA_VERSION,
+    HISTORY_VERSION_KEY,
+};
+pub use record_replay::{Recording, StrategyRecorder, StrategyReplay};
+pub use session::{HistoryBackend, HistoryStorageError, JsonFileHistory, Session, SessionConfig};
+pub use shared_history::{HistoryConflict, SharedHistory};
+pub use slurm::{ArrayBatch, ArrayTaskEvent, ResourceShape, SubmissionPlan};
+pub use tracing::level_filters::LevelFilter;
+
+static LOGGER_INIT: Once = Once::new();
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum PPGEvaluatorError {
+    #[error("API error. You're holding it wrong")]
+    APIError(String),
+    #[error("Ephemeral {job_id} was validated, but rerun for downstreams. It changed output, violating the constant input->constant output assumption. Output was \n'{last_history}' is now \n'{new_history}'. You are holding it very wrong.")]
+    EphemeralChangedOutput {
+        job_id: String,
+        last_history: String,
+        new_history: String,
+    },
+    #[error(
+        "Internal error. Something in the pipegraph2 engine is wrong. Graph execution aborted. Msg was {0}"
+    )]
+    InternalError(String),
+    #[error("Strategy error. {0}")]
+    StrategyError(#[from] StrategyError),
+    #[error("Job graph contains a cycle (involving {0})")]
+    Cycle(String),
+    #[error("Reported a job as finished that was not running! {0}")]
+    JobNotRunning(String),
+    #[error("Job {0} was already added")]
+    JobRedefinition(String),
+    #[error("Invalid job id '{0}' (empty or containing '!!!')")]
+    InvalidJobId(String),
+    #[error("Unknown job {0}")]
+    UnknownJob(String),
+    #[error("Job {0} can't depend on itself")]
+    SelfDependency(String),
+    #[error("History (schema version {found}) can't be used: {msg}")]
+    HistoryMigration { found: u32, msg: String },
+    #[error("Conflicting duplicate finish event for {job_id} ({now}): it was already reported as {prior}, and is now {state}")]
+    DuplicateEvent {
+        job_id: String,
+        state: String,
+        prior: String,
+        now: String,
+    },
+    #[error("Evaluation stalled - unfinished jobs, but none running or ready:\n{snapshot}")]
+    Stalled { snapshot: String },
+    #[error("Job {job_id} is {kind:?} here, but {other_kind:?} in the merged graph")]
+    ConflictingJobKind {
+        job_id: String,
+        kind: JobKind,
+        other_kind: JobKind,
+    },
+    #[error("Invariant {job_id} is '{value}' here, but '{other_value}' in the merged graph")]
+    ConflictingInvariant {
+        job_id: String,
+        value: String,
+        other_value: String,
+    },
+    #[error("History storage {path}: {source}")]
+    HistoryStorage {
+        path: String,
+        #[source]
+        source: HistoryStorageError,
+    },
+}
+
+/// A strategy could not answer a query - the evaluation can't continue,
+/// since assuming either answer would be wrong.
+#[derive(Error, Debug)]
+pub enum StrategyError {
+    #[error("Could not query {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Strategy callback failed: {0}")]
+    Callback(String),
+    #[error("Replay diverged from recording: {0}")]
+    Replay(String),
+}
+
+pub trait PPGEvaluatorStrategy {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError>;
+    fn is_history_altered(
+        &self,
+        job_id_upstream: &str,
+        job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError>;
+
+    fn get_input_list(
+        &self,
+        node_idx: engine::NodeIndex,
+        dag: &engine::GraphType,
+        jobs: &[engine::NodeInfo],
+    ) -> Result<String, StrategyError>;
+
+    /// Presence of many outputs at once (used to prefetch in event_startup).
+    /// Strategies may answer these in parallel.
+    fn outputs_already_present(&self, queries: &[&str]) -> Vec<Result<bool, StrategyError>> {
+        queries
+            .iter()
+            .map(|query| self.output_already_present(query))
+            .collect()
+    }
+
+    /// Input lists of many jobs at once (used to prefetch in event_startup),
+    /// e.g. to cross into python only once.
+    fn get_input_lists(
+        &self,
+        node_idxs: &[engine::NodeIndex],
+        dag: &engine::GraphType,
+        jobs: &[engine::NodeInfo],
+    ) -> Vec<Result<String, StrategyError>> {
+        node_idxs
+            .iter()
+            .map(|node_idx| self.get_input_list(*node_idx, dag, jobs))
+            .collect()
+    }
+
+    /// The history value job_id would report if it ran now - for jobs whose output
+    /// is present but whose history was lost (see PPGEvaluator::set_assume_unchanged).
+    /// None if the strategy can't tell, the job is then rebuilt.
+    fn current_history(&self, _job_id: &str) -> Result<Option<String>, StrategyError> {
+        Ok(None)
+    }
+
+    /// Whether a JobKind::Conditional job that's ready to run should run -
+    /// otherwise it's finished with SKIPPED_HISTORY.
+    fn should_run(&self, _job_id: &str) -> Result<bool, StrategyError> {
+        Ok(true)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StrategyForTesting {
+    pub already_done: Rc<RefCell<HashSet<String>>>,
+}
+
+impl StrategyForTesting {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        StrategyForTesting {
+            already_done: Rc::new(RefCell::new(HashSet::new())),
+        }
+    }
+}
+
+impl PPGEvaluatorStrategy for StrategyForTesting {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError> {
+        Ok(self.already_done.borrow().contains(query))
+    }
+
+    fn is_history_altered(
+        &self,
+        _job_id_upstream: &str,
+        _job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError> {
+        Ok(last_recorded_value != current_value)
+    }
+
+    fn get_input_list(
+        &self,
+        node_idx: engine::NodeIndex,
+        dag: &engine::GraphType,
+        jobs: &[engine::NodeInfo],
+    ) -> Result<String, StrategyError> {
+        Ok(sorted_upstream_job_ids(node_idx, dag, jobs))
+    }
+
+    /// what TestGraphRunner reports for jobs without a configured output
+    fn current_history(&self, job_id: &str) -> Result<Option<String>, StrategyError> {
+        Ok(if self.already_done.borrow().contains(job_id) {
+            Some(format!("history_{}", job_id))
+        } else {
+            None
+        })
+    }
+}
+
+/// The default input list: upstream job ids, sorted, newline separated
+pub fn sorted_upstream_job_ids(
+    node_idx: engine::NodeIndex,
+    dag: &engine::GraphType,
+    jobs: &[engine::NodeInfo],
+) -> String {
+    let mut names = Vec::new();
+    let upstreams = dag.neighbors_directed(node_idx, graph::Direction::Incoming);
+    for upstream_idx in upstreams {
+        names.push(jobs[upstream_idx].get_job_id());
+    }
+    names.sort();
+    names.join("\n")
+}
+
+/// Log to stderr via env_logger.
+/// The engine emits tracing spans ('evaluation_pass' per signal pass,
+/// 'job' with job_id/kind per job lifecycle) - embedders wanting structured
+/// diagnostics can install their own tracing subscriber instead of calling this.
+pub fn start_logging() {
+    let start_time = std::time::Instant::now();
+    if !LOGGER_INIT.is_completed() {
+        LOGGER_INIT.call_once(move || {
+            use colored::Colorize;
+            let start_time2 = start_time;
+            env_logger::builder()
+                .filter_level(log::LevelFilter::Debug)
+                .format(move |buf, record| {
+                    let filename = record
+                        .file()
+                        .unwrap_or("unknown")
+                        .trim_start_matches("src/");
+                    let ff = format!("{}:{}", filename, record.line().unwrap_or(0));
+                    let ff = match record.level() {
+                        log::Level::Error => ff.red(),
+                        log::Level::Warn => ff.yellow(),
+                        log::Level::Info => ff.blue(),
+                        log::Level::Debug => ff.green(),
+                        log::Level::Trace => ff.normal(),
+                    };
+
+                    writeln!(
+                        buf,
+                        "{}\t{:.4} | {}",
+                        ff,
+                        (std::time::Instant::now() - start_time2).as_millis(),
+                        //chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"),
+                        record.args()
+                    )
+                })
+                .is_test(true)
+                .init()
+        });
+    }
+}
+pub fn start_logging_to_file(filename: impl AsRef<Path>) {
+    let start_time = std::time::Instant::now();
+    if !LOGGER_INIT.is_completed() {
+        LOGGER_INIT.call_once(move || {
+            let fh = std::fs::File::create(filename).expect("Could not open log file");
+            let start_time2 = start_time;
+            env_logger::builder()
+                .filter_level(log::LevelFilter::Debug)
+                .format(move |buf, record| {
+                    let filename = record
+                        .file()
+                        .unwrap_or("unknown")
+                        .trim_start_matches("src/");
+                    let ff = format!(
+                        "{:15}:{:5} | {:5} |",
+                        filename,
+                        record.line().unwrap_or(0),
+                        record.level()
+                    );
+                    writeln!(
+                        buf,
+                        "{}\t{:.4}ms | {}",
+                        ff,
+                        (std::time::Instant::now() - start_time2).as_millis(),
+                        //chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"),
+                        record.args()
+                    )
+                })
+                .is_test(true)
+                .target(env_logger::Target::Pipe(Box::new(fh)))
+                .init()
+        })
+    }
+}
+
+pub struct RunError<T: PPGEvaluatorStrategy = StrategyForTesting>(
+    pub engine::PPGEvaluator<T>,
+    pub PPGEvaluatorError,
+);
+
+impl<T: PPGEvaluatorStrategy> std::fmt::Debug for RunError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunError").field("error", &self.1).finish()
+    }
+}
+
+/// Makespan and per worker busy time of a simulated run, see TestGraphRunner::workers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedSchedule {
+    pub makespan: std::time::Duration,
+    /// per worker, the time spent running jobs
+    pub busy: Vec<std::time::Duration>,
+}
+
+impl SimulatedSchedule {
+    /// per worker, busy / makespan
+    pub fn utilization(&self) -> Vec<f64> {
+        self.busy
+            .iter()
+            .map(|busy| {
+                if self.makespan.is_zero() {
+                    0.0
+                } else {
+                    busy.as_secs_f64() / self.makespan.as_secs_f64()
+                }
+            })
+            .collect()
+    }
+}
+
+/// What TestGraphRunner's before_run / after_run hooks may change
+pub struct RunContext<'a> {
+    /// 0 for the first run
+    pub run_index: usize,
+    pub outputs: &'a mut HashMap<String, String>,
+    pub already_done: &'a mut HashSet<String>,
+    pub history: &'a mut HashMap<String, String>,
+}
+
+type RunHook = Box<dyn FnMut(&mut RunContext)>;
+
+// simulates a complete (deterministic)
+// run - jobs just register that they've been run,
+// and output a 'dummy' history.
+pub struct TestGraphRunner {
+    /// Builds the graph - called with the run index
+    #[allow(clippy::type_complexity)]
+    pub setup_graph: Box<dyn FnMut(&mut PPGEvaluator<StrategyForTesting>, usize)>,
+    /// runs so far
+    pub runs: usize,
+    pub before_run: Option<RunHook>,
+    pub after_run: Option<RunHook>,
+    pub run_counters: HashMap<String, usize>,
+    pub history: HashMap<String, String>,
+    pub already_done: HashSet<String>,
+    pub allowed_nesting: u32,
+    pub outputs: HashMap<String, String>,
+    /// (from run index, job_id, output) - see set_output_from_run
+    pub output_schedule: Vec<(usize, String, String)>,
+    pub run_order: Vec<String>,
+    pub cleaned_up: HashSet<String>,
+    /// Simulate this many workers on a SimulatedClock - jobs take their
+    /// durations, and are started in query_ready_to_run_ordered's order
+    /// whenever a worker is free. None runs everything ready at once.
+    pub workers: Option<usize>,
+    /// job_id -> how long it runs in the simulation. Zero if missing.
+    pub durations: HashMap<String, std::time::Duration>,
+    /// The last simulated run's schedule
+    pub schedule: Option<SimulatedSchedule>,
+}
+
+// only a TempFile's output is gone after its cleanup - the next run has to
+// recreate it. Other ephemerals keep counting as done, as they always did.
+fn forget_cleaned_up_temp_file(
+    g: &PPGEvaluator<StrategyForTesting>,
+    job_id: &str,
+    already_done: &RefCell<HashSet<String>>,
+) {
+    if let Some(idx) = g.job_id_to_node_idx.get(job_id) {
+        if g.jobs[*idx].temp_file {
+            already_done.borrow_mut().remove(job_id);
+        }
+    }
+}
+
+// report a job's outcome like an executor would
+fn finish_test_job(
+    g: &mut PPGEvaluator<StrategyForTesting>,
+    job_id: &str,
+    fail: bool,
+    output: String,
+    already_done: &RefCell<HashSet<String>>,
+) -> Result<(), PPGEvaluatorError> {
+    if fail {
+        return g.event_job_finished_failure(job_id);
+    }
+    match g.event_job_finished_success(job_id, output) {
+        Ok(_) => {
+            already_done.borrow_mut().insert(job_id.to_string());
+        }
+        Err(err) => match err {
+            PPGEvaluatorError::APIError(x) => panic!("api error {}", x),
+            PPGEvaluatorError::EphemeralChangedOutput { .. } => {
+                debug!("EphemeralChangedOutput error. ignoring for tests");
+            }
+            PPGEvaluatorError::InternalError(x) => {
+                panic!("internal error {}", x)
+            }
+            PPGEvaluatorError::StrategyError(x) => {
+                panic!("strategy error {}", x)
+            }
+            x @ PPGEvaluatorError::Cycle(_)
+            | x @ PPGEvaluatorError::JobNotRunning(_)
+            | x @ PPGEvaluatorError::JobRedefinition(_)
+            | x @ PPGEvaluatorError::InvalidJobId(_)
+            | x @ PPGEvaluatorError::UnknownJob(_)
+            | x @ PPGEvaluatorError::SelfDependency(_)
+            | x @ PPGEvaluatorError::HistoryMigration { .. }
+            | x @ PPGEvaluatorError::Stalled { .. }
+            | x @ PPGEvaluatorError::DuplicateEvent { .. }
+            | x @ PPGEvaluatorError::ConflictingJobKind { .. }
+            | x @ PPGEvaluatorError::ConflictingInvariant { .. }
+            | x @ PPGEvaluatorError::HistoryStorage { .. } => {
+                panic!("{}", x)
+            }
+        },
+    }
+    Ok(())
+}
+
+impl TestGraphRunner {
+    #[allow(clippy::type_complexity)]
+    pub fn new(setup_func: Box<dyn Fn(&mut PPGEvaluator<StrategyForTesting>)>) -> Self {
+        Self::with_run_index(Box::new(move |g, _| setup_func(g)))
+    }
+
+    /// A graph that may change from run to run
+    #[allow(clippy::type_complexity)]
+    pub fn with_run_index(
+        setup_func: Box<dyn FnMut(&mut PPGEvaluator<StrategyForTesting>, usize)>,
+    ) -> Self {
+        TestGraphRunner {
+            setup_graph: setup_func,
+            runs: 0,
+            before_run: None,
+            after_run: None,
+            run_counters: HashMap::new(),
+            history: HashMap::new(),
+            already_done: HashSet::new(),
+            allowed_nesting: 250,
+            outputs: HashMap::new(),
+            output_schedule: Vec::new(),
+            run_order: Vec::new(),
+            cleaned_up: HashSet::new(),
+            workers: None,
+            durations: HashMap::new(),
+            schedule: None,
+        }
+    }
+
+    /// Build the graph with setup from now on, whatever the run index
+    pub fn set_setup_graph(
+        &mut self,
+        setup: impl Fn(&mut PPGEvaluator<StrategyForTesting>) + 'static,
+    ) {
+        self.setup_graph = Box::new(move |g, _| setup(g));
+    }
+
+    /// From run run_index (0 is the first) onward, job_id outputs output -
+    /// until a later scheduled output takes over
+    pub fn set_output_from_run(&mut self, run_index: usize, job_id: &str, output: &str) {
+        self.output_schedule
+            .push((run_index, job_id.to_string(), output.to_string()));
+        self.output_schedule.sort_by_key(|(from, _, _)| *from);
+    }
+
+    fn apply_output_schedule(&mut self) {
+        for (from, job_id, output) in self.output_schedule.iter() {
+            if *from <= self.runs {
+                self.outputs.insert(job_id.clone(), output.clone());
+            }
+        }
+    }
+
+    fn call_hook(&mut self, hook: fn(&mut Self) -> &mut Option<RunHook>) {
+        if let Some(mut f) = hook(self).take() {
+            f(&mut RunContext {
+                run_index: self.runs,
+                outputs: &mut self.outputs,
+                already_done: &mut self.already_done,
+                history: &mut self.history,
+            });
+            *hook(self) = Some(f);
+        }
+    }
+
+    pub fn debug_(&mut self) -> String {
+        let strat = StrategyForTesting::new();
+        let mut g = PPGEvaluator::new_with_history(self.history.clone(), strat);
+
+        (self.setup_graph)(&mut g, self.runs);
+        g.debug_()
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn run(
+        &mut self,
+        jobs_to_fail: &[&str],
+    ) -> Result<PPGEvaluator<StrategyForTesting>, RunError> {
+        debug!("");
+        debug!("GOGOGO ----------------------------------------------------------------");
+        self.apply_output_schedule();
+        self.call_hook(|runner| &mut runner.before_run);
+        let strat = StrategyForTesting::new();
+        for k in self.already_done.iter() {
+            strat.already_done.borrow_mut().insert(k.to_string());
+        }
+        let already_done2 = Rc::clone(&strat.already_done);
+        let mut session = Session::new(std::mem::take(&mut self.history), strat);
+        self.run_order.clear();
+        self.schedule = None;
+
+        let setup_graph = &mut self.setup_graph;
+        let run_index = self.runs;
+        let allowed_nesting = self.allowed_nesting;
+        let run_order = &mut self.run_order;
+        let run_counters = &mut self.run_counters;
+        let cleaned_up = &mut self.cleaned_up;
+        let outputs = &self.outputs;
+        let output_of = |job_id: &str| {
+            outputs
+                .get(job_id)
+                .cloned()
+                .unwrap_or_else(|| format!("history_{}", job_id))
+        };
+        let clock = SimulatedClock::default();
+        let workers = self.workers;
+        let durations = &self.durations;
+        let schedule = &mut self.schedule;
+        let res = session.run(
+            |g| {
+                (setup_graph)(g, run_index);
+                if workers.is_some() {
+                    g.set_clock(Box::new(clock.clone()));
+                }
+            },
+            |g| {
+                if let Some(workers) = workers {
+                    // (ends at, worker, job_id)
+                    let mut running: Vec<(std::time::Duration, usize, String)> = Vec::new();
+                    let mut busy = vec![std::time::Duration::ZERO; workers];
+                    while !g.is_finished() {
+                        let free: Vec<usize> = (0..workers)
+                            .filter(|worker| running.iter().all(|(_, w, _)| w != worker))
+                            .collect();
+                        let mut free = free.into_iter();
+                        for job_id in g.query_ready_to_run_ordered() {
+                            let worker = match free.next() {
+                                Some(worker) => worker,
+                                None => break,
+                            };
+                            debug!("Running {} on worker {}", job_id, worker);
+                            g.event_now_running(&job_id)?;
+                            run_order.push(job_id.to_string());
+                            *run_counters.entry(job_id.clone()).or_insert(0) += 1;
+                            let duration = durations.get(&job_id).copied().unwrap_or_default();
+                            busy[worker] += duration;
+                            running.push((clock.elapsed() + duration, worker, job_id));
+                        }
+                        if running.is_empty() {
+                            g.debug_is_finished();
+                        }
+                        assert!(!running.is_empty());
+                        running.sort();
+                        let (ends_at, _, job_id) = running.remove(0);
+                        clock.advance(ends_at - clock.elapsed());
+                        let fail = jobs_to_fail.contains(&job_id.as_str());
+                        finish_test_job(g, &job_id, fail, output_of(&job_id), &already_done2)?;
+                        for c in g.query_ready_for_cleanup() {
+                            g.event_job_cleanup_done(&c)
+                                .expect("cleanup registering failed");
+                            forget_cleaned_up_temp_file(g, &c, &already_done2);
+                            cleaned_up.insert(c);
+                        }
+                    }
+                    *schedule = Some(SimulatedSchedule {
+                        makespan: clock.elapsed(),
+                        busy,
+                    });
+                    return Ok(());
+                }
+                //debug!("{}", g.debug_());
+                let mut counter = allowed_nesting;
+                while !g.is_finished() {
+                    let to_run = g.query_ready_to_run();
+                    if to_run.is_empty() {
+                        g.debug_is_finished();
+                    }
+                    assert!(!to_run.is_empty());
+                    for job_id in to_run.iter() {
+                        debug!("Running {}", job_id);
+                        g.event_now_running(job_id)?;
+                        run_order.push(job_id.to_string());
+                        *run_counters.entry(job_id.clone()).or_insert(0) += 1;
+                        let fail = jobs_to_fail.contains(&&job_id[..]);
+                        finish_test_job(g, job_id, fail, output_of(job_id), &already_done2)?;
+                    }
+                    counter -= 1;
+                    if counter == 0 {
+                        return Err(PPGEvaluatorError::APIError(format!(
+                            "run out of room, you nested them more than {} deep?",
+                            allowed_nesting
+                        )));
+                    }
+
+                    for c in g.query_ready_for_cleanup() {
+                        g.event_job_cleanup_done(&c)
+                            .expect("cleanup registering failed");
+                        forget_cleaned_up_temp_file(g, &c, &already_done2);
+                        cleaned_up.insert(c);
+                    }
+                }
+                Ok(())
+            },
+        );
+        self.history = session.into_backend();
+        let g = match res {
+            Ok(g) => g,
+            Err(e) => {
+                self.runs += 1;
+                return Err(e);
+            }
+        };
+        self.already_done = already_done2.take();
+        g.verify_event_order()
+            .expect("Run order was not topological");
+        self.call_hook(|runner| &mut runner.after_run);
+        self.runs += 1;
+        Ok(g)
+    }
+}
+
+pub fn test_big_linear_graph(count: u32) {
+    let c2 = count;
+    let create_graph = move |g: &mut PPGEvaluator<StrategyForTesting>| {
+        let c = c2 - 1;
+        for ii in 0..c {
+            g.add_node(&format!("A{}", ii), JobKind::Output).unwrap();
+        }
+        for ii in 1..c {
+            g.depends_on(&format!("A{}", ii - 1), &format!("A{}", ii))
+                .unwrap();
+        }
+    };
+    let mut ro = TestGraphRunner::new(Box::new(create_graph));
+    ro.allowed_nesting = count + 1;
+    let g = ro.run(&Vec::new());
+    assert!(g.is_ok())
+    //dbg!(g.new_history().len());
+}
+
+pub fn test_big_linear_graph_half_ephemeral(count: u32) {
+    let c2 = count;
+    let create_graph = move |g: &mut PPGEvaluator<StrategyForTesting>| {
+        let c = c2 - 1;
+        for ii in 0..c {
+            g.add_node(
+                &format!("A{}", ii),
+                if ii % 2 == 0 {
+                    JobKind::Output
+                } else {
+                    JobKind::Ephemeral
+                },
+            )
+            .unwrap();
+        }
+        for ii in 1..c {
+            g.depends_on(&format!("A{}", ii - 1), &format!("A{}", ii))
+                .unwrap();
+        }
+    };
+    let mut ro = TestGraphRunner::new(Box::new(create_graph));
+    ro.allowed_nesting = count + 1;
+    let g = ro.run(&Vec::new());
+    assert!(g.is_ok())
+    //dbg!(g.new_history().len());
+}
+
+pub fn test_big_graph_in_layers(nodes_per_layer: u32, layers: u32, run_count: u32) {
+    let create_graph = move |g: &mut PPGEvaluator<StrategyForTesting>| {
+        for ll in 0..layers {
+            if ll == 0 {
+                for ii in 0..nodes_per_layer {
+                    g.add_node(&format!("A{}_{}", ll, ii), JobKind::Always)
+                        .unwrap();
+                }
+            } else {
+                for ii in 0..nodes_per_layer {
+                    g.add_node(
+                        &format!("A{}_{}", ll, ii),
+                        if ii % 2 == 0 {
+                            JobKind::Output
+                        } else {
+                            JobKind::Ephemeral
+                        },
+                    )
+                    .unwrap();
+                    for yy in 0..nodes_per_layer {
+                        g.depends_on(&format!("A{}_{}", ll, ii), &format!("A{}_{}", ll - 1, yy))
+                            .unwrap()
+                    }
+                }
+            }
+        }
+    };
+    let mut ro = TestGraphRunner::new(Box::new(create_graph));
+    ro.allowed_nesting = layers + 1;
+    for _ in 0..run_count {
+        let g = ro.run(&Vec::new());
+        assert!(g.is_ok())
+    }
+    //dbg!(g.new_history().len());
+}
+
+// bytes history values from python are stored base64 encoded behind this tag,
+// so they come back out as bytes (the engine only deals in strings).
+// str values starting with HISTORY_ESCAPE get another one prepended,
+// so no str can pass for a bytes value.
+const HISTORY_ESCAPE: char = '\u{1}';
+const BYTES_HISTORY_TAG: &str = "\u{1}bytes\u{1}";
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A history value as handed to / from python
+#[derive(Debug, PartialEq, Eq)]
+pub enum HistoryValue<'a> {
+    Str(&'a str),
+    Bytes(Vec<u8>),
+}
+
+pub fn encode_str_history(value: &str) -> String {
+    if value.starts_with(HISTORY_ESCAPE) {
+        format!("{}{}", HISTORY_ESCAPE, value)
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn encode_bytes_history(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(BYTES_HISTORY_TAG.len() + bytes.len().div_ceil(3) * 4);
+    res.push_str(BYTES_HISTORY_TAG);
+    for chunk in bytes.chunks(3) {
+        let block = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (ii, b)| acc | (*b as u32) << (16 - 8 * ii));
+        for ii in 0..4 {
+            if ii <= chunk.len() {
+                res.push(BASE64_ALPHABET[(block >> (18 - 6 * ii) & 0x3f) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    if !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut res = Vec::with_capacity(encoded.len() / 4 * 3);
+    for chunk in encoded.as_bytes().chunks(4) {
+        let padding = chunk.iter().rev().take_while(|x| **x == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut block = 0u32;
+        for (ii, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|x| x == c)? as u32;
+            block |= value << (18 - 6 * ii);
+        }
+        res.extend(block.to_be_bytes()[1..4 - padding].iter());
+    }
+    Some(res)
+}
+
+/// Undo encode_str_history / encode_bytes_history
+pub fn decode_history(value: &str) -> HistoryValue<'_> {
+    if let Some(encoded) = value.strip_prefix(BYTES_HISTORY_TAG) {
+        if let Some(bytes) = decode_base64(encoded) {
+            return HistoryValue::Bytes(bytes);
+        }
+    }
+    match value.strip_prefix(HISTORY_ESCAPE) {
+        Some(unescaped) if unescaped.starts_with(HISTORY_ESCAPE) => HistoryValue::Str(unescaped),
+        _ => HistoryValue::Str(value),
+    }
+}
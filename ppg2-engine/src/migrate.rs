@@ -0,0 +1,178 @@
+// History schema versions, and importing pypipegraph1 histories.
+//
+// new_history stamps the history with HISTORY_SCHEMA_VERSION (under
+// HISTORY_VERSION_KEY). event_startup runs older histories through the
+// evaluator's HistoryMigrator, one registered step per version, so a change
+// to the key structure upgrades histories instead of silently invalidating
+// every job. Histories from before the stamp are version 0, which needs no
+// changes. Newer histories than this build understands are refused.
+//
+// ppg1 keeps its history pickled, which only python can read. The python
+// side dumps it into this intermediate json:
+//
+//     {
+//       "format": "ppg1-history",
+//       "version": 1,
+//       "jobs": {
+//         "<ppg2 job id>": {"value": "<ppg2 history value>", "upstreams": ["<job id>", ...]},
+//         ...
+//       }
+//     }
+//
+// Job ids and values already have to be in ppg2 form (e.g. ':::' joined
+// multi file job ids, ppg2's file fingerprints) - what's converted here is
+// the key structure: the output ('job'), the input list ('job!!!') and
+// what each job last saw of its upstreams ('upstream!!!job').
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+
+use crate::PPGEvaluatorError;
+
+pub const HISTORY_SCHEMA_VERSION: u32 = 1;
+pub const HISTORY_VERSION_KEY: &str = "!!!schema_version!!!";
+
+/// Upgrades a history from one version to the next
+pub type HistoryMigration = Box<dyn Fn(&mut HashMap<String, String>) -> Result<(), String> + Send>;
+
+/// Migration steps by the version they upgrade from
+pub struct HistoryMigrator {
+    steps: BTreeMap<u32, HistoryMigration>,
+}
+
+impl Default for HistoryMigrator {
+    fn default() -> Self {
+        let mut res = HistoryMigrator {
+            steps: BTreeMap::new(),
+        };
+        // unstamped histories have the version 1 key structure
+        res.register(0, Box::new(|_| Ok(())));
+        res
+    }
+}
+
+impl HistoryMigrator {
+    /// Replaces an earlier step for from_version
+    pub fn register(&mut self, from_version: u32, migration: HistoryMigration) {
+        self.steps.insert(from_version, migration);
+    }
+
+    pub fn version(history: &HashMap<String, String>) -> Result<u32, PPGEvaluatorError> {
+        Self::parse_version(history.get(HISTORY_VERSION_KEY).map(|x| x.as_str()))
+    }
+
+    /// The version from the value stored under HISTORY_VERSION_KEY
+    pub fn parse_version(raw: Option<&str>) -> Result<u32, PPGEvaluatorError> {
+        match raw {
+            None => Ok(0),
+            Some(raw) => raw
+                .parse()
+                .map_err(|_| PPGEvaluatorError::HistoryMigration {
+                    found: 0,
+                    msg: format!("unreadable schema version '{}'", raw),
+                }),
+        }
+    }
+
+    /// Bring history up to target_version. Returns the version it had.
+    pub fn migrate_to(
+        &self,
+        history: &mut HashMap<String, String>,
+        target_version: u32,
+    ) -> Result<u32, PPGEvaluatorError> {
+        let found = Self::version(history)?;
+        if found > target_version {
+            return Err(PPGEvaluatorError::HistoryMigration {
+                found,
+                msg: format!(
+                    "written by a newer pypipegraph2 (this one knows up to {})",
+                    target_version
+                ),
+            });
+        }
+        for version in found..target_version {
+            let step =
+                self.steps
+                    .get(&version)
+                    .ok_or_else(|| PPGEvaluatorError::HistoryMigration {
+                        found,
+                        msg: format!("no migration registered from version {}", version),
+                    })?;
+            step(history).map_err(|msg| PPGEvaluatorError::HistoryMigration {
+                found,
+                msg: format!("migration from version {} failed: {}", version, msg),
+            })?;
+            history.insert(HISTORY_VERSION_KEY.to_string(), (version + 1).to_string());
+        }
+        Ok(found)
+    }
+
+    pub fn migrate(&self, history: &mut HashMap<String, String>) -> Result<u32, PPGEvaluatorError> {
+        self.migrate_to(history, HISTORY_SCHEMA_VERSION)
+    }
+}
+
+pub const PPG1_FORMAT: &str = "ppg1-history";
+pub const PPG1_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+struct Ppg1Dump {
+    format: String,
+    version: u32,
+    jobs: BTreeMap<String, Ppg1Job>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ppg1Job {
+    value: Option<String>,
+    #[serde(default)]
+    upstreams: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ppg1Import {
+    pub history: HashMap<String, String>,
+    pub jobs_imported: usize,
+    /// (upstream, downstream) edges whose upstream had no value -
+    /// these downstreams will be seen as invalidated and rebuild
+    pub missing_upstreams: Vec<(String, String)>,
+}
+
+/// Convert the intermediate json (see module comment) into ppg2 history keys
+pub fn import_ppg1_history(dump: &str) -> Result<Ppg1Import, String> {
+    let dump: Ppg1Dump =
+        serde_json::from_str(dump).map_err(|e| format!("Invalid ppg1 history dump: {}", e))?;
+    if dump.format != PPG1_FORMAT || dump.version != PPG1_FORMAT_VERSION {
+        return Err(format!(
+            "Unsupported ppg1 history dump {} version {} - expected {} version {}",
+            dump.format, dump.version, PPG1_FORMAT, PPG1_FORMAT_VERSION
+        ));
+    }
+    let mut res = Ppg1Import::default();
+    for (job_id, job) in dump.jobs.iter() {
+        if job_id.contains("!!!") {
+            return Err(format!("Job id {} contains the reserved '!!!'", job_id));
+        }
+        if let Some(value) = &job.value {
+            res.history.insert(job_id.clone(), value.clone());
+            res.jobs_imported += 1;
+        }
+        let mut upstreams = job.upstreams.clone();
+        upstreams.sort();
+        upstreams.dedup();
+        for upstream in upstreams.iter() {
+            match dump.jobs.get(upstream).and_then(|u| u.value.as_ref()) {
+                Some(value) => {
+                    res.history
+                        .insert(format!("{}!!!{}", upstream, job_id), value.clone());
+                }
+                None => res
+                    .missing_upstreams
+                    .push((upstream.clone(), job_id.clone())),
+            }
+        }
+        res.history
+            .insert(format!("{}!!!", job_id), upstreams.join("\n"));
+    }
+    Ok(res)
+}
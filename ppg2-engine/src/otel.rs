@@ -0,0 +1,163 @@
+// OpenTelemetry trace of a finished run, as OTLP/JSON (feature 'otel').
+//
+// One root span for the run, one child span per executed job, linked to
+// the spans of the upstream jobs it depended on. The output is an
+// ExportTraceServiceRequest - POST it to a collector's /v1/traces or drop
+// it where the otlpjsonfile receiver picks it up, and Jaeger / Tempo show
+// the run next to the service traces.
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use xxhash_rust::xxh3::{xxh3_128, xxh3_64};
+
+use crate::engine::{JobRecord, PPGEvaluator};
+use crate::graph::Direction;
+use crate::PPGEvaluatorStrategy;
+
+// OTLP span kind / status codes
+const SPAN_KIND_INTERNAL: u32 = 1;
+const STATUS_OK: u32 = 1;
+const STATUS_ERROR: u32 = 2;
+
+fn nanos(seconds: f64) -> String {
+    // OTLP/JSON wants 64 bit integers as strings
+    format!("{}", (seconds * 1e9) as u64)
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    let value = match value {
+        Value::Number(n) if n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n }),
+        other => {
+            json!({ "stringValue": other.as_str().map(|x| x.to_string()).unwrap_or_default() })
+        }
+    };
+    json!({"key": key, "value": value})
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    fn job_span_id(trace_id: &str, job_id: &str) -> String {
+        format!(
+            "{:016x}",
+            xxh3_64(format!("{}{}", trace_id, job_id).as_bytes())
+        )
+    }
+
+    fn job_span(&self, trace_id: &str, run_span_id: &str, record: &JobRecord) -> Value {
+        let node_idx = self.job_id_to_node_idx[&record.job_id];
+        let links: Vec<Value> = self
+            .dag
+            .neighbors_directed(node_idx, Direction::Incoming)
+            .filter(|idx| self.jobs[*idx].started_at.is_some())
+            .map(|idx| {
+                json!({
+                    "traceId": trace_id,
+                    "spanId": Self::job_span_id(trace_id, &self.jobs[idx].job_id),
+                })
+            })
+            .collect();
+        let mut attributes = vec![
+            attribute("ppg2.job_id", json!(record.job_id)),
+            attribute("ppg2.kind", json!(record.kind)),
+            attribute("ppg2.state", json!(record.state)),
+        ];
+        if let Some(attempt) = record.attempt {
+            attributes.push(attribute("ppg2.attempt", json!(attempt)));
+        }
+        if let Some(reason) = &record.invalidation_reason {
+            attributes.push(attribute("ppg2.invalidation_reason", json!(reason)));
+        }
+        let start = record.start.unwrap_or_default();
+        let status = if record.state == "failed" {
+            json!({
+                "code": STATUS_ERROR,
+                "message": self.jobs[node_idx].error.clone().unwrap_or_default(),
+            })
+        } else {
+            json!({ "code": STATUS_OK })
+        };
+        json!({
+            "traceId": trace_id,
+            "spanId": Self::job_span_id(trace_id, &record.job_id),
+            "parentSpanId": run_span_id,
+            "name": record.job_id,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": nanos(start),
+            // still running jobs end now
+            "endTimeUnixNano": nanos(record.end.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|x| x.as_secs_f64())
+                    .unwrap_or(start)
+            })),
+            "attributes": attributes,
+            "links": links,
+            "status": status,
+        })
+    }
+
+    /// The run as an OTLP/JSON ExportTraceServiceRequest
+    pub fn otel_trace(&self, service_name: &str) -> Value {
+        let records: Vec<JobRecord> = self
+            .run_records()
+            .into_iter()
+            .filter(|record| record.start.is_some())
+            .collect();
+        let run_start = records
+            .iter()
+            .filter_map(|x| x.start)
+            .fold(f64::INFINITY, f64::min);
+        let run_end = records
+            .iter()
+            .filter_map(|x| x.end.or(x.start))
+            .fold(f64::NEG_INFINITY, f64::max);
+        let (run_start, run_end) = if records.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (run_start, run_end)
+        };
+        let trace_id = format!(
+            "{:032x}",
+            xxh3_128(format!("{}{:?}", service_name, SystemTime::now()).as_bytes())
+        );
+        let run_span_id = Self::job_span_id(&trace_id, "!!!run");
+        let mut spans = vec![json!({
+            "traceId": trace_id,
+            "spanId": run_span_id,
+            "name": "pypipegraph2 run",
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": nanos(run_start),
+            "endTimeUnixNano": nanos(run_end),
+            "attributes": [
+                attribute("ppg2.jobs", json!(self.jobs.len())),
+                attribute("ppg2.executed", json!(records.len())),
+            ],
+            "status": {
+                "code": if self.query_failed().is_empty() { STATUS_OK } else { STATUS_ERROR }
+            },
+        })];
+        for record in records.iter() {
+            spans.push(self.job_span(&trace_id, &run_span_id, record));
+        }
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [attribute("service.name", json!(service_name))],
+                },
+                "scopeSpans": [{
+                    "scope": {"name": "pypipegraph2", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    pub fn write_otel_trace(
+        &self,
+        path: impl AsRef<Path>,
+        service_name: &str,
+    ) -> std::io::Result<()> {
+        std::fs::write(path, self.otel_trace(service_name).to_string())
+    }
+}
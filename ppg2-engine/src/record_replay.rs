@@ -0,0 +1,272 @@
+// Record / replay of strategy decisions for deterministic debugging.
+//
+// StrategyRecorder wraps a real strategy and appends every query and its
+// answer as one json line: {"query": ..., "args": [...], "answer"|"error": ...}.
+// StrategyReplay serves the answers from such a recording back,
+// so invalidation bugs can be reproduced without the user's filesystem.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::warn;
+use serde_json::{json, Value};
+
+use crate::engine::{GraphType, NodeIndex, NodeInfo};
+use crate::{PPGEvaluatorStrategy, StrategyError};
+
+const OUTPUT_ALREADY_PRESENT: &str = "output_already_present";
+const IS_HISTORY_ALTERED: &str = "is_history_altered";
+const GET_INPUT_LIST: &str = "get_input_list";
+const SHOULD_RUN: &str = "should_run";
+
+/// The json lines writer shared by StrategyRecorder and the python strategy.
+/// Send + Sync, so the strategy holding it may move between threads.
+#[derive(Debug)]
+pub struct Recording {
+    out: Mutex<File>,
+}
+
+impl Recording {
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Recording {
+            out: Mutex::new(File::create(path)?),
+        })
+    }
+
+    fn record<T: Into<Value> + Clone>(
+        &self,
+        query: &str,
+        args: &[&str],
+        answer: &Result<T, StrategyError>,
+    ) {
+        let line = match answer {
+            Ok(answer) => json!({"query": query, "args": args, "answer": answer.clone().into()}),
+            Err(e) => json!({"query": query, "args": args, "error": e.to_string()}),
+        };
+        // written unbuffered - the recording is most useful when the run crashed
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(out, "{}", line) {
+            warn!("Could not write strategy recording: {}", e);
+        }
+    }
+
+    pub fn output_already_present(&self, query: &str, answer: &Result<bool, StrategyError>) {
+        self.record(OUTPUT_ALREADY_PRESENT, &[query], answer)
+    }
+
+    pub fn is_history_altered(&self, args: [&str; 4], answer: &Result<bool, StrategyError>) {
+        self.record(IS_HISTORY_ALTERED, &args, answer)
+    }
+
+    pub fn get_input_list(&self, job_id: &str, answer: &Result<String, StrategyError>) {
+        self.record(GET_INPUT_LIST, &[job_id], answer)
+    }
+
+    pub fn should_run(&self, job_id: &str, answer: &Result<bool, StrategyError>) {
+        self.record(SHOULD_RUN, &[job_id], answer)
+    }
+}
+
+/// Wrap a strategy, recording every query it answers.
+pub struct StrategyRecorder<S: PPGEvaluatorStrategy> {
+    pub inner: S,
+    recording: Recording,
+}
+
+impl<S: PPGEvaluatorStrategy> StrategyRecorder<S> {
+    pub fn new(inner: S, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(StrategyRecorder {
+            inner,
+            recording: Recording::create(path)?,
+        })
+    }
+}
+
+impl<S: PPGEvaluatorStrategy> PPGEvaluatorStrategy for StrategyRecorder<S> {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError> {
+        let res = self.inner.output_already_present(query);
+        self.recording.output_already_present(query, &res);
+        res
+    }
+
+    fn is_history_altered(
+        &self,
+        job_id_upstream: &str,
+        job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError> {
+        let res = self.inner.is_history_altered(
+            job_id_upstream,
+            job_id_downstream,
+            last_recorded_value,
+            current_value,
+        );
+        self.recording.is_history_altered(
+            [
+                job_id_upstream,
+                job_id_downstream,
+                last_recorded_value,
+                current_value,
+            ],
+            &res,
+        );
+        res
+    }
+
+    fn get_input_list(
+        &self,
+        node_idx: NodeIndex,
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Result<String, StrategyError> {
+        let res = self.inner.get_input_list(node_idx, dag, jobs);
+        self.recording
+            .get_input_list(jobs[node_idx].get_job_id(), &res);
+        res
+    }
+
+    fn outputs_already_present(&self, queries: &[&str]) -> Vec<Result<bool, StrategyError>> {
+        let res = self.inner.outputs_already_present(queries);
+        for (query, present) in queries.iter().zip(res.iter()) {
+            self.recording.output_already_present(query, present);
+        }
+        res
+    }
+
+    fn get_input_lists(
+        &self,
+        node_idxs: &[NodeIndex],
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Vec<Result<String, StrategyError>> {
+        let res = self.inner.get_input_lists(node_idxs, dag, jobs);
+        for (node_idx, input_list) in node_idxs.iter().zip(res.iter()) {
+            self.recording
+                .get_input_list(jobs[*node_idx].get_job_id(), input_list);
+        }
+        res
+    }
+    // not recorded - replays don't reconstruct, StrategyReplay answers None
+    fn current_history(&self, job_id: &str) -> Result<Option<String>, StrategyError> {
+        self.inner.current_history(job_id)
+    }
+
+    fn should_run(&self, job_id: &str) -> Result<bool, StrategyError> {
+        let res = self.inner.should_run(job_id);
+        self.recording.should_run(job_id, &res);
+        res
+    }
+}
+
+type RecordedAnswer = Result<Value, String>;
+
+/// Answer queries from a recording made by StrategyRecorder.
+/// Queries that were not recorded are an error - the replayed run diverged.
+#[derive(Debug, Default)]
+pub struct StrategyReplay {
+    answers: HashMap<(String, Vec<String>), RecordedAnswer>,
+}
+
+impl StrategyReplay {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(recording: &str) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+        let mut answers = HashMap::new();
+        for (line_no, line) in recording.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Value = serde_json::from_str(line)
+                .map_err(|e| invalid(format!("line {}: {}", line_no + 1, e)))?;
+            let query = record["query"]
+                .as_str()
+                .ok_or_else(|| invalid(format!("line {}: no query", line_no + 1)))?;
+            let args: Option<Vec<String>> = record["args"].as_array().and_then(|args| {
+                args.iter()
+                    .map(|x| x.as_str().map(|x| x.to_string()))
+                    .collect()
+            });
+            let args = args.ok_or_else(|| invalid(format!("line {}: no args", line_no + 1)))?;
+            let answer = match (&record.get("answer"), &record.get("error")) {
+                (Some(answer), _) => Ok((*answer).clone()),
+                (None, Some(Value::String(error))) => Err(error.to_string()),
+                _ => return Err(invalid(format!("line {}: no answer", line_no + 1))),
+            };
+            answers.insert((query.to_string(), args), answer);
+        }
+        Ok(StrategyReplay { answers })
+    }
+
+    fn answer(&self, query: &str, args: &[&str]) -> Result<&Value, StrategyError> {
+        let key = (
+            query.to_string(),
+            args.iter().map(|x| x.to_string()).collect(),
+        );
+        match self.answers.get(&key) {
+            Some(Ok(answer)) => Ok(answer),
+            Some(Err(error)) => Err(StrategyError::Replay(format!("recorded error: {}", error))),
+            None => Err(StrategyError::Replay(format!(
+                "{}({:?}) was not recorded",
+                query, args
+            ))),
+        }
+    }
+
+    fn bool_answer(&self, query: &str, args: &[&str]) -> Result<bool, StrategyError> {
+        self.answer(query, args)?.as_bool().ok_or_else(|| {
+            StrategyError::Replay(format!("{}({:?}) recorded a non-bool", query, args))
+        })
+    }
+}
+
+impl PPGEvaluatorStrategy for StrategyReplay {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError> {
+        self.bool_answer(OUTPUT_ALREADY_PRESENT, &[query])
+    }
+
+    fn is_history_altered(
+        &self,
+        job_id_upstream: &str,
+        job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError> {
+        self.bool_answer(
+            IS_HISTORY_ALTERED,
+            &[
+                job_id_upstream,
+                job_id_downstream,
+                last_recorded_value,
+                current_value,
+            ],
+        )
+    }
+
+    fn get_input_list(
+        &self,
+        node_idx: NodeIndex,
+        _dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Result<String, StrategyError> {
+        let job_id = jobs[node_idx].get_job_id();
+        self.answer(GET_INPUT_LIST, &[job_id])?
+            .as_str()
+            .map(|x| x.to_string())
+            .ok_or_else(|| {
+                StrategyError::Replay(format!(
+                    "{}({:?}) recorded a non-string",
+                    GET_INPUT_LIST, job_id
+                ))
+            })
+    }
+
+    fn should_run(&self, job_id: &str) -> Result<bool, StrategyError> {
+        self.bool_answer(SHOULD_RUN, &[job_id])
+    }
+}
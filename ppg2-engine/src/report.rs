@@ -0,0 +1,281 @@
+// Self contained html report of an evaluator run.
+use std::path::Path;
+
+use crate::engine::{
+    JobKind, JobState, JobStateAlways, JobStateEphemeral, JobStateOutput, NodeIndex, PPGEvaluator,
+    Required,
+};
+use crate::graph::Direction;
+use crate::PPGEvaluatorStrategy;
+
+const SLOWEST_JOB_COUNT: usize = 20;
+
+pub(crate) fn outcome(state: &JobState) -> &'static str {
+    match state {
+        JobState::Always(JobStateAlways::FinishedSuccess)
+        | JobState::Output(JobStateOutput::FinishedSuccess)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedSuccessNotReadyForCleanup)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedSuccessReadyForCleanup)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedSuccessCleanedUp)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedSuccessSkipCleanup) => "success",
+        JobState::Always(JobStateAlways::FinishedFailure)
+        | JobState::Output(JobStateOutput::FinishedFailure)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedFailure) => "failed",
+        JobState::Always(JobStateAlways::FinishedUpstreamFailure)
+        | JobState::Output(JobStateOutput::FinishedUpstreamFailure)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedUpstreamFailure) => "upstream failed",
+        JobState::Output(JobStateOutput::FinishedSkipped)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedSkipped) => "skipped",
+        JobState::Always(JobStateAlways::FinishedAborted)
+        | JobState::Output(JobStateOutput::FinishedAborted)
+        | JobState::Ephemeral(JobStateEphemeral::FinishedAborted) => "aborted",
+        _ => "unfinished",
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn dot_quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+fn anchor(idx: NodeIndex) -> String {
+    format!("job_{}", idx)
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    pub(crate) fn invalidating_upstreams(&self, node_idx: NodeIndex) -> Vec<NodeIndex> {
+        let mut res: Vec<NodeIndex> = self
+            .dag
+            .edges_directed(node_idx, Direction::Incoming)
+            .filter(|(_, _, weight)| weight.invalidated == Required::Yes)
+            .map(|(upstream_idx, _, _)| upstream_idx)
+            .collect();
+        res.sort_by(|a, b| self.jobs[*a].job_id.cmp(&self.jobs[*b].job_id));
+        res
+    }
+
+    /// follow the (first) invalidating upstream until we reach a job that was
+    /// not invalidated by an upstream -> the root cause
+    fn invalidation_chain(&self, node_idx: NodeIndex) -> Vec<NodeIndex> {
+        let mut chain = vec![node_idx];
+        let mut current = node_idx;
+        while let Some(upstream_idx) = self.invalidating_upstreams(current).first() {
+            if chain.contains(upstream_idx) {
+                break;
+            }
+            chain.push(*upstream_idx);
+            current = *upstream_idx;
+        }
+        chain
+    }
+
+    /// Render a self contained html report:
+    /// summary counts, failed jobs with their error payloads,
+    /// slowest jobs, invalidation chains, ignored changes and the executed subgraph.
+    pub fn render_report(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>pypipegraph2 run report</title>\n");
+        out.push_str("<style>body{font-family:sans-serif} table{border-collapse:collapse} td,th{border:1px solid #ccc;padding:2px 6px} pre{background:#f4f4f4;padding:4px} .failed{color:#b00}</style>\n");
+        out.push_str("</head><body>\n<h1>pypipegraph2 run report</h1>\n");
+
+        // summary
+        let categories = [
+            "success",
+            "failed",
+            "upstream failed",
+            "skipped",
+            "aborted",
+            "unfinished",
+        ];
+        out.push_str("<h2>Summary</h2>\n<table>\n");
+        out.push_str(&format!(
+            "<tr><th>total jobs</th><td>{}</td></tr>\n",
+            self.jobs.len()
+        ));
+        let executed = self.jobs.iter().filter(|j| j.started_at.is_some()).count();
+        out.push_str(&format!(
+            "<tr><th>executed</th><td>{}</td></tr>\n",
+            executed
+        ));
+        for category in categories.iter() {
+            let count = self
+                .jobs
+                .iter()
+                .filter(|j| outcome(&j.state) == *category)
+                .count();
+            out.push_str(&format!(
+                "<tr><th>{}</th><td>{}</td></tr>\n",
+                category, count
+            ));
+        }
+        out.push_str("</table>\n");
+
+        // failures
+        let mut failed: Vec<NodeIndex> = (0..self.jobs.len())
+            .filter(|idx| outcome(&self.jobs[*idx].state) == "failed")
+            .collect();
+        failed.sort_by(|a, b| self.jobs[*a].job_id.cmp(&self.jobs[*b].job_id));
+        out.push_str(&format!("<h2>Failed jobs ({})</h2>\n", failed.len()));
+        for idx in failed.iter() {
+            let job = &self.jobs[*idx];
+            out.push_str(&format!(
+                "<details class=\"failed\"><summary><a href=\"#{}\">{}</a></summary>\n<pre>{}</pre></details>\n",
+                anchor(*idx),
+                escape(&job.job_id),
+                escape(job.error.as_deref().unwrap_or("(no error payload recorded)"))
+            ));
+            if let Some(metadata) = &job.metadata {
+                out.push_str(&format!(
+                    "<pre>{}</pre>
+",
+                    escape(metadata)
+                ));
+            }
+        }
+
+        // slowest
+        let mut timed: Vec<NodeIndex> = (0..self.jobs.len())
+            .filter(|idx| self.jobs[*idx].runtime.is_some())
+            .collect();
+        timed.sort_by(|a, b| self.jobs[*b].runtime.cmp(&self.jobs[*a].runtime));
+        out.push_str("<h2>Slowest jobs</h2>\n<table><tr><th>job</th><th>runtime (s)</th></tr>\n");
+        for idx in timed.iter().take(SLOWEST_JOB_COUNT) {
+            out.push_str(&format!(
+                "<tr><td><a href=\"#{}\">{}</a></td><td>{:.3}</td></tr>\n",
+                anchor(*idx),
+                escape(&self.jobs[*idx].job_id),
+                self.jobs[*idx].runtime.unwrap().as_secs_f64()
+            ));
+        }
+        out.push_str("</table>\n");
+
+        // invalidation chains
+        out.push_str("<h2>Invalidation chains</h2>\n<ul>\n");
+        for idx in 0..self.jobs.len() {
+            if self.jobs[idx].started_at.is_none() || self.invalidating_upstreams(idx).is_empty() {
+                continue;
+            }
+            let chain: Vec<String> = self
+                .invalidation_chain(idx)
+                .iter()
+                .map(|x| escape(&self.jobs[*x].job_id))
+                .collect();
+            out.push_str(&format!("<li>{}</li>\n", chain.join(" &larr; ")));
+        }
+        out.push_str("</ul>\n");
+
+        // ignore_changes
+        let ignored = self.query_ignored_changes();
+        if !ignored.is_empty() {
+            out.push_str(&format!(
+                "<h2>Ignored changes ({})</h2>\n<ul>\n",
+                ignored.len()
+            ));
+            for key in ignored.iter() {
+                out.push_str(&format!("<li>{}</li>\n", escape(key)));
+            }
+            out.push_str("</ul>\n");
+        }
+
+        // executed subgraph
+        out.push_str("<h2>Executed subgraph</h2>\n");
+        let mut executed: Vec<NodeIndex> = (0..self.jobs.len())
+            .filter(|idx| self.jobs[*idx].started_at.is_some())
+            .collect();
+        executed.sort_by(|a, b| self.jobs[*a].job_id.cmp(&self.jobs[*b].job_id));
+        for idx in executed.iter() {
+            let job = &self.jobs[*idx];
+            out.push_str(&format!(
+                "<details id=\"{}\"><summary>{} ({:?}) - {}</summary>\n",
+                anchor(*idx),
+                escape(&job.job_id),
+                job.kind(),
+                outcome(&job.state)
+            ));
+            for (direction, label) in [
+                (Direction::Incoming, "upstreams"),
+                (Direction::Outgoing, "downstreams"),
+            ]
+            .iter()
+            {
+                let mut neighbours: Vec<NodeIndex> = self
+                    .dag
+                    .neighbors_directed(*idx, *direction)
+                    .filter(|other| self.jobs[*other].started_at.is_some())
+                    .collect();
+                neighbours.sort_by(|a, b| self.jobs[*a].job_id.cmp(&self.jobs[*b].job_id));
+                if neighbours.is_empty() {
+                    continue;
+                }
+                out.push_str(&format!("{}:<ul>\n", label));
+                for other in neighbours {
+                    out.push_str(&format!(
+                        "<li><a href=\"#{}\">{}</a></li>\n",
+                        anchor(other),
+                        escape(&self.jobs[other].job_id)
+                    ));
+                }
+                out.push_str("</ul>\n");
+            }
+            out.push_str("</details>\n");
+        }
+
+        out.push_str("</body></html>\n");
+        out
+    }
+
+    pub fn write_report(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.render_report())
+    }
+
+    /// The graph in graphviz' dot format - job metadata as tooltip.
+    /// Pruned ephemerals are left out.
+    pub fn render_dot(&self) -> String {
+        let mut out = String::from("digraph pypipegraph2 {\n");
+        for idx in self.dag.nodes() {
+            let job = &self.jobs[idx];
+            let tooltip = match &job.metadata {
+                Some(metadata) => format!(", tooltip={}", dot_quote(metadata)),
+                None => String::new(),
+            };
+            out.push_str(&format!(
+                "  n{} [label={}, shape={}{}];\n",
+                idx,
+                dot_quote(&job.job_id),
+                match job.kind() {
+                    JobKind::Output => "box",
+                    JobKind::Ephemeral | JobKind::TempFile => "ellipse",
+                    JobKind::Invariant => "diamond",
+                    _ => "octagon",
+                },
+                tooltip
+            ));
+        }
+        let mut edges: Vec<(NodeIndex, NodeIndex)> =
+            self.dag.all_edges().map(|(a, b, _)| (a, b)).collect();
+        edges.sort_unstable();
+        for (a, b) in edges {
+            out.push_str(&format!("  n{} -> n{};\n", a, b));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
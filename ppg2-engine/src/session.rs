@@ -0,0 +1,189 @@
+// Several runs of (possibly changing) graphs against one history.
+//
+// Session owns where the history lives, the strategy and the evaluator
+// configuration. run loads the history, builds a configured evaluator,
+// lets graph_builder add the jobs, starts it, hands it to the executor
+// (which drives the events until it's finished) and stores the new history.
+// If anything fails on the way, the history is left as it was.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use thiserror::Error;
+use tracing::level_filters::LevelFilter;
+
+use crate::engine::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy, RunError, SharedHistory};
+
+/// Why a HistoryBackend could not load / store the history
+#[derive(Error, Debug)]
+pub enum HistoryStorageError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid json: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// Where a Session's history is loaded from and stored to
+pub trait HistoryBackend {
+    fn load(&mut self) -> Result<HashMap<String, String>, PPGEvaluatorError>;
+    fn store(&mut self, history: &HashMap<String, String>) -> Result<(), PPGEvaluatorError>;
+}
+
+/// In memory - tests, and callers persisting it themselves
+impl HistoryBackend for HashMap<String, String> {
+    fn load(&mut self) -> Result<HashMap<String, String>, PPGEvaluatorError> {
+        Ok(self.clone())
+    }
+
+    fn store(&mut self, history: &HashMap<String, String>) -> Result<(), PPGEvaluatorError> {
+        self.clone_from(history);
+        Ok(())
+    }
+}
+
+/// A plain json file. A missing file is an empty history.
+#[derive(Debug, Clone)]
+pub struct JsonFileHistory {
+    path: PathBuf,
+}
+
+impl JsonFileHistory {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        JsonFileHistory {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+fn storage_error(path: &Path, source: impl Into<HistoryStorageError>) -> PPGEvaluatorError {
+    PPGEvaluatorError::HistoryStorage {
+        path: path.display().to_string(),
+        source: source.into(),
+    }
+}
+
+impl HistoryBackend for JsonFileHistory {
+    fn load(&mut self) -> Result<HashMap<String, String>, PPGEvaluatorError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(raw) => serde_json::from_str(&raw).map_err(|e| storage_error(&self.path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(storage_error(&self.path, e)),
+        }
+    }
+
+    fn store(&mut self, history: &HashMap<String, String>) -> Result<(), PPGEvaluatorError> {
+        // readers never see half a file
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let raw = serde_json::to_string(history).map_err(|e| storage_error(&self.path, e))?;
+        std::fs::write(&tmp, raw).map_err(|e| storage_error(&tmp, e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| storage_error(&self.path, e))
+    }
+}
+
+/// Conflicts with other processes are resolved by SharedHistory and logged
+impl HistoryBackend for SharedHistory {
+    fn load(&mut self) -> Result<HashMap<String, String>, PPGEvaluatorError> {
+        Ok(self.history())
+    }
+
+    fn store(&mut self, history: &HashMap<String, String>) -> Result<(), PPGEvaluatorError> {
+        let conflicts = SharedHistory::store(self, history)
+            .map_err(|e| storage_error(Path::new("shared history"), e))?;
+        for conflict in conflicts {
+            warn!(
+                "History conflict on {}: {}",
+                conflict.key,
+                if conflict.kept_ours {
+                    "kept ours"
+                } else {
+                    "kept theirs"
+                }
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Applied to each run's evaluator before the graph is built
+#[derive(Debug, Clone, Default)]
+pub struct SessionConfig {
+    pub log_level: Option<LevelFilter>,
+    pub force_rerun_all: bool,
+    pub tolerate_duplicate_events: bool,
+    pub interactive: bool,
+}
+
+pub struct Session<T: PPGEvaluatorStrategy + Clone, H: HistoryBackend> {
+    backend: H,
+    strategy: T,
+    pub config: SessionConfig,
+    runs: usize,
+}
+
+impl<T: PPGEvaluatorStrategy + Clone, H: HistoryBackend> Session<T, H> {
+    /// Every run gets a clone of strategy
+    pub fn new(backend: H, strategy: T) -> Self {
+        Self::with_config(backend, strategy, SessionConfig::default())
+    }
+
+    pub fn with_config(backend: H, strategy: T, config: SessionConfig) -> Self {
+        Session {
+            backend,
+            strategy,
+            config,
+            runs: 0,
+        }
+    }
+
+    pub fn backend(&self) -> &H {
+        &self.backend
+    }
+
+    pub fn into_backend(self) -> H {
+        self.backend
+    }
+
+    /// Runs with their history stored so far
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn run<B, E>(
+        &mut self,
+        graph_builder: B,
+        executor: E,
+    ) -> Result<PPGEvaluator<T>, RunError<T>>
+    where
+        B: FnOnce(&mut PPGEvaluator<T>),
+        E: FnOnce(&mut PPGEvaluator<T>) -> Result<(), PPGEvaluatorError>,
+    {
+        let history = match self.backend.load() {
+            Ok(history) => history,
+            Err(e) => return Err(RunError(PPGEvaluator::new(self.strategy.clone()), e)),
+        };
+        let mut g = PPGEvaluator::new_with_history(history, self.strategy.clone());
+        if let Some(level) = self.config.log_level {
+            g.set_log_level(level);
+        }
+        g.set_force_rerun_all(self.config.force_rerun_all);
+        g.set_tolerate_duplicate_events(self.config.tolerate_duplicate_events);
+        g.set_interactive(self.config.interactive);
+        graph_builder(&mut g);
+        let res = g
+            .event_startup()
+            .and_then(|_| executor(&mut g))
+            .and_then(|_| g.new_history())
+            .and_then(|history| self.backend.store(&history));
+        match res {
+            Ok(()) => {
+                self.runs += 1;
+                Ok(g)
+            }
+            Err(e) => Err(RunError(g, e)),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// A history file shared by several pipeline processes.
+//
+// Plain history files are last-writer-wins: two processes that loaded the
+// same history each write back their view, and one loses its updates.
+// SharedHistory remembers, per key, the run that wrote it. store only
+// writes the keys this run changed, merged into what's on disk under an
+// advisory lock ('<path>.lock', created exclusively). If another run changed
+// a key meanwhile as well, the later run id wins and a HistoryConflict is
+// reported.
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    value: String,
+    run_id: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    entries: HashMap<String, Entry>,
+}
+
+/// A key both this run and another one changed since this run loaded the history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryConflict {
+    pub key: String,
+    /// None: removed
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    /// ours had the later run id
+    pub kept_ours: bool,
+}
+
+struct LockGuard {
+    path: PathBuf,
+}
+
+impl LockGuard {
+    fn acquire(path: PathBuf, timeout: Duration) -> std::io::Result<Self> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(LockGuard { path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if Instant::now() >= deadline {
+                        return Err(Error::new(
+                            ErrorKind::TimedOut,
+                            format!(
+                                "History is locked by another process - remove {} if it's stale",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    sleep(LOCK_POLL_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Debug)]
+pub struct SharedHistory {
+    path: PathBuf,
+    lock_timeout: Duration,
+    run_id: u64,
+    loaded: HashMap<String, Entry>,
+}
+
+impl SharedHistory {
+    /// Load path (missing = empty) and start a new run
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::open_with_lock_timeout(path, DEFAULT_LOCK_TIMEOUT)
+    }
+
+    pub fn open_with_lock_timeout(
+        path: impl AsRef<Path>,
+        lock_timeout: Duration,
+    ) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let loaded = {
+            let _lock = LockGuard::acquire(Self::lock_path(&path), lock_timeout)?;
+            Self::read(&path)?.entries
+        };
+        // later runs get larger ids - also when the clocks are off a bit
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let newest = loaded.values().map(|e| e.run_id).max().unwrap_or(0);
+        Ok(SharedHistory {
+            path,
+            lock_timeout,
+            run_id: now.max(newest + 1),
+            loaded,
+        })
+    }
+
+    fn lock_path(path: &Path) -> PathBuf {
+        let mut lock = path.as_os_str().to_owned();
+        lock.push(".lock");
+        PathBuf::from(lock)
+    }
+
+    fn read(path: &Path) -> std::io::Result<HistoryFile> {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw).map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(HistoryFile::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn run_id(&self) -> u64 {
+        self.run_id
+    }
+
+    /// The history as loaded - for PPGEvaluator::new_with_history
+    pub fn history(&self) -> HashMap<String, String> {
+        self.loaded
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.value.clone()))
+            .collect()
+    }
+
+    /// Merge this run's new_history into the file - see the module comment.
+    /// Afterwards, history() is the merged state.
+    pub fn store(
+        &mut self,
+        new_history: &HashMap<String, String>,
+    ) -> std::io::Result<Vec<HistoryConflict>> {
+        let _lock = LockGuard::acquire(Self::lock_path(&self.path), self.lock_timeout)?;
+        let mut on_disk = Self::read(&self.path)?;
+        let mut changes: Vec<(&String, Option<&String>)> = new_history
+            .iter()
+            .filter(|(key, value)| self.loaded.get(*key).map(|e| &e.value) != Some(*value))
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        changes.extend(
+            self.loaded
+                .keys()
+                .filter(|key| !new_history.contains_key(*key))
+                .map(|key| (key, None)),
+        );
+        changes.sort();
+
+        let mut conflicts = Vec::new();
+        for (key, ours) in changes {
+            let theirs = on_disk.entries.get(key);
+            let changed_meanwhile = theirs != self.loaded.get(key);
+            let keep_ours = !changed_meanwhile || theirs.is_none_or(|e| e.run_id < self.run_id);
+            if changed_meanwhile {
+                conflicts.push(HistoryConflict {
+                    key: key.clone(),
+                    ours: ours.cloned(),
+                    theirs: theirs.map(|e| e.value.clone()),
+                    kept_ours: keep_ours,
+                });
+            }
+            if keep_ours {
+                match ours {
+                    Some(value) => on_disk.entries.insert(
+                        key.clone(),
+                        Entry {
+                            value: value.clone(),
+                            run_id: self.run_id,
+                        },
+                    ),
+                    None => on_disk.entries.remove(key),
+                };
+            }
+        }
+        self.write(&on_disk)?;
+        self.loaded = on_disk.entries;
+        Ok(conflicts)
+    }
+
+    fn write(&self, file: &HistoryFile) -> std::io::Result<()> {
+        // readers never see half a file
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, serde_json::to_string(file)?)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
@@ -0,0 +1,114 @@
+// SLURM job array export.
+//
+// Ready jobs are mutually independent (none waits for another), so they can
+// be submitted together. plan_slurm_arrays groups them by resource shape
+// (required cores and capabilities) into batches of at most max_array_size,
+// one job array each. Array task i of a batch is the batch's job_ids[i].
+// The plan serializes to json for the submitting side;
+// event_array_task maps the array tasks' lifecycle back onto engine events.
+use serde::{Deserialize, Serialize};
+
+use crate::engine::PPGEvaluator;
+use crate::{PPGEvaluatorError, PPGEvaluatorStrategy};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ResourceShape {
+    pub cores: usize,
+    /// sorted
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArrayBatch {
+    pub shape: ResourceShape,
+    /// array task id -> job id
+    pub job_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionPlan {
+    pub batches: Vec<ArrayBatch>,
+}
+
+impl SubmissionPlan {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("plans always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn job_id(&self, batch: usize, task: usize) -> Option<&str> {
+        self.batches
+            .get(batch)
+            .and_then(|b| b.job_ids.get(task))
+            .map(|x| x.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayTaskEvent {
+    Started,
+    Success { history: String },
+    Failure { error: Option<String> },
+}
+
+impl<T: PPGEvaluatorStrategy> PPGEvaluator<T> {
+    /// The jobs ready to run (in query_ready_to_run_ordered order) as job arrays
+    pub fn plan_slurm_arrays(&self, max_array_size: usize) -> SubmissionPlan {
+        let max_array_size = max_array_size.max(1);
+        let mut by_shape: Vec<(ResourceShape, Vec<String>)> = Vec::new();
+        for job_id in self.query_ready_to_run_ordered() {
+            let shape = ResourceShape {
+                cores: self.required_cores(&job_id),
+                capabilities: self.required_capabilities(&job_id),
+            };
+            match by_shape.iter_mut().find(|(s, _)| *s == shape) {
+                Some((_, job_ids)) => job_ids.push(job_id),
+                None => by_shape.push((shape, vec![job_id])),
+            }
+        }
+        by_shape.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut batches = Vec::new();
+        for (shape, job_ids) in by_shape {
+            for chunk in job_ids.chunks(max_array_size) {
+                batches.push(ArrayBatch {
+                    shape: shape.clone(),
+                    job_ids: chunk.to_vec(),
+                });
+            }
+        }
+        SubmissionPlan { batches }
+    }
+
+    /// What happened to array task (batch, task) of plan, as engine event
+    pub fn event_array_task(
+        &mut self,
+        plan: &SubmissionPlan,
+        batch: usize,
+        task: usize,
+        event: ArrayTaskEvent,
+    ) -> Result<(), PPGEvaluatorError> {
+        let job_id = plan
+            .job_id(batch, task)
+            .ok_or_else(|| {
+                PPGEvaluatorError::APIError(format!(
+                    "No array task {} in batch {} of the plan",
+                    task, batch
+                ))
+            })?
+            .to_string();
+        self.known_idx(&job_id)?;
+        match event {
+            ArrayTaskEvent::Started => self.event_now_running(&job_id),
+            ArrayTaskEvent::Success { history } => {
+                self.event_job_finished_success(&job_id, history)
+            }
+            ArrayTaskEvent::Failure { error: Some(error) } => {
+                self.event_job_finished_failure_with_error(&job_id, error)
+            }
+            ArrayTaskEvent::Failure { error: None } => self.event_job_finished_failure(&job_id),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Random DAGs for measuring how the engine scales.
+//!
+//! [StressConfig] describes a graph - node count, edge probability, the mix
+//! of job kinds, how many jobs fail - and a seed, so the same config builds
+//! the same graph on every machine. [StressConfig::run] evaluates it twice
+//! (the second run has everything but the failures done) and reports
+//! how long each took.
+use std::time::{Duration, Instant};
+
+use crate::{JobKind, PPGEvaluator, PPGEvaluatorError, StrategyForTesting, TestGraphRunner};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressConfig {
+    pub nodes: usize,
+    /// chance of an edge between any two jobs (always from the lower to the higher index)
+    pub edge_probability: f64,
+    pub ephemeral_fraction: f64,
+    pub always_fraction: f64,
+    /// chance of a job failing
+    pub failure_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        StressConfig {
+            nodes: 1000,
+            edge_probability: 0.01,
+            ephemeral_fraction: 0.25,
+            always_fraction: 0.05,
+            failure_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// A generated graph - job ids are 'S0', 'S1', ...
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StressGraph {
+    pub jobs: Vec<(String, JobKind)>,
+    /// (downstream, upstream)
+    pub edges: Vec<(String, String)>,
+    pub failing: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StressReport {
+    pub nodes: usize,
+    pub edges: usize,
+    /// jobs run / failed in the first run
+    pub jobs_run: usize,
+    pub jobs_failed: usize,
+    pub first_run: Duration,
+    pub second_run: Duration,
+}
+
+// splitmix64 - small, seedable, and the same everywhere
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl StressConfig {
+    fn check(&self) -> Result<(), PPGEvaluatorError> {
+        let fractions = [
+            self.edge_probability,
+            self.ephemeral_fraction,
+            self.always_fraction,
+            self.failure_rate,
+            self.ephemeral_fraction + self.always_fraction,
+        ];
+        if fractions.iter().all(|x| (0.0..=1.0).contains(x)) {
+            Ok(())
+        } else {
+            Err(PPGEvaluatorError::APIError(format!(
+                "Stress config probabilities / fractions must be within 0..=1: {:?}",
+                self
+            )))
+        }
+    }
+
+    pub fn generate(&self) -> Result<StressGraph, PPGEvaluatorError> {
+        self.check()?;
+        let mut random = Random(self.seed);
+        let mut res = StressGraph {
+            jobs: Vec::new(),
+            edges: Vec::new(),
+            failing: Vec::new(),
+        };
+        for ii in 0..self.nodes {
+            let job_id = format!("S{}", ii);
+            let roll = random.next_f64();
+            let kind = if roll < self.ephemeral_fraction {
+                JobKind::Ephemeral
+            } else if roll < self.ephemeral_fraction + self.always_fraction {
+                JobKind::Always
+            } else {
+                JobKind::Output
+            };
+            for upstream in 0..ii {
+                if random.next_f64() < self.edge_probability {
+                    res.edges.push((job_id.clone(), format!("S{}", upstream)));
+                }
+            }
+            if random.next_f64() < self.failure_rate {
+                res.failing.push(job_id.clone());
+            }
+            res.jobs.push((job_id, kind));
+        }
+        Ok(res)
+    }
+
+    /// Generate and evaluate the graph twice
+    pub fn run(&self) -> Result<StressReport, PPGEvaluatorError> {
+        let graph = self.generate()?;
+        let failing: Vec<&str> = graph.failing.iter().map(|x| x.as_str()).collect();
+        let mut report = StressReport {
+            nodes: graph.jobs.len(),
+            edges: graph.edges.len(),
+            jobs_run: 0,
+            jobs_failed: 0,
+            first_run: Duration::ZERO,
+            second_run: Duration::ZERO,
+        };
+        let setup = graph.clone();
+        let mut ro = TestGraphRunner::new(Box::new(move |g| setup.build(g)));
+        ro.allowed_nesting = self.nodes as u32 + 1;
+        let started = Instant::now();
+        let g = ro.run(&failing).map_err(|e| e.1)?;
+        report.first_run = started.elapsed();
+        report.jobs_run = ro.run_order.len();
+        report.jobs_failed = g.query_failed().len();
+        let started = Instant::now();
+        ro.run(&failing).map_err(|e| e.1)?;
+        report.second_run = started.elapsed();
+        Ok(report)
+    }
+}
+
+impl StressGraph {
+    pub fn build(&self, g: &mut PPGEvaluator<StrategyForTesting>) {
+        for (job_id, kind) in self.jobs.iter() {
+            g.add_node(job_id, *kind).expect("stress job rejected");
+        }
+        for (downstream, upstream) in self.edges.iter() {
+            g.depends_on(downstream, upstream)
+                .expect("stress edge rejected");
+        }
+    }
+}
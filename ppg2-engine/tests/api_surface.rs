@@ -0,0 +1,76 @@
+// Drive the evaluator from outside the crate, with nothing but the api
+// module - if this stops compiling, the stable surface broke
+// (or api::unstable changed, which get_input_list needs).
+use std::collections::HashMap;
+
+use ppg2_engine::api::unstable::{sorted_upstream_job_ids, GraphType, NodeIndex, NodeInfo};
+use ppg2_engine::api::*;
+
+struct AllMissing;
+
+impl PPGEvaluatorStrategy for AllMissing {
+    fn output_already_present(&self, _query: &str) -> Result<bool, StrategyError> {
+        Ok(false)
+    }
+
+    fn is_history_altered(
+        &self,
+        _job_id_upstream: &str,
+        _job_id_downstream: &str,
+        last_recorded_value: &str,
+        current_value: &str,
+    ) -> Result<bool, StrategyError> {
+        Ok(last_recorded_value != current_value)
+    }
+
+    fn get_input_list(
+        &self,
+        node_idx: NodeIndex,
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Result<String, StrategyError> {
+        Ok(sorted_upstream_job_ids(node_idx, dag, jobs))
+    }
+}
+
+fn describe(kind: JobKind) -> &'static str {
+    match kind {
+        JobKind::Output => "output",
+        JobKind::Ephemeral => "ephemeral",
+        _ => "other",
+    }
+}
+
+#[test]
+fn test_strategy_from_api_only() {
+    let mut g = PPGEvaluator::new_with_history(HashMap::new(), AllMissing);
+    g.add_node("A", JobKind::Output).unwrap();
+    g.add_node("B", JobKind::Ephemeral).unwrap();
+    g.add_node("C", JobKind::Output).unwrap();
+    g.depends_on("B", "A").unwrap();
+    g.depends_on("C", "B").unwrap();
+    assert!(matches!(
+        g.depends_on("C", "nope"),
+        Err(PPGEvaluatorError::UnknownJob(_))
+    ));
+    assert_eq!(describe(JobKind::Ephemeral), "ephemeral");
+    assert_eq!(describe(JobKind::Always), "other");
+
+    g.event_startup().unwrap();
+    while !g.is_finished() {
+        let mut ready: Vec<String> = g.query_ready_to_run().into_iter().collect();
+        ready.sort();
+        assert!(!ready.is_empty());
+        for job_id in ready {
+            g.event_now_running(&job_id).unwrap();
+            g.event_job_finished_success(&job_id, format!("out_{}", job_id))
+                .unwrap();
+        }
+        for job_id in g.query_ready_for_cleanup() {
+            g.event_job_cleanup_done(&job_id).unwrap();
+        }
+    }
+    assert_eq!(g.finish_state(), FinishState::Success);
+    let history = g.new_history().unwrap();
+    assert_eq!(history.get("C").map(|x| x.as_str()), Some("out_C"));
+}
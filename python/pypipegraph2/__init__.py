@@ -45,6 +45,7 @@ from .enums import Resources, RunMode
 from . import util
 from .util import assert_uniqueness_of_object
 from .pypipegraph2 import enable_logging as enable_rust_logging
+from .pypipegraph2 import engine_info
 
 reuse_last_or_default = object()
 default = object()
@@ -230,4 +231,5 @@ __all__ = [
     "inside_ppg",
     "assert_uniqueness_of_object",
     "enable_rust_logging",
+    "engine_info",
 ]
@@ -0,0 +1,21 @@
+"""Exceptions raised by the rust evaluation engine.
+
+All but StrategyError are ValueErrors, for code that caught those before.
+"""
+from .pypipegraph2 import errors as _errors
+
+CycleError = _errors.CycleError
+JobRedefinitionError = _errors.JobRedefinitionError
+NotRunningError = _errors.NotRunningError
+ContractViolationError = _errors.ContractViolationError
+StalledError = _errors.StalledError
+StrategyError = _errors.StrategyError
+
+__all__ = [
+    "CycleError",
+    "JobRedefinitionError",
+    "NotRunningError",
+    "ContractViolationError",
+    "StalledError",
+    "StrategyError",
+]
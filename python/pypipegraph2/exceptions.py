@@ -94,3 +94,8 @@ class JobCanceled(PPGException):
 
 class HistoryLoadingFailed(FatalGraphException):
     pass
+
+
+# raised by the rust engine when its strategy could not answer a query
+# (permission denied, network filesystem timeouts...)
+from .pypipegraph2 import PPGStrategyError as StrategyError  # noqa: E402
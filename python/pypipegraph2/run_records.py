@@ -0,0 +1,54 @@
+"""Per-job run records as an Arrow table or Parquet file.
+
+Needs pyarrow (pip install pypipegraph2[arrow]).
+pandas / polars users can also pass evaluator.run_records() to their
+DataFrame constructor directly.
+"""
+
+COLUMNS = [
+    "job_id",
+    "kind",
+    "state",
+    "start",
+    "end",
+    "runtime",
+    "attempt",
+    "invalidation_reason",
+]
+
+
+def _pyarrow():
+    try:
+        import pyarrow
+    except ImportError:  # pragma: no cover
+        raise ImportError(
+            "Exporting run records needs pyarrow - pip install pypipegraph2[arrow]"
+        )
+    return pyarrow
+
+
+def to_arrow(evaluator):
+    """evaluator.run_records() as a pyarrow.Table.
+    start / end are unix timestamps in seconds, runtime is in seconds"""
+    pa = _pyarrow()
+    records = evaluator.run_records()
+    schema = pa.schema(
+        [
+            ("job_id", pa.string()),
+            ("kind", pa.string()),
+            ("state", pa.string()),
+            ("start", pa.float64()),
+            ("end", pa.float64()),
+            ("runtime", pa.float64()),
+            ("attempt", pa.uint32()),
+            ("invalidation_reason", pa.string()),
+        ]
+    )
+    return pa.table({name: records[name] for name in COLUMNS}, schema=schema)
+
+
+def write_parquet(evaluator, path):
+    _pyarrow()
+    import pyarrow.parquet
+
+    pyarrow.parquet.write_table(to_arrow(evaluator), path)
@@ -367,15 +367,20 @@ class Runner:
             history,
             self.compare_history,
             lambda job_id: self.__class__.get_job_inputs_str(self.job_graph, job_id),
+            # set to a filename to record the engine's strategy decisions for bug reports
+            os.environ.get("PYPIPEGRAPH_RECORD_STRATEGY"),
         )
         # todo: see how much we can push into rust of
         # the whole networkx business.
         # no need to keep multiple graphs, I suppose.
-        for job_id in self.dag.nodes:
-            job = self.jobs[job_id]
-            e.add_node(job_id, job.eval_job_kind)
-        for a, b in self.dag.edges:
-            e.add_edge(b, a)
+        e.load_graph(
+            {
+                "jobs": {
+                    job_id: self.jobs[job_id].eval_job_kind for job_id in self.dag.nodes
+                },
+                "edges": [(b, a) for (a, b) in self.dag.edges],
+            }
+        )
         return e
 
     def run(self, history, last_job_states, print_failures):  # noqa:C901
@@ -1,477 +1,549 @@
-#![allow(clippy::borrow_deref_ref)]
+//! The python binding of the pypipegraph2 evaluator.
+//!
+//! The evaluator itself lives in the ppg2-engine crate,
+//! this just wraps it (and a python callback strategy) for pyo3.
+#![allow(clippy::borrow_deref_ref, clippy::needless_option_as_deref)]
 #[allow(unused_imports)]
 use log::{debug, error, info, warn};
-use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::exceptions::{PyIOError, PyKeyError, PyTimeoutError, PyTypeError, PyValueError};
 use pyo3::types::PyDict;
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
-use std::io::Write;
-use std::path::Path;
-use std::rc::Rc;
-use std::sync::Once;
-
-use thiserror::Error;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use pyo3::prelude::*;
 
-mod engine;
-#[cfg(test)]
-mod tests;
+pub use ppg2_engine::*;
 
-pub use engine::{JobKind, PPGEvaluator};
+fn history_from_py(value: &PyAny) -> PyResult<String> {
+    if let Ok(bytes) = value.downcast::<pyo3::types::PyBytes>() {
+        Ok(encode_bytes_history(bytes.as_bytes()))
+    } else {
+        value
+            .extract::<&str>()
+            .map(encode_str_history)
+            .map_err(|_| PyTypeError::new_err("History values must be str or bytes"))
+    }
+}
 
-static LOGGER_INIT: Once = Once::new();
+fn history_to_py(py: Python, value: &str) -> PyObject {
+    match decode_history(value) {
+        HistoryValue::Bytes(bytes) => pyo3::types::PyBytes::new(py, &bytes).into(),
+        HistoryValue::Str(value) => value.into_py(py),
+    }
+}
 
-#[derive(Error, Debug)]
-pub enum PPGEvaluatorError {
-    #[error("API error. You're holding it wrong")]
-    APIError(String),
-    #[error("Ephemeral {job_id} was validated, but rerun for downstreams. It changed output, violating the constant input->constant output assumption. Output was \n'{last_history}' is now \n'{new_history}'. You are holding it very wrong.")]
-    EphemeralChangedOutput {
-        job_id: String,
-        last_history: String,
-        new_history: String,
-    },
-    #[error(
-        "Internal error. Something in the pipegraph2 engine is wrong. Graph execution aborted. Msg was {0}"
-    )]
-    InternalError(String),
+// the python callbacks are optional - without them, we compare
+// fingerprints / input job ids in rust (see StrategyFileSystem)
+//
+// Every call takes the GIL itself - so the evaluator holding this may run
+// with the GIL released (see apply_reported_events), on any thread.
+struct StrategyForPython {
+    history_altered_callback: Option<PyObject>,
+    get_job_inputs_str_callback: Option<PyObject>,
+    // JobKind::Conditional predicate - job_id -> bool
+    should_run_callback: Option<PyObject>,
+    recording: Option<Recording>,
 }
 
-pub trait PPGEvaluatorStrategy {
-    fn output_already_present(&self, query: &str) -> bool;
-    fn is_history_altered(
+// PyPPG2Evaluator moves between python threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+    assert_send_sync::<StrategyForPython>();
+    assert_send::<PPGEvaluator<StrategyForPython>>();
+};
+
+impl StrategyForPython {
+    fn clone_callbacks(&self, py: Python) -> Self {
+        StrategyForPython {
+            history_altered_callback: self
+                .history_altered_callback
+                .as_ref()
+                .map(|x| x.clone_ref(py)),
+            get_job_inputs_str_callback: self
+                .get_job_inputs_str_callback
+                .as_ref()
+                .map(|x| x.clone_ref(py)),
+            should_run_callback: self.should_run_callback.as_ref().map(|x| x.clone_ref(py)),
+            recording: None,
+        }
+    }
+
+    fn compare_history(
         &self,
         job_id_upstream: &str,
         job_id_downstream: &str,
         last_recorded_value: &str,
         current_value: &str,
-    ) -> bool;
-
-    fn get_input_list(
-        &self,
-        node_idx: engine::NodeIndex,
-        dag: &engine::GraphType,
-        jobs: &[engine::NodeInfo],
-    ) -> String;
-}
+    ) -> Result<bool, StrategyError> {
+        if last_recorded_value == current_value {
+            Ok(false)
+        } else if let Some(history_altered_callback) = &self.history_altered_callback {
+            Python::with_gil(|py| {
+                history_altered_callback
+                    .call1(
+                        py,
+                        (
+                            job_id_upstream,
+                            job_id_downstream,
+                            history_to_py(py, last_recorded_value),
+                            history_to_py(py, current_value),
+                        ),
+                    )
+                    .and_then(|res| res.extract::<bool>(py))
+                    .map_err(|e| {
+                        StrategyError::Callback(format!("History comparison failed: {}", e))
+                    })
+            })
+        } else {
+            Ok(fingerprints_altered(last_recorded_value, current_value))
+        }
 
-#[derive(Clone, Debug)]
-pub struct StrategyForTesting {
-    pub already_done: Rc<RefCell<HashSet<String>>>,
-}
+        //last_recorded_value != current_value // todo
+    }
 
-impl StrategyForTesting {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
-        StrategyForTesting {
-            already_done: Rc::new(RefCell::new(HashSet::new())),
-        }
+    fn input_lists(
+        &self,
+        node_idxs: &[NodeIndex],
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Vec<Result<String, StrategyError>> {
+        let get_job_inputs_str_callback = match &self.get_job_inputs_str_callback {
+            Some(callback) => callback,
+            None => {
+                return node_idxs
+                    .iter()
+                    .map(|node_idx| Ok(sorted_upstream_job_ids(*node_idx, dag, jobs)))
+                    .collect()
+            }
+        };
+        // one GIL acquisition for the whole batch
+        Python::with_gil(|py| {
+            node_idxs
+                .iter()
+                .map(|node_idx| {
+                    let job_id = jobs[*node_idx].get_job_id();
+                    get_job_inputs_str_callback
+                        .call1(py, (job_id,))
+                        .and_then(|res| res.extract::<String>(py))
+                        .map_err(|e| {
+                            StrategyError::Callback(format!(
+                                "Input list for {} failed: {}",
+                                job_id, e
+                            ))
+                        })
+                })
+                .collect()
+        })
     }
 }
 
-impl PPGEvaluatorStrategy for StrategyForTesting {
-    fn output_already_present(&self, query: &str) -> bool {
-        self.already_done.borrow().contains(query)
+impl PPGEvaluatorStrategy for StrategyForPython {
+    fn output_already_present(&self, query: &str) -> Result<bool, StrategyError> {
+        // support for multi file generating jobs
+        let res = all_paths_present(query);
+        if let Some(recording) = &self.recording {
+            recording.output_already_present(query, &res);
+        }
+        res
     }
 
     fn is_history_altered(
         &self,
-        _job_id_upstream: &str,
-        _job_id_downstream: &str,
+        job_id_upstream: &str,
+        job_id_downstream: &str,
         last_recorded_value: &str,
         current_value: &str,
-    ) -> bool {
-        last_recorded_value != current_value
+    ) -> Result<bool, StrategyError> {
+        let res = self.compare_history(
+            job_id_upstream,
+            job_id_downstream,
+            last_recorded_value,
+            current_value,
+        );
+        if let Some(recording) = &self.recording {
+            recording.is_history_altered(
+                [
+                    job_id_upstream,
+                    job_id_downstream,
+                    last_recorded_value,
+                    current_value,
+                ],
+                &res,
+            );
+        }
+        res
     }
 
     fn get_input_list(
         &self,
-        node_idx: engine::NodeIndex,
-        dag: &engine::GraphType,
-        jobs: &[engine::NodeInfo],
-    ) -> String {
-        let mut names = Vec::new();
-        let upstreams = dag.neighbors_directed(node_idx, petgraph::Direction::Incoming);
-        for upstream_idx in upstreams {
-            names.push(jobs[upstream_idx].get_job_id());
+        node_idx: NodeIndex,
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Result<String, StrategyError> {
+        self.get_input_lists(&[node_idx], dag, jobs).remove(0)
+    }
+
+    fn outputs_already_present(&self, queries: &[&str]) -> Vec<Result<bool, StrategyError>> {
+        let res = all_paths_present_parallel(queries);
+        if let Some(recording) = &self.recording {
+            for (query, present) in queries.iter().zip(res.iter()) {
+                recording.output_already_present(query, present);
+            }
         }
-        names.sort();
-        names.join("\n")
-    }
-}
-
-pub fn start_logging() {
-    let start_time = std::time::Instant::now();
-    if !LOGGER_INIT.is_completed() {
-        LOGGER_INIT.call_once(move || {
-            use colored::Colorize;
-            let start_time2 = start_time;
-            env_logger::builder()
-                .filter_level(log::LevelFilter::Debug)
-                .format(move |buf, record| {
-                    let filename = record
-                        .file()
-                        .unwrap_or("unknown")
-                        .trim_start_matches("src/");
-                    let ff = format!("{}:{}", filename, record.line().unwrap_or(0));
-                    let ff = match record.level() {
-                        log::Level::Error => ff.red(),
-                        log::Level::Warn => ff.yellow(),
-                        log::Level::Info => ff.blue(),
-                        log::Level::Debug => ff.green(),
-                        log::Level::Trace => ff.normal(),
-                    };
+        res
+    }
 
-                    writeln!(
-                        buf,
-                        "{}\t{:.4} | {}",
-                        ff,
-                        (std::time::Instant::now() - start_time2).as_millis(),
-                        //chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"),
-                        record.args()
-                    )
-                })
-                .is_test(true)
-                .init()
-        });
+    fn get_input_lists(
+        &self,
+        node_idxs: &[NodeIndex],
+        dag: &GraphType,
+        jobs: &[NodeInfo],
+    ) -> Vec<Result<String, StrategyError>> {
+        let res = self.input_lists(node_idxs, dag, jobs);
+        if let Some(recording) = &self.recording {
+            for (node_idx, input_list) in node_idxs.iter().zip(res.iter()) {
+                recording.get_input_list(jobs[*node_idx].get_job_id(), input_list);
+            }
+        }
+        res
     }
-}
-pub fn start_logging_to_file(filename: impl AsRef<Path>) {
-    let start_time = std::time::Instant::now();
-    if !LOGGER_INIT.is_completed() {
-        LOGGER_INIT.call_once(move || {
-            let fh = std::fs::File::create(filename).expect("Could not open log file");
-            let start_time2 = start_time;
-            env_logger::builder()
-                .filter_level(log::LevelFilter::Debug)
-                .format(move |buf, record| {
-                    let filename = record
-                        .file()
-                        .unwrap_or("unknown")
-                        .trim_start_matches("src/");
-                    let ff = format!(
-                        "{:15}:{:5} | {:5} |",
-                        filename,
-                        record.line().unwrap_or(0),
-                        record.level()
-                    );
-                    writeln!(
-                        buf,
-                        "{}\t{:.4}ms | {}",
-                        ff,
-                        (std::time::Instant::now() - start_time2).as_millis(),
-                        //chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"),
-                        record.args()
-                    )
-                })
-                .is_test(true)
-                .target(env_logger::Target::Pipe(Box::new(fh)))
-                .init()
-        })
+
+    fn current_history(&self, job_id: &str) -> Result<Option<String>, StrategyError> {
+        // python side history values can't be rebuilt from here
+        if self.history_altered_callback.is_some() {
+            return Ok(None);
+        }
+        StrategyFileSystem::new(false).current_history(job_id)
+    }
+
+    fn should_run(&self, job_id: &str) -> Result<bool, StrategyError> {
+        let res = match &self.should_run_callback {
+            Some(callback) => Python::with_gil(|py| {
+                callback
+                    .call1(py, (job_id,))
+                    .and_then(|res| res.extract::<bool>(py))
+                    .map_err(|e| {
+                        StrategyError::Callback(format!("should_run for {} failed: {}", job_id, e))
+                    })
+            }),
+            None => Ok(true),
+        };
+        if let Some(recording) = &self.recording {
+            recording.should_run(job_id, &res);
+        }
+        res
     }
 }
 
-pub struct RunError(
-    pub engine::PPGEvaluator<StrategyForTesting>,
-    pub PPGEvaluatorError,
-);
+#[pyclass(name = "PPG2Evaluator", module = "pypipegraph2.pypipegraph2")]
+pub struct PyPPG2Evaluator {
+    evaluator: PPGEvaluator<StrategyForPython>, // todo
+    ready_notify: Arc<ReadyNotify>,
+    progress_callback: Option<ProgressCallback>,
+    reported: Arc<ReportedEvents>,
+    async_waiters: Arc<AsyncWaiters>,
+}
+
+struct ProgressCallback {
+    callback: PyObject,
+    min_interval: Duration,
+    last_call: Option<Instant>,
+    last_reported: Option<(usize, usize, usize, usize)>,
+}
+
+// default minimum seconds between two on_progress calls
+const PROGRESS_MIN_INTERVAL: f64 = 0.1;
+
+/// Counts events, so ReadyJobsIter can sleep (without the GIL)
+/// until another thread changed the evaluator.
+#[derive(Default)]
+struct ReadyNotify {
+    changes: Mutex<u64>,
+    cond: Condvar,
+}
+
+impl ReadyNotify {
+    fn bump(&self) {
+        *self.changes.lock().unwrap() += 1;
+        self.cond.notify_all();
+    }
+
+    fn current(&self) -> u64 {
+        *self.changes.lock().unwrap()
+    }
 
-impl std::fmt::Debug for RunError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("RunError").field("error", &self.1).finish()
+    fn wait_for_change(&self, seen: u64, timeout: Duration) {
+        let guard = self.changes.lock().unwrap();
+        let _ = self
+            .cond
+            .wait_timeout_while(guard, timeout, |changes| *changes == seen)
+            .unwrap();
     }
 }
 
-// simulates a complete (deterministic)
-// run - jobs just register that they've been run,
-// and output a 'dummy' history.
-pub struct TestGraphRunner {
-    #[allow(clippy::type_complexity)]
-    pub setup_graph: Box<dyn Fn(&mut PPGEvaluator<StrategyForTesting>)>,
-    pub run_counters: HashMap<String, usize>,
-    pub history: HashMap<String, String>,
-    pub already_done: HashSet<String>,
-    pub allowed_nesting: u32,
-    pub outputs: HashMap<String, String>,
-    pub run_order: Vec<String>,
-    pub cleaned_up: HashSet<String>,
+type WaiterCheck = Box<dyn FnMut(Python, &mut PyPPG2Evaluator) -> Option<PyObject> + Send>;
+
+/// A future handed out by wait_for_ready / wait_finished, see spawn_waiter.
+struct AsyncWaiter {
+    event_loop: PyObject,
+    future: PyObject,
+    deadline: Option<Instant>,
+    check: WaiterCheck,
 }
 
-impl TestGraphRunner {
-    #[allow(clippy::type_complexity)]
-    pub fn new(setup_func: Box<dyn Fn(&mut PPGEvaluator<StrategyForTesting>)>) -> Self {
-        TestGraphRunner {
-            setup_graph: setup_func,
-            run_counters: HashMap::new(),
-            history: HashMap::new(),
-            already_done: HashSet::new(),
-            allowed_nesting: 250,
-            outputs: HashMap::new(),
-            run_order: Vec::new(),
-            cleaned_up: HashSet::new(),
-        }
+/// The futures waiting on one evaluator - all served by a single background
+/// thread, started with the first one and ending once none are left.
+#[derive(Default)]
+struct AsyncWaiters {
+    queue: Mutex<AsyncWaiterQueue>,
+}
+
+#[derive(Default)]
+struct AsyncWaiterQueue {
+    waiters: Vec<AsyncWaiter>,
+    thread_running: bool,
+}
+
+impl AsyncWaiters {
+    fn lock(&self) -> std::sync::MutexGuard<'_, AsyncWaiterQueue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
     }
 
-    pub fn debug_(&self) -> String {
-        let strat = StrategyForTesting::new();
-        let mut g = PPGEvaluator::new_with_history(self.history.clone(), strat);
+    /// true if the caller has to start the thread
+    fn add(&self, waiter: AsyncWaiter) -> bool {
+        let mut queue = self.lock();
+        queue.waiters.push(waiter);
+        !std::mem::replace(&mut queue.thread_running, true)
+    }
 
-        (self.setup_graph)(&mut g);
-        g.debug_()
+    /// The waiters for the thread to check - taken out, so the lock
+    /// is never held while python code runs.
+    fn take(&self) -> Vec<AsyncWaiter> {
+        std::mem::take(&mut self.lock().waiters)
     }
 
-    pub fn run(
-        &mut self,
-        jobs_to_fail: &[&str],
-    ) -> Result<PPGEvaluator<StrategyForTesting>, RunError> {
-        debug!("");
-        debug!("GOGOGO ----------------------------------------------------------------");
-        let strat = StrategyForTesting::new();
-        for k in self.already_done.iter() {
-            strat.already_done.borrow_mut().insert(k.to_string());
-        }
-        let already_done2 = Rc::clone(&strat.already_done);
-        let mut g = PPGEvaluator::new_with_history(self.history.clone(), strat);
-        self.run_order.clear();
-
-        (self.setup_graph)(&mut g);
-        //debug!("{}", g.debug_());
-        let mut counter = self.allowed_nesting;
-        g.event_startup().unwrap();
-        while !g.is_finished() {
-            let to_run = g.query_ready_to_run();
-            if to_run.is_empty() {
-                //  start_logging();
-                // debug!("{}", g.debug_());
-                //g.debug_is_finished();
-            }
-            if to_run.is_empty() {
-                g.debug_is_finished();
-            }
-            assert!(!to_run.is_empty());
-            for job_id in to_run.iter() {
-                debug!("Running {}", job_id);
-                match g.event_now_running(job_id) {
-                    Ok(_) => {}
-                    Err(e) => return Err(RunError(g, e.into())),
-                }
-                self.run_order.push(job_id.to_string());
-                *self.run_counters.entry(job_id.clone()).or_insert(0) += 1;
-                if jobs_to_fail.contains(&&job_id[..]) {
-                    match g.event_job_finished_failure(job_id) {
-                        Ok(_) => {}
-                        Err(e) => return Err(RunError(g, e.into())),
-                    }
-                } else {
-                    match g.event_job_finished_success(
-                        job_id,
-                        self.outputs
-                            .get(job_id)
-                            .unwrap_or(&format!("history_{}", job_id))
-                            .to_string(),
-                    ) {
-                        Ok(_) => {
-                            already_done2.borrow_mut().insert(job_id.clone());
-                        }
-                        Err(err) => match err {
-                            PPGEvaluatorError::APIError(x) => panic!("api error {}", x),
-                            PPGEvaluatorError::EphemeralChangedOutput { .. } => {
-                                debug!("EphemeralChangedOutput error. ignoring for tests");
-                            }
-                            PPGEvaluatorError::InternalError(x) => panic!("internal error {}", x),
-                        },
-                    }
-                }
-            }
-            counter -= 1;
-            if counter == 0 {
-                return Err(RunError(
-                    g,
-                    PPGEvaluatorError::APIError(format!(
-                        "run out of room, you nested them more than {} deep?",
-                        self.allowed_nesting
-                    )),
-                ));
-            }
+    /// Put the unresolved ones back. false if there are none left -
+    /// the thread has to end then.
+    fn put_back(&self, waiters: Vec<AsyncWaiter>) -> bool {
+        let mut queue = self.lock();
+        queue.waiters.extend(waiters);
+        queue.thread_running = !queue.waiters.is_empty();
+        queue.thread_running
+    }
+}
 
-            for c in g.query_ready_for_cleanup() {
-                g.event_job_cleanup_done(&c)
-                    .expect("cleanup registering failed");
-                self.cleaned_up.insert(c);
-            }
-        }
+/// Job outcomes reported by worker threads through an EventReporter,
+/// waiting for the thread driving the evaluator (see apply_reported_events).
+enum ReportedEvent {
+    Success(String, String),
+    Failure(String, Option<String>),
+}
 
-        self.history.clear();
-        for (k, v) in {
-            match g.new_history() {
-                Ok(x) => x,
-                Err(e) => return Err(RunError(g, e)),
-            }
-        }
-        .iter()
-        {
-            self.history.insert(k.clone(), v.clone());
-        }
-        for k in already_done2.take().into_iter() {
-            self.already_done.insert(k);
-        }
-        /* #[cfg(debug_assertions)]
-               if !g.verify_order_was_topological(&self.run_order) {
-                   panic!("Run order was not topological");
-               }
-        */
-        Ok(g)
+#[derive(Default)]
+struct ReportedEvents {
+    events: Mutex<VecDeque<ReportedEvent>>,
+}
+
+impl ReportedEvents {
+    fn push(&self, event: ReportedEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(event);
+    }
+
+    fn pop(&self) -> Option<ReportedEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
     }
 }
 
-pub fn test_big_linear_graph(count: u32) {
-    let c2 = count;
-    let create_graph = move |g: &mut PPGEvaluator<StrategyForTesting>| {
-        let c = c2 - 1;
-        for ii in 0..c {
-            g.add_node(&format!("A{}", ii), JobKind::Output);
-        }
-        for ii in 1..c {
-            g.depends_on(&format!("A{}", ii - 1), &format!("A{}", ii));
-        }
-    };
-    let mut ro = TestGraphRunner::new(Box::new(create_graph));
-    ro.allowed_nesting = count + 1;
-    let g = ro.run(&Vec::new());
-    assert!(g.is_ok())
-    //dbg!(g.new_history().len());
-}
-
-pub fn test_big_linear_graph_half_ephemeral(count: u32) {
-    let c2 = count;
-    let create_graph = move |g: &mut PPGEvaluator<StrategyForTesting>| {
-        let c = c2 - 1;
-        for ii in 0..c {
-            g.add_node(
-                &format!("A{}", ii),
-                if ii % 2 == 0 {
-                    JobKind::Output
-                } else {
-                    JobKind::Ephemeral
-                },
-            );
-        }
-        for ii in 1..c {
-            g.depends_on(&format!("A{}", ii - 1), &format!("A{}", ii));
-        }
-    };
-    let mut ro = TestGraphRunner::new(Box::new(create_graph));
-    ro.allowed_nesting = count + 1;
-    let g = ro.run(&Vec::new());
-    assert!(g.is_ok())
-    //dbg!(g.new_history().len());
-}
-
-pub fn test_big_graph_in_layers(nodes_per_layer: u32, layers: u32, run_count: u32) {
-    let create_graph = move |g: &mut PPGEvaluator<StrategyForTesting>| {
-        for ll in 0..layers {
-            if ll == 0 {
-                for ii in 0..nodes_per_layer {
-                    g.add_node(&format!("A{}_{}", ll, ii), JobKind::Always);
-                }
-            } else {
-                for ii in 0..nodes_per_layer {
-                    g.add_node(
-                        &format!("A{}_{}", ll, ii),
-                        if ii % 2 == 0 {
-                            JobKind::Output
-                        } else {
-                            JobKind::Ephemeral
-                        },
-                    );
-                    for yy in 0..nodes_per_layer {
-                        g.depends_on(&format!("A{}_{}", ll, ii), &format!("A{}_{}", ll - 1, yy))
-                    }
-                }
-            }
-        }
-    };
-    let mut ro = TestGraphRunner::new(Box::new(create_graph));
-    ro.allowed_nesting = layers + 1;
-    for _ in 0..run_count {
-        let g = ro.run(&Vec::new());
-        assert!(g.is_ok())
+/// Hand this to worker threads - reporting never touches the evaluator,
+/// so workers don't collide with the thread driving it.
+#[pyclass(name = "EventReporter", module = "pypipegraph2.pypipegraph2")]
+pub struct PyEventReporter {
+    reported: Arc<ReportedEvents>,
+    notify: Arc<ReadyNotify>,
+}
+
+#[pymethods]
+impl PyEventReporter {
+    /// new_history may be str or bytes
+    pub fn job_success(&self, job_id: &str, new_history: &PyAny) -> PyResult<()> {
+        self.reported.push(ReportedEvent::Success(
+            job_id.to_string(),
+            history_from_py(new_history)?,
+        ));
+        self.notify.bump();
+        Ok(())
+    }
+
+    pub fn job_failure(&self, job_id: &str, error: Option<&str>) {
+        self.reported.push(ReportedEvent::Failure(
+            job_id.to_string(),
+            error.map(|e| e.to_string()),
+        ));
+        self.notify.bump();
     }
-    //dbg!(g.new_history().len());
 }
 
-struct StrategyForPython {
-    history_altered_callback: PyObject,
-    get_job_inputs_str_callback: PyObject,
+/// A python callable as SchedulingPolicy:
+/// callback([(job_id, runtime_estimate_seconds | None, downstream_count, required_cores, ready_order)],
+/// running_job_ids, cores) -> [job_id]
+struct PythonSchedulingPolicy {
+    callback: PyObject,
 }
 
-impl PPGEvaluatorStrategy for StrategyForPython {
-    fn output_already_present(&self, query: &str) -> bool {
-        use std::path::PathBuf;
-        // support for multi file generating jobs
-        for sub_path in query.split(":::") {
-            let p = PathBuf::from(sub_path);
-            if !p.exists() {
-                return false;
+impl SchedulingPolicy for PythonSchedulingPolicy {
+    fn order(&self, candidates: Vec<ReadyCandidate>, resources: &ResourceState) -> Vec<String> {
+        let args: Vec<(String, Option<f64>, usize, usize, u64)> = candidates
+            .iter()
+            .map(|c| {
+                (
+                    c.job_id.clone(),
+                    c.runtime_estimate.map(|x| x.as_secs_f64()),
+                    c.downstream_count,
+                    c.required_cores,
+                    c.ready_order,
+                )
+            })
+            .collect();
+        let res = Python::with_gil(|py| {
+            self.callback
+                .call1(py, (args, resources.running.clone(), resources.cores))
+                .and_then(|res| res.extract::<Vec<String>>(py))
+        });
+        match res {
+            Ok(res) => res,
+            Err(e) => {
+                // can't fail query_ready_to_run - better run in some order than not at all
+                warn!("Scheduling policy failed, using job id order: {}", e);
+                candidates.into_iter().map(|c| c.job_id).collect()
             }
         }
-        true
     }
+}
 
-    fn is_history_altered(
-        &self,
-        job_id_upstream: &str,
-        job_id_downstream: &str,
-        last_recorded_value: &str,
-        current_value: &str,
-    ) -> bool {
-        if last_recorded_value == current_value {
-            false
-        } else {
-            Python::with_gil(|py| {
-                let res = self.history_altered_callback.call1(
-                    py,
-                    (
-                        job_id_upstream,
-                        job_id_downstream,
-                        last_recorded_value,
-                        current_value,
-                    ),
-                );
-                res.expect("History comparison failed on python side")
-                    .extract::<bool>(py)
-                    .expect("history comparison did not return a bool")
-            })
-        }
+// what add_node accepts - invariants have their own add_invariant
+const JOB_KINDS: &[(&str, JobKind)] = &[
+    ("Always", JobKind::Always),
+    ("Output", JobKind::Output),
+    ("Ephemeral", JobKind::Ephemeral),
+    ("TempFile", JobKind::TempFile),
+    ("Conditional", JobKind::Conditional),
+    ("Barrier", JobKind::Barrier),
+];
 
-        //last_recorded_value != current_value // todo
-    }
+fn parse_job_kind(job_kind: &str) -> Result<JobKind, PyErr> {
+    JOB_KINDS
+        .iter()
+        .find(|(name, _)| *name == job_kind)
+        .map(|(_, kind)| *kind)
+        .ok_or_else(|| PyTypeError::new_err("Invalid job kind"))
+}
 
-    fn get_input_list(
-        &self,
-        node_idx: engine::NodeIndex,
-        _dag: &engine::GraphType,
-        jobs: &[engine::NodeInfo],
-    ) -> String {
-        let job_id = jobs[node_idx as usize].clone_job_id();
-        Python::with_gil(|py| {
-            let res = self.get_job_inputs_str_callback.call1(py, (job_id,));
-            res.expect("input_list_different failed on python side")
-                .extract::<String>(py)
-                .expect("input_list_changed_callback did not return a bool")
+fn edge_details_to_py(py: Python, edges: Vec<EdgeDetails>) -> PyResult<Vec<PyObject>> {
+    edges
+        .into_iter()
+        .map(|edge| {
+            let res = PyDict::new(py);
+            res.set_item("job_id", edge.job_id)?;
+            res.set_item("kind", format!("{:?}", edge.kind))?;
+            res.set_item(
+                "last_history",
+                edge.last_history.map(|x| history_to_py(py, &x)),
+            )?;
+            res.set_item("invalidated", edge.invalidated)?;
+            Ok(res.into())
         })
-    }
+        .collect()
 }
 
-#[pyclass(name = "PPG2Evaluator")]
-pub struct PyPPG2Evaluator {
-    evaluator: PPGEvaluator<StrategyForPython>, // todo
+fn parse_log_level(level: &str) -> Result<LevelFilter, PyErr> {
+    level
+        .parse()
+        .map_err(|_| PyValueError::new_err(format!("Invalid log level {}", level)))
+}
+
+pyo3::create_exception!(
+    pypipegraph2,
+    PPGStrategyError,
+    pyo3::exceptions::PyRuntimeError
+);
+// pypipegraph2.errors - ValueErrors, for compatibility with code catching those
+pyo3::create_exception!(pypipegraph2, CycleError, PyValueError);
+pyo3::create_exception!(pypipegraph2, JobRedefinitionError, PyValueError);
+pyo3::create_exception!(pypipegraph2, NotRunningError, PyValueError);
+pyo3::create_exception!(pypipegraph2, ContractViolationError, PyValueError);
+pyo3::create_exception!(pypipegraph2, StalledError, PyValueError);
+
+// PyErr and PPGEvaluatorError both live in other crates,
+// so this can't be a From impl - map_err(py_err) at each call site
+fn py_err(val: PPGEvaluatorError) -> PyErr {
+    {
+        let msg = val.to_string();
+        match val {
+            PPGEvaluatorError::StrategyError(_) => PPGStrategyError::new_err(msg),
+            PPGEvaluatorError::Cycle(_) => CycleError::new_err(msg),
+            PPGEvaluatorError::JobNotRunning(_) | PPGEvaluatorError::DuplicateEvent { .. } => {
+                NotRunningError::new_err(msg)
+            }
+            PPGEvaluatorError::JobRedefinition(_)
+            | PPGEvaluatorError::ConflictingJobKind { .. }
+            | PPGEvaluatorError::ConflictingInvariant { .. } => JobRedefinitionError::new_err(msg),
+            PPGEvaluatorError::UnknownJob(_) => PyKeyError::new_err(msg),
+            PPGEvaluatorError::InvalidJobId(_)
+            | PPGEvaluatorError::SelfDependency(_)
+            | PPGEvaluatorError::HistoryMigration { .. } => PyValueError::new_err(msg),
+            PPGEvaluatorError::EphemeralChangedOutput { .. } => {
+                ContractViolationError::new_err(msg)
+            }
+            PPGEvaluatorError::Stalled { .. } => StalledError::new_err(msg),
+            PPGEvaluatorError::HistoryStorage {
+                source: HistoryStorageError::Io(_),
+                ..
+            } => PyIOError::new_err(msg),
+            // APIError, InternalError, and whatever a newer engine adds
+            _ => PyValueError::new_err(msg),
+        }
+    }
 }
 
-impl From<PPGEvaluatorError> for PyErr {
-    fn from(val: PPGEvaluatorError) -> Self {
-        PyValueError::new_err(val.to_string())
+impl PyPPG2Evaluator {
+    fn state_changed(&mut self) {
+        self.ready_notify.bump();
+        if let Some(progress_callback) = self.progress_callback.as_mut() {
+            let progress = self.evaluator.progress();
+            let counts = (
+                progress.finished(),
+                progress.total(),
+                progress.running,
+                progress.failed,
+            );
+            if progress_callback.last_reported == Some(counts) {
+                return;
+            }
+            // the final state is always reported
+            let due = counts.0 == counts.1
+                || progress_callback
+                    .last_call
+                    .is_none_or(|last| last.elapsed() >= progress_callback.min_interval);
+            if !due {
+                return;
+            }
+            progress_callback.last_call = Some(Instant::now());
+            progress_callback.last_reported = Some(counts);
+            Python::with_gil(|py| {
+                // the event itself went through - don't fail it
+                if let Err(e) = progress_callback.callback.call1(py, counts) {
+                    e.print(py);
+                }
+            });
+        }
     }
 }
 
@@ -481,13 +553,22 @@ impl PyPPG2Evaluator {
     fn __new__(
         _py: Python,
         py_history: &PyDict,
-        history_compare_callable: PyObject,
-        get_job_inputs_str_callback: PyObject,
+        history_compare_callable: Option<PyObject>,
+        get_job_inputs_str_callback: Option<PyObject>,
+        record_strategy_to: Option<&str>,
     ) -> Result<Self, PyErr> {
-        let mut history: HashMap<String, String> = HashMap::new();
+        // record every strategy decision, for bug reports (see StrategyReplay)
+        let recording = match record_strategy_to {
+            Some(path) => Some(Recording::create(path).map_err(|e| {
+                PyValueError::new_err(format!("Could not create recording {}: {}", path, e))
+            })?),
+            None => None,
+        };
+        // values are interned as they come in - the dict holds one copy already
+        let mut history = HistoryStore::new();
         for (k, v) in py_history.iter() {
             let ko: String = k.extract()?;
-            let vo: String = v.extract()?;
+            let vo = history_from_py(v)?;
             history.insert(ko, vo);
         }
         Ok(PyPPG2Evaluator {
@@ -496,123 +577,1587 @@ impl PyPPG2Evaluator {
                 StrategyForPython {
                     history_altered_callback: history_compare_callable,
                     get_job_inputs_str_callback,
+                    should_run_callback: None,
+                    recording,
                 },
             ),
+            ready_notify: Arc::new(ReadyNotify::default()),
+            progress_callback: None,
+            reported: Arc::new(ReportedEvents::default()),
+            async_waiters: Arc::new(AsyncWaiters::default()),
         })
     }
 
     pub fn add_node(&mut self, job_id: &str, job_kind: &str) -> Result<(), PyErr> {
-        let jk = match job_kind {
-            "Output" => JobKind::Output,
-            "Always" => JobKind::Always,
-            "Ephemeral" => JobKind::Ephemeral,
-            _ => return Err(PyTypeError::new_err("Invalid job kind")),
-        };
-        self.evaluator.add_node(job_id, jk);
-        Ok(())
+        let jk = parse_job_kind(job_kind)?;
+        self.evaluator.add_node(job_id, jk).map_err(py_err)
     }
 
-    pub fn add_edge(&mut self, from: &str, to: &str) {
-        self.evaluator.depends_on(from, to);
+    /// A job that never runs - value is its output. See JobKind::Invariant
+    pub fn add_invariant(&mut self, job_id: &str, value: &str) -> Result<(), PyErr> {
+        self.evaluator.add_invariant(job_id, value).map_err(py_err)
     }
 
-    pub fn event_startup(&mut self) -> Result<(), PyErr> {
-        Ok(self.evaluator.event_startup()?)
+    /// python version, tool versions... - only subscribed jobs depend on it
+    pub fn add_global_invariant(&mut self, name: &str, value: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .add_global_invariant(name, value)
+            .map_err(py_err)
     }
 
-    pub fn event_now_running(&mut self, job_id: &str) -> Result<(), PyErr> {
-        Ok(self.evaluator.event_now_running(job_id)?)
+    /// only run job_id while path does not exist
+    pub fn require_absent(&mut self, job_id: &str, path: &str) -> Result<(), PyErr> {
+        self.evaluator.require_absent(job_id, path).map_err(py_err)
     }
 
-    pub fn event_job_success(&mut self, job_id: &str, new_history: &str) -> Result<(), PyErr> {
-        Ok(self
-            .evaluator
-            .event_job_finished_success(job_id, new_history.to_string())?)
+    /// required-absent paths that exist, sorted
+    pub fn query_blocking_files(&self) -> Vec<String> {
+        self.evaluator.query_blocking_files()
     }
 
-    pub fn event_job_failure(&mut self, job_id: &str) -> Result<(), PyErr> {
-        Ok(self.evaluator.event_job_finished_failure(job_id)?)
+    pub fn subscribe_global_invariant(&mut self, job_id: &str, name: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .subscribe_global_invariant(job_id, name)
+            .map_err(py_err)
     }
 
-    pub fn list_upstream_failed_jobs(&self) -> Vec<String> {
-        self.evaluator.query_upstream_failed().into_iter().collect()
+    /// all jobs tagged with tag at startup
+    pub fn subscribe_tag_to_global_invariant(
+        &mut self,
+        tag: &str,
+        name: &str,
+    ) -> Result<(), PyErr> {
+        self.evaluator
+            .subscribe_tag_to_global_invariant(tag, name)
+            .map_err(py_err)
     }
 
-    pub fn jobs_ready_to_run(&self) -> Vec<String> {
-        self.evaluator.query_ready_to_run().into_iter().collect()
+    /// all jobs in subgraph prefix at startup
+    pub fn subscribe_subgraph_to_global_invariant(
+        &mut self,
+        prefix: &str,
+        name: &str,
+    ) -> Result<(), PyErr> {
+        self.evaluator
+            .subscribe_subgraph_to_global_invariant(prefix, name)
+            .map_err(py_err)
     }
 
-    pub fn jobs_running(&self) -> Vec<String> {
-        self.evaluator.query_jobs_running().into_iter().collect()
+    pub fn add_edge(&mut self, from: &str, to: &str) -> Result<(), PyErr> {
+        self.evaluator.depends_on(from, to).map_err(py_err)
     }
 
-    pub fn jobs_ready_for_cleanup(&self) -> Vec<String> {
+    /// add_edge, if to is part of the graph at startup - ignored otherwise
+    pub fn add_edge_if_present(&mut self, from: &str, to: &str) -> Result<(), PyErr> {
         self.evaluator
-            .query_ready_for_cleanup()
-            .into_iter()
-            .collect()
+            .depends_on_if_present(from, to)
+            .map_err(py_err)
     }
 
-    pub fn event_job_cleanup_done(&mut self, job_id: &str) -> Result<(), PyErr> {
-        Ok(self.evaluator.event_job_cleanup_done(job_id)?)
+    /// Undo add_edge(from, to) - before event_startup
+    pub fn remove_edge(&mut self, from: &str, to: &str) -> Result<(), PyErr> {
+        self.evaluator.remove_dependency(from, to).map_err(py_err)
     }
 
-    pub fn is_finished(&mut self) -> bool {
-        self.evaluator.is_finished()
+    /// Remove a job and its edges - before event_startup
+    pub fn remove_node(&mut self, job_id: &str) -> Result<(), PyErr> {
+        self.evaluator.remove_node(job_id).map_err(py_err)
     }
 
-    pub fn new_history(&self) -> Result<HashMap<String, String>, PyErr> {
-        Ok(self.evaluator.new_history()?)
+    /// Add a whole graph in one call:
+    /// {"jobs": {job_id: job_kind}, "edges": [(from, to)]}, edges as in add_edge.
+    /// Everything is validated before anything is added.
+    pub fn load_graph(&mut self, graph: &PyDict) -> Result<(), PyErr> {
+        let jobs: HashMap<String, String> = match graph.get_item("jobs") {
+            Some(jobs) => jobs.extract()?,
+            None => HashMap::new(),
+        };
+        let edges: Vec<(String, String)> = match graph.get_item("edges") {
+            Some(edges) => edges.extract()?,
+            None => Vec::new(),
+        };
+        let mut kinds = Vec::with_capacity(jobs.len());
+        // the same checks add_node / depends_on do, but before changing anything
+        for (job_id, job_kind) in jobs.iter() {
+            if job_id.is_empty() || job_id.contains("!!!") {
+                return Err(py_err(PPGEvaluatorError::InvalidJobId(job_id.to_string())));
+            }
+            if self.evaluator.contains_node(job_id) {
+                return Err(py_err(PPGEvaluatorError::JobRedefinition(
+                    job_id.to_string(),
+                )));
+            }
+            kinds.push((job_id, parse_job_kind(job_kind)?));
+        }
+        for (from, to) in edges.iter() {
+            for job_id in [from, to].iter() {
+                if !jobs.contains_key(*job_id) && !self.evaluator.contains_node(job_id) {
+                    return Err(py_err(PPGEvaluatorError::UnknownJob(job_id.to_string())));
+                }
+            }
+            if from == to {
+                return Err(py_err(PPGEvaluatorError::SelfDependency(from.to_string())));
+            }
+        }
+        for (job_id, kind) in kinds {
+            self.evaluator.add_node(job_id, kind).map_err(py_err)?;
+        }
+        for (from, to) in edges.iter() {
+            self.evaluator.depends_on(from, to).map_err(py_err)?;
+        }
+        Ok(())
     }
 
-    pub fn get_job_output(&self, job_id: &str) -> Result<String, PyErr> {
-        match self.evaluator.get_job_output(job_id) {
-            engine::JobOutputResult::Done(v) => Ok(v),
-            engine::JobOutputResult::NoSuchJob => Err(PyKeyError::new_err("Invalid job id")),
-            engine::JobOutputResult::NotDone => Err(PyValueError::new_err("job not done")),
-        }
+    /// Add the jobs and edges of another (not started) evaluator
+    pub fn merge(&mut self, other: PyRef<PyPPG2Evaluator>) -> Result<(), PyErr> {
+        self.evaluator.merge(&other.evaluator).map_err(py_err)
     }
 
-    pub fn debug(&self) -> String {
-        self.evaluator.debug_()
+    /// Changes to history keys matching pattern don't invalidate (this run only)
+    pub fn ignore_changes(&mut self, pattern: &str) -> Result<(), PyErr> {
+        self.evaluator.ignore_changes(pattern).map_err(py_err)
     }
 
-    pub fn debug_is_finished(&self) {
-        self.evaluator.debug_is_finished();
+    pub fn query_ignored_changes(&self) -> Vec<String> {
+        self.evaluator.query_ignored_changes()
     }
 
-    pub fn reconsider_all_jobs(&mut self) -> Result<(), PyErr> {
-        error!("Reconsidering all jobs!");
-        self.evaluator.reconsider_all_jobs()?;
-        Ok(())
+    /// Keep target's history under the stable name alias (before event_startup)
+    pub fn add_alias(&mut self, alias: &str, target: &str) -> Result<(), PyErr> {
+        self.evaluator.add_alias(alias, target).map_err(py_err)
     }
 
-    pub fn event_abort(&mut self) -> Result<(), PyErr> {
-        self.evaluator.abort_remaining()?;
-        Ok(())
+    /// Move old_id's history to new_id (before event_startup),
+    /// so a renamed job and its downstreams are not rebuilt.
+    pub fn rename_history(&mut self, old_id: &str, new_id: &str) -> Result<usize, PyErr> {
+        self.evaluator
+            .rename_history(old_id, new_id)
+            .map_err(py_err)
     }
-}
 
-/// Formats the sum of two numbers as string.
-#[pyfunction]
-fn enable_logging() -> PyResult<()> {
-    start_logging();
-    error!("hello from rust");
-    Ok(())
-}
-/// Formats the sum of two numbers as string.
-#[pyfunction]
-fn enable_logging_to_file(filename: &str) -> PyResult<()> {
-    start_logging_to_file(filename);
-    error!("hello from rust");
-    Ok(())
-}
+    /// Present (likely half written) outputs of failed Output / Ephemeral jobs
+    pub fn query_failed_outputs_for_cleanup(&self) -> Result<Vec<String>, PyErr> {
+        self.evaluator
+            .query_failed_outputs_for_cleanup()
+            .map_err(py_err)
+    }
+
+    /// Present outputs of jobs in the history that are no longer in the graph
+    pub fn query_orphaned_outputs(&self) -> Result<Vec<String>, PyErr> {
+        self.evaluator.query_orphaned_outputs().map_err(py_err)
+    }
+
+    /// [(old_id, new_id)] - jobs of this run whose output matches a vanished job's
+    pub fn detect_renames(&self) -> Vec<(String, String)> {
+        self.evaluator.detect_renames()
+    }
+
+    /// Graph sanity checks - call before event_startup.
+    /// [{"severity": "warning"|"error", "kind": ..., "jobs": [...], "message": ...}]
+    pub fn validate(&self) -> PyResult<Vec<PyObject>> {
+        Python::with_gil(|py| {
+            self.evaluator
+                .validate()
+                .issues
+                .into_iter()
+                .map(|issue| {
+                    let res = PyDict::new(py);
+                    let severity = match issue.severity {
+                        Severity::Warning => "warning",
+                        Severity::Error => "error",
+                    };
+                    res.set_item("severity", severity)?;
+                    res.set_item("kind", format!("{:?}", issue.kind))?;
+                    res.set_item("jobs", issue.job_ids)?;
+                    res.set_item("message", issue.message)?;
+                    Ok(res.into())
+                })
+                .collect()
+        })
+    }
+
+    pub fn event_startup(&mut self) -> Result<(), PyErr> {
+        let res = self.evaluator.event_startup();
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// after is_finished and adding jobs: evaluate again, running only what's needed
+    pub fn event_resume(&mut self) -> Result<(), PyErr> {
+        let res = self.evaluator.event_resume();
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// watch mode: re-run job_id (and what it invalidates) if its output changed.
+    /// Returns whether it did
+    pub fn notify_external_change(&mut self, job_id: &str) -> Result<bool, PyErr> {
+        let res = self.evaluator.notify_external_change(job_id);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// notify_external_change for several jobs at once - returns the changed ones
+    pub fn notify_external_changes(&mut self, job_ids: Vec<&str>) -> Result<Vec<String>, PyErr> {
+        let res = self.evaluator.notify_external_changes(&job_ids);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// interactive mode: graph changes during a run are queued, new jobs join
+    /// the run at the next event_replan, the rest is applied once it finished
+    pub fn set_interactive(&mut self, enabled: bool) {
+        self.evaluator.set_interactive(enabled)
+    }
+
+    /// Mark job_id as generating jobs - returns those pre-created from the last run
+    pub fn set_job_generator(&mut self, job_id: &str) -> Result<Vec<String>, PyErr> {
+        let res = self.evaluator.set_job_generator(job_id);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// The running generator generated these (job_id, job_kind) jobs
+    pub fn event_jobs_generated(
+        &mut self,
+        generator_id: &str,
+        jobs: Vec<(String, String)>,
+    ) -> Result<(), PyErr> {
+        let jobs = jobs
+            .iter()
+            .map(|(job_id, kind)| Ok((job_id.as_str(), parse_job_kind(kind)?)))
+            .collect::<Result<Vec<_>, PyErr>>()?;
+        let res = self.evaluator.event_jobs_generated(generator_id, &jobs);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    pub fn query_generated_jobs(&self, generator_id: &str) -> Vec<String> {
+        self.evaluator.query_generated_jobs(generator_id)
+    }
+
+    /// generated last time, but not this run - their outputs can be removed
+    pub fn query_vanished_generated_jobs(&self) -> Vec<String> {
+        self.evaluator.query_vanished_generated_jobs()
+    }
+
+    /// Apply graph changes made since the run finished and continue -
+    /// False if there were none, or the run is still going
+    pub fn event_replan(&mut self) -> Result<bool, PyErr> {
+        let res = self.evaluator.event_replan();
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    pub fn event_now_running(&mut self, job_id: &str) -> Result<(), PyErr> {
+        let res = self.evaluator.event_now_running(job_id);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// new_history may be str or bytes
+    pub fn event_job_success(&mut self, job_id: &str, new_history: &PyAny) -> Result<(), PyErr> {
+        let res = self
+            .evaluator
+            .event_job_finished_success(job_id, history_from_py(new_history)?);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// error is the (optional) payload shown in reports - exception, traceback...
+    pub fn event_job_failure(&mut self, job_id: &str, error: Option<&str>) -> Result<(), PyErr> {
+        let res = match error {
+            Some(error) => self
+                .evaluator
+                .event_job_finished_failure_with_error(job_id, error.to_string()),
+            None => self.evaluator.event_job_finished_failure(job_id),
+        };
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// For worker threads to report job outcomes with - see apply_reported_events
+    pub fn event_reporter(&self) -> PyEventReporter {
+        PyEventReporter {
+            reported: self.reported.clone(),
+            notify: self.ready_notify.clone(),
+        }
+    }
+
+    /// Apply what the event reporters collected, in order. Returns how many.
+    /// Stops at the first event that fails and raises its error - the events
+    /// reported after it stay queued for the next call.
+    /// The GIL is released while the engine works, so workers keep reporting.
+    /// ready_jobs_iter does this by itself.
+    pub fn apply_reported_events(&mut self, py: Python) -> Result<usize, PyErr> {
+        let mut applied = 0;
+        let mut res = Ok(());
+        while let Some(event) = self.reported.pop() {
+            let evaluator = &mut self.evaluator;
+            res = py.allow_threads(move || match event {
+                ReportedEvent::Success(job_id, history) => {
+                    evaluator.event_job_finished_success(&job_id, history)
+                }
+                ReportedEvent::Failure(job_id, Some(error)) => {
+                    evaluator.event_job_finished_failure_with_error(&job_id, error)
+                }
+                ReportedEvent::Failure(job_id, None) => {
+                    evaluator.event_job_finished_failure(&job_id)
+                }
+            });
+            if res.is_err() {
+                break;
+            }
+            applied += 1;
+        }
+        if applied > 0 || res.is_err() {
+            self.state_changed();
+        }
+        res.map(|_| applied).map_err(py_err)
+    }
+
+    /// Awaitable: the jobs ready to run (ordered as jobs_ready_to_run), once there are any -
+    /// or [] once the evaluator finished.
+    /// Must be called from a running asyncio loop.
+    /// Raises TimeoutError after timeout seconds.
+    pub fn wait_for_ready(
+        slf: PyRef<Self>,
+        py: Python,
+        timeout: Option<f64>,
+    ) -> PyResult<PyObject> {
+        spawn_waiter(py, slf, timeout, |py, e| {
+            let ready = e.jobs_ready_to_run();
+            if ready.is_empty() && !e.evaluator.is_finished() {
+                return None;
+            }
+            Some(ready.into_py(py))
+        })
+    }
+
+    /// Awaitable: True once the evaluator finished. See wait_for_ready.
+    pub fn wait_finished(slf: PyRef<Self>, py: Python, timeout: Option<f64>) -> PyResult<PyObject> {
+        spawn_waiter(py, slf, timeout, |py, e| {
+            if e.evaluator.is_finished() {
+                Some(true.into_py(py))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// callback(done, total, running, failed) on state changes,
+    /// at most every min_interval seconds (default 0.1). None to unregister.
+    pub fn set_progress_callback(&mut self, callback: Option<PyObject>, min_interval: Option<f64>) {
+        self.progress_callback = callback.map(|callback| ProgressCallback {
+            callback,
+            min_interval: Duration::from_secs_f64(min_interval.unwrap_or(PROGRESS_MIN_INTERVAL)),
+            last_call: None,
+            last_reported: None,
+        });
+    }
+
+    /// Yields job ids as they become ready to run - each one once -
+    /// blocking (GIL released) while events are applied from other threads.
+    /// Stops once the evaluator is finished.
+    /// Raises TimeoutError if no job became ready within timeout seconds.
+    pub fn ready_jobs_iter(slf: PyRef<Self>, timeout: Option<f64>) -> ReadyJobsIter {
+        let notify = slf.ready_notify.clone();
+        ReadyJobsIter {
+            evaluator: slf.into(),
+            notify,
+            yielded: HashSet::new(),
+            timeout: timeout.map(Duration::from_secs_f64),
+        }
+    }
+
+    pub fn output_already_present(&mut self, job_id: &str) -> Result<bool, PyErr> {
+        self.evaluator
+            .query_output_already_present(job_id)
+            .map_err(py_err)
+    }
+
+    /// for outputs created / removed outside of job completion events
+    pub fn invalidate_presence(&mut self, job_id: &str) {
+        self.evaluator.invalidate_presence(job_id)
+    }
+
+    pub fn list_upstream_failed_jobs(&self) -> Vec<String> {
+        self.evaluator.query_upstream_failed().into_iter().collect()
+    }
+
+    /// jobs grouped into dependency waves - no job depends on one in its own layer
+    pub fn topological_layers(&self) -> PyResult<Vec<Vec<String>>> {
+        self.evaluator.topological_layers().map_err(py_err)
+    }
+
+    /// in the order the scheduling policy wants them started
+    pub fn jobs_ready_to_run(&self) -> Vec<String> {
+        self.evaluator.query_ready_to_run_ordered()
+    }
+
+    /// jobs_ready_to_run as (job_id, reason, changed upstreams) - reason is one of
+    /// 'always', 'forced', 'new_job', 'output_missing', 'inputs_changed',
+    /// 'upstream_changed', 'needed_by_downstream' or None
+    pub fn jobs_ready_to_run_with_reasons(
+        &self,
+    ) -> Vec<(String, Option<&'static str>, Vec<String>)> {
+        self.evaluator
+            .query_ready_to_run_ordered()
+            .into_iter()
+            .map(|job_id| {
+                let (reason, upstreams) = match self.evaluator.query_run_reason(&job_id) {
+                    Ok(Some(RunReason::Always)) => (Some("always"), Vec::new()),
+                    Ok(Some(RunReason::Forced)) => (Some("forced"), Vec::new()),
+                    Ok(Some(RunReason::NewJob)) => (Some("new_job"), Vec::new()),
+                    Ok(Some(RunReason::OutputMissing)) => (Some("output_missing"), Vec::new()),
+                    Ok(Some(RunReason::InputsChanged)) => (Some("inputs_changed"), Vec::new()),
+                    Ok(Some(RunReason::UpstreamChanged(upstreams))) => {
+                        (Some("upstream_changed"), upstreams)
+                    }
+                    Ok(Some(RunReason::NeededByDownstream)) => {
+                        (Some("needed_by_downstream"), Vec::new())
+                    }
+                    Ok(None) | Err(_) => (None, Vec::new()),
+                };
+                (job_id, reason, upstreams)
+            })
+            .collect()
+    }
+
+    /// 'fifo', 'longest_job_first', 'longest_job_first_with_aging', 'most_downstreams_first',
+    /// a callable (see PythonSchedulingPolicy) - or None for job id order
+    pub fn set_scheduling_policy(&mut self, policy: Option<&PyAny>) -> Result<(), PyErr> {
+        let policy: Option<Box<dyn SchedulingPolicy>> = match policy {
+            None => None,
+            Some(policy) if policy.is_callable() => Some(Box::new(PythonSchedulingPolicy {
+                callback: policy.into(),
+            })),
+            Some(policy) => match policy.extract::<&str>()? {
+                "fifo" => Some(Box::new(Fifo)),
+                "longest_job_first" => Some(Box::new(LongestJobFirst)),
+                "longest_job_first_with_aging" => {
+                    Some(Box::new(LongestJobFirstWithAging::default()))
+                }
+                "most_downstreams_first" => Some(Box::new(MostDownstreamsFirst)),
+                other => {
+                    return Err(PyValueError::new_err(format!(
+                        "Unknown scheduling policy {}",
+                        other
+                    )))
+                }
+            },
+        };
+        self.evaluator.set_scheduling_policy(policy);
+        self.state_changed();
+        Ok(())
+    }
+
+    /// interleave ready jobs across 'components' / 'tags' - None to turn it off
+    pub fn set_fair_share(&mut self, mode: Option<&str>) -> Result<(), PyErr> {
+        let fair_share = match mode {
+            None => FairShare::Off,
+            Some("components") => FairShare::Components,
+            Some("tags") => FairShare::Tags,
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown fair share mode {}",
+                    other
+                )))
+            }
+        };
+        self.evaluator.set_fair_share(fair_share);
+        Ok(())
+    }
+
+    /// jobs without upstreams, optionally only those of kind ('Output', 'Always'...)
+    pub fn query_roots(&self, kind: Option<&str>) -> PyResult<Vec<String>> {
+        let kind = kind.map(parse_job_kind).transpose()?;
+        Ok(self.evaluator.query_roots(kind))
+    }
+
+    /// jobs without downstreams, optionally only those of kind
+    pub fn query_leaves(&self, kind: Option<&str>) -> PyResult<Vec<String>> {
+        let kind = kind.map(parse_job_kind).transpose()?;
+        Ok(self.evaluator.query_leaves(kind))
+    }
+
+    /// [{'job_id', 'kind', 'last_history', 'invalidated'}] of the direct upstreams
+    pub fn upstreams_of(&self, py: Python, job_id: &str) -> PyResult<Vec<PyObject>> {
+        edge_details_to_py(py, self.evaluator.upstreams_of(job_id).map_err(py_err)?)
+    }
+
+    /// like upstreams_of, for the direct downstreams
+    pub fn downstreams_of(&self, py: Python, job_id: &str) -> PyResult<Vec<PyObject>> {
+        edge_details_to_py(py, self.evaluator.downstreams_of(job_id).map_err(py_err)?)
+    }
+
+    /// {job_id: component id} - jobs sharing an id are (indirectly) connected
+    pub fn weakly_connected_components(&self) -> HashMap<String, usize> {
+        self.evaluator.weakly_connected_components()
+    }
+
+    pub fn set_runtime_estimate(&mut self, job_id: &str, seconds: f64) -> Result<(), PyErr> {
+        if !(seconds >= 0.0 && seconds.is_finite()) {
+            return Err(PyValueError::new_err("seconds must be >= 0"));
+        }
+        self.evaluator
+            .set_runtime_estimate(job_id, Duration::from_secs_f64(seconds))
+            .map_err(py_err)
+    }
+
+    /// (scheduled, deferred, expected_seconds) - pending jobs that fit into budget_seconds
+    pub fn plan_within(
+        &self,
+        budget_seconds: f64,
+    ) -> Result<(Vec<String>, Vec<String>, f64), PyErr> {
+        let plan = self
+            .evaluator
+            .plan_within(Duration::from_secs_f64(budget_seconds.max(0.0)))
+            .map_err(py_err)?;
+        Ok((plan.scheduled, plan.deferred, plan.expected.as_secs_f64()))
+    }
+
+    /// ignore repeated identical finish events (distributed executors)
+    pub fn set_tolerate_duplicate_events(&mut self, tolerate: bool) {
+        self.evaluator.set_tolerate_duplicate_events(tolerate)
+    }
+
+    /// suggest a second attempt once a job ran factor x its runtime estimate, None = off
+    pub fn set_speculation(&mut self, factor: Option<f64>) -> Result<(), PyErr> {
+        self.evaluator.set_speculation(factor).map_err(py_err)
+    }
+
+    pub fn list_speculation_candidates(&self) -> Vec<String> {
+        self.evaluator.query_speculation_candidates()
+    }
+
+    pub fn event_speculative_attempt_started(&mut self, job_id: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .event_speculative_attempt_started(job_id)
+            .map_err(py_err)
+    }
+
+    /// jobs with losing attempts still running - cancel them,
+    /// then report each with event_speculative_attempt_cancelled
+    pub fn list_attempts_to_cancel(&self) -> Vec<String> {
+        self.evaluator.query_attempts_to_cancel()
+    }
+
+    pub fn event_speculative_attempt_cancelled(&mut self, job_id: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .event_speculative_attempt_cancelled(job_id)
+            .map_err(py_err)
+    }
+
+    /// the ready jobs as SLURM job arrays - a json SubmissionPlan
+    pub fn plan_slurm_arrays(&self, max_array_size: usize) -> String {
+        self.evaluator.plan_slurm_arrays(max_array_size).to_json()
+    }
+
+    /// event: 'started', 'success' (payload = history) or 'failure' (payload = error)
+    pub fn event_array_task(
+        &mut self,
+        plan: &str,
+        batch: usize,
+        task: usize,
+        event: &str,
+        payload: Option<String>,
+    ) -> Result<(), PyErr> {
+        let plan = SubmissionPlan::from_json(plan)
+            .map_err(|e| PyValueError::new_err(format!("Invalid submission plan: {}", e)))?;
+        let event = match (event, payload) {
+            ("started", _) => ArrayTaskEvent::Started,
+            ("success", Some(history)) => ArrayTaskEvent::Success { history },
+            ("success", None) => {
+                return Err(PyValueError::new_err(
+                    "success needs the history as payload",
+                ))
+            }
+            ("failure", error) => ArrayTaskEvent::Failure { error },
+            (other, _) => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown array task event {}",
+                    other
+                )))
+            }
+        };
+        let res = self.evaluator.event_array_task(&plan, batch, task, event);
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// record successful jobs' runtimes in the history, for the next run's scheduling policy
+    pub fn set_record_runtimes(&mut self, record: bool) {
+        self.evaluator.set_record_runtimes(record);
+    }
+
+    /// record when jobs last succeeded (and in which run) in the history
+    pub fn set_record_last_success(&mut self, record: bool) {
+        self.evaluator.set_record_last_success(record);
+    }
+
+    pub fn query_run_id(&self) -> u64 {
+        self.evaluator.query_run_id()
+    }
+
+    /// (unix seconds, run id) of the job's last success, or None
+    pub fn last_success(&self, job_id: &str) -> PyResult<Option<(f64, u64)>> {
+        Ok(self
+            .evaluator
+            .last_success(job_id)
+            .map_err(py_err)?
+            .map(|last| {
+                let seconds = last
+                    .finished
+                    .duration_since(UNIX_EPOCH)
+                    .map(|x| x.as_secs_f64())
+                    .unwrap_or(0.0);
+                (seconds, last.run_id)
+            }))
+    }
+
+    /// jobs that haven't succeeded since unix_seconds, sorted
+    pub fn jobs_not_built_since(&self, unix_seconds: f64) -> Vec<String> {
+        self.evaluator
+            .jobs_not_built_since(UNIX_EPOCH + Duration::from_secs_f64(unix_seconds.max(0.0)))
+    }
+
+    pub fn set_required_cores(&mut self, job_id: &str, cores: usize) -> Result<(), PyErr> {
+        self.evaluator
+            .set_required_cores(job_id, cores)
+            .map_err(py_err)
+    }
+
+    pub fn set_required_memory(&mut self, job_id: &str, bytes: u64) -> Result<(), PyErr> {
+        self.evaluator
+            .set_required_memory(job_id, bytes)
+            .map_err(py_err)
+    }
+
+    /// {timeline: [(at, cores, memory, running, ready)], peak_cores, peak_memory,
+    /// average_cores, average_occupancy, idle_gaps: [(start, end)]} - cores defaults to all of them
+    pub fn utilization(&self, py: Python, cores: Option<usize>) -> PyResult<PyObject> {
+        let report = self
+            .evaluator
+            .query_utilization(cores.unwrap_or_else(num_cpus::get));
+        let timeline: Vec<(f64, usize, u64, usize, usize)> = report
+            .timeline
+            .into_iter()
+            .map(|p| (p.at, p.cores, p.memory, p.running, p.ready))
+            .collect();
+        let res = PyDict::new(py);
+        res.set_item("timeline", timeline)?;
+        res.set_item("peak_cores", report.peak_cores)?;
+        res.set_item("peak_memory", report.peak_memory)?;
+        res.set_item("average_cores", report.average_cores)?;
+        res.set_item("average_occupancy", report.average_occupancy)?;
+        res.set_item("idle_gaps", report.idle_gaps)?;
+        Ok(res.into())
+    }
+
+    /// ready jobs expected to finish within window_seconds on free_cores,
+    /// to fill the gap while a wide job waits for cores
+    pub fn backfill_candidates(&self, free_cores: usize, window_seconds: f64) -> Vec<String> {
+        self.evaluator
+            .query_backfill_candidates(free_cores, Duration::from_secs_f64(window_seconds.max(0.0)))
+    }
+
+    /// run this job alone - jobs_ready_to_run holds it (or everything else) back
+    pub fn set_exclusive(&mut self, job_id: &str, exclusive: bool) -> Result<(), PyErr> {
+        let res = self.evaluator.set_exclusive(job_id, exclusive);
+        // may have released held back jobs
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    pub fn list_exclusive(&self) -> Vec<String> {
+        self.evaluator.query_exclusive().into_iter().collect()
+    }
+
+    /// demote this job - only handed out when no less nice job is ready. 0 is normal
+    pub fn set_nice(&mut self, job_id: &str, nice: u32) -> Result<(), PyErr> {
+        let res = self.evaluator.set_nice(job_id, nice);
+        // may have released held back jobs
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    pub fn require_capability(&mut self, job_id: &str, capability: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .require_capability(job_id, capability)
+            .map_err(py_err)
+    }
+
+    /// jobs_ready_to_run a worker with these capabilities can execute
+    pub fn jobs_ready_to_run_for(&self, capabilities: Vec<String>) -> Vec<String> {
+        let capabilities: Vec<&str> = capabilities.iter().map(|x| x.as_str()).collect();
+        self.evaluator.query_ready_to_run_for(&capabilities)
+    }
+
+    pub fn tag_job(&mut self, job_id: &str, tag: &str) -> Result<(), PyErr> {
+        self.evaluator.tag_job(job_id, tag).map_err(py_err)
+    }
+
+    /// hand out at most max_jobs jobs tagged with tag per window_seconds
+    pub fn set_rate_limit(
+        &mut self,
+        tag: &str,
+        max_jobs: usize,
+        window_seconds: f64,
+    ) -> Result<(), PyErr> {
+        if !(window_seconds > 0.0 && window_seconds.is_finite()) {
+            return Err(PyValueError::new_err("window_seconds must be > 0"));
+        }
+        self.evaluator
+            .set_rate_limit(tag, max_jobs, Duration::from_secs_f64(window_seconds))
+            .map_err(py_err)
+    }
+
+    pub fn remove_rate_limit(&mut self, tag: &str) {
+        self.evaluator.remove_rate_limit(tag);
+        self.state_changed();
+    }
+
+    /// seconds until a rate limit admits another ready job, None if none is held back
+    pub fn rate_limit_wait(&self) -> Option<f64> {
+        self.evaluator
+            .query_rate_limit_wait()
+            .map(|wait| wait.as_secs_f64())
+    }
+
+    pub fn jobs_running(&self) -> Vec<String> {
+        self.evaluator.query_jobs_running().into_iter().collect()
+    }
+
+    /// job_id -> seconds since event_now_running, for cross checking
+    /// the executor's process table against the engine's view
+    pub fn jobs_currently_running(&self) -> HashMap<String, f64> {
+        self.evaluator
+            .query_running_durations()
+            .into_iter()
+            .map(|(job_id, running_for)| (job_id, running_for.as_secs_f64()))
+            .collect()
+    }
+
+    pub fn jobs_ready_for_cleanup(&self) -> Vec<String> {
+        self.evaluator
+            .query_ready_for_cleanup()
+            .into_iter()
+            .collect()
+    }
+
+    /// bytes_freed: how much space the cleanup reclaimed, if known
+    pub fn event_job_cleanup_done(
+        &mut self,
+        job_id: &str,
+        bytes_freed: Option<u64>,
+    ) -> Result<(), PyErr> {
+        let res = match bytes_freed {
+            Some(bytes_freed) => self
+                .evaluator
+                .event_job_cleanup_done_with_size(job_id, bytes_freed),
+            None => self.evaluator.event_job_cleanup_done(job_id),
+        };
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    /// {run_cleanups, run_bytes_freed, total_cleanups, total_bytes_freed}
+    pub fn cleanup_stats(&self) -> HashMap<&'static str, u64> {
+        let stats = self.evaluator.cleanup_stats();
+        let mut res = HashMap::new();
+        res.insert("run_cleanups", stats.run_cleanups as u64);
+        res.insert("run_bytes_freed", stats.run_bytes_freed);
+        res.insert("total_cleanups", stats.total_cleanups as u64);
+        res.insert("total_bytes_freed", stats.total_bytes_freed);
+        res
+    }
+
+    pub fn set_expected_output_size(&mut self, job_id: &str, bytes: u64) -> Result<(), PyErr> {
+        self.evaluator
+            .set_expected_output_size(job_id, bytes)
+            .map_err(py_err)
+    }
+
+    /// don't offer this ephemeral for cleanup until unpin
+    pub fn pin(&mut self, job_id: &str) -> Result<(), PyErr> {
+        self.evaluator.pin(job_id).map_err(py_err)
+    }
+
+    pub fn unpin(&mut self, job_id: &str) -> Result<(), PyErr> {
+        let res = self.evaluator.unpin(job_id);
+        // may have become ready for cleanup
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    pub fn list_pinned(&self) -> Vec<String> {
+        self.evaluator.query_pinned().into_iter().collect()
+    }
+
+    /// keep this ephemeral's output for 'runs' further successful runs after it ran
+    pub fn set_retention(&mut self, job_id: &str, runs: u32) -> Result<(), PyErr> {
+        self.evaluator.set_retention(job_id, runs).map_err(py_err)
+    }
+
+    /// retained ephemeral outputs to remove now that this run finished
+    pub fn list_expired_retentions(&self) -> Vec<String> {
+        self.evaluator.query_expired_retentions()
+    }
+
+    /// jobs_ready_for_cleanup, largest declared output size first
+    pub fn jobs_ready_for_cleanup_by_size(&self) -> Vec<String> {
+        self.evaluator.query_ready_for_cleanup_by_size()
+    }
+
+    pub fn is_finished(&mut self) -> bool {
+        self.evaluator.is_finished()
+    }
+
+    /// 'not_finished', 'success', 'nothing_to_do', 'failures', 'aborted'
+    /// or 'contract_violation' ('unknown' for states newer than this shim)
+    /// - for picking exit code and message
+    pub fn finish_state(&self) -> &'static str {
+        match self.evaluator.finish_state() {
+            FinishState::NotFinished => "not_finished",
+            FinishState::Success => "success",
+            FinishState::NothingToDo => "nothing_to_do",
+            FinishState::FinishedWithFailures => "failures",
+            FinishState::Aborted => "aborted",
+            FinishState::ContractViolation => "contract_violation",
+            _ => "unknown",
+        }
+    }
+
+    /// (depth, nodes visited, seconds) for the most recent propagation passes
+    pub fn evaluation_stats(&self) -> Vec<(u32, usize, f64)> {
+        self.evaluator
+            .evaluation_stats()
+            .recent_passes()
+            .map(|p| (p.depth, p.nodes_visited, p.duration.as_secs_f64()))
+            .collect()
+    }
+
+    /// (passes, nodes visited, seconds) over the whole evaluation
+    pub fn evaluation_totals(&self) -> (usize, usize, f64) {
+        let stats = self.evaluator.evaluation_stats();
+        (
+            stats.pass_count(),
+            stats.nodes_visited(),
+            stats.duration().as_secs_f64(),
+        )
+    }
+
+    /// (entries, unique values, bytes stored, bytes without interning)
+    pub fn history_stats(&self) -> (usize, usize, usize, usize) {
+        let stats = self.evaluator.history_stats();
+        (
+            stats.entries,
+            stats.unique_values,
+            stats.value_bytes,
+            stats.logical_value_bytes,
+        )
+    }
+
+    /// job counts per state: pending, running, succeeded, failed, upstream_failed, skipped, aborted
+    pub fn progress(&self) -> HashMap<&'static str, usize> {
+        let progress = self.evaluator.progress();
+        vec![
+            ("pending", progress.pending),
+            ("running", progress.running),
+            ("succeeded", progress.succeeded),
+            ("failed", progress.failed),
+            ("upstream_failed", progress.upstream_failed),
+            ("skipped", progress.skipped),
+            ("aborted", progress.aborted),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    /// values are str or bytes - whatever they were handed in as
+    pub fn new_history(&self, py: Python) -> Result<HashMap<String, PyObject>, PyErr> {
+        Ok(self
+            .evaluator
+            .new_history()
+            .map_err(py_err)?
+            .into_iter()
+            .map(|(k, v)| {
+                let v = history_to_py(py, &v);
+                (k, v)
+            })
+            .collect())
+    }
+
+    pub fn get_job_output(&self, py: Python, job_id: &str) -> Result<PyObject, PyErr> {
+        match self.evaluator.get_job_output(job_id) {
+            JobOutputResult::Done(v) => Ok(history_to_py(py, &v)),
+            JobOutputResult::NoSuchJob => Err(PyKeyError::new_err("Invalid job id")),
+            JobOutputResult::NotDone => Err(PyValueError::new_err("job not done")),
+        }
+    }
+
+    // pickling: the state round-trips as a json EvaluatorSnapshot.
+    // The python callbacks (and a strategy recording) are not part of it -
+    // an unpickled evaluator compares histories in rust until
+    // set_strategy_callbacks is called.
+    // no 'py' arguments - pickle calls these without an args vector
+    pub fn __getnewargs__(&self) -> (PyObject,) {
+        Python::with_gil(|py| (PyDict::new(py).into(),))
+    }
+
+    pub fn __getstate__(&self) -> Result<PyObject, PyErr> {
+        let snapshot = self.evaluator.snapshot().map_err(py_err)?;
+        Ok(Python::with_gil(|py| {
+            pyo3::types::PyBytes::new(py, snapshot.to_json().as_bytes()).into()
+        }))
+    }
+
+    pub fn __setstate__(&mut self, py: Python, state: &[u8]) -> Result<(), PyErr> {
+        let json = std::str::from_utf8(state)
+            .map_err(|_| PyValueError::new_err("Evaluator state is not utf-8"))?;
+        let snapshot = EvaluatorSnapshot::from_json(json).map_err(py_err)?;
+        let strategy = self.evaluator.strategy().clone_callbacks(py);
+        self.evaluator = PPGEvaluator::from_snapshot(snapshot, strategy).map_err(py_err)?;
+        Ok(())
+    }
+
+    /// One compressed blob to attach to bug reports - see load_debug_dump
+    pub fn dump_debug(&self, py: Python) -> Result<PyObject, PyErr> {
+        let blob = self.evaluator.dump_debug().map_err(py_err)?;
+        Ok(pyo3::types::PyBytes::new(py, &blob).into())
+    }
+
+    /// Replace the state with that of a dump_debug blob - keeps the callbacks
+    pub fn load_debug_dump(&mut self, py: Python, blob: &[u8]) -> Result<(), PyErr> {
+        let strategy = self.evaluator.strategy().clone_callbacks(py);
+        self.evaluator = PPGEvaluator::load_debug_dump(blob, strategy).map_err(py_err)?;
+        Ok(())
+    }
+
+    /// The last events handled and their outcome, oldest first
+    pub fn query_recent_events(&self) -> Vec<String> {
+        self.evaluator.query_recent_events()
+    }
+
+    pub fn set_strategy_callbacks(
+        &mut self,
+        history_compare_callable: Option<PyObject>,
+        get_job_inputs_str_callback: Option<PyObject>,
+    ) {
+        self.evaluator.strategy_mut().history_altered_callback = history_compare_callable;
+        self.evaluator.strategy_mut().get_job_inputs_str_callback = get_job_inputs_str_callback;
+    }
+
+    /// only changes to these fields of upstream's (JSON object) history invalidate downstream
+    pub fn set_edge_fields(
+        &mut self,
+        downstream: &str,
+        upstream: &str,
+        fields: Vec<&str>,
+    ) -> PyResult<()> {
+        self.evaluator
+            .set_edge_fields(downstream, upstream, &fields)
+            .map_err(py_err)
+    }
+
+    /// Always / Conditional jobs only - run once all upstreams are done, failed or not
+    pub fn set_run_even_if_upstreams_failed(&mut self, job_id: &str, run: bool) -> PyResult<()> {
+        self.evaluator
+            .set_run_even_if_upstreams_failed(job_id, run)
+            .map_err(py_err)
+    }
+
+    /// direct upstreams of job_id that failed, sorted
+    pub fn query_failed_upstreams(&self, job_id: &str) -> PyResult<Vec<String>> {
+        self.evaluator
+            .query_failed_upstreams(job_id)
+            .map_err(py_err)
+    }
+
+    /// Gate an Always job: run at most every every_seconds, or only when
+    /// the invariant's value changed. Neither removes the gate.
+    pub fn set_always_gate(
+        &mut self,
+        job_id: &str,
+        every_seconds: Option<f64>,
+        invariant: Option<&str>,
+    ) -> PyResult<()> {
+        let gate = match (every_seconds, invariant) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "Pass either every_seconds or invariant, not both",
+                ))
+            }
+            (Some(seconds), None) => Some(AlwaysGate::Interval(Duration::from_secs_f64(
+                seconds.max(0.0),
+            ))),
+            (None, Some(invariant)) => Some(AlwaysGate::InvariantChanged(invariant.to_string())),
+            (None, None) => None,
+        };
+        self.evaluator.set_always_gate(job_id, gate).map_err(py_err)
+    }
+
+    /// Gated Always jobs held back this run
+    pub fn query_gated_jobs(&self) -> Vec<String> {
+        self.evaluator.query_gated_jobs()
+    }
+
+    /// producers: Output jobs writing the same artifact, most preferred first.
+    /// The first to succeed satisfies the downstreams, failures fall back to the next.
+    pub fn add_shared_output(&mut self, group: &str, producers: Vec<String>) -> PyResult<()> {
+        let producers: Vec<&str> = producers.iter().map(|x| x.as_str()).collect();
+        self.evaluator
+            .add_shared_output(group, &producers)
+            .map_err(py_err)
+    }
+
+    /// the producer whose output the group's downstreams got this run
+    pub fn query_shared_output_producer(&self, group: &str) -> Option<String> {
+        self.evaluator.query_shared_output_producer(group)
+    }
+
+    /// job_id -> bool, deciding whether a ready Conditional job runs
+    pub fn set_should_run_callback(&mut self, should_run_callable: Option<PyObject>) {
+        self.evaluator.strategy_mut().should_run_callback = should_run_callable;
+    }
+
+    /// Conditional jobs the should_run callback skipped this run
+    pub fn query_skipped_conditionals(&self) -> Vec<String> {
+        self.evaluator.query_skipped_conditionals()
+    }
+
+    pub fn debug(&self) -> String {
+        self.evaluator.debug_()
+    }
+
+    /// metadata: str, or anything json.dumps takes (dicts...). None removes it.
+    pub fn set_job_metadata(
+        &mut self,
+        py: Python,
+        job_id: &str,
+        metadata: Option<PyObject>,
+    ) -> PyResult<()> {
+        let metadata = match metadata {
+            None => None,
+            Some(obj) => match obj.extract::<String>(py) {
+                Ok(text) => Some(text),
+                Err(_) => Some(
+                    py.import("json")?
+                        .call_method1("dumps", (obj,))?
+                        .extract::<String>()?,
+                ),
+            },
+        };
+        self.evaluator
+            .set_job_metadata(job_id, metadata)
+            .map_err(py_err)
+    }
+
+    pub fn query_job_metadata(&self, job_id: &str) -> PyResult<Option<String>> {
+        Ok(self
+            .evaluator
+            .query_job_metadata(job_id)
+            .map_err(py_err)?
+            .map(|x| x.to_string()))
+    }
+
+    /// Raise if a job was started before an upstream that ran had finished
+    pub fn verify_event_order(&self) -> Result<(), PyErr> {
+        self.evaluator.verify_event_order().map_err(py_err)
+    }
+
+    /// the graph in graphviz' dot format
+    pub fn render_dot(&self) -> String {
+        self.evaluator.render_dot()
+    }
+
+    pub fn write_report(&self, path: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .write_report(path)
+            .map_err(|e| PyValueError::new_err(format!("Could not write report: {}", e)))
+    }
+
+    /// {'failed': [{'job_id', 'error', 'doomed_downstreams', 'metadata'}], 'root_causes': [...],
+    ///  'upstream_failed': count, 'retry_candidates': [...]}
+    pub fn failure_report(&self, py: Python) -> PyResult<PyObject> {
+        let report = self.evaluator.failure_report();
+        let failed: PyResult<Vec<PyObject>> = report
+            .failed
+            .into_iter()
+            .map(|job| {
+                let res = PyDict::new(py);
+                res.set_item("job_id", job.job_id)?;
+                res.set_item("error", job.error)?;
+                res.set_item("doomed_downstreams", job.doomed_downstreams)?;
+                res.set_item("metadata", job.metadata)?;
+                Ok(res.into())
+            })
+            .collect();
+        let res = PyDict::new(py);
+        res.set_item("failed", failed?)?;
+        res.set_item("root_causes", report.root_causes)?;
+        res.set_item("upstream_failed", report.upstream_failed)?;
+        res.set_item("retry_candidates", report.retry_candidates)?;
+        Ok(res.into())
+    }
+
+    /// {column name: list} of the per-job run records -
+    /// pypipegraph2.run_records turns it into an Arrow table / Parquet file
+    /// per failed job: attempts, started / failed (unix seconds),
+    /// upstream_fingerprint and error - sorted by job id
+    pub fn query_failed_info(&self, py: Python) -> PyResult<Vec<PyObject>> {
+        self.evaluator
+            .query_failed_info()
+            .into_iter()
+            .map(|info| {
+                let res = PyDict::new(py);
+                res.set_item("job_id", info.job_id)?;
+                res.set_item("attempts", info.attempts)?;
+                res.set_item("started", info.started)?;
+                res.set_item("failed", info.failed)?;
+                res.set_item("upstream_fingerprint", info.upstream_fingerprint)?;
+                res.set_item("error", info.error)?;
+                Ok(res.into())
+            })
+            .collect()
+    }
+
+    pub fn run_records(&self, py: Python) -> PyResult<PyObject> {
+        let columns = self.evaluator.run_record_columns();
+        let res = PyDict::new(py);
+        res.set_item("job_id", columns.job_id)?;
+        res.set_item("kind", columns.kind)?;
+        res.set_item("state", columns.state)?;
+        res.set_item("start", columns.start)?;
+        res.set_item("end", columns.end)?;
+        res.set_item("runtime", columns.runtime)?;
+        res.set_item("attempt", columns.attempt)?;
+        res.set_item("invalidation_reason", columns.invalidation_reason)?;
+        Ok(res.into())
+    }
+
+    /// the run as OTLP/JSON, for an OpenTelemetry collector
+    #[cfg(feature = "otel")]
+    pub fn write_otel_trace(&self, path: &str, service_name: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .write_otel_trace(path, service_name)
+            .map_err(|e| PyValueError::new_err(format!("Could not write trace: {}", e)))
+    }
+
+    /// level is one of "off", "error", "warn", "info", "debug", "trace"
+    pub fn set_log_level(&mut self, level: &str) -> Result<(), PyErr> {
+        self.evaluator.set_log_level(parse_log_level(level)?);
+        Ok(())
+    }
+
+    /// present outputs without history count as up to date - set before event_startup.
+    /// Only rebuilds history in rust fingerprint mode (no history compare callback)
+    pub fn set_assume_unchanged(&mut self, assume_unchanged: bool) {
+        self.evaluator.set_assume_unchanged(assume_unchanged);
+    }
+
+    /// rerun every job (history is still recorded) - set before event_startup
+    pub fn set_force_rerun_all(&mut self, force: bool) {
+        self.evaluator.set_force_rerun_all(force);
+    }
+
+    /// restrict / widen the engine diagnostics for job ids matching pattern ('*' wildcards)
+    pub fn set_job_log_level(&mut self, pattern: &str, level: &str) -> Result<(), PyErr> {
+        self.evaluator
+            .set_job_log_level(pattern, parse_log_level(level)?);
+        Ok(())
+    }
+
+    pub fn debug_is_finished(&self) {
+        self.evaluator.debug_is_finished();
+    }
+
+    pub fn reconsider_all_jobs(&mut self) -> Result<(), PyErr> {
+        error!("Reconsidering all jobs!");
+        let res = self.evaluator.reconsider_all_jobs();
+        self.state_changed();
+        res.map_err(py_err)
+    }
+
+    pub fn event_abort(&mut self) -> Result<(), PyErr> {
+        let res = self.evaluator.abort_remaining();
+        self.state_changed();
+        res.map_err(py_err)
+    }
+}
+
+// how often a blocked waiter wakes up to check for KeyboardInterrupt
+const READY_WAIT_SLICE: Duration = Duration::from_millis(100);
+
+/// Run check after every event until it returns Some,
+/// without holding the GIL while waiting. Ok(None) on timeout.
+fn wait_for_evaluator<R>(
+    py: Python,
+    evaluator: &Py<PyPPG2Evaluator>,
+    notify: &Arc<ReadyNotify>,
+    timeout: Option<Duration>,
+    mut check: impl FnMut(Python, &mut PyPPG2Evaluator) -> Option<R>,
+) -> PyResult<Option<R>> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        // read before looking, so a change in between wakes us right away
+        let seen = notify.current();
+        // borrowed elsewhere = another thread is in the middle of an event
+        if let Ok(mut evaluator) = evaluator.as_ref(py).try_borrow_mut() {
+            if let Some(res) = check(py, &mut evaluator) {
+                return Ok(Some(res));
+            }
+        }
+        let wait = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::ZERO {
+                    return Ok(None);
+                }
+                remaining.min(READY_WAIT_SLICE)
+            }
+            None => READY_WAIT_SLICE,
+        };
+        let notify = notify.clone();
+        py.allow_threads(move || notify.wait_for_change(seen, wait));
+        py.check_signals()?;
+    }
+}
+
+/// SharedHistory for python: open(path, lock_timeout_seconds=None)
+#[pyclass(name = "SharedHistory", module = "pypipegraph2.pypipegraph2")]
+pub struct PySharedHistory {
+    history: SharedHistory,
+}
+
+fn history_io_error(e: std::io::Error) -> PyErr {
+    match e.kind() {
+        std::io::ErrorKind::TimedOut => PyTimeoutError::new_err(e.to_string()),
+        _ => PyValueError::new_err(format!("Shared history: {}", e)),
+    }
+}
+
+#[pymethods]
+impl PySharedHistory {
+    #[new]
+    pub fn new(path: &str, lock_timeout_seconds: Option<f64>) -> Result<Self, PyErr> {
+        let history = match lock_timeout_seconds {
+            Some(timeout) => SharedHistory::open_with_lock_timeout(
+                path,
+                Duration::from_secs_f64(timeout.max(0.0)),
+            ),
+            None => SharedHistory::open(path),
+        }
+        .map_err(history_io_error)?;
+        Ok(PySharedHistory { history })
+    }
+
+    #[getter]
+    pub fn run_id(&self) -> u64 {
+        self.history.run_id()
+    }
+
+    pub fn history(&self) -> HashMap<String, String> {
+        self.history.history()
+    }
+
+    /// merge new_history in - returns the conflicts as (key, ours, theirs, kept_ours)
+    #[allow(clippy::type_complexity)]
+    pub fn store(
+        &mut self,
+        new_history: HashMap<String, String>,
+    ) -> Result<Vec<(String, Option<String>, Option<String>, bool)>, PyErr> {
+        let conflicts = self.history.store(&new_history).map_err(history_io_error)?;
+        Ok(conflicts
+            .into_iter()
+            .map(|c| (c.key, c.ours, c.theirs, c.kept_ours))
+            .collect())
+    }
+}
+
+#[pyclass]
+pub struct ReadyJobsIter {
+    evaluator: Py<PyPPG2Evaluator>,
+    notify: Arc<ReadyNotify>,
+    yielded: HashSet<String>,
+    timeout: Option<Duration>,
+}
+
+impl ReadyJobsIter {
+    fn next_ready(&mut self, py: Python) -> PyResult<Option<String>> {
+        let yielded = &mut self.yielded;
+        let next = wait_for_evaluator(py, &self.evaluator, &self.notify, self.timeout, |_, e| {
+            if let Err(err) = e.apply_reported_events(py) {
+                return Some(Err(err));
+            }
+            let next = e
+                .evaluator
+                .query_ready_to_run_ordered()
+                .into_iter()
+                .find(|job_id| !yielded.contains(job_id));
+            match next {
+                Some(job_id) => {
+                    yielded.insert(job_id.clone());
+                    Some(Ok(Some(job_id)))
+                }
+                None if e.evaluator.is_finished() => Some(Ok(None)),
+                None => None,
+            }
+        })?;
+        next.ok_or_else(|| PyTimeoutError::new_err("No job became ready in time"))?
+    }
+}
+
+/// Resolve an asyncio future - on its event loop, see spawn_waiter.
+#[pyfunction]
+fn resolve_future(future: &PyAny, value: PyObject, is_error: bool) -> PyResult<()> {
+    // cancelled meanwhile
+    if future.call_method0("done")?.is_true()? {
+        return Ok(());
+    }
+    let method = if is_error {
+        "set_exception"
+    } else {
+        "set_result"
+    };
+    future.call_method1(method, (value,))?;
+    Ok(())
+}
+
+/// Asyncio variant of wait_for_evaluator: returns a future of the running loop,
+/// resolved from the evaluator's waiter thread (see AsyncWaiters).
+fn spawn_waiter(
+    py: Python,
+    slf: PyRef<PyPPG2Evaluator>,
+    timeout: Option<f64>,
+    check: impl FnMut(Python, &mut PyPPG2Evaluator) -> Option<PyObject> + Send + 'static,
+) -> PyResult<PyObject> {
+    let event_loop: PyObject = py
+        .import("asyncio")?
+        .call_method0("get_running_loop")?
+        .into();
+    let future: PyObject = event_loop.call_method0(py, "create_future")?;
+    let waiters = slf.async_waiters.clone();
+    let notify = slf.ready_notify.clone();
+    let start_thread = waiters.add(AsyncWaiter {
+        event_loop,
+        future: future.clone_ref(py),
+        deadline: timeout.map(|timeout| Instant::now() + Duration::from_secs_f64(timeout)),
+        check: Box::new(check),
+    });
+    if start_thread {
+        let evaluator: Py<PyPPG2Evaluator> = slf.into();
+        std::thread::spawn(move || serve_async_waiters(evaluator, notify, waiters));
+    } else {
+        // wake the thread, so it checks the new one right away
+        notify.bump();
+    }
+    Ok(future)
+}
+
+/// The waiter thread: checks every pending future after each event,
+/// resolves them on their event loop.
+fn serve_async_waiters(
+    evaluator: Py<PyPPG2Evaluator>,
+    notify: Arc<ReadyNotify>,
+    waiters: Arc<AsyncWaiters>,
+) {
+    loop {
+        // read before looking, so a change in between wakes us right away
+        let seen = notify.current();
+        let wait = Python::with_gil(|py| {
+            let pending = check_async_waiters(py, &evaluator, waiters.take());
+            let now = Instant::now();
+            // cancelling a future is no event - look again every slice
+            let wait = pending
+                .iter()
+                .filter_map(|waiter| waiter.deadline)
+                .map(|deadline| deadline.saturating_duration_since(now))
+                .fold(READY_WAIT_SLICE, Duration::min);
+            match waiters.put_back(pending) {
+                true => Some(wait),
+                false => None,
+            }
+        });
+        match wait {
+            Some(wait) => notify.wait_for_change(seen, wait),
+            None => return,
+        }
+    }
+}
+
+/// Resolve the waiters that are done, return the others.
+fn check_async_waiters(
+    py: Python,
+    evaluator: &Py<PyPPG2Evaluator>,
+    waiters: Vec<AsyncWaiter>,
+) -> Vec<AsyncWaiter> {
+    // borrowed elsewhere = another thread is in the middle of an event
+    let mut evaluator = evaluator.as_ref(py).try_borrow_mut().ok();
+    let mut pending = Vec::new();
+    for mut waiter in waiters {
+        let cancelled = waiter
+            .future
+            .call_method0(py, "done")
+            .and_then(|done| done.is_true(py))
+            .unwrap_or(true);
+        if cancelled {
+            continue;
+        }
+        let res = match evaluator.as_mut() {
+            Some(evaluator) => (waiter.check)(py, evaluator),
+            None => None,
+        };
+        let (value, is_error) = match res {
+            Some(value) => (value, false),
+            None if waiter
+                .deadline
+                .is_some_and(|deadline| deadline <= Instant::now()) =>
+            {
+                (
+                    PyTimeoutError::new_err("Evaluator wait timed out").into_py(py),
+                    true,
+                )
+            }
+            None => {
+                pending.push(waiter);
+                continue;
+            }
+        };
+        let scheduled = wrap_pyfunction!(resolve_future, py).and_then(|resolve| {
+            waiter.event_loop.call_method1(
+                py,
+                "call_soon_threadsafe",
+                (resolve, waiter.future, value, is_error),
+            )
+        });
+        // loop closed - nobody is waiting
+        if let Err(e) = scheduled {
+            debug!("Could not resolve evaluator future: {}", e);
+        }
+    }
+    pending
+}
+
+#[pyproto]
+impl pyo3::PyIterProtocol for ReadyJobsIter {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<String>> {
+        Python::with_gil(|py| slf.next_ready(py))
+    }
+}
+
+/// Enable rust side logging.
+/// mode is "pretty" (default, colorized unless logging to a file) or "json" (JSON lines)
+#[pyfunction]
+fn enable_logging(mode: Option<&str>, filename: Option<&str>) -> PyResult<()> {
+    match (mode.unwrap_or("pretty"), filename) {
+        ("pretty", None) => start_logging(),
+        ("pretty", Some(filename)) => start_logging_to_file(filename),
+        ("json", filename) => start_logging_json(filename),
+        (other, _) => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid logging mode {}, expected 'pretty' or 'json'",
+                other
+            )))
+        }
+    }
+    error!("hello from rust");
+    Ok(())
+}
+/// Formats the sum of two numbers as string.
+#[pyfunction]
+fn enable_logging_to_file(filename: &str) -> PyResult<()> {
+    start_logging_to_file(filename);
+    error!("hello from rust");
+    Ok(())
+}
+
+/// The history value (mtime/size[/xxh3] fingerprint) for a (multi ':::') file job,
+/// as understood by the rust side history comparison.
+#[pyfunction]
+fn fingerprint_outputs(job_id: &str, hash_contents: Option<bool>) -> PyResult<String> {
+    StrategyFileSystem::new(hash_contents.unwrap_or(false))
+        .fingerprint(job_id)
+        .map_err(|e| PyValueError::new_err(format!("Could not fingerprint {}: {}", job_id, e)))
+}
+
+/// ppg2 history from the json dump of a ppg1 history (format: see src/migrate.rs).
+/// Returns (history, [(upstream, downstream) without upstream value]).
+#[pyfunction(name = "import_ppg1_history")]
+#[allow(clippy::type_complexity)]
+fn py_import_ppg1_history(
+    dump: &str,
+) -> PyResult<(HashMap<String, String>, Vec<(String, String)>)> {
+    let res = import_ppg1_history(dump).map_err(PyValueError::new_err)?;
+    Ok((res.history, res.missing_upstreams))
+}
+
+/// Integrity problems of a history: [(kind, keys, message)]
+#[pyfunction(name = "verify_history")]
+fn py_verify_history(history: HashMap<String, String>) -> Vec<(String, Vec<String>, String)> {
+    verify_history(&history)
+        .into_iter()
+        .map(|issue| (format!("{:?}", issue.kind), issue.keys, issue.message))
+        .collect()
+}
+
+/// A Python module implemented in Rust.
+/// cargo features and whether this build has them
+const ENGINE_FEATURES: &[(&str, bool)] = &[
+    ("petgraph-backend", cfg!(feature = "petgraph-backend")),
+    ("otel", cfg!(feature = "otel")),
+    ("no-debug-logging", cfg!(feature = "no-debug-logging")),
+    ("debug-invariants", cfg!(feature = "debug-invariants")),
+];
+
+/// Crate version, enabled cargo features and the job kinds add_node accepts,
+/// for feature detection in the python frontend.
+#[pyfunction]
+fn engine_info(py: Python) -> PyResult<PyObject> {
+    let info = PyDict::new(py);
+    info.set_item("version", env!("CARGO_PKG_VERSION"))?;
+    let features: Vec<&str> = ENGINE_FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    info.set_item("features", features)?;
+    let job_kinds: Vec<&str> = JOB_KINDS.iter().map(|(name, _)| *name).collect();
+    info.set_item("job_kinds", job_kinds)?;
+    Ok(info.into())
+}
 
-/// A Python module implemented in Rust.
 #[pymodule]
-fn pypipegraph2(_py: Python, m: &PyModule) -> PyResult<()> {
+fn pypipegraph2(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(enable_logging, m)?)?;
     m.add_function(wrap_pyfunction!(enable_logging_to_file, m)?)?;
+    m.add_function(wrap_pyfunction!(fingerprint_outputs, m)?)?;
+    m.add_function(wrap_pyfunction!(engine_info, m)?)?;
+    m.add_function(wrap_pyfunction!(py_import_ppg1_history, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_history, m)?)?;
     m.add_class::<PyPPG2Evaluator>()?;
+    m.add_class::<ReadyJobsIter>()?;
+    m.add_class::<PyEventReporter>()?;
+    m.add_class::<PySharedHistory>()?;
+    m.add("PPGStrategyError", py.get_type::<PPGStrategyError>())?;
+    let errors = PyModule::new(py, "errors")?;
+    errors.add("CycleError", py.get_type::<CycleError>())?;
+    errors.add(
+        "JobRedefinitionError",
+        py.get_type::<JobRedefinitionError>(),
+    )?;
+    errors.add("NotRunningError", py.get_type::<NotRunningError>())?;
+    errors.add(
+        "ContractViolationError",
+        py.get_type::<ContractViolationError>(),
+    )?;
+    errors.add("StalledError", py.get_type::<StalledError>())?;
+    errors.add("StrategyError", py.get_type::<PPGStrategyError>())?;
+    m.add_submodule(errors)?;
     Ok(())
 }
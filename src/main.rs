@@ -3,17 +3,17 @@ use pypipegraph2::{
 };
 fn test_fuzz_3() {
     fn create_graph(g: &mut PPGEvaluator<StrategyForTesting>) {
-        g.add_node("N0", JobKind::Always);
-        g.add_node("N1", JobKind::Ephemeral);
-        g.add_node("N2", JobKind::Ephemeral);
-        g.add_node("N3", JobKind::Ephemeral);
-        g.add_node("N4", JobKind::Output);
-        g.depends_on("N4", "N0");
-        g.depends_on("N2", "N1");
-        g.depends_on("N3", "N1");
-        g.depends_on("N4", "N1");
-        g.depends_on("N3", "N2");
-        g.depends_on("N4", "N3");
+        g.add_node("N0", JobKind::Always).unwrap();
+        g.add_node("N1", JobKind::Ephemeral).unwrap();
+        g.add_node("N2", JobKind::Ephemeral).unwrap();
+        g.add_node("N3", JobKind::Ephemeral).unwrap();
+        g.add_node("N4", JobKind::Output).unwrap();
+        g.depends_on("N4", "N0").unwrap();
+        g.depends_on("N2", "N1").unwrap();
+        g.depends_on("N3", "N1").unwrap();
+        g.depends_on("N4", "N1").unwrap();
+        g.depends_on("N3", "N2").unwrap();
+        g.depends_on("N4", "N3").unwrap();
     }
     let mut ro = TestGraphRunner::new(Box::new(create_graph));
     println!("first run");
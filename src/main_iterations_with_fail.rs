@@ -21,7 +21,7 @@ use backtrace::Backtrace;
 use std::cell::RefCell;
 
 thread_local! {
-    static BACKTRACE: RefCell<Option<Backtrace>> = RefCell::new(None);
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
 }
 
 #[derive(Clone)]
@@ -32,7 +32,7 @@ struct AllNodes {
 impl AllNodes {
     fn new(node_count: usize) -> AllNodes {
         AllNodes {
-            state: vec![0; node_count as usize],
+            state: vec![0; node_count],
         }
     }
     fn apply(&self, g: &mut PPGEvaluator<StrategyForTesting>, fails: &AllFails) {
@@ -44,7 +44,7 @@ impl AllNodes {
                     2 => JobKind::Output,
                     _ => panic!(),
                 };
-                g.add_node(&format!("N{}", node_id), kind);
+                g.add_node(&format!("N{}", node_id), kind).unwrap();
             }
         }
     }
@@ -56,7 +56,7 @@ impl AllNodes {
                 2 => JobKind::Output,
                 _ => panic!(),
             };
-            g.add_node(&format!("N{}", node_id), kind);
+            g.add_node(&format!("N{}", node_id), kind).unwrap();
         }
     }
     fn advance(&mut self) -> bool {
@@ -67,18 +67,14 @@ impl AllNodes {
             if pos == 0 {
                 break;
             }
-            pos = pos - 1;
+            pos -= 1;
             self.state[pos] += 1;
         }
-        if self.state.iter().all(|x| *x == 0) {
-            return false;
-        } else {
-            return true;
-        }
+        !self.state.iter().all(|x| *x == 0)
     }
 
     fn len(&self) -> usize {
-        return 3usize.pow(self.state.len() as u32);
+        3usize.pow(self.state.len() as u32)
     }
 }
 
@@ -101,7 +97,7 @@ impl AllEdges {
         for n in 0..self.node_count {
             for m in (n + 1)..self.node_count {
                 if self.state[edge_pos] == 1 && fails.state[n] == 0 && fails.state[m] == 0 {
-                    g.depends_on(&format!("N{m}"), &format!("N{n}"));
+                    g.depends_on(&format!("N{m}"), &format!("N{n}")).unwrap();
                 }
                 edge_pos += 1;
             }
@@ -112,7 +108,7 @@ impl AllEdges {
         for n in 0..self.node_count {
             for m in (n + 1)..self.node_count {
                 if self.state[edge_pos] == 1 {
-                    g.depends_on(&format!("N{m}"), &format!("N{n}"));
+                    g.depends_on(&format!("N{m}"), &format!("N{n}")).unwrap();
                 }
                 edge_pos += 1;
             }
@@ -127,14 +123,10 @@ impl AllEdges {
             if pos == 0 {
                 break;
             }
-            pos = pos - 1;
+            pos -= 1;
             self.state[pos] += 1;
         }
-        if self.state.iter().all(|x| *x == 0) {
-            return false;
-        } else {
-            return true;
-        }
+        !self.state.iter().all(|x| *x == 0)
     }
 
     fn len(&self) -> usize {
@@ -160,7 +152,7 @@ impl AllFails {
                 jobs_to_fail.push(format!("N{}", node_id));
             }
         }
-        return jobs_to_fail;
+        jobs_to_fail
     }
 
     fn advance(&mut self) -> bool {
@@ -171,14 +163,10 @@ impl AllFails {
             if pos == 0 {
                 break;
             }
-            pos = pos - 1;
+            pos -= 1;
             self.state[pos] += 1;
         }
-        if self.state.iter().all(|x| *x == 0) {
-            return false;
-        } else {
-            return true;
-        }
+        !self.state.iter().all(|x| *x == 0)
     }
 
     fn len(&self) -> usize {
@@ -272,9 +260,7 @@ fn main() {
                         "done: {} of {}, elapsed: {}s. Rate {:.2}, estimated remaining: {:.2} s",
                         done, node_total, elapsed, rate * edges_per_node_variation as f64, eta
                     );
-                } else
-                {
-                }
+                } 
                 let mut all_edges = AllEdges::new(problem_size);
                 let mut edge_count = 0;
                 loop { //for every edge...
@@ -318,7 +304,7 @@ fn main() {
                             //part2: now all nodes are in the graph
                             let n2b = all_nodes.clone();
                             let m2b = all_edges.clone();
-                            t.setup_graph = Box::new(move |g| {
+                            t.set_setup_graph(move |g| {
                                 n2b.apply_(g);
                                 m2b.apply_(g);
                             });
@@ -359,14 +345,14 @@ fn main() {
                                 let m2 = all_edges.clone();
                                 let f2 = all_fails.clone();
                                 //se we can print them.
-                                let t1 = TestGraphRunner::new(Box::new(move |g| {
+                                let mut t1 = TestGraphRunner::new(Box::new(move |g| {
                                 n2.apply(g, &f2);
                                 m2.apply(g, &f2);
                                 }));
                                 let n2 = all_nodes.clone();
                                 let m2 = all_edges.clone();
 
-                                 let t2 = TestGraphRunner::new(Box::new(move |g| {
+                                 let mut t2 = TestGraphRunner::new(Box::new(move |g| {
                                     n2.apply_(g);
                                     m2.apply_(g);
                                  }));
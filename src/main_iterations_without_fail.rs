@@ -25,7 +25,7 @@ use backtrace::Backtrace;
 use std::cell::RefCell;
 
 thread_local! {
-    static BACKTRACE: RefCell<Option<Backtrace>> = RefCell::new(None);
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
 }
 
 #[derive(Clone)]
@@ -36,7 +36,7 @@ struct AllNodes {
 impl AllNodes {
     fn new(node_count: usize) -> AllNodes {
         AllNodes {
-            state: vec![0; node_count as usize],
+            state: vec![0; node_count],
         }
     }
     fn apply(&self, g: &mut PPGEvaluator<StrategyForTesting>) {
@@ -47,7 +47,7 @@ impl AllNodes {
                 2 => JobKind::Output,
                 _ => panic!(),
             };
-            g.add_node(&format!("N{}", node_id), kind);
+            g.add_node(&format!("N{}", node_id), kind).unwrap();
         }
     }
 
@@ -59,18 +59,14 @@ impl AllNodes {
             if pos == 0 {
                 break;
             }
-            pos = pos - 1;
+            pos -= 1;
             self.state[pos] += 1;
         }
-        if self.state.iter().all(|x| *x == 0) {
-            return false;
-        } else {
-            return true;
-        }
+        !self.state.iter().all(|x| *x == 0)
     }
 
     fn len(&self) -> usize {
-        return 3usize.pow(self.state.len() as u32);
+        3usize.pow(self.state.len() as u32)
         /*
         let mut m = AllNodes::new(self.state.len());
         let mut counter = 1;
@@ -101,7 +97,7 @@ impl AllEdges {
         for n in 0..self.node_count {
             for m in (n + 1)..self.node_count {
                 if self.state[edge_pos] == 1 {
-                    g.depends_on(&format!("N{m}"), &format!("N{n}"));
+                    g.depends_on(&format!("N{m}"), &format!("N{n}")).unwrap();
                 }
                 edge_pos += 1;
             }
@@ -115,14 +111,10 @@ impl AllEdges {
             if pos == 0 {
                 break;
             }
-            pos = pos - 1;
+            pos -= 1;
             self.state[pos] += 1;
         }
-        if self.state.iter().all(|x| *x == 0) {
-            return false;
-        } else {
-            return true;
-        }
+        !self.state.iter().all(|x| *x == 0)
     }
 
     fn len(&self) -> usize {
@@ -209,9 +201,7 @@ fn main() {
                         "done: {} of {}, elapsed: {}s. Rate {:.2}, estimated remaining: {:.2} s",
                         done, node_total, elapsed, rate * edges_per_node_variation as f64, eta
                     );
-                } else
-                {
-                }
+                } 
                 let mut m = AllEdges::new(problem_size);
                 let mut edge_count = 0;
                 loop {
@@ -273,7 +263,7 @@ if (edge_count == 24) {
                             errors.lock().unwrap().push((node_count, edge_count));
                             let n2 = n.clone();
                             let m2 = m.clone();
-                            let t = TestGraphRunner::new(Box::new(move |g| {
+                            let mut t = TestGraphRunner::new(Box::new(move |g| {
                                 n2.apply(g);
                                 m2.apply(g);
                             }));
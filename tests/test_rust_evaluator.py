@@ -0,0 +1,332 @@
+import asyncio
+import os
+import re
+import threading
+import pytest
+
+from pathlib import Path
+from pypipegraph2.pypipegraph2 import PPG2Evaluator, engine_info, errors
+
+
+def _new_evaluator(jobs, edges):
+    """jobs: {job_id: kind}, edges: [(downstream, upstream)]"""
+    e = PPG2Evaluator({})
+    for job_id, kind in jobs.items():
+        e.add_node(job_id, kind)
+    for downstream, upstream in edges:
+        e.add_edge(downstream, upstream)
+    return e
+
+
+class TestEventReporter:
+    def test_threaded_reporter(self):
+        # roots R0..R9, each with a downstream D0..D9 - all reported by workers
+        jobs = {}
+        edges = []
+        for ii in range(10):
+            jobs[f"R{ii}"] = "Output"
+            jobs[f"D{ii}"] = "Output"
+            edges.append((f"D{ii}", f"R{ii}"))
+        e = _new_evaluator(jobs, edges)
+        reporter = e.event_reporter()
+        e.event_startup()
+
+        def work(job_id):
+            if job_id == "R3":
+                reporter.job_failure(job_id, "boom")
+            else:
+                reporter.job_success(job_id, job_id)
+
+        seen = []
+        workers = []
+        for job_id in e.ready_jobs_iter(timeout=10):
+            seen.append(job_id)
+            e.event_now_running(job_id)
+            t = threading.Thread(target=work, args=(job_id,))
+            t.start()
+            workers.append(t)
+        for t in workers:
+            t.join()
+        assert sorted(seen) == sorted(x for x in jobs if x != "D3")
+        assert e.is_finished()
+        assert e.finish_state() == "failures"
+        assert e.query_failed_upstreams("D3") == ["R3"]
+        assert e.apply_reported_events() == 0
+
+    def test_apply_stops_at_first_error(self):
+        e = _new_evaluator({"A": "Output", "B": "Output"}, [])
+        reporter = e.event_reporter()
+        e.event_startup()
+        e.event_now_running("A")
+        e.event_now_running("B")
+        reporter.job_success("A", "a")
+        reporter.job_success("nope", "x")
+        reporter.job_success("B", "b")
+        with pytest.raises(KeyError):
+            e.apply_reported_events()
+        # A was applied, B is still queued
+        assert e.progress()["succeeded"] == 1
+        assert e.apply_reported_events() == 1
+        assert e.finish_state() == "success"
+
+
+class TestLoadGraph:
+    def test_load_graph(self):
+        e = PPG2Evaluator({})
+        e.load_graph(
+            {
+                "jobs": {"A": "Output", "B": "Ephemeral", "C": "Always"},
+                "edges": [("B", "A"), ("C", "B")],
+            }
+        )
+        assert e.query_roots(None) == ["A"]
+        assert e.query_leaves(None) == ["C"]
+        assert [(x["job_id"], x["kind"]) for x in e.upstreams_of("C")] == [
+            ("B", "Ephemeral")
+        ]
+        assert [(x["job_id"], x["kind"]) for x in e.upstreams_of("B")] == [
+            ("A", "Output")
+        ]
+        assert e.query_roots("Always") == []
+        # onto jobs already in the graph
+        e.load_graph({"jobs": {"D": "Output"}, "edges": [("D", "C")]})
+        assert e.query_leaves(None) == ["D"]
+        e.event_startup()
+        assert e.jobs_ready_to_run() == ["A"]
+
+    def test_load_graph_invalid_kind(self):
+        e = PPG2Evaluator({})
+        with pytest.raises(TypeError):
+            e.load_graph({"jobs": {"A": "Output", "B": "Nope"}, "edges": []})
+        # nothing was added
+        e.add_node("A", "Output")
+        e.add_node("B", "Output")
+
+    def test_load_graph_missing_node(self):
+        e = PPG2Evaluator({})
+        with pytest.raises(KeyError):
+            e.load_graph({"jobs": {"A": "Output"}, "edges": [("A", "B")]})
+        e.add_node("A", "Output")
+        with pytest.raises(ValueError):
+            e.load_graph({"jobs": {"B": "Output"}, "edges": [("B", "B")]})
+        with pytest.raises(errors.JobRedefinitionError):
+            e.load_graph({"jobs": {"A": "Output"}})
+        assert e.query_roots(None) == ["A"]
+
+
+class TestReadyJobsIter:
+    def test_yields_newly_ready_jobs(self):
+        # A <- B <- C, and D on its own
+        e = _new_evaluator(
+            {"A": "Output", "B": "Output", "C": "Output", "D": "Output"},
+            [("B", "A"), ("C", "B")],
+        )
+        e.event_startup()
+        seen = []
+        for job_id in e.ready_jobs_iter(timeout=10):
+            seen.append(job_id)
+            e.event_now_running(job_id)
+            e.event_job_success(job_id, job_id)
+        assert sorted(seen) == ["A", "B", "C", "D"]
+        # B only once A finished, C once B did
+        assert seen.index("A") < seen.index("B") < seen.index("C")
+        assert e.is_finished()
+        # a finished evaluator yields nothing
+        assert list(e.ready_jobs_iter(timeout=10)) == []
+
+    def test_each_job_once(self):
+        e = _new_evaluator({"A": "Output", "B": "Output"}, [("B", "A")])
+        e.event_startup()
+        it = e.ready_jobs_iter(timeout=0.1)
+        assert next(it) == "A"
+        # A is still ready (not yet running) - but was yielded already
+        with pytest.raises(TimeoutError):
+            next(it)
+        e.event_now_running("A")
+        e.event_job_success("A", "a")
+        assert next(it) == "B"
+        e.event_now_running("B")
+        e.event_job_failure("B", "boom")
+        with pytest.raises(StopIteration):
+            next(it)
+
+
+class TestProgressCallback:
+    def test_counts_increase_monotonically(self):
+        jobs = {f"J{ii}": "Output" for ii in range(6)}
+        edges = [(f"J{ii}", f"J{ii - 1}") for ii in range(1, 3)]
+        e = _new_evaluator(jobs, edges)
+        calls = []
+        e.set_progress_callback(lambda *counts: calls.append(counts), 0)
+        e.event_startup()
+        for job_id in e.ready_jobs_iter(timeout=10):
+            e.event_now_running(job_id)
+            if job_id == "J4":
+                e.event_job_failure(job_id, "boom")
+            else:
+                e.event_job_success(job_id, job_id)
+        assert calls
+        for (done, total, running, failed) in calls:
+            assert total == 6
+            assert done <= total
+        for earlier, later in zip(calls, calls[1:]):
+            assert later != earlier
+            assert later[0] >= earlier[0]  # done
+            assert later[3] >= earlier[3]  # failed
+        assert calls[-1] == (6, 6, 0, 1)
+
+    def test_min_interval_and_unregister(self):
+        e = _new_evaluator({f"J{ii}": "Output" for ii in range(4)}, [])
+        calls = []
+        e.set_progress_callback(lambda *counts: calls.append(counts), 60)
+        e.event_startup()
+        for job_id in ["J0", "J1", "J2"]:
+            e.event_now_running(job_id)
+            e.event_job_success(job_id, job_id)
+        # the first change, nothing within the minute after
+        assert len(calls) == 1
+        e.event_now_running("J3")
+        e.event_job_success("J3", "J3")
+        # but the final state always
+        assert calls[-1] == (4, 4, 0, 0)
+        e.set_progress_callback(None, None)
+
+
+class TestHistoryValues:
+    def test_str_and_bytes_round_trip(self):
+        values = {
+            "A": b"\x00\xffabc",
+            "B": "\x01bytes\x01YWJj",  # a str that looks like encoded bytes
+            "C": "\x01",
+            "D": "plain",
+        }
+
+        def run(history):
+            e = PPG2Evaluator(history)
+            for job_id in values:
+                e.add_node(job_id, "Output")
+            e.event_startup()
+            for job_id, value in values.items():
+                e.event_now_running(job_id)
+                e.event_job_success(job_id, value)
+            assert e.is_finished()
+            return e.new_history()
+
+        history = run({})
+        for job_id, value in values.items():
+            assert history[job_id] == value
+            assert type(history[job_id]) is type(value)
+        # and back in
+        assert run(history) == history
+
+
+class TestStrategyCallbacks:
+    def test_input_list_callback_errors_are_raised(self):
+        def fail(job_id):
+            raise ValueError("no inputs for " + job_id)
+
+        for callback in [fail, lambda job_id: 5]:
+            e = PPG2Evaluator({"A": "a", "A!!!": ""}, None, callback)
+            e.add_node("A", "Output")
+            with pytest.raises(errors.StrategyError, match="Input list for A failed"):
+                e.event_startup()
+
+
+def _os_threads():
+    return len(os.listdir("/proc/self/task"))
+
+
+async def _waiter_thread_ended(threads_before):
+    # the waiter thread ends shortly after resolving the last future
+    for _ in range(100):
+        if _os_threads() == threads_before:
+            return True
+        await asyncio.sleep(0.01)
+    return False
+
+
+@pytest.mark.skipif(
+    not os.path.exists("/proc/self/task"), reason="counts threads via /proc"
+)
+class TestAsyncWaiters:
+    def test_wait_for_ready_and_finished(self):
+        e = _new_evaluator({"A": "Output", "B": "Output"}, [("B", "A")])
+
+        async def run():
+            threads_before = _os_threads()
+            finished = e.wait_finished(10)
+            e.event_startup()
+            assert await e.wait_for_ready(10) == ["A"]
+            e.event_now_running("A")
+            ready = e.wait_for_ready(10)
+
+            async def finish_a():
+                await asyncio.sleep(0.05)
+                e.event_job_success("A", "a")
+
+            asyncio.get_running_loop().create_task(finish_a())
+            assert await ready == ["B"]
+            assert not finished.done()
+            e.event_now_running("B")
+            e.event_job_success("B", "b")
+            assert await finished is True
+            # finished - nothing ready anymore
+            assert await e.wait_for_ready(10) == []
+            assert await _waiter_thread_ended(threads_before)
+
+        asyncio.run(run())
+
+    def test_one_thread_for_many_waiters(self):
+        e = _new_evaluator({"A": "Output"}, [])
+
+        async def run():
+            e.event_startup()
+            e.event_now_running("A")
+            threads_before = _os_threads()
+            futures = [e.wait_finished(10) for _ in range(20)]
+            assert _os_threads() == threads_before + 1
+            e.event_job_success("A", "a")
+            assert await asyncio.gather(*futures) == [True] * 20
+            assert await _waiter_thread_ended(threads_before)
+
+        asyncio.run(run())
+
+    def test_timeout_and_cancel(self):
+        e = _new_evaluator({"A": "Output"}, [])
+
+        async def run():
+            threads_before = _os_threads()
+            e.event_startup()
+            e.event_now_running("A")
+            cancelled = e.wait_finished(None)
+            cancelled.cancel()
+            with pytest.raises(TimeoutError):
+                await e.wait_finished(0.1)
+            finished = e.wait_finished(10)
+            e.event_job_success("A", "a")
+            assert await finished is True
+            assert await _waiter_thread_ended(threads_before)
+
+        asyncio.run(run())
+
+
+class TestEngineInfo:
+    def test_engine_info(self):
+        info = engine_info()
+        cargo_toml = (Path(__file__).parent.parent / "Cargo.toml").read_text()
+        version = re.search(r'^version = "([^"]+)"', cargo_toml, re.M).group(1)
+        assert info["version"] == version
+        assert set(info["features"]).issubset(
+            {"petgraph-backend", "otel", "no-debug-logging", "debug-invariants"}
+        )
+        assert "TempFile" in info["job_kinds"]
+        assert len(set(info["job_kinds"])) == len(info["job_kinds"])
+
+    def test_job_kinds_are_accepted(self):
+        e = PPG2Evaluator({})
+        for kind in engine_info()["job_kinds"]:
+            e.add_node(kind, kind)
+        with pytest.raises(TypeError):
+            e.add_node("X", "Invariant")
+        with pytest.raises(TypeError):
+            e.add_node("X", "Nope")